@@ -0,0 +1,29 @@
+-- Create payment_status_history table
+-- Records every transition made by the payment state machine
+CREATE TABLE payment_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    payment_id BIGINT NOT NULL REFERENCES payment_transactions(id) ON DELETE CASCADE,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    from_status VARCHAR(50) NOT NULL,
+    to_status VARCHAR(50) NOT NULL,
+    actor VARCHAR(100) NOT NULL,  -- "system", "customer", "merchant:<id>", "admin:<id>"
+    reason TEXT,
+    transaction_hash VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for payment_status_history table
+CREATE INDEX idx_payment_status_history_payment ON payment_status_history(payment_id, created_at);
+CREATE INDEX idx_payment_status_history_merchant ON payment_status_history(merchant_id);
+
+-- Restrict status to values known to the payment state machine
+ALTER TABLE payment_transactions
+    ADD CONSTRAINT chk_payment_status_valid
+    CHECK (status IN (
+        'PENDING', 'CONFIRMING', 'CONFIRMED', 'FAILED', 'EXPIRED', 'REFUNDED',
+        'CANCELLED', 'UNDERPAID', 'OVERPAID', 'PARTIALLY_REFUNDED'
+    ));
+
+COMMENT ON TABLE payment_status_history IS 'Audit trail of payment status transitions enforced by the payment state machine';
+COMMENT ON COLUMN payment_status_history.actor IS 'Who triggered the transition (system, customer, merchant:<id>, admin:<id>)';
+COMMENT ON COLUMN payment_status_history.transaction_hash IS 'Blockchain transaction that caused the transition, if any';
@@ -0,0 +1,31 @@
+-- Underpayment / overpayment handling
+-- Payments can now receive more than the requested amount
+ALTER TABLE payment_transactions DROP CONSTRAINT chk_total_paid_valid;
+
+ALTER TABLE payment_transactions
+    ADD CONSTRAINT chk_total_paid_valid CHECK (total_paid >= 0);
+
+ALTER TABLE payment_transactions ADD COLUMN overpaid_amount DECIMAL(20,8) NOT NULL DEFAULT 0;
+
+-- Per-merchant thresholds and automatic actions for amount mismatches
+CREATE TABLE merchant_payment_policies (
+    merchant_id BIGINT PRIMARY KEY REFERENCES merchants(id) ON DELETE CASCADE,
+    underpayment_tolerance_percent DECIMAL(5,2) NOT NULL DEFAULT 0.10,
+    overpayment_tolerance_percent DECIMAL(5,2) NOT NULL DEFAULT 0.10,
+    underpayment_action VARCHAR(50) NOT NULL DEFAULT 'await_top_up',  -- "await_top_up", "accept", "fail"
+    overpayment_action VARCHAR(50) NOT NULL DEFAULT 'record_surplus',  -- "record_surplus", "auto_refund"
+    top_up_window_minutes INT NOT NULL DEFAULT 30,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT chk_policy_tolerances CHECK (
+        underpayment_tolerance_percent >= 0 AND underpayment_tolerance_percent <= 10 AND
+        overpayment_tolerance_percent >= 0 AND overpayment_tolerance_percent <= 10
+    ),
+    CONSTRAINT chk_policy_top_up_window CHECK (top_up_window_minutes > 0 AND top_up_window_minutes <= 1440)
+);
+
+COMMENT ON COLUMN payment_transactions.overpaid_amount IS 'Amount received above the requested amount, refundable to the customer';
+COMMENT ON TABLE merchant_payment_policies IS 'Per-merchant thresholds and automatic actions for underpaid and overpaid payments';
+COMMENT ON COLUMN merchant_payment_policies.underpayment_tolerance_percent IS 'Shortfall (percent of amount) still accepted as a full payment';
+COMMENT ON COLUMN merchant_payment_policies.overpayment_tolerance_percent IS 'Surplus (percent of amount) not treated as an overpayment';
+COMMENT ON COLUMN merchant_payment_policies.top_up_window_minutes IS 'How long an underpaid payment stays open for a top-up';
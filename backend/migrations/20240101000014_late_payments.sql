@@ -0,0 +1,18 @@
+-- Late-payment recovery
+-- Funds that arrive after a payment expired are recorded as PAID_LATE for merchant review
+ALTER TABLE payment_transactions DROP CONSTRAINT chk_payment_status_valid;
+
+ALTER TABLE payment_transactions
+    ADD CONSTRAINT chk_payment_status_valid
+    CHECK (status IN (
+        'PENDING', 'CONFIRMING', 'CONFIRMED', 'FAILED', 'EXPIRED', 'REFUNDED',
+        'CANCELLED', 'UNDERPAID', 'OVERPAID', 'PARTIALLY_REFUNDED', 'PAID_LATE'
+    ));
+
+ALTER TABLE payment_transactions ADD COLUMN late_amount_usd DECIMAL(20,2);
+ALTER TABLE payment_transactions ADD COLUMN late_exchange_rate DECIMAL(20,8);
+ALTER TABLE payment_transactions ADD COLUMN late_received_at TIMESTAMPTZ;
+
+COMMENT ON COLUMN payment_transactions.late_amount_usd IS 'USD value of funds received after expiry, repriced at the rate when they arrived';
+COMMENT ON COLUMN payment_transactions.late_exchange_rate IS 'USD price of the crypto used to reprice late funds';
+COMMENT ON COLUMN payment_transactions.late_received_at IS 'When funds were last received after the payment expired';
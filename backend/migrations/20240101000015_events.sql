@@ -0,0 +1,27 @@
+-- Create events table
+-- Durable log of every domain event, exposed through GET /api/v1/merchant/events
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    event_id VARCHAR(64) UNIQUE NOT NULL,  -- Public-facing ID (e.g., "evt_abc123")
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    event_type VARCHAR(100) NOT NULL,  -- "payment.confirmed", "refund.completed", "withdrawal.created", ...
+    api_version VARCHAR(20) NOT NULL,
+    object_type VARCHAR(50) NOT NULL,  -- "payment", "refund", "withdrawal", "invoice", "balance", "security"
+    object_id VARCHAR(255) NOT NULL,
+    data JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for events table
+CREATE INDEX idx_events_merchant ON events(merchant_id, id DESC);
+CREATE INDEX idx_events_merchant_type ON events(merchant_id, event_type, id DESC);
+CREATE INDEX idx_events_object ON events(object_type, object_id);
+
+-- Link webhook deliveries to the event they carry
+ALTER TABLE webhook_deliveries ADD COLUMN event_id VARCHAR(64) REFERENCES events(event_id) ON DELETE SET NULL;
+CREATE INDEX idx_webhook_deliveries_event ON webhook_deliveries(event_id);
+
+COMMENT ON TABLE events IS 'Append-only log of domain events per merchant';
+COMMENT ON COLUMN events.api_version IS 'API version the data snapshot was rendered with';
+COMMENT ON COLUMN events.data IS 'Snapshot of the object at the time of the event';
+COMMENT ON COLUMN webhook_deliveries.event_id IS 'Event carried by this delivery, used by consumers for deduplication';
@@ -0,0 +1,48 @@
+-- Create webhook_endpoints table
+-- Multiple webhook endpoints per merchant, each with its own signing secret and subscriptions
+CREATE TABLE webhook_endpoints (
+    id BIGSERIAL PRIMARY KEY,
+    endpoint_id VARCHAR(64) UNIQUE NOT NULL,  -- Public-facing ID (e.g., "we_abc123")
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    url VARCHAR(500) NOT NULL,
+    description TEXT,
+    enabled_events TEXT[] NOT NULL DEFAULT '{*}',  -- "*", "payment.*", "refund.completed", ...
+    secret_encrypted TEXT,  -- NULL for endpoints migrated from webhook_configs until first rotation
+    previous_secret_encrypted TEXT,
+    previous_secret_expires_at TIMESTAMPTZ,
+    is_primary BOOLEAN NOT NULL DEFAULT false,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Indexes for webhook_endpoints table
+CREATE INDEX idx_webhook_endpoints_merchant ON webhook_endpoints(merchant_id);
+CREATE UNIQUE INDEX idx_webhook_endpoints_primary ON webhook_endpoints(merchant_id) WHERE is_primary;
+
+CREATE TRIGGER update_webhook_endpoints_updated_at
+    BEFORE UPDATE ON webhook_endpoints
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+-- Existing single-URL configurations become each merchant's primary endpoint
+INSERT INTO webhook_endpoints (endpoint_id, merchant_id, url, is_primary, is_active, created_at)
+SELECT 'we_' || replace(gen_random_uuid()::text, '-', ''), merchant_id, url, true, is_active, created_at
+FROM webhook_configs;
+
+-- Deliveries are now addressed to an endpoint
+ALTER TABLE webhook_deliveries
+    ADD COLUMN endpoint_id BIGINT REFERENCES webhook_endpoints(id) ON DELETE SET NULL;
+CREATE INDEX idx_webhook_deliveries_endpoint ON webhook_deliveries(endpoint_id);
+
+UPDATE webhook_deliveries d
+SET endpoint_id = e.id
+FROM webhook_endpoints e
+WHERE e.merchant_id = d.merchant_id AND e.is_primary;
+
+COMMENT ON TABLE webhook_endpoints IS 'Webhook endpoints per merchant with event subscriptions and signing secrets';
+COMMENT ON COLUMN webhook_endpoints.enabled_events IS 'Subscribed event types; "*" for all, "payment.*" for a whole family';
+COMMENT ON COLUMN webhook_endpoints.secret_encrypted IS 'Encrypted signing secret; NULL means the legacy platform key is used until the secret is rotated';
+COMMENT ON COLUMN webhook_endpoints.previous_secret_encrypted IS 'Secret being rotated out; deliveries are signed with both until previous_secret_expires_at';
+COMMENT ON COLUMN webhook_endpoints.is_primary IS 'Endpoint managed through the legacy PUT /api/v1/merchant/webhook API';
+COMMENT ON COLUMN webhook_deliveries.endpoint_id IS 'Endpoint this delivery is addressed to; NULL once the endpoint is deleted';
@@ -0,0 +1,31 @@
+-- Webhook delivery dashboard, redelivery and dead-letter queue
+
+-- Public delivery IDs, latency and dead-letter tracking
+ALTER TABLE webhook_deliveries
+    ADD COLUMN delivery_id VARCHAR(64) NOT NULL DEFAULT ('whd_' || replace(gen_random_uuid()::text, '-', '')),
+    ADD COLUMN latency_ms INTEGER,
+    ADD COLUMN dead_lettered_at TIMESTAMPTZ,
+    ADD COLUMN redelivery_of BIGINT REFERENCES webhook_deliveries(id) ON DELETE SET NULL;
+
+CREATE UNIQUE INDEX idx_webhook_deliveries_delivery_id ON webhook_deliveries(delivery_id);
+CREATE INDEX idx_webhook_deliveries_merchant_created ON webhook_deliveries(merchant_id, id DESC);
+CREATE INDEX idx_webhook_deliveries_dead_letter ON webhook_deliveries(merchant_id, dead_lettered_at)
+    WHERE dead_lettered_at IS NOT NULL;
+
+-- Deliveries that already gave up are the initial dead-letter queue
+UPDATE webhook_deliveries
+SET dead_lettered_at = COALESCE(last_attempt_at, created_at)
+WHERE status = 'failed';
+
+-- Sustained-failure tracking for automatic disabling
+ALTER TABLE webhook_endpoints
+    ADD COLUMN failing_since TIMESTAMPTZ,
+    ADD COLUMN disabled_at TIMESTAMPTZ,
+    ADD COLUMN disabled_reason TEXT;
+
+COMMENT ON COLUMN webhook_deliveries.delivery_id IS 'Public-facing delivery ID (e.g., "whd_abc123")';
+COMMENT ON COLUMN webhook_deliveries.latency_ms IS 'Duration of the last delivery attempt in milliseconds';
+COMMENT ON COLUMN webhook_deliveries.dead_lettered_at IS 'When the delivery exhausted its retries and entered the dead-letter queue';
+COMMENT ON COLUMN webhook_deliveries.redelivery_of IS 'Delivery this row was manually redelivered from';
+COMMENT ON COLUMN webhook_endpoints.failing_since IS 'First failed attempt since the last successful delivery';
+COMMENT ON COLUMN webhook_endpoints.disabled_reason IS 'Why the endpoint was disabled automatically; cleared when re-enabled';
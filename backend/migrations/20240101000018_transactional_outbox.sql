@@ -0,0 +1,41 @@
+-- Transactional outbox
+-- Events, webhook deliveries and emails are written in the same transaction as the state change
+
+-- Webhook deliveries now cover every event family, not only payments
+ALTER TABLE webhook_deliveries ALTER COLUMN payment_id DROP NOT NULL;
+
+CREATE INDEX idx_webhook_deliveries_dispatch ON webhook_deliveries(next_retry_at, id)
+    WHERE status = 'pending';
+
+-- Create email_outbox table
+CREATE TABLE email_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    merchant_id BIGINT REFERENCES merchants(id) ON DELETE CASCADE,
+    recipient VARCHAR(255) NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',  -- pending, sent, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ,
+    CONSTRAINT chk_email_outbox_status CHECK (status IN ('pending', 'sent', 'failed'))
+);
+
+CREATE INDEX idx_email_outbox_dispatch ON email_outbox(next_attempt_at, id) WHERE status = 'pending';
+CREATE INDEX idx_email_outbox_merchant ON email_outbox(merchant_id);
+
+-- Address-only merchants configured merchants.webhook_url; fold it into webhook endpoints
+INSERT INTO webhook_endpoints (endpoint_id, merchant_id, url, is_primary)
+SELECT 'we_' || replace(gen_random_uuid()::text, '-', ''), m.id, m.webhook_url, true
+FROM merchants m
+WHERE m.webhook_url IS NOT NULL
+  AND m.webhook_url <> ''
+  AND NOT EXISTS (SELECT 1 FROM webhook_endpoints e WHERE e.merchant_id = m.id);
+
+-- Inline address-only webhook log, superseded by webhook_deliveries
+DROP TABLE IF EXISTS webhook_logs;
+
+COMMENT ON TABLE email_outbox IS 'Queued outbound emails, written in the transaction that triggers them';
+COMMENT ON COLUMN merchants.webhook_url IS 'Deprecated: migrated to webhook_endpoints';
@@ -0,0 +1,20 @@
+-- Two-factor step-up verification
+-- Replay protection for TOTP codes and single-use recovery codes
+
+-- Last accepted TOTP time step; a code for this step or an earlier one is rejected
+ALTER TABLE two_factor_auth ADD COLUMN last_used_step BIGINT;
+
+-- Recovery codes move to their own table, stored as SHA-256 hashes.
+-- Codes still in the encrypted column are imported on first use.
+ALTER TABLE two_factor_auth ALTER COLUMN recovery_codes_encrypted DROP NOT NULL;
+
+CREATE TABLE two_factor_recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (merchant_id, code_hash)
+);
+
+CREATE INDEX idx_two_factor_recovery_codes_merchant ON two_factor_recovery_codes(merchant_id) WHERE used_at IS NULL;
@@ -0,0 +1,34 @@
+-- Account lockout
+-- Persisted authentication attempts and the lockouts they trigger
+
+-- Attempts are recorded once per key: a login failure for an email from
+-- an IP produces an 'email' row and an 'ip' row.
+CREATE TABLE login_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    scope VARCHAR(20) NOT NULL,
+    key_type VARCHAR(10) NOT NULL CHECK (key_type IN ('email', 'ip')),
+    key_value VARCHAR(255) NOT NULL,
+    succeeded BOOLEAN NOT NULL DEFAULT FALSE,
+    ip_address VARCHAR(45),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_login_attempts_key ON login_attempts(scope, key_type, key_value, created_at DESC);
+CREATE INDEX idx_login_attempts_created ON login_attempts(created_at);
+
+-- Throttle rows implement the progressive delay between failures;
+-- the others are full lockouts that notify the account owner.
+CREATE TABLE account_lockouts (
+    id BIGSERIAL PRIMARY KEY,
+    scope VARCHAR(20) NOT NULL,
+    key_type VARCHAR(10) NOT NULL CHECK (key_type IN ('email', 'ip')),
+    key_value VARCHAR(255) NOT NULL,
+    is_throttle BOOLEAN NOT NULL DEFAULT FALSE,
+    failed_attempts INTEGER NOT NULL,
+    locked_until TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    unlocked_at TIMESTAMPTZ,
+    unlocked_by BIGINT
+);
+
+CREATE INDEX idx_account_lockouts_active ON account_lockouts(key_type, key_value, locked_until) WHERE unlocked_at IS NULL;
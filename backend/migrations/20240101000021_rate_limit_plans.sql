@@ -0,0 +1,6 @@
+-- Rate limit plans
+-- Each merchant's API quota is a multiple of the configured base rate
+
+ALTER TABLE merchants
+    ADD COLUMN rate_limit_plan VARCHAR(20) NOT NULL DEFAULT 'standard'
+    CHECK (rate_limit_plan IN ('standard', 'business', 'enterprise'));
@@ -0,0 +1,6 @@
+-- API key scoped IP allowlists
+-- Entries with an api_key_hash apply only to that key, in addition to the merchant-wide entries
+
+ALTER TABLE ip_whitelist ADD COLUMN api_key_hash VARCHAR(64);
+
+CREATE INDEX idx_ip_whitelist_merchant_key ON ip_whitelist(merchant_id, api_key_hash) WHERE is_active = true;
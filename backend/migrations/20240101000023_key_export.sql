@@ -0,0 +1,27 @@
+-- Hardened private key export
+-- Merchant passwords for re-authentication, stored wallet keys and delayed export requests
+
+-- Argon2 hash; NULL for merchants registered before passwords were stored
+ALTER TABLE merchants ADD COLUMN password_hash VARCHAR(255);
+
+-- Encrypted key of generated or imported wallets; NULL for address-only wallets
+ALTER TABLE merchant_wallets ADD COLUMN encrypted_private_key TEXT;
+
+-- An export is released only between available_at (the cooling-off delay)
+-- and expires_at, and only once.
+CREATE TABLE key_export_requests (
+    id BIGSERIAL PRIMARY KEY,
+    export_id VARCHAR(64) UNIQUE NOT NULL,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    crypto_type VARCHAR(50) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'released', 'cancelled', 'expired')),
+    ip_address VARCHAR(45),
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    available_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    released_at TIMESTAMPTZ,
+    cancelled_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_key_export_requests_merchant ON key_export_requests(merchant_id, requested_at DESC);
@@ -0,0 +1,43 @@
+-- Tamper-evident audit log
+-- Each row carries the hash of the previous row of its tenant; rows are append-only
+
+ALTER TABLE audit_logs
+    -- Chain the row belongs to: the merchant, or 0 for platform events.
+    -- Kept separately from merchant_id so the chain survives merchant deletion.
+    ADD COLUMN tenant_id BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN actor_type VARCHAR(20),   -- "merchant", "admin", "system"
+    ADD COLUMN actor_id VARCHAR(100),
+    ADD COLUMN actor_role VARCHAR(50),
+    ADD COLUMN request_id VARCHAR(100),
+    -- NULL on rows written before chaining
+    ADD COLUMN prev_hash VARCHAR(64),
+    ADD COLUMN hash VARCHAR(64);
+
+UPDATE audit_logs SET tenant_id = COALESCE(merchant_id, 0);
+
+-- Rows must outlive the merchant they describe
+ALTER TABLE audit_logs DROP CONSTRAINT IF EXISTS audit_logs_merchant_id_fkey;
+
+CREATE INDEX idx_audit_logs_tenant_chain ON audit_logs(tenant_id, id);
+CREATE INDEX idx_audit_logs_request_id ON audit_logs(request_id) WHERE request_id IS NOT NULL;
+
+-- Latest row of each chain. Writers lock the head row, so rows of a tenant
+-- are chained one at a time, and deleting the newest rows is detected.
+CREATE TABLE audit_chain_heads (
+    tenant_id BIGINT PRIMARY KEY,
+    last_id BIGINT,
+    last_hash VARCHAR(64),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE OR REPLACE FUNCTION audit_logs_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_logs is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_logs_append_only
+    BEFORE UPDATE OR DELETE ON audit_logs
+    FOR EACH ROW EXECUTE FUNCTION audit_logs_append_only();
+
+COMMENT ON COLUMN audit_logs.hash IS 'SHA-256 of prev_hash and the canonical row content';
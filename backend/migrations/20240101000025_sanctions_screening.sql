@@ -0,0 +1,43 @@
+-- Sanctions screening
+-- Deny-listed addresses from imported and custom lists, and compliance holds on screened transactions
+
+CREATE TABLE sanctions_list_entries (
+    id BIGSERIAL PRIMARY KEY,
+    source VARCHAR(20) NOT NULL,         -- "ofac_sdn", "file", "custom"
+    list_name VARCHAR(100) NOT NULL,
+    -- Normalized: EVM and bech32 addresses lowercased, others as listed
+    address VARCHAR(255) NOT NULL,
+    currency VARCHAR(20),                -- currency code from the list (XBT, ETH, USDT, ...)
+    reason TEXT,
+    created_by BIGINT,                   -- admin who added a custom entry
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (source, list_name, address)
+);
+
+CREATE INDEX idx_sanctions_list_entries_address ON sanctions_list_entries(address);
+
+CREATE TABLE screening_holds (
+    id BIGSERIAL PRIMARY KEY,
+    hold_id VARCHAR(50) UNIQUE NOT NULL,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id),
+    subject_type VARCHAR(20) NOT NULL,   -- "payment", "withdrawal", "refund"
+    subject_id VARCHAR(100) NOT NULL,    -- public id (pay_..., wd_..., ref_...)
+    address VARCHAR(255) NOT NULL,
+    transaction_hash VARCHAR(255),
+    matched_source VARCHAR(20) NOT NULL,
+    matched_list VARCHAR(100) NOT NULL,
+    matched_reason TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'held', -- "held", "released", "rejected"
+    reviewed_by BIGINT,
+    review_notes TEXT,
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (subject_type, subject_id, address)
+);
+
+CREATE INDEX idx_screening_holds_status ON screening_holds(status, created_at);
+CREATE INDEX idx_screening_holds_merchant ON screening_holds(merchant_id);
+
+COMMENT ON TABLE screening_holds IS 'Transactions stopped for compliance review after a sanctions list match';
+COMMENT ON COLUMN withdrawals.status IS 'PENDING, HELD, APPROVED, PROCESSING, COMPLETED, REJECTED, CANCELLED';
+COMMENT ON COLUMN refunds.status IS 'pending, held, completed, failed';
@@ -0,0 +1,67 @@
+-- AML transaction monitoring
+-- Configurable rules over confirmed payments and withdrawals, security alert cases and withdrawal freezes
+
+CREATE TABLE aml_rules (
+    rule_id VARCHAR(50) PRIMARY KEY,     -- "velocity", "structuring", "rapid_in_out", "large_transfer"
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    severity VARCHAR(20) NOT NULL DEFAULT 'medium', -- "low", "medium", "high", "critical"
+    -- Freeze the merchant's withdrawals when the rule raises a new alert
+    freeze_withdrawals BOOLEAN NOT NULL DEFAULT false,
+    params JSONB NOT NULL DEFAULT '{}',
+    updated_by BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO aml_rules (rule_id, severity, freeze_withdrawals, params) VALUES
+    ('velocity', 'medium', false, '{"window_minutes": 60, "max_transactions": 5}'),
+    ('structuring', 'high', true, '{"threshold_usd": 10000, "margin_percent": 10, "window_hours": 24, "min_transactions": 3}'),
+    ('rapid_in_out', 'high', true, '{"window_minutes": 60, "min_ratio_percent": 80, "min_inbound_usd": 1000}'),
+    ('large_transfer', 'medium', false, '{"threshold_usd": 50000}');
+
+CREATE TABLE security_alerts (
+    id BIGSERIAL PRIMARY KEY,
+    alert_id VARCHAR(50) UNIQUE NOT NULL,
+    merchant_id BIGINT REFERENCES merchants(id) ON DELETE CASCADE,
+    alert_type VARCHAR(50) NOT NULL,     -- rule id for AML alerts
+    severity VARCHAR(20) NOT NULL,
+    message TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    -- One open case per rule and subject; new activity updates it
+    dedupe_key VARCHAR(255) NOT NULL,
+    -- Latest transfer covered by the alert; later activity opens a new case once this one is closed
+    last_activity_at TIMESTAMPTZ NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- "open", "investigating", "closed"
+    assigned_to BIGINT,
+    acknowledged BOOLEAN NOT NULL DEFAULT false,
+    acknowledged_at TIMESTAMPTZ,
+    resolution TEXT,
+    closed_by BIGINT,
+    closed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_security_alerts_open_dedupe ON security_alerts(dedupe_key) WHERE status <> 'closed';
+CREATE INDEX idx_security_alerts_status ON security_alerts(status, created_at);
+CREATE INDEX idx_security_alerts_merchant ON security_alerts(merchant_id);
+
+-- Case history: status changes and notes
+CREATE TABLE security_alert_events (
+    id BIGSERIAL PRIMARY KEY,
+    alert_id VARCHAR(50) NOT NULL REFERENCES security_alerts(alert_id) ON DELETE CASCADE,
+    admin_id BIGINT,
+    from_status VARCHAR(20),
+    to_status VARCHAR(20),
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_security_alert_events_alert ON security_alert_events(alert_id, id);
+
+ALTER TABLE merchants
+    ADD COLUMN withdrawals_frozen BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN withdrawals_frozen_reason TEXT,
+    ADD COLUMN withdrawals_frozen_at TIMESTAMPTZ,
+    ADD COLUMN withdrawals_frozen_alert_id VARCHAR(50);
+
+CREATE INDEX idx_withdrawals_merchant_created ON withdrawals(merchant_id, created_at);
@@ -0,0 +1,13 @@
+-- KYC volume tiers
+-- Daily volume limits depend on the merchant's verification tier
+
+ALTER TABLE merchants ADD COLUMN IF NOT EXISTS kyc_verified BOOLEAN DEFAULT false;
+
+ALTER TABLE merchants
+    ADD COLUMN kyc_tier VARCHAR(20) NOT NULL DEFAULT 'unverified'
+    CHECK (kyc_tier IN ('unverified', 'basic', 'full'));
+
+UPDATE merchants SET kyc_tier = 'full' WHERE kyc_verified = true;
+
+CREATE INDEX IF NOT EXISTS idx_payment_transactions_merchant_confirmed
+    ON payment_transactions(merchant_id, confirmed_at);
@@ -0,0 +1,61 @@
+-- Merchant onboarding
+-- KYC/KYB state machine, business details, uploaded documents and review history
+
+ALTER TABLE merchants
+    ADD COLUMN onboarding_status VARCHAR(30) NOT NULL DEFAULT 'registered'
+    CHECK (onboarding_status IN (
+        'registered', 'email_verified', 'business_info_submitted',
+        'documents_uploaded', 'under_review', 'approved', 'rejected'
+    )),
+    ADD COLUMN email_verified_at TIMESTAMPTZ,
+    ADD COLUMN onboarding_reviewed_by BIGINT,
+    ADD COLUMN onboarding_reviewed_at TIMESTAMPTZ,
+    ADD COLUMN onboarding_rejection_reason TEXT;
+
+-- Existing merchants keep the access they already had
+UPDATE merchants SET onboarding_status = CASE WHEN kyc_verified = true THEN 'approved' ELSE 'email_verified' END,
+                     email_verified_at = created_at;
+
+CREATE INDEX idx_merchants_onboarding_status ON merchants(onboarding_status);
+
+CREATE TABLE merchant_business_profiles (
+    merchant_id BIGINT PRIMARY KEY REFERENCES merchants(id) ON DELETE CASCADE,
+    legal_name VARCHAR(255) NOT NULL,
+    business_type VARCHAR(50) NOT NULL,  -- "sole_proprietor", "company", "non_profit", ...
+    registration_number VARCHAR(100),
+    country CHAR(2) NOT NULL,            -- ISO 3166-1 alpha-2
+    address TEXT NOT NULL,
+    website VARCHAR(255),
+    contact_name VARCHAR(255) NOT NULL,
+    submitted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE merchant_documents (
+    id BIGSERIAL PRIMARY KEY,
+    document_id VARCHAR(50) UNIQUE NOT NULL,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    document_type VARCHAR(50) NOT NULL,  -- "identity_document", "business_registration", "proof_of_address", "other"
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    sha256 VARCHAR(64) NOT NULL,
+    storage_backend VARCHAR(20) NOT NULL, -- "local", "s3"
+    storage_key VARCHAR(500) NOT NULL,
+    uploaded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_merchant_documents_merchant ON merchant_documents(merchant_id, document_type);
+
+-- Status changes with the reviewing admin and notes
+CREATE TABLE merchant_onboarding_events (
+    id BIGSERIAL PRIMARY KEY,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    from_status VARCHAR(30),
+    to_status VARCHAR(30) NOT NULL,
+    admin_id BIGINT,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_merchant_onboarding_events_merchant ON merchant_onboarding_events(merchant_id, id);
@@ -0,0 +1,39 @@
+-- Email tokens and notification preferences
+-- Verify-email and password-reset tokens, per-user email preferences and templated outbox rows
+
+CREATE TABLE email_tokens (
+    id BIGSERIAL PRIMARY KEY,
+    token_id VARCHAR(64) UNIQUE NOT NULL,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    purpose VARCHAR(30) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    email VARCHAR(255) NOT NULL,         -- Address the token was sent to
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_email_tokens_outstanding ON email_tokens(merchant_id, purpose) WHERE used_at IS NULL;
+
+-- One row per merchant account owner (merchant_user_id NULL) or team member
+CREATE TABLE notification_preferences (
+    id BIGSERIAL PRIMARY KEY,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    merchant_user_id INTEGER REFERENCES merchant_users(id) ON DELETE CASCADE,
+    locale VARCHAR(10) NOT NULL DEFAULT 'en',
+    payments BOOLEAN NOT NULL DEFAULT true,
+    withdrawals BOOLEAN NOT NULL DEFAULT true,
+    invoices BOOLEAN NOT NULL DEFAULT true,
+    webhooks BOOLEAN NOT NULL DEFAULT true,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_notification_preferences_user
+    ON notification_preferences(merchant_id, COALESCE(merchant_user_id, 0));
+
+ALTER TABLE email_outbox
+    ADD COLUMN template VARCHAR(50),
+    ADD COLUMN locale VARCHAR(10),
+    ADD COLUMN html_body TEXT;
+
+COMMENT ON TABLE email_tokens IS 'Single-use tokens behind signed verify-email and password-reset links';
+COMMENT ON TABLE notification_preferences IS 'Which optional emails each merchant user receives, and in which language';
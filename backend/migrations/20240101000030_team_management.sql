@@ -0,0 +1,26 @@
+-- Merchant team management
+-- Email invitations for team members and revocable team member sessions
+
+CREATE TABLE team_invitations (
+    id BIGSERIAL PRIMARY KEY,
+    invitation_id VARCHAR(64) UNIQUE NOT NULL,
+    merchant_id BIGINT NOT NULL REFERENCES merchants(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    role user_role NOT NULL,
+    invited_by INTEGER REFERENCES merchant_users(id) ON DELETE SET NULL, -- NULL when the owner invited
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- At most one open invitation per address and merchant
+CREATE UNIQUE INDEX idx_team_invitations_open
+    ON team_invitations(merchant_id, LOWER(email))
+    WHERE accepted_at IS NULL AND revoked_at IS NULL;
+
+-- Bumped to sign a member out of every session (removal, sign-out)
+ALTER TABLE merchant_users ADD COLUMN session_version INTEGER NOT NULL DEFAULT 0;
+
+COMMENT ON TABLE team_invitations IS 'Pending and past invitations to join a merchant team';
+COMMENT ON COLUMN merchant_users.session_version IS 'Team sessions signed with an older version are refused';
@@ -0,0 +1,4 @@
+-- Every webhook endpoint signs with its own secret
+-- Endpoints migrated from webhook_configs and merchants.webhook_url have none yet;
+-- the outbox dispatcher generates them at startup, before delivering anything
+COMMENT ON COLUMN webhook_endpoints.secret_encrypted IS 'Encrypted signing secret; NULL only for migrated endpoints until the dispatcher generates one';
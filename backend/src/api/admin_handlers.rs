@@ -4,9 +4,21 @@
 use crate::middleware::admin_auth::AdminContext;
 use crate::api::state::AppState;
 use crate::middleware::auth::MerchantContext;
+use crate::error::ServiceError;
+use crate::payment::models::PaymentStatus;
+use crate::payment::state_machine::TransitionActor;
+use crate::services::aml_service::{AlertQuery, CaseStatus, UpdateAmlRule};
+use crate::services::audit_service::AuditChange;
+use crate::services::onboarding_service::OnboardingStatus;
+use crate::services::payment_service::PaymentServiceError;
+use crate::services::rate_limit_service::RateLimitPlan;
+use crate::services::volume_tracking_service::KycTier;
+use crate::services::screening_service::{
+    ListSource, LocalFileSource, OfacSdnSource, ScreeningHold, SubjectType, SOURCE_FILE, SOURCE_OFAC_SDN,
+};
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Json},
     Extension,
 };
@@ -64,6 +76,11 @@ pub struct UserPermissions {
     pub permissions: Vec<String>,
 }
 
+#[derive(Deserialize)]
+pub struct ForceStatusRequest {
+    pub reason: Option<String>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct TransferFunds {
     pub from_wallet: String,
@@ -309,6 +326,67 @@ pub async fn delete_merchant(
     })).into_response()
 }
 
+#[derive(Deserialize)]
+pub struct RateLimitPlanRequest {
+    pub plan: RateLimitPlan,
+}
+
+/// Set the API rate limit plan of a merchant
+pub async fn update_merchant_rate_limit_plan(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<i64>,
+    Json(req): Json<RateLimitPlanRequest>,
+) -> impl IntoResponse {
+    let previous = state.rate_limit_service.plan(merchant_id).await;
+
+    match state.rate_limit_service.set_plan(merchant_id, req.plan).await {
+        Ok(()) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_before(json!({ "rate_limit_plan": previous }))
+                .with_after(json!({ "rate_limit_plan": req.plan }));
+            (Extension(change), Json(json!({
+                "merchant_id": merchant_id,
+                "rate_limit_plan": req.plan,
+                "message": "Rate limit plan updated"
+            }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct KycTierRequest {
+    pub tier: KycTier,
+}
+
+/// Set the KYC tier of a merchant, which determines its daily volume limit
+pub async fn update_merchant_kyc_tier(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<i64>,
+    Json(req): Json<KycTierRequest>,
+) -> impl IntoResponse {
+    let previous = match state.volume_tracking_service.kyc_tier(merchant_id).await {
+        Ok(tier) => tier,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.volume_tracking_service.set_kyc_tier(merchant_id, req.tier).await {
+        Ok(()) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_before(json!({ "kyc_tier": previous }))
+                .with_after(json!({ "kyc_tier": req.tier }));
+            (Extension(change), Json(json!({
+                "merchant_id": merchant_id,
+                "kyc_tier": req.tier,
+                "message": "KYC tier updated"
+            }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
 /// Get security settings
 pub async fn get_security_settings(
     State(state): State<AppState>,
@@ -407,10 +485,13 @@ pub async fn update_fee_config(
         return response.into_response();
     }
 
-    Json(json!({
+    let change = AuditChange::new("fee_config", "platform")
+        .with_before(json!({ "platform_fee_percentage": state.config.default_fee_percentage }))
+        .with_after(json!(config));
+    (Extension(change), Json(json!({
         "message": "Fee configuration updated successfully",
         "config": config
-    })).into_response()
+    }))).into_response()
 }
 
 /// Get system limits
@@ -486,16 +567,34 @@ pub async fn force_confirm_payment(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
     Path(payment_id): Path<String>,
+    body: Option<Json<ForceStatusRequest>>,
 ) -> impl IntoResponse {
     if let Err(response) = verify_admin_access(&state, &context).await {
         return response.into_response();
     }
 
-    Json(json!({
-        "payment_id": payment_id,
-        "status": "confirmed",
-        "message": "Payment force confirmed by admin"
-    })).into_response()
+    let reason = body.and_then(|Json(req)| req.reason)
+        .unwrap_or_else(|| "Force confirmed by admin".to_string());
+
+    match state.payment_service.transition_status(
+        &payment_id,
+        PaymentStatus::Confirmed,
+        TransitionActor::Admin(context.merchant_id),
+        Some(reason),
+    ).await {
+        Ok(previous) => {
+            let change = AuditChange::new("payment", &payment_id)
+                .with_before(json!({ "status": previous }))
+                .with_after(json!({ "status": PaymentStatus::Confirmed }));
+            (Extension(change), Json(json!({
+                "payment_id": payment_id,
+                "previous_status": previous,
+                "status": PaymentStatus::Confirmed,
+                "message": "Payment force confirmed by admin"
+            }))).into_response()
+        }
+        Err(e) => payment_transition_error(e),
+    }
 }
 
 /// Force fail payment
@@ -503,16 +602,45 @@ pub async fn force_fail_payment(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
     Path(payment_id): Path<String>,
+    body: Option<Json<ForceStatusRequest>>,
 ) -> impl IntoResponse {
     if let Err(response) = verify_admin_access(&state, &context).await {
         return response.into_response();
     }
 
-    Json(json!({
-        "payment_id": payment_id,
-        "status": "failed",
-        "message": "Payment force failed by admin"
-    })).into_response()
+    let reason = body.and_then(|Json(req)| req.reason)
+        .unwrap_or_else(|| "Force failed by admin".to_string());
+
+    match state.payment_service.transition_status(
+        &payment_id,
+        PaymentStatus::Failed,
+        TransitionActor::Admin(context.merchant_id),
+        Some(reason),
+    ).await {
+        Ok(previous) => {
+            let change = AuditChange::new("payment", &payment_id)
+                .with_before(json!({ "status": previous }))
+                .with_after(json!({ "status": PaymentStatus::Failed }));
+            (Extension(change), Json(json!({
+                "payment_id": payment_id,
+                "previous_status": previous,
+                "status": PaymentStatus::Failed,
+                "message": "Payment force failed by admin"
+            }))).into_response()
+        }
+        Err(e) => payment_transition_error(e),
+    }
+}
+
+fn payment_transition_error(error: PaymentServiceError) -> axum::response::Response {
+    match error {
+        PaymentServiceError::PaymentNotFound => ServiceError::PaymentNotFound.into_response(),
+        PaymentServiceError::ServiceError(e) => e.into_response(),
+        e => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": e.to_string()}))
+        ).into_response(),
+    }
 }
 
 /// Get all withdrawals (admin view)
@@ -730,15 +858,18 @@ pub async fn transfer_funds(
         return response.into_response();
     }
 
-    Json(json!({
+    let transfer_id = "txn_123456789";
+    let change = AuditChange::new("fund_transfer", transfer_id)
+        .with_after(json!({ "transfer": transfer, "status": "pending" }));
+    (Extension(change), Json(json!({
         "message": "Fund transfer initiated successfully",
-        "transfer_id": "txn_123456789",
+        "transfer_id": transfer_id,
         "from_wallet": transfer.from_wallet,
         "to_wallet": transfer.to_wallet,
         "amount": transfer.amount,
         "crypto_type": transfer.crypto_type,
         "status": "pending"
-    })).into_response()
+    }))).into_response()
 }
 
 /// Get admin users
@@ -840,7 +971,8 @@ pub async fn get_system_health(
         },
         "memory_usage": "45%",
         "cpu_usage": "12%",
-        "disk_usage": "67%"
+        "disk_usage": "67%",
+        "audit_write_failures": crate::middleware::audit::AUDIT_WRITE_FAILURES.get()
     })).into_response()
 }
 
@@ -939,30 +1071,652 @@ pub async fn get_security_events(
     }))).into_response()
 }
 
+/// List security alert cases, newest first
 pub async fn get_security_alerts(
-    Query(query): Query<SecurityQuery>,
+    State(state): State<AppState>,
+    Query(query): Query<AlertQuery>,
 ) -> impl IntoResponse {
-    let alerts = vec![
-        json!({
-            "id": "alert_001",
-            "priority": "high",
-            "status": "active",
-            "description": "Multiple failed login attempts detected",
-            "created_at": chrono::Utc::now()
-        })
-    ];
-    
-    (StatusCode::OK, Json(json!({
-        "data": alerts,
-        "total": 1
-    }))).into_response()
+    match state.aml_service.list_alerts(&query).await {
+        Ok(alerts) => (StatusCode::OK, Json(json!({
+            "data": alerts,
+            "total": alerts.len()
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Get an alert with its case history
+pub async fn get_security_alert(
+    State(state): State<AppState>,
+    Path(alert_id): Path<String>,
+) -> impl IntoResponse {
+    match state.aml_service.get_alert(&alert_id).await {
+        Ok((alert, events)) => (StatusCode::OK, Json(json!({
+            "alert": alert,
+            "events": events
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
 }
 
+/// Acknowledge an alert and start investigating it
 pub async fn acknowledge_alert(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
     Path(alert_id): Path<String>,
 ) -> impl IntoResponse {
-    (StatusCode::OK, Json(json!({
-        "success": true,
-        "message": format!("Alert {} acknowledged", alert_id)
-    }))).into_response()
+    match state.aml_service.acknowledge(&alert_id, admin.admin_id).await {
+        Ok(alert) => {
+            let change = AuditChange::new("security_alert", &alert.alert_id)
+                .with_after(json!({ "status": alert.status, "acknowledged": true }));
+            let change = match alert.merchant_id {
+                Some(merchant_id) => change.with_merchant(merchant_id),
+                None => change,
+            };
+            (Extension(change), Json(alert)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AlertCaseRequest {
+    pub status: CaseStatus,
+    /// Required when closing; stored as the resolution
+    pub note: Option<String>,
+}
+
+/// Move an alert case to open, investigating or closed
+pub async fn update_alert_case(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(alert_id): Path<String>,
+    Json(req): Json<AlertCaseRequest>,
+) -> impl IntoResponse {
+    let before = match state.aml_service.get_alert(&alert_id).await {
+        Ok((alert, _)) => alert.status,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.aml_service.update_case(&alert_id, req.status, admin.admin_id, req.note.as_deref()).await {
+        Ok(alert) => {
+            let change = AuditChange::new("security_alert", &alert.alert_id)
+                .with_before(json!({ "status": before }))
+                .with_after(json!({ "status": alert.status, "resolution": alert.resolution }));
+            let change = match alert.merchant_id {
+                Some(merchant_id) => change.with_merchant(merchant_id),
+                None => change,
+            };
+            (Extension(change), Json(alert)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AlertNoteRequest {
+    pub note: String,
+}
+
+/// Add a note to an alert case
+pub async fn add_alert_note(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(alert_id): Path<String>,
+    Json(req): Json<AlertNoteRequest>,
+) -> impl IntoResponse {
+    match state.aml_service.add_note(&alert_id, admin.admin_id, &req.note).await {
+        Ok(()) => (StatusCode::CREATED, Json(json!({
+            "success": true,
+            "alert_id": alert_id
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UnlockRequest {
+    pub email: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// List active account and IP lockouts
+pub async fn get_account_lockouts(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.account_lockout_service.list_active_lockouts().await {
+        Ok(lockouts) => (StatusCode::OK, Json(json!({
+            "total": lockouts.len(),
+            "data": lockouts
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Lift the lockouts on an email and/or IP
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Json(req): Json<UnlockRequest>,
+) -> impl IntoResponse {
+    match state
+        .account_lockout_service
+        .unlock(req.email.as_deref(), req.ip.as_deref(), admin.admin_id)
+        .await
+    {
+        Ok(unlocked) => (StatusCode::OK, Json(json!({
+            "success": true,
+            "unlocked": unlocked,
+            "message": format!("Lifted {} lockout(s)", unlocked)
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Current encryption key and the stored secrets still on older keys
+pub async fn get_encryption_status(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.key_rotation_service.status().await {
+        Ok(status) => (StatusCode::OK, Json(status)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Re-wrap stored secrets onto the current encryption key now
+pub async fn rewrap_encryption_keys(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+) -> impl IntoResponse {
+    match state.key_rotation_service.rewrap_all().await {
+        Ok(report) => {
+            tracing::info!(
+                "Admin {} re-wrapped {} secrets onto key {}",
+                admin.username, report.rewrapped, report.current_key_id
+            );
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuditVerifyQuery {
+    /// Chain to check; all chains when omitted (0 is the platform chain)
+    pub tenant_id: Option<i64>,
+}
+
+/// Recompute audit log hash chains and report any break
+pub async fn verify_audit_chain(
+    State(state): State<AppState>,
+    Query(query): Query<AuditVerifyQuery>,
+) -> impl IntoResponse {
+    let result = match query.tenant_id {
+        Some(tenant_id) => state.audit_service.verify_chain(tenant_id).await.map(|r| vec![r]),
+        None => state.audit_service.verify_all().await,
+    };
+
+    match result {
+        Ok(chains) => {
+            let intact = chains.iter().all(|c| c.intact);
+            (StatusCode::OK, Json(json!({
+                "intact": intact,
+                "chains": chains
+            }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScreeningHoldQuery {
+    /// held, released or rejected; all holds when omitted
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ReviewHoldRequest {
+    pub notes: Option<String>,
+}
+
+/// List transactions held by sanctions screening
+pub async fn list_screening_holds(
+    State(state): State<AppState>,
+    Query(query): Query<ScreeningHoldQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    match state.screening_service.list_holds(query.status.as_deref(), limit, offset).await {
+        Ok(holds) => (StatusCode::OK, Json(json!({
+            "data": holds,
+            "limit": limit,
+            "offset": offset
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Release a held transaction after compliance review
+///
+/// A released payment is verified again right away, so it settles
+/// without waiting for the merchant to retry.
+pub async fn release_screening_hold(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(hold_id): Path<String>,
+    body: Option<Json<ReviewHoldRequest>>,
+) -> impl IntoResponse {
+    let notes = body.and_then(|Json(req)| req.notes);
+
+    let hold = match state.screening_service.release_hold(&hold_id, admin.admin_id, notes.as_deref()).await {
+        Ok(hold) => hold,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut response = json!({ "hold": hold });
+    if let (SubjectType::Payment, Some(transaction_hash)) = (subject_type(&hold), &hold.transaction_hash) {
+        match state.payment_service.verify_payment(&hold.subject_id, transaction_hash, hold.merchant_id).await {
+            Ok(settled) => response["settled"] = json!(settled),
+            Err(e) => response["verification_error"] = json!(e.to_string()),
+        }
+    }
+
+    let change = AuditChange::new("screening_hold", &hold.hold_id)
+        .with_merchant(hold.merchant_id)
+        .with_before(json!({ "status": "held" }))
+        .with_after(json!({ "status": hold.status, "notes": hold.review_notes }));
+    (Extension(change), Json(response)).into_response()
+}
+
+/// Reject a held transaction after compliance review
+///
+/// Withdrawals are rejected, refunds fail and payments are moved to failed.
+pub async fn reject_screening_hold(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(hold_id): Path<String>,
+    body: Option<Json<ReviewHoldRequest>>,
+) -> impl IntoResponse {
+    let notes = body.and_then(|Json(req)| req.notes);
+
+    let hold = match state.screening_service.reject_hold(&hold_id, admin.admin_id, notes.as_deref()).await {
+        Ok(hold) => hold,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut response = json!({ "hold": hold });
+    if subject_type(&hold) == SubjectType::Payment {
+        match state.payment_service.transition_status(
+            &hold.subject_id,
+            PaymentStatus::Failed,
+            TransitionActor::Admin(admin.admin_id),
+            Some("Rejected by compliance review".to_string()),
+        ).await {
+            Ok(_) => response["payment_status"] = json!(PaymentStatus::Failed),
+            Err(e) => response["payment_error"] = json!(e.to_string()),
+        }
+    }
+
+    let change = AuditChange::new("screening_hold", &hold.hold_id)
+        .with_merchant(hold.merchant_id)
+        .with_before(json!({ "status": "held" }))
+        .with_after(json!({ "status": hold.status, "notes": hold.review_notes }));
+    (Extension(change), Json(response)).into_response()
+}
+
+fn subject_type(hold: &ScreeningHold) -> SubjectType {
+    match hold.subject_type.as_str() {
+        "withdrawal" => SubjectType::Withdrawal,
+        "refund" => SubjectType::Refund,
+        _ => SubjectType::Payment,
+    }
+}
+
+/// Entry counts of the imported sanctions lists and the custom deny list
+pub async fn get_sanctions_lists(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.screening_service.list_summaries().await {
+        Ok(lists) => (StatusCode::OK, Json(json!({
+            "screening_enabled": state.config.sanctions_screening_enabled,
+            "lists": lists
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportListRequest {
+    /// "ofac_sdn" or "file"
+    pub source: String,
+    /// Name of a local list; defaults to "local"
+    pub list_name: Option<String>,
+    /// List content; the configured file is read when omitted
+    pub content: Option<String>,
+}
+
+/// Replace a sanctions list with uploaded content or its configured file
+pub async fn import_sanctions_list(
+    State(state): State<AppState>,
+    Json(req): Json<ImportListRequest>,
+) -> impl IntoResponse {
+    let (source, path): (Box<dyn ListSource>, Option<&String>) = match req.source.as_str() {
+        SOURCE_OFAC_SDN => (Box::new(OfacSdnSource), state.config.ofac_sdn_file.as_ref()),
+        SOURCE_FILE => (
+            Box::new(LocalFileSource::new(req.list_name.as_deref().unwrap_or("local"))),
+            state.config.sanctions_list_file.as_ref(),
+        ),
+        other => {
+            return ServiceError::ValidationError(format!("Unknown list source: {}", other)).into_response();
+        }
+    };
+
+    let result = match (&req.content, path) {
+        (Some(content), _) => state.screening_service.import_list(source.as_ref(), content).await,
+        (None, Some(path)) => state.screening_service.import_file(source.as_ref(), path).await,
+        (None, None) => Err(ServiceError::ValidationError(format!(
+            "No content given and no file configured for {}",
+            req.source
+        ))),
+    };
+
+    match result {
+        Ok(report) => {
+            let change = AuditChange::new("sanctions_list", &format!("{}:{}", report.source, report.list_name))
+                .with_after(json!({ "imported": report.imported, "removed": report.removed }));
+            (Extension(change), Json(report)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// List the custom deny-list entries
+pub async fn get_deny_list(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.screening_service.list_custom_entries().await {
+        Ok(entries) => (StatusCode::OK, Json(json!({
+            "total": entries.len(),
+            "data": entries
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DenyListEntryRequest {
+    pub address: String,
+    pub currency: Option<String>,
+    pub reason: String,
+}
+
+/// Add an address to the custom deny list
+pub async fn add_deny_list_entry(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Json(req): Json<DenyListEntryRequest>,
+) -> impl IntoResponse {
+    match state
+        .screening_service
+        .add_custom_entry(&req.address, req.currency.as_deref(), &req.reason, admin.admin_id)
+        .await
+    {
+        Ok(entry) => {
+            let change = AuditChange::new("deny_list_entry", &entry.id.to_string())
+                .with_after(json!({ "address": entry.address, "reason": entry.reason }));
+            (StatusCode::CREATED, Extension(change), Json(entry)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Remove an address from the custom deny list
+pub async fn remove_deny_list_entry(
+    State(state): State<AppState>,
+    Path(entry_id): Path<i64>,
+) -> impl IntoResponse {
+    match state.screening_service.remove_custom_entry(entry_id).await {
+        Ok(entry) => {
+            let change = AuditChange::new("deny_list_entry", &entry.id.to_string())
+                .with_before(json!({ "address": entry.address, "reason": entry.reason }));
+            (Extension(change), Json(json!({
+                "success": true,
+                "removed": entry
+            }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ScreenAddressQuery {
+    pub address: String,
+}
+
+/// Check an address against every list without creating a hold
+pub async fn screen_address(
+    State(state): State<AppState>,
+    Query(query): Query<ScreenAddressQuery>,
+) -> impl IntoResponse {
+    match state.screening_service.find_match(&query.address).await {
+        Ok(list_match) => (StatusCode::OK, Json(json!({
+            "address": query.address,
+            "listed": list_match.is_some(),
+            "match": list_match
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// List the AML monitoring rules and their parameters
+pub async fn get_aml_rules(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.aml_service.list_rules().await {
+        Ok(rules) => (StatusCode::OK, Json(json!({
+            "monitoring_enabled": state.config.aml_monitoring_enabled,
+            "rules": rules
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Enable, disable or tune an AML rule
+pub async fn update_aml_rule(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(rule_id): Path<String>,
+    Json(req): Json<UpdateAmlRule>,
+) -> impl IntoResponse {
+    let before = match state.aml_service.list_rules().await {
+        Ok(rules) => rules.into_iter().find(|rule| rule.rule_id == rule_id),
+        Err(e) => return e.into_response(),
+    };
+
+    match state.aml_service.update_rule(&rule_id, req, admin.admin_id).await {
+        Ok(rule) => {
+            let mut change = AuditChange::new("aml_rule", &rule.rule_id).with_after(json!(rule));
+            if let Some(before) = before {
+                change = change.with_before(json!(before));
+            }
+            (Extension(change), Json(rule)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Run the AML rules now instead of waiting for the next scheduled run
+pub async fn run_aml_monitoring(
+    State(state): State<AppState>,
+) -> impl IntoResponse {
+    match state.aml_service.evaluate().await {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FreezeWithdrawalsRequest {
+    pub reason: String,
+    /// Alert the freeze belongs to, if any
+    pub alert_id: Option<String>,
+}
+
+/// Freeze a merchant's withdrawals pending review
+pub async fn freeze_merchant_withdrawals(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<i64>,
+    Json(req): Json<FreezeWithdrawalsRequest>,
+) -> impl IntoResponse {
+    if req.reason.trim().is_empty() {
+        return ServiceError::ValidationError("A reason is required".to_string()).into_response();
+    }
+
+    match state.aml_service.freeze_withdrawals(merchant_id, &req.reason, req.alert_id.as_deref()).await {
+        Ok(freeze) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_after(json!({ "withdrawals_frozen": freeze.frozen, "reason": freeze.reason }));
+            (Extension(change), Json(freeze)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UnfreezeWithdrawalsRequest {
+    pub note: Option<String>,
+}
+
+/// Lift a withdrawal freeze after review
+pub async fn unfreeze_merchant_withdrawals(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(merchant_id): Path<i64>,
+    body: Option<Json<UnfreezeWithdrawalsRequest>>,
+) -> impl IntoResponse {
+    let note = body.and_then(|Json(req)| req.note);
+
+    match state.aml_service.unfreeze_withdrawals(merchant_id, admin.admin_id, note.as_deref()).await {
+        Ok(freeze) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_before(json!({ "withdrawals_frozen": true }))
+                .with_after(json!({ "withdrawals_frozen": freeze.frozen }));
+            (Extension(change), Json(freeze)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OnboardingReviewQuery {
+    /// Defaults to under_review
+    pub status: Option<OnboardingStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Merchants waiting for (or past) onboarding review
+pub async fn list_onboarding_reviews(
+    State(state): State<AppState>,
+    Query(query): Query<OnboardingReviewQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    match state.onboarding_service.list_reviews(query.status, limit, offset).await {
+        Ok(reviews) => (StatusCode::OK, Json(json!({
+            "data": reviews,
+            "limit": limit,
+            "offset": offset
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Business details, documents and history of a merchant's onboarding
+pub async fn get_merchant_onboarding_review(
+    State(state): State<AppState>,
+    Path(merchant_id): Path<i64>,
+) -> impl IntoResponse {
+    match state.onboarding_service.get_onboarding(merchant_id).await {
+        Ok(onboarding) => (StatusCode::OK, Json(onboarding)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Download an uploaded onboarding document
+pub async fn download_onboarding_document(
+    State(state): State<AppState>,
+    Path((merchant_id, document_id)): Path<(i64, String)>,
+) -> impl IntoResponse {
+    match state.onboarding_service.get_document(merchant_id, &document_id).await {
+        Ok((document, bytes)) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, document.content_type),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", document.file_name)),
+            ],
+            bytes,
+        ).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ApproveOnboardingRequest {
+    /// KYC tier granted on approval; basic when omitted
+    pub tier: Option<KycTier>,
+    pub note: Option<String>,
+}
+
+/// Approve a merchant under review, unlocking live mode, withdrawals and a higher KYC tier
+pub async fn approve_merchant_onboarding(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(merchant_id): Path<i64>,
+    body: Option<Json<ApproveOnboardingRequest>>,
+) -> impl IntoResponse {
+    let (tier, note) = body
+        .map(|Json(req)| (req.tier.unwrap_or(KycTier::Basic), req.note))
+        .unwrap_or((KycTier::Basic, None));
+
+    match state.onboarding_service.approve(merchant_id, admin.admin_id, tier, note.as_deref()).await {
+        Ok(onboarding) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_before(json!({ "onboarding_status": OnboardingStatus::UnderReview }))
+                .with_after(json!({ "onboarding_status": onboarding.status, "kyc_tier": tier, "note": note }));
+            (Extension(change), Json(onboarding)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RejectOnboardingRequest {
+    pub reason: String,
+}
+
+/// Reject a merchant under review; the merchant may correct and resubmit
+pub async fn reject_merchant_onboarding(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AdminContext>,
+    Path(merchant_id): Path<i64>,
+    Json(req): Json<RejectOnboardingRequest>,
+) -> impl IntoResponse {
+    match state.onboarding_service.reject(merchant_id, admin.admin_id, &req.reason).await {
+        Ok(onboarding) => {
+            let change = AuditChange::new("merchant", &merchant_id.to_string())
+                .with_merchant(merchant_id)
+                .with_before(json!({ "onboarding_status": OnboardingStatus::UnderReview }))
+                .with_after(json!({ "onboarding_status": onboarding.status, "reason": req.reason }));
+            (Extension(change), Json(onboarding)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
 }
@@ -2,7 +2,7 @@
 // Separate admin routing with session-based authentication
 
 use crate::api::admin_handlers;
-use crate::middleware::admin_auth;
+use crate::middleware::{admin_auth, audit};
 use axum::{
     middleware as axum_middleware,
     routing::{get, post, put, delete},
@@ -24,11 +24,23 @@ pub fn create_admin_router(state: AppState) -> Router<AppState> {
         .route("/api/v1/admin/merchants/:merchant_id/suspend", post(admin_handlers::suspend_merchant))
         .route("/api/v1/admin/merchants/:merchant_id/activate", post(admin_handlers::activate_merchant))
         .route("/api/v1/admin/merchants/:merchant_id/delete", delete(admin_handlers::delete_merchant))
+        .route("/api/v1/admin/merchants/:merchant_id/rate-limit-plan", put(admin_handlers::update_merchant_rate_limit_plan))
+        .route("/api/v1/admin/merchants/:merchant_id/kyc-tier", put(admin_handlers::update_merchant_kyc_tier))
+        .route("/api/v1/admin/merchants/:merchant_id/withdrawals/freeze", post(admin_handlers::freeze_merchant_withdrawals))
+        .route("/api/v1/admin/merchants/:merchant_id/withdrawals/unfreeze", post(admin_handlers::unfreeze_merchant_withdrawals))
         
         // Admin Security Management
         .route("/api/v1/admin/security/events", get(admin_handlers::get_security_events))
         .route("/api/v1/admin/security/alerts", get(admin_handlers::get_security_alerts))
+        .route("/api/v1/admin/security/alerts/:alert_id", get(admin_handlers::get_security_alert))
         .route("/api/v1/admin/security/alerts/:alert_id/acknowledge", post(admin_handlers::acknowledge_alert))
+        .route("/api/v1/admin/security/alerts/:alert_id/status", post(admin_handlers::update_alert_case))
+        .route("/api/v1/admin/security/alerts/:alert_id/notes", post(admin_handlers::add_alert_note))
+        .route("/api/v1/admin/security/lockouts", get(admin_handlers::get_account_lockouts))
+        .route("/api/v1/admin/security/lockouts/unlock", post(admin_handlers::unlock_account))
+        .route("/api/v1/admin/security/encryption", get(admin_handlers::get_encryption_status))
+        .route("/api/v1/admin/security/encryption/rewrap", post(admin_handlers::rewrap_encryption_keys))
+        .route("/api/v1/admin/security/audit/verify", get(admin_handlers::verify_audit_chain))
         .route("/api/v1/admin/security/settings", get(admin_handlers::get_security_settings))
         .route("/api/v1/admin/security/settings", put(admin_handlers::update_security_settings))
         
@@ -51,6 +63,27 @@ pub fn create_admin_router(state: AppState) -> Router<AppState> {
         .route("/api/v1/admin/withdrawals/:withdrawal_id/approve", post(admin_handlers::approve_withdrawal))
         .route("/api/v1/admin/withdrawals/:withdrawal_id/reject", post(admin_handlers::reject_withdrawal))
         
+        // Admin Compliance Screening
+        .route("/api/v1/admin/compliance/holds", get(admin_handlers::list_screening_holds))
+        .route("/api/v1/admin/compliance/holds/:hold_id/release", post(admin_handlers::release_screening_hold))
+        .route("/api/v1/admin/compliance/holds/:hold_id/reject", post(admin_handlers::reject_screening_hold))
+        .route("/api/v1/admin/compliance/sanctions-lists", get(admin_handlers::get_sanctions_lists))
+        .route("/api/v1/admin/compliance/sanctions-lists/import", post(admin_handlers::import_sanctions_list))
+        .route("/api/v1/admin/compliance/deny-list", get(admin_handlers::get_deny_list))
+        .route("/api/v1/admin/compliance/deny-list", post(admin_handlers::add_deny_list_entry))
+        .route("/api/v1/admin/compliance/deny-list/:entry_id", delete(admin_handlers::remove_deny_list_entry))
+        .route("/api/v1/admin/compliance/screen", get(admin_handlers::screen_address))
+        .route("/api/v1/admin/compliance/aml/rules", get(admin_handlers::get_aml_rules))
+        .route("/api/v1/admin/compliance/aml/rules/:rule_id", put(admin_handlers::update_aml_rule))
+        .route("/api/v1/admin/compliance/aml/run", post(admin_handlers::run_aml_monitoring))
+        
+        // Admin Merchant Onboarding Review
+        .route("/api/v1/admin/onboarding", get(admin_handlers::list_onboarding_reviews))
+        .route("/api/v1/admin/onboarding/:merchant_id", get(admin_handlers::get_merchant_onboarding_review))
+        .route("/api/v1/admin/onboarding/:merchant_id/documents/:document_id", get(admin_handlers::download_onboarding_document))
+        .route("/api/v1/admin/onboarding/:merchant_id/approve", post(admin_handlers::approve_merchant_onboarding))
+        .route("/api/v1/admin/onboarding/:merchant_id/reject", post(admin_handlers::reject_merchant_onboarding))
+        
         // Admin Analytics & Reporting
         .route("/api/v1/admin/analytics/platform", get(admin_handlers::get_platform_analytics))
         .route("/api/v1/admin/analytics/revenue", get(admin_handlers::get_revenue_analytics))
@@ -75,6 +108,12 @@ pub fn create_admin_router(state: AppState) -> Router<AppState> {
         .route("/api/v1/admin/system/backup", post(admin_handlers::create_system_backup))
         .route("/api/v1/admin/system/maintenance", post(admin_handlers::toggle_maintenance_mode))
         
+        // Record mutating admin calls
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            audit::audit_middleware,
+        ))
+
         // Apply admin session authentication
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
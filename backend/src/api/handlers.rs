@@ -10,12 +10,26 @@
 
 use crate::api::state::AppState;
 use crate::middleware::auth::MerchantContext;
+use crate::middleware::client_ip::ClientIp;
+use crate::services::account_lockout_service::AttemptKind;
+use crate::services::ip_whitelist_service::IpWhitelistScope;
+use crate::services::audit_service::AuditChange;
+use crate::services::merchant_service::MerchantService;
+use crate::services::multi_user_service::{InviteUserRequest, Permission};
+use crate::services::notification_service::UpdatePreferencesRequest;
+use crate::services::onboarding_service::{BusinessInfoRequest, Capability, DocumentType};
 use crate::payment::models::{CreatePaymentRequest, PaymentFilters, CryptoType};
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State, Request, Extension},
-    http::StatusCode,
-    response::{IntoResponse, Json},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json,
+    },
 };
+use futures::StreamExt;
+use std::convert::Infallible;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sqlx::PgPool;
@@ -91,10 +105,27 @@ pub struct MerchantProfile {
 
 pub async fn register_merchant(
     State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
     Json(req): Json<RegisterMerchantRequest>,
 ) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+    if let Err(e) = state.account_lockout_service.check_lockout(AttemptKind::Register, None, ip.as_deref()).await {
+        return e.into_response();
+    }
+    if let Err(e) = state.account_lockout_service.record_submission(AttemptKind::Register, None, ip.as_deref()).await {
+        tracing::warn!("Failed to record registration attempt: {}", e);
+    }
+
     match state.merchant_service.register_merchant(&req.email, &req.business_name).await {
         Ok(response) => {
+            if let Err(e) = state.merchant_service.set_password(response.merchant_id, &req.password).await {
+                return e.into_response();
+            }
+            if state.config.merchant_email_verification_required {
+                if let Err(e) = state.email_token_service.send_verification(response.merchant_id).await {
+                    tracing::warn!("Failed to queue verification email for merchant {}: {}", response.merchant_id, e);
+                }
+            }
             let auth_response = AuthResponse {
                 user: MerchantProfile {
                     id: response.merchant_id,
@@ -113,17 +144,61 @@ pub async fn register_merchant(
 
 pub async fn login_merchant(
     State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
     Json(req): Json<LoginMerchantRequest>,
 ) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+    if let Err(e) = state.account_lockout_service.check_lockout(AttemptKind::Login, Some(&req.email), ip.as_deref()).await {
+        return e.into_response();
+    }
+
     // Query the database for the user
     match sqlx::query!(
-        "SELECT id, business_name, email, sandbox_mode, created_at, role::text as role, api_key_hash FROM merchants WHERE email = $1 AND is_active = true",
+        "SELECT id, business_name, email, sandbox_mode, created_at, role::text as role, api_key_hash, password_hash FROM merchants WHERE email = $1 AND is_active = true",
         req.email
     )
     .fetch_optional(&state.db_pool)
     .await
     {
         Ok(Some(merchant)) => {
+            // Merchants registered before passwords were stored have no hash yet
+            if let Some(password_hash) = merchant.password_hash.as_deref() {
+                if !MerchantService::password_matches(password_hash, &req.password) {
+                    record_failed_login(&state, &req.email, ip.as_deref()).await;
+                    return (StatusCode::UNAUTHORIZED, Json(json!({
+                        "error": "Invalid credentials",
+                        "message": "Email or password is incorrect"
+                    }))).into_response();
+                }
+            }
+
+            // Merchants with 2FA enabled must supply a current code
+            let two_factor_enabled = match state.two_factor_service.is_enabled(merchant.id).await {
+                Ok(enabled) => enabled,
+                Err(e) => return e.into_response(),
+            };
+            if two_factor_enabled {
+                let verified = match req.two_factor_code.as_deref() {
+                    Some(code) => state.two_factor_service.verify_code(merchant.id, code).await,
+                    None => Ok(false),
+                };
+                match verified {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        record_failed_login(&state, &req.email, ip.as_deref()).await;
+                        return crate::error::ServiceError::TwoFactorRequired(
+                            "A valid two_factor_code is required".to_string(),
+                        )
+                        .into_response()
+                    }
+                    Err(e) => return e.into_response(),
+                }
+            }
+
+            if let Err(e) = state.account_lockout_service.record_successful_login(&req.email, ip.as_deref()).await {
+                tracing::warn!("Failed to record successful login: {}", e);
+            }
+
             let api_key = match merchant.role.as_deref() {
                 Some("SUPER_ADMIN") if req.email == "superadmin@fiddupay.com" => {
                     "superadmin_api_key_2026_secure".to_string()
@@ -148,7 +223,7 @@ pub async fn login_merchant(
                             business_name: merchant.business_name,
                             email: merchant.email,
                             created_at: merchant.created_at.to_rfc3339(),
-                            two_factor_enabled: false,
+                            two_factor_enabled,
                         },
                         api_key: format!("admin_session_{}", merchant.id), // Session token, not API key
                     }
@@ -161,7 +236,7 @@ pub async fn login_merchant(
                             business_name: merchant.business_name,
                             email: merchant.email,
                             created_at: merchant.created_at.to_rfc3339(),
-                            two_factor_enabled: false,
+                            two_factor_enabled,
                         },
                         api_key,
                     }
@@ -170,6 +245,7 @@ pub async fn login_merchant(
             (StatusCode::OK, Json(auth_response)).into_response()
         }
         Ok(None) => {
+            record_failed_login(&state, &req.email, ip.as_deref()).await;
             (StatusCode::UNAUTHORIZED, Json(json!({
                 "error": "Invalid credentials",
                 "message": "Email or password is incorrect"
@@ -184,6 +260,103 @@ pub async fn login_merchant(
     }
 }
 
+/// Count a failed login toward the lockout for its email and IP
+async fn record_failed_login(state: &AppState, email: &str, ip: Option<&str>) {
+    if let Err(e) = state
+        .account_lockout_service
+        .record_failed_attempt(AttemptKind::Login, Some(email), ip)
+        .await
+    {
+        tracing::warn!("Failed to record failed login: {}", e);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+/// Redeem the link from the verification email
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> impl IntoResponse {
+    match state.email_token_service.verify_email(&req.token).await {
+        Ok(status) => (StatusCode::OK, Json(json!({
+            "email_verified": true,
+            "onboarding_status": status
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Send a fresh verification link, superseding earlier ones
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.email_token_service.send_verification(context.merchant_id).await {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({
+            "message": "Verification email sent"
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct PasswordResetRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+/// Email a password reset link; the response does not reveal whether the address has an account
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
+    Json(req): Json<PasswordResetRequest>,
+) -> impl IntoResponse {
+    if let Err(e) = req.validate() {
+        return crate::error::ServiceError::ValidationError(e.to_string()).into_response();
+    }
+
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+    if let Err(e) = state.account_lockout_service.check_lockout(AttemptKind::PasswordReset, Some(&req.email), ip.as_deref()).await {
+        return e.into_response();
+    }
+    if let Err(e) = state.account_lockout_service.record_submission(AttemptKind::PasswordReset, Some(&req.email), ip.as_deref()).await {
+        tracing::warn!("Failed to record password reset request: {}", e);
+    }
+
+    match state.email_token_service.request_password_reset(&req.email, ip.as_deref()).await {
+        Ok(()) => (StatusCode::ACCEPTED, Json(json!({
+            "message": "If an account exists for this email, a password reset link has been sent"
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Set a new password with the token from the reset email
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
+    Json(req): Json<ConfirmPasswordResetRequest>,
+) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+
+    match state.email_token_service.reset_password(&req.token, &req.new_password, ip.as_deref()).await {
+        Ok(()) => (StatusCode::OK, Json(json!({
+            "password_reset": true
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
 pub async fn get_merchant_profile(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
@@ -196,6 +369,11 @@ pub async fn get_merchant_profile(
     .await
     {
         Ok(Some(merchant)) => {
+            let two_factor_enabled = state
+                .two_factor_service
+                .is_enabled(merchant.id)
+                .await
+                .unwrap_or(false);
             let mut profile = json!({
                 "id": merchant.id,
                 "business_name": merchant.business_name,
@@ -203,13 +381,16 @@ pub async fn get_merchant_profile(
                 "sandbox_mode": merchant.sandbox_mode,
                 "kyc_verified": merchant.kyc_verified,
                 "created_at": merchant.created_at.to_rfc3339(),
-                "two_factor_enabled": false
+                "two_factor_enabled": two_factor_enabled
             });
             
-            // Add daily volume remaining for non-KYC merchants
-            if !merchant.kyc_verified.unwrap_or(false) {
-                // For now, return a mock value - in production this would be calculated
-                profile["daily_volume_remaining"] = json!("1000.00");
+            // Add daily volume remaining for merchants with a limit
+            if let Ok(Some(remaining)) = state
+                .volume_tracking_service
+                .get_remaining_daily_volume(merchant.id)
+                .await
+            {
+                profile["daily_volume_remaining"] = json!(remaining);
             }
             
             (StatusCode::OK, Json(profile)).into_response()
@@ -219,11 +400,368 @@ pub async fn get_merchant_profile(
     }
 }
 
+/// Onboarding stage, submitted details and documents, and unlocked capabilities
+pub async fn get_onboarding(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.onboarding_service.get_onboarding(context.merchant_id).await {
+        Ok(onboarding) => (StatusCode::OK, Json(onboarding)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn submit_business_info(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<BusinessInfoRequest>,
+) -> impl IntoResponse {
+    match state.onboarding_service.submit_business_info(context.merchant_id, req).await {
+        Ok(onboarding) => (StatusCode::OK, Json(onboarding)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UploadDocumentQuery {
+    pub document_type: DocumentType,
+    pub file_name: String,
+}
+
+/// Upload an onboarding document as the raw request body
+///
+/// The `Content-Type` header gives the file type (PDF, PNG or JPEG).
+pub async fn upload_onboarding_document(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<UploadDocumentQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let content_type = headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    match state
+        .onboarding_service
+        .upload_document(context.merchant_id, query.document_type, &query.file_name, content_type, body.to_vec())
+        .await
+    {
+        Ok(document) => (StatusCode::CREATED, Json(document)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Send the completed application for review
+pub async fn submit_onboarding_for_review(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.onboarding_service.submit_for_review(context.merchant_id).await {
+        Ok(onboarding) => (StatusCode::OK, Json(onboarding)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NotificationPreferencesQuery {
+    /// Team member whose preferences to use; the caller's own when omitted
+    pub user_id: Option<i32>,
+    /// Use the merchant-wide preferences instead of a team member's
+    #[serde(default)]
+    pub merchant: bool,
+}
+
+/// Whose preferences a request may read or change
+///
+/// The owner may pick any team member; a team member gets their own unless
+/// their role lets them manage users. The merchant-wide preferences
+/// (`?merchant=true`) likewise need the owner or a role that manages users.
+fn preferences_user(
+    context: &MerchantContext,
+    query: &NotificationPreferencesQuery,
+) -> Result<Option<i32>, crate::error::ServiceError> {
+    if query.merchant {
+        if !context.allows(Permission::ManageUsers) {
+            return Err(crate::error::ServiceError::Forbidden(
+                "Your role cannot manage the merchant's notification preferences".to_string(),
+            ));
+        }
+        return Ok(None);
+    }
+    let requested = query.user_id;
+    let Some(user) = &context.user else {
+        return Ok(requested);
+    };
+    match requested {
+        None => Ok(Some(user.user_id)),
+        Some(user_id) if user_id == user.user_id || context.allows(Permission::ManageUsers) => Ok(Some(user_id)),
+        Some(_) => Err(crate::error::ServiceError::Forbidden(
+            "Team members can only manage their own notification preferences".to_string(),
+        )),
+    }
+}
+
+/// Which notification emails a merchant user receives, and in which language
+pub async fn get_notification_preferences(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<NotificationPreferencesQuery>,
+) -> impl IntoResponse {
+    let user_id = match preferences_user(&context, &query) {
+        Ok(user_id) => user_id,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.notification_service.get_preferences(context.merchant_id, user_id).await {
+        Ok(preferences) => (StatusCode::OK, Json(preferences)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn update_notification_preferences(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<NotificationPreferencesQuery>,
+    Json(req): Json<UpdatePreferencesRequest>,
+) -> impl IntoResponse {
+    let user_id = match preferences_user(&context, &query) {
+        Ok(user_id) => user_id,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.notification_service.update_preferences(context.merchant_id, user_id, req).await {
+        Ok(preferences) => (StatusCode::OK, Json(preferences)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EmailDeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
+/// Recently queued emails with their delivery status
+pub async fn list_email_deliveries(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<EmailDeliveriesQuery>,
+) -> impl IntoResponse {
+    match state.notification_service.list_emails(context.merchant_id, query.limit.unwrap_or(50)).await {
+        Ok(emails) => (StatusCode::OK, Json(json!({ "emails": emails }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TeamLoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+/// Sign a team member in, returning a session token for the merchant routes
+pub async fn team_login(
+    State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
+    Json(req): Json<TeamLoginRequest>,
+) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+    if let Err(e) = state.account_lockout_service.check_lockout(AttemptKind::Login, Some(&req.email), ip.as_deref()).await {
+        return e.into_response();
+    }
+
+    match state.multi_user_service.login(&req.email, &req.password).await {
+        Ok(session) => {
+            if let Err(e) = state.account_lockout_service.record_successful_login(&req.email, ip.as_deref()).await {
+                tracing::warn!("Failed to record successful login: {}", e);
+            }
+            (StatusCode::OK, Json(session)).into_response()
+        }
+        Err(e @ crate::error::ServiceError::Unauthorized(_)) => {
+            record_failed_login(&state, &req.email, ip.as_deref()).await;
+            e.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+    pub password: String,
+}
+
+/// Join a merchant's team with the token from the invitation email
+pub async fn accept_team_invitation(
+    State(state): State<AppState>,
+    Json(req): Json<AcceptInvitationRequest>,
+) -> impl IntoResponse {
+    match state.multi_user_service.accept_invitation(&req.token, &req.password).await {
+        Ok(session) => (StatusCode::CREATED, Json(session)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Who the request is authenticated as, and what their role allows
+pub async fn get_team_identity(
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    let permissions: Vec<&str> = [Permission::ManageUsers, Permission::ApproveWithdrawals, Permission::ViewAnalytics]
+        .into_iter()
+        .filter(|permission| context.allows(*permission))
+        .map(|permission| permission.as_str())
+        .collect();
+
+    (StatusCode::OK, Json(json!({
+        "merchant_id": context.merchant_id,
+        "owner": context.is_owner(),
+        "user_id": context.user.as_ref().map(|user| user.user_id),
+        "email": context.user.as_ref().map(|user| user.email.clone()),
+        "role": context.user.as_ref().map_or("MERCHANT", |user| user.role.as_str()),
+        "permissions": permissions
+    }))).into_response()
+}
+
+/// End every session of the calling team member
+pub async fn team_logout(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    let Some(user) = &context.user else {
+        return crate::error::ServiceError::ValidationError(
+            "API keys have no session to end".to_string(),
+        )
+        .into_response();
+    };
+
+    match state.multi_user_service.sign_out(context.merchant_id, user.user_id).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_team_members(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.multi_user_service.list_users(context.merchant_id).await {
+        Ok(members) => (StatusCode::OK, Json(json!({ "members": members }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Email an invitation to join the team with a role
+pub async fn invite_team_member(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<InviteUserRequest>,
+) -> impl IntoResponse {
+    let invited_by = context.user.as_ref().map(|user| user.user_id);
+
+    match state.multi_user_service.invite(context.merchant_id, invited_by, req).await {
+        Ok(invitation) => {
+            let change = AuditChange::new("team_invitation", &invitation.invitation_id)
+                .with_after(json!({ "email": invitation.email, "role": invitation.role }));
+            (StatusCode::CREATED, Extension(change), Json(invitation)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_team_invitations(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.multi_user_service.list_invitations(context.merchant_id).await {
+        Ok(invitations) => (StatusCode::OK, Json(json!({ "invitations": invitations }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn revoke_team_invitation(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(invitation_id): Path<String>,
+) -> impl IntoResponse {
+    match state.multi_user_service.revoke_invitation(context.merchant_id, &invitation_id).await {
+        Ok(()) => (StatusCode::OK, Json(json!({
+            "invitation_id": invitation_id,
+            "revoked": true
+        }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTeamRoleRequest {
+    pub role: String,
+}
+
+pub async fn update_team_member_role(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(user_id): Path<i32>,
+    Json(req): Json<UpdateTeamRoleRequest>,
+) -> impl IntoResponse {
+    let previous = match state.multi_user_service.get_user(context.merchant_id, user_id).await {
+        Ok(user) => user.role,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.multi_user_service.update_role(context.merchant_id, user_id, &req.role).await {
+        Ok(user) => {
+            let change = AuditChange::new("merchant_user", &user_id.to_string())
+                .with_before(json!({ "role": previous }))
+                .with_after(json!({ "role": user.role }));
+            (StatusCode::OK, Extension(change), Json(user)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Remove a member from the team, ending their sessions
+pub async fn remove_team_member(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(user_id): Path<i32>,
+) -> impl IntoResponse {
+    match state.multi_user_service.deactivate_user(context.merchant_id, user_id).await {
+        Ok(()) => {
+            let change = AuditChange::new("merchant_user", &user_id.to_string())
+                .with_before(json!({ "is_active": true }))
+                .with_after(json!({ "is_active": false }));
+            (StatusCode::OK, Extension(change), Json(json!({
+                "user_id": user_id,
+                "removed": true
+            }))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Daily volume limit of the merchant's KYC tier, usage and remaining headroom
+pub async fn get_merchant_limits(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.volume_tracking_service.get_limits(context.merchant_id).await {
+        Ok(limits) => (StatusCode::OK, Json(limits)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
 pub async fn switch_environment(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<SwitchEnvironmentRequest>,
 ) -> impl IntoResponse {
+    if req.to_live {
+        if let Err(e) = state.onboarding_service.require(context.merchant_id, Capability::LiveMode).await {
+            return e.into_response();
+        }
+    }
+
     match state.merchant_service.switch_environment(context.merchant_id, req.to_live).await {
         Ok(api_key) => (StatusCode::OK, Json(json!({"api_key": api_key, "environment": if req.to_live { "live" } else { "sandbox" }}))).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
@@ -240,6 +778,12 @@ pub async fn generate_api_key(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<GenerateApiKeyRequest>,
 ) -> impl IntoResponse {
+    if req.is_live {
+        if let Err(e) = state.onboarding_service.require(context.merchant_id, Capability::LiveMode).await {
+            return e.into_response();
+        }
+    }
+
     match state.merchant_service.generate_and_store_api_key(context.merchant_id, req.is_live).await {
         Ok(api_key) => (StatusCode::OK, Json(json!({"api_key": api_key}))).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
@@ -303,12 +847,263 @@ pub async fn set_webhook(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<SetWebhookRequest>,
 ) -> impl IntoResponse {
+    let previous = match state.webhook_endpoint_service.list_endpoints(context.merchant_id).await {
+        Ok(endpoints) => endpoints.into_iter().find(|e| e.is_primary).map(|e| e.url),
+        Err(e) => return e.into_response(),
+    };
+    let change = AuditChange::new("webhook_config", &context.merchant_id.to_string())
+        .with_before(json!({ "url": previous }))
+        .with_after(json!({ "url": req.url }));
+
     match state.webhook_service.set_webhook_url(context.merchant_id, req.url).await {
-        Ok(_) => (StatusCode::OK, Json(json!({"success": true}))).into_response(),
+        // A newly created primary endpoint's secret is only shown here
+        Ok(Some(secret)) => (StatusCode::OK, Extension(change), Json(json!({"success": true, "secret": secret}))).into_response(),
+        Ok(None) => (StatusCode::OK, Extension(change), Json(json!({"success": true}))).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
 
+// ============================================================================
+// Two-Factor Authentication
+// ============================================================================
+
+pub async fn get_two_factor_status(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.two_factor_service.get_status(context.merchant_id).await {
+        Ok(status) => (StatusCode::OK, Json(status)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn setup_two_factor(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    let email = match sqlx::query_scalar!(
+        "SELECT email FROM merchants WHERE id = $1",
+        context.merchant_id
+    )
+    .fetch_one(&state.db_pool)
+    .await
+    {
+        Ok(email) => email,
+        Err(e) => return crate::error::ServiceError::from(e).into_response(),
+    };
+
+    match state.two_factor_service.setup_2fa(context.merchant_id, &email).await {
+        Ok(setup) => (StatusCode::OK, Json(setup)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn enable_two_factor(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::two_factor_service::TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    match state.two_factor_service.enable_2fa(context.merchant_id, &req.code).await {
+        Ok(_) => (StatusCode::OK, Json(json!({"success": true, "enabled": true}))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn disable_two_factor(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::two_factor_service::TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    match state.two_factor_service.disable_2fa(context.merchant_id, &req.code).await {
+        Ok(_) => (StatusCode::OK, Json(json!({"success": true, "enabled": false}))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::two_factor_service::TwoFactorCodeRequest>,
+) -> impl IntoResponse {
+    match state.two_factor_service.regenerate_recovery_codes(context.merchant_id, &req.code).await {
+        Ok(codes) => (StatusCode::OK, Json(json!({"recovery_codes": codes}))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============================================================================
+// Webhook Endpoint Management
+// ============================================================================
+
+pub async fn list_webhook_endpoints(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.webhook_endpoint_service.list_endpoints(context.merchant_id).await {
+        Ok(endpoints) => (StatusCode::OK, Json(json!({"data": endpoints}))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn create_webhook_endpoint(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::webhook_endpoint_service::CreateWebhookEndpointRequest>,
+) -> impl IntoResponse {
+    match state.webhook_endpoint_service.create_endpoint(context.merchant_id, req).await {
+        Ok(created) => {
+            let change = AuditChange::new("webhook_endpoint", &created.endpoint.endpoint_id)
+                .with_after(json!(created.endpoint));
+            (StatusCode::CREATED, Extension(change), Json(created)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_webhook_endpoint(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(endpoint_id): Path<String>,
+) -> impl IntoResponse {
+    match state.webhook_endpoint_service.get_endpoint(context.merchant_id, &endpoint_id).await {
+        Ok(endpoint) => (StatusCode::OK, Json(endpoint)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn update_webhook_endpoint(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(endpoint_id): Path<String>,
+    Json(req): Json<crate::services::webhook_endpoint_service::UpdateWebhookEndpointRequest>,
+) -> impl IntoResponse {
+    let previous = match state.webhook_endpoint_service.get_endpoint(context.merchant_id, &endpoint_id).await {
+        Ok(endpoint) => endpoint,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.webhook_endpoint_service.update_endpoint(context.merchant_id, &endpoint_id, req).await {
+        Ok(endpoint) => {
+            let change = AuditChange::new("webhook_endpoint", &endpoint_id)
+                .with_before(json!(previous))
+                .with_after(json!(endpoint));
+            (StatusCode::OK, Extension(change), Json(endpoint)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn delete_webhook_endpoint(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(endpoint_id): Path<String>,
+) -> impl IntoResponse {
+    let previous = match state.webhook_endpoint_service.get_endpoint(context.merchant_id, &endpoint_id).await {
+        Ok(endpoint) => endpoint,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.webhook_endpoint_service.delete_endpoint(context.merchant_id, &endpoint_id).await {
+        Ok(_) => {
+            let change = AuditChange::new("webhook_endpoint", &endpoint_id).with_before(json!(previous));
+            (StatusCode::OK, Extension(change), Json(json!({"success": true}))).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn rotate_webhook_secret(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(endpoint_id): Path<String>,
+    req: Option<Json<crate::services::webhook_endpoint_service::RotateSecretRequest>>,
+) -> impl IntoResponse {
+    let rollover_hours = req.and_then(|Json(req)| req.rollover_hours);
+    match state
+        .webhook_endpoint_service
+        .rotate_secret(context.merchant_id, &endpoint_id, rollover_hours)
+        .await
+    {
+        Ok(rotated) => {
+            // Secrets never go into the audit log, only when the old one stops working
+            let change = AuditChange::new("webhook_endpoint", &endpoint_id)
+                .with_after(json!({ "previous_secret_expires_at": rotated.endpoint.previous_secret_expires_at }));
+            (StatusCode::OK, Extension(change), Json(rotated)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============================================================================
+// Webhook Deliveries
+// ============================================================================
+
+pub async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<crate::services::webhook_delivery_service::DeliveryQuery>,
+) -> impl IntoResponse {
+    match state.webhook_delivery_service.list_deliveries(context.merchant_id, query, false).await {
+        Ok(deliveries) => (StatusCode::OK, Json(deliveries)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_dead_letter_deliveries(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<crate::services::webhook_delivery_service::DeliveryQuery>,
+) -> impl IntoResponse {
+    match state.webhook_delivery_service.list_deliveries(context.merchant_id, query, true).await {
+        Ok(deliveries) => (StatusCode::OK, Json(deliveries)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_webhook_delivery(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(delivery_id): Path<String>,
+) -> impl IntoResponse {
+    match state.webhook_delivery_service.get_delivery(context.merchant_id, &delivery_id).await {
+        Ok(delivery) => (StatusCode::OK, Json(delivery)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn redeliver_webhook(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(delivery_id): Path<String>,
+) -> impl IntoResponse {
+    match state.webhook_delivery_service.redeliver(context.merchant_id, &delivery_id).await {
+        Ok(delivery) => (StatusCode::ACCEPTED, Json(delivery)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn bulk_redeliver_webhooks(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::webhook_delivery_service::BulkRedeliverRequest>,
+) -> impl IntoResponse {
+    match state.webhook_delivery_service.bulk_redeliver(context.merchant_id, req).await {
+        Ok(response) => (StatusCode::ACCEPTED, Json(response)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn send_test_webhook(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::webhook_test_service::SendTestWebhookRequest>,
+) -> impl IntoResponse {
+    match state.webhook_test_service.send_test_event(context.merchant_id, req).await {
+        Ok(result) => (StatusCode::OK, Json(result)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
 // ============================================================================
 // Payment Endpoints
 // ============================================================================
@@ -325,6 +1120,7 @@ pub async fn create_payment(
 
     match state.payment_service.create_payment(context.merchant_id, req).await {
         Ok(response) => (StatusCode::CREATED, Json(response)).into_response(),
+        Err(crate::services::payment_service::PaymentServiceError::ServiceError(e @ crate::error::ServiceError::DailyVolumeLimitExceeded(_))) => e.into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
@@ -357,6 +1153,136 @@ pub async fn verify_payment(
     }
 }
 
+pub async fn get_payment_history(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(payment_id): Path<String>,
+) -> impl IntoResponse {
+    match state.payment_service.get_status_history(&payment_id, context.merchant_id).await {
+        Ok(history) => (StatusCode::OK, Json(json!({"payment_id": payment_id, "history": history}))).into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PaymentEventsQuery {
+    /// Only stream this payment (sends its current state first)
+    pub payment_id: Option<String>,
+}
+
+/// Server-Sent Events for all of the merchant's payments
+///
+/// Streams a `payment.status` event for every status or confirmation change.
+pub async fn payment_event_stream(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<PaymentEventsQuery>,
+) -> impl IntoResponse {
+    let merchant_id = context.merchant_id;
+    let filter_payment_id = query.payment_id.clone();
+    let updates = state.payment_stream.updates(move |update| {
+        update.merchant_id == merchant_id
+            && filter_payment_id.as_ref().is_none_or(|id| *id == update.payment_id)
+    });
+
+    let current = match &query.payment_id {
+        Some(payment_id) => {
+            let payment = sqlx::query!(
+                "SELECT id FROM payment_transactions WHERE payment_id = $1 AND merchant_id = $2",
+                payment_id,
+                merchant_id
+            )
+            .fetch_optional(&state.db_pool)
+            .await;
+
+            match payment {
+                Ok(Some(payment)) => state.payment_stream.snapshot(payment.id).await.ok().flatten(),
+                Ok(None) => return (StatusCode::NOT_FOUND, Json(json!({"error": "Payment not found"}))).into_response(),
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+            }
+        }
+        None => None,
+    };
+
+    let events = futures::stream::iter(current)
+        .chain(updates)
+        .map(|update| {
+            Ok::<_, Infallible>(
+                Event::default()
+                    .event("payment.status")
+                    .data(serde_json::to_string(&update).unwrap_or_default()),
+            )
+        });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CancelPaymentRequest {
+    pub reason: Option<String>,
+}
+
+pub async fn cancel_payment(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(payment_id): Path<String>,
+    body: Option<Json<CancelPaymentRequest>>,
+) -> impl IntoResponse {
+    let reason = body.and_then(|Json(req)| req.reason);
+
+    match state.payment_service.transition_status(
+        &payment_id,
+        crate::payment::models::PaymentStatus::Cancelled,
+        crate::payment::state_machine::TransitionActor::Merchant(context.merchant_id),
+        reason,
+    ).await {
+        Ok(_) => (StatusCode::OK, Json(json!({"payment_id": payment_id, "status": "CANCELLED"}))).into_response(),
+        Err(crate::services::payment_service::PaymentServiceError::ServiceError(e)) => e.into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+pub async fn accept_late_payment(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(payment_id): Path<String>,
+) -> impl IntoResponse {
+    match state.payment_service.accept_late_payment(&payment_id, context.merchant_id).await {
+        Ok(_) => {
+            let change = AuditChange::new("payment", &payment_id)
+                .with_before(json!({ "status": "PAID_LATE" }))
+                .with_after(json!({ "status": "CONFIRMED" }));
+            (StatusCode::OK, Extension(change), Json(json!({"payment_id": payment_id, "status": "CONFIRMED"}))).into_response()
+        }
+        Err(crate::services::payment_service::PaymentServiceError::ServiceError(e)) => e.into_response(),
+        Err(e) => (StatusCode::NOT_FOUND, Json(json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RefundLatePaymentRequest {
+    pub reason: Option<String>,
+}
+
+pub async fn refund_late_payment(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(payment_id): Path<String>,
+    body: Option<Json<RefundLatePaymentRequest>>,
+) -> impl IntoResponse {
+    let reason = body
+        .and_then(|Json(req)| req.reason)
+        .unwrap_or_else(|| "Payment received after expiry".to_string());
+
+    match state.refund_service.refund_late_payment(context.merchant_id, payment_id, reason).await {
+        Ok(response) => {
+            let change = AuditChange::new("refund", &response.refund_id).with_after(json!(response));
+            (StatusCode::CREATED, Extension(change), Json(response)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
 pub async fn list_payments(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
@@ -385,7 +1311,10 @@ pub async fn create_refund(
     Json(req): Json<CreateRefundRequest>,
 ) -> impl IntoResponse {
     match state.refund_service.create_refund(context.merchant_id, req.payment_id, req.amount, req.reason).await {
-        Ok(response) => (StatusCode::CREATED, Json(response)).into_response(),
+        Ok(response) => {
+            let change = AuditChange::new("refund", &response.refund_id).with_after(json!(response));
+            (StatusCode::CREATED, Extension(change), Json(response)).into_response()
+        }
         Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
@@ -410,8 +1339,18 @@ pub async fn complete_refund(
     Path(refund_id): Path<String>,
     Json(req): Json<CompleteRefundRequest>,
 ) -> impl IntoResponse {
-    match state.refund_service.complete_refund(refund_id, req.transaction_hash).await {
-        Ok(_) => (StatusCode::OK, Json(json!({"success": true}))).into_response(),
+    let previous = match state.refund_service.get_refund(refund_id.clone()).await {
+        Ok(refund) => refund,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
+    };
+
+    match state.refund_service.complete_refund(refund_id.clone(), req.transaction_hash.clone()).await {
+        Ok(_) => {
+            let change = AuditChange::new("refund", &refund_id)
+                .with_before(json!({ "status": previous.status, "transaction_hash": previous.transaction_hash }))
+                .with_after(json!({ "status": "completed", "transaction_hash": req.transaction_hash }));
+            (StatusCode::OK, Extension(change), Json(json!({"success": true}))).into_response()
+        }
         Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
@@ -553,8 +1492,9 @@ pub async fn payment_page(
 
     // Determine status flags
     let is_pending = payment.status == "PENDING" || payment.status == "CONFIRMING";
-    let is_confirmed = payment.status == "CONFIRMED";
-    let is_expired = payment.status == "FAILED" || payment.expires_at < now;
+    let is_underpaid = payment.status == "UNDERPAID" && payment.expires_at > now;
+    let is_confirmed = payment.status == "CONFIRMED" || payment.status == "OVERPAID";
+    let is_expired = matches!(payment.status.as_str(), "FAILED" | "EXPIRED" | "CANCELLED") || payment.expires_at < now;
 
     // Check if sandbox
     let merchant = sqlx::query!("SELECT sandbox_mode FROM merchants WHERE id = (SELECT merchant_id FROM payment_transactions WHERE id = $1)", payment_link.payment_id)
@@ -576,7 +1516,10 @@ pub async fn payment_page(
         time_remaining,
         expires_at: payment.expires_at.to_rfc3339(),
         transaction_hash: payment.transaction_hash,
+        amount_received: payment.total_paid.to_string(),
+        remaining_balance: payment.remaining_balance.unwrap_or_default().to_string(),
         is_pending,
+        is_underpaid,
         is_confirmed,
         is_expired,
         sandbox,
@@ -592,7 +1535,7 @@ pub async fn payment_status(
     // Look up payment status for polling
     let result = sqlx::query!(
         r#"
-        SELECT pt.status 
+        SELECT pt.status, pt.total_paid, pt.remaining_balance
         FROM payment_transactions pt
         JOIN payment_links pl ON pl.payment_id = pt.id
         WHERE pl.link_id = $1
@@ -603,12 +1546,69 @@ pub async fn payment_status(
     .await;
 
     match result {
-        Ok(Some(payment)) => (StatusCode::OK, Json(json!({"status": payment.status}))).into_response(),
+        Ok(Some(payment)) => (StatusCode::OK, Json(json!({
+            "status": payment.status,
+            "amount_received": payment.total_paid.to_string(),
+            "remaining_balance": payment.remaining_balance.map(|b| b.to_string()),
+        }))).into_response(),
         Ok(None) => (StatusCode::NOT_FOUND, Json(json!({"error": "Payment not found"}))).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
 
+/// Server-Sent Events for the hosted payment page
+///
+/// Sends the current status first, then every status or confirmation change.
+/// The stream ends once the payment is settled or closed.
+pub async fn payment_events(
+    State(state): State<AppState>,
+    Path(link_id): Path<String>,
+) -> impl IntoResponse {
+    let payment = sqlx::query!(
+        "SELECT payment_id FROM payment_links WHERE link_id = $1",
+        &link_id
+    )
+    .fetch_optional(&state.db_pool)
+    .await;
+
+    let payment_id = match payment {
+        Ok(Some(payment)) => payment.payment_id,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(json!({"error": "Payment not found"}))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+    };
+
+    // Subscribe before reading the snapshot so no change falls in between
+    let filter_link_id = link_id.clone();
+    let updates = state
+        .payment_stream
+        .updates(move |update| update.link_id.as_deref() == Some(filter_link_id.as_str()));
+
+    let current = match state.payment_stream.snapshot(payment_id).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json(json!({"error": "Payment not found"}))).into_response(),
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+    };
+
+    let events = futures::stream::once(async move { current })
+        .chain(updates)
+        .scan(false, |finished, update| {
+            if *finished {
+                return futures::future::ready(None);
+            }
+            *finished = update.is_final();
+            futures::future::ready(Some(update))
+        })
+        .map(|update| {
+            Ok::<_, Infallible>(
+                Event::default()
+                    .event("payment.status")
+                    .data(update.public_view().to_string()),
+            )
+        });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
 // Helper functions
 fn generate_qr_code(data: &str) -> Result<String, Box<dyn std::error::Error>> {
     use qrcode::QrCode;
@@ -637,7 +1637,10 @@ struct PaymentPageData {
     time_remaining: String,
     expires_at: String,
     transaction_hash: Option<String>,
+    amount_received: String,
+    remaining_balance: String,
     is_pending: bool,
+    is_underpaid: bool,
     is_confirmed: bool,
     is_expired: bool,
     sandbox: bool,
@@ -659,6 +1662,10 @@ fn render_payment_page(data: PaymentPageData) -> String {
         .replace("{{time_remaining}}", &encode_text(&data.time_remaining))
         .replace("{{expires_at}}", &encode_text(&data.expires_at))
         .replace("{{transaction_hash}}", &encode_text(&data.transaction_hash.unwrap_or_default()))
+        .replace("{{amount_received}}", &encode_text(&data.amount_received))
+        .replace("{{remaining_balance}}", &encode_text(&data.remaining_balance))
+        .replace("{{#if is_underpaid}}", if data.is_underpaid { "" } else { "<!--" })
+        .replace("{{/if_underpaid}}", if data.is_underpaid { "" } else { "-->" })
         .replace("{{#if is_pending}}", if data.is_pending { "" } else { "<!--" })
         .replace("{{/if}}", if data.is_pending { "" } else { "-->" })
         .replace("{{#if is_confirmed}}", if data.is_confirmed { "" } else { "<!--" })
@@ -674,6 +1681,67 @@ pub async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, Json(json!({"status": "healthy"})))
 }
 
+// ============================================================================
+// Event Endpoints
+// ============================================================================
+
+pub async fn list_events(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Query(query): Query<crate::services::event_service::EventQuery>,
+) -> impl IntoResponse {
+    match state.event_service.list_events(context.merchant_id, query).await {
+        Ok(events) => (StatusCode::OK, Json(events)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn get_event(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Path(event_id): Path<String>,
+) -> impl IntoResponse {
+    match state.event_service.get_event(context.merchant_id, &event_id).await {
+        Ok(event) => (StatusCode::OK, Json(event)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============================================================================
+// Payment Policy Endpoints
+// ============================================================================
+
+pub async fn get_payment_policy(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.payment_policy_service.get_policy(context.merchant_id).await {
+        Ok(policy) => (StatusCode::OK, Json(policy)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn update_payment_policy(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    Json(req): Json<crate::services::payment_policy_service::UpdatePaymentPolicyRequest>,
+) -> impl IntoResponse {
+    let previous = match state.payment_policy_service.get_policy(context.merchant_id).await {
+        Ok(policy) => policy,
+        Err(e) => return e.into_response(),
+    };
+
+    match state.payment_policy_service.update_policy(context.merchant_id, req).await {
+        Ok(policy) => {
+            let change = AuditChange::new("payment_policy", &context.merchant_id.to_string())
+                .with_before(json!(previous))
+                .with_after(json!(policy));
+            (StatusCode::OK, Extension(change), Json(policy)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
 // ============================================================================
 // IP Whitelist Endpoints
 // ============================================================================
@@ -681,6 +1749,9 @@ pub async fn health_check() -> impl IntoResponse {
 #[derive(Deserialize)]
 pub struct SetIpWhitelistRequest {
     pub ip_addresses: Vec<String>,
+    /// `merchant` (default) for every API key, `api_key` for the key making this request
+    #[serde(default)]
+    pub scope: IpWhitelistScope,
 }
 
 pub async fn set_ip_whitelist(
@@ -688,8 +1759,12 @@ pub async fn set_ip_whitelist(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<SetIpWhitelistRequest>,
 ) -> impl IntoResponse {
-    match state.ip_whitelist_service.set_whitelist(context.merchant_id, req.ip_addresses).await {
-        Ok(_) => (StatusCode::OK, Json(json!({"message": "IP whitelist updated"}))).into_response(),
+    let api_key = match req.scope {
+        IpWhitelistScope::Merchant => None,
+        IpWhitelistScope::ApiKey => Some(context.api_key.as_str()),
+    };
+    match state.ip_whitelist_service.set_whitelist(context.merchant_id, req.ip_addresses, api_key).await {
+        Ok(_) => (StatusCode::OK, Json(json!({"message": "IP whitelist updated", "scope": req.scope}))).into_response(),
         Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
@@ -698,9 +1773,14 @@ pub async fn get_ip_whitelist(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
 ) -> impl IntoResponse {
-    match state.ip_whitelist_service.get_whitelist(context.merchant_id).await {
-        Ok(ips) => (StatusCode::OK, Json(json!({"ip_addresses": ips}))).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
+    let merchant_ips = state.ip_whitelist_service.get_whitelist(context.merchant_id).await;
+    let api_key_ips = state.ip_whitelist_service.get_api_key_whitelist(context.merchant_id, &context.api_key).await;
+    match (merchant_ips, api_key_ips) {
+        (Ok(ips), Ok(api_key_ips)) => (StatusCode::OK, Json(json!({
+            "ip_addresses": ips,
+            "api_key_ip_addresses": api_key_ips
+        }))).into_response(),
+        (Err(e), _) | (_, Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
 
@@ -774,8 +1854,13 @@ pub async fn create_withdrawal(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<crate::services::withdrawal_service::WithdrawalRequest>,
 ) -> impl IntoResponse {
+    if let Err(e) = state.onboarding_service.require(context.merchant_id, Capability::Withdrawals).await {
+        return e.into_response();
+    }
+
     match state.withdrawal_service.create_withdrawal(context.merchant_id, req).await {
         Ok(withdrawal) => (StatusCode::CREATED, Json(withdrawal)).into_response(),
+        Err(e @ crate::error::ServiceError::DailyVolumeLimitExceeded(_)) => e.into_response(),
         Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
     }
 }
@@ -862,8 +1947,17 @@ pub struct ContactFormRequest {
 
 pub async fn submit_contact_form(
     State(state): State<AppState>,
+    client_ip: Option<Extension<ClientIp>>,
     Json(req): Json<ContactFormRequest>,
 ) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+    if let Err(e) = state.account_lockout_service.check_lockout(AttemptKind::Contact, None, ip.as_deref()).await {
+        return e.into_response();
+    }
+    if let Err(e) = state.account_lockout_service.record_submission(AttemptKind::Contact, None, ip.as_deref()).await {
+        tracing::warn!("Failed to record contact form submission: {}", e);
+    }
+
     // Validate input
     if let Err(validation_errors) = req.validate() {
         return (StatusCode::BAD_REQUEST, Json(json!({
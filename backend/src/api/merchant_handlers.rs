@@ -15,22 +15,73 @@ use serde_json::json;
 pub use crate::api::handlers::{
     // Profile management
     get_merchant_profile,
+    get_merchant_limits,
+    get_onboarding,
+    submit_business_info,
+    upload_onboarding_document,
+    submit_onboarding_for_review,
+    resend_verification_email,
+    get_notification_preferences,
+    update_notification_preferences,
+    list_email_deliveries,
+
+    // Team management
+    get_team_identity,
+    team_logout,
+    list_team_members,
+    invite_team_member,
+    list_team_invitations,
+    revoke_team_invitation,
+    update_team_member_role,
+    remove_team_member,
     switch_environment,
     generate_api_key,
     rotate_api_key,
+
+    // Two-factor authentication
+    get_two_factor_status,
+    setup_two_factor,
+    enable_two_factor,
+    disable_two_factor,
+    regenerate_recovery_codes,
     set_webhook,
     
+    // Webhook endpoints
+    list_webhook_endpoints,
+    create_webhook_endpoint,
+    get_webhook_endpoint,
+    update_webhook_endpoint,
+    delete_webhook_endpoint,
+    rotate_webhook_secret,
+    list_webhook_deliveries,
+    list_dead_letter_deliveries,
+    get_webhook_delivery,
+    redeliver_webhook,
+    bulk_redeliver_webhooks,
+    send_test_webhook,
+    
     // Payment management
     create_payment,
     list_payments,
     get_payment,
     verify_payment,
+    get_payment_history,
+    payment_event_stream,
+    cancel_payment,
+    accept_late_payment,
+    refund_late_payment,
+    get_payment_policy,
+    update_payment_policy,
     
     // Refund management
     create_refund,
     get_refund,
     complete_refund,
     
+    // Events
+    list_events,
+    get_event,
+    
     // Analytics
     get_analytics,
     export_analytics,
@@ -65,4 +116,9 @@ pub use crate::api::handlers::{
     // Auth
     register_merchant,
     login_merchant,
+    verify_email,
+    request_password_reset,
+    confirm_password_reset,
+    team_login,
+    accept_team_invitation,
 };
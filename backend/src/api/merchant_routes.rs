@@ -1,85 +1,181 @@
 // Merchant Routes
-// All merchant-specific API endpoints with API key authentication
+// All merchant-specific API endpoints with API key or team member authentication
 
 use crate::api::{merchant_handlers, wallet_management, security_monitoring};
-use crate::middleware::auth;
+use crate::middleware::{audit, auth, ip_whitelist, permissions, rate_limit, two_factor};
 use axum::{
+    extract::DefaultBodyLimit,
     middleware as axum_middleware,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use crate::api::state::AppState;
 
 pub fn create_merchant_router(state: AppState) -> Router<AppState> {
+    // Sensitive operations require a 2FA step-up code (X-2FA-Code)
+    let step_up = axum_middleware::from_fn_with_state(state.clone(), two_factor::require_step_up);
+
+    // Team members need a role with the permission; the owner's API key always passes
+    let owner_only = axum_middleware::from_fn(permissions::require_owner);
+    let manage_users = axum_middleware::from_fn(permissions::require_manage_users);
+    let approve_withdrawals = axum_middleware::from_fn(permissions::require_approve_withdrawals);
+    let view_analytics = axum_middleware::from_fn(permissions::require_view_analytics);
+    let manage_payments = axum_middleware::from_fn(permissions::require_manage_payments);
+
+    let team_routes = Router::new()
+        .route("/api/v1/merchant/team", get(merchant_handlers::list_team_members))
+        .route("/api/v1/merchant/team/me", get(merchant_handlers::get_team_identity))
+        .route("/api/v1/merchant/team/logout", post(merchant_handlers::team_logout))
+        .route("/api/v1/merchant/team/invitations", post(merchant_handlers::invite_team_member).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/team/invitations", get(merchant_handlers::list_team_invitations).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/team/invitations/:invitation_id", delete(merchant_handlers::revoke_team_invitation).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/team/:user_id/role", put(merchant_handlers::update_team_member_role).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/team/:user_id", delete(merchant_handlers::remove_team_member).route_layer(manage_users.clone()))
+        .route_layer(axum_middleware::from_fn_with_state(state.clone(), permissions::require_multi_user));
+
     Router::new()
         // Merchant profile management
         .route("/api/v1/merchant/profile", get(merchant_handlers::get_merchant_profile))
-        .route("/api/v1/merchant/environment/switch", post(merchant_handlers::switch_environment))
-        .route("/api/v1/merchant/api-keys/generate", post(merchant_handlers::generate_api_key))
-        .route("/api/v1/merchant/api-keys/rotate", post(merchant_handlers::rotate_api_key))
-        .route("/api/v1/merchant/webhook", put(merchant_handlers::set_webhook))
+        .route("/api/v1/merchant/limits", get(merchant_handlers::get_merchant_limits))
+
+        // KYC/KYB onboarding
+        .route("/api/v1/merchant/onboarding", get(merchant_handlers::get_onboarding))
+        .route("/api/v1/merchant/onboarding/business-info", put(merchant_handlers::submit_business_info).route_layer(manage_users.clone()))
+        .route(
+            "/api/v1/merchant/onboarding/documents",
+            post(merchant_handlers::upload_onboarding_document)
+                .layer(DefaultBodyLimit::max(state.config.kyc_document_max_bytes))
+                .route_layer(manage_users.clone()),
+        )
+        .route("/api/v1/merchant/onboarding/submit", post(merchant_handlers::submit_onboarding_for_review).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/verify-email/resend", post(merchant_handlers::resend_verification_email))
+
+        // Notification emails
+        .route("/api/v1/merchant/notification-preferences", get(merchant_handlers::get_notification_preferences))
+        .route("/api/v1/merchant/notification-preferences", put(merchant_handlers::update_notification_preferences))
+        .route("/api/v1/merchant/emails", get(merchant_handlers::list_email_deliveries))
+        .route("/api/v1/merchant/environment/switch", post(merchant_handlers::switch_environment).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/api-keys/generate", post(merchant_handlers::generate_api_key).route_layer(step_up.clone()).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/api-keys/rotate", post(merchant_handlers::rotate_api_key).route_layer(step_up.clone()).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/webhook", put(merchant_handlers::set_webhook).route_layer(manage_users.clone()))
+        
+        // Webhook endpoints
+        .route("/api/v1/merchant/webhooks", get(merchant_handlers::list_webhook_endpoints))
+        .route("/api/v1/merchant/webhooks", post(merchant_handlers::create_webhook_endpoint).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/webhooks/test", post(merchant_handlers::send_test_webhook).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/webhooks/:endpoint_id", get(merchant_handlers::get_webhook_endpoint))
+        .route("/api/v1/merchant/webhooks/:endpoint_id", put(merchant_handlers::update_webhook_endpoint).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/webhooks/:endpoint_id", delete(merchant_handlers::delete_webhook_endpoint).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/webhooks/:endpoint_id/rotate-secret", post(merchant_handlers::rotate_webhook_secret).route_layer(step_up.clone()).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/webhooks/deliveries", get(merchant_handlers::list_webhook_deliveries))
+        .route("/api/v1/merchant/webhooks/deliveries/dead-letter", get(merchant_handlers::list_dead_letter_deliveries))
+        .route("/api/v1/merchant/webhooks/deliveries/redeliver", post(merchant_handlers::bulk_redeliver_webhooks).route_layer(manage_users.clone()))
+        .route("/api/v1/merchant/webhooks/deliveries/:delivery_id", get(merchant_handlers::get_webhook_delivery))
+        .route("/api/v1/merchant/webhooks/deliveries/:delivery_id/redeliver", post(merchant_handlers::redeliver_webhook).route_layer(manage_users.clone()))
         
         // Payment management
-        .route("/api/v1/merchant/payments", post(merchant_handlers::create_payment))
+        .route("/api/v1/merchant/payments", post(merchant_handlers::create_payment).route_layer(manage_payments.clone()))
         .route("/api/v1/merchant/payments", get(merchant_handlers::list_payments))
+        .route("/api/v1/merchant/payments/events", get(merchant_handlers::payment_event_stream))
         .route("/api/v1/merchant/payments/:payment_id", get(merchant_handlers::get_payment))
-        .route("/api/v1/merchant/payments/:payment_id/verify", post(merchant_handlers::verify_payment))
+        .route("/api/v1/merchant/payments/:payment_id/verify", post(merchant_handlers::verify_payment).route_layer(manage_payments.clone()))
+        .route("/api/v1/merchant/payments/:payment_id/cancel", post(merchant_handlers::cancel_payment).route_layer(manage_payments.clone()))
+        .route("/api/v1/merchant/payments/:payment_id/history", get(merchant_handlers::get_payment_history))
+        .route("/api/v1/merchant/payments/:payment_id/late/accept", post(merchant_handlers::accept_late_payment).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/payments/:payment_id/late/refund", post(merchant_handlers::refund_late_payment).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/payment-policy", get(merchant_handlers::get_payment_policy))
+        .route("/api/v1/merchant/payment-policy", put(merchant_handlers::update_payment_policy).route_layer(manage_users.clone()))
         
         // Refund management
-        .route("/api/v1/merchant/refunds", post(merchant_handlers::create_refund))
+        .route("/api/v1/merchant/refunds", post(merchant_handlers::create_refund).route_layer(approve_withdrawals.clone()))
         .route("/api/v1/merchant/refunds/:refund_id", get(merchant_handlers::get_refund))
-        .route("/api/v1/merchant/refunds/:refund_id/complete", post(merchant_handlers::complete_refund))
+        .route("/api/v1/merchant/refunds/:refund_id/complete", post(merchant_handlers::complete_refund).route_layer(approve_withdrawals.clone()))
+        
+        // Event log
+        .route("/api/v1/merchant/events", get(merchant_handlers::list_events))
+        .route("/api/v1/merchant/events/:event_id", get(merchant_handlers::get_event))
         
         // Analytics and reporting
-        .route("/api/v1/merchant/analytics", get(merchant_handlers::get_analytics))
-        .route("/api/v1/merchant/analytics/export", get(merchant_handlers::export_analytics))
-        .route("/api/v1/merchant/audit-logs", get(merchant_handlers::get_audit_logs))
+        .route("/api/v1/merchant/analytics", get(merchant_handlers::get_analytics).route_layer(view_analytics.clone()))
+        .route("/api/v1/merchant/analytics/export", get(merchant_handlers::export_analytics).route_layer(view_analytics.clone()))
+        .route("/api/v1/merchant/audit-logs", get(merchant_handlers::get_audit_logs).route_layer(view_analytics.clone()))
         
         // Balance and financial
-        .route("/api/v1/merchant/balance", get(merchant_handlers::get_balance))
-        .route("/api/v1/merchant/balance/history", get(merchant_handlers::get_balance_history))
+        .route("/api/v1/merchant/balance", get(merchant_handlers::get_balance).route_layer(view_analytics.clone()))
+        .route("/api/v1/merchant/balance/history", get(merchant_handlers::get_balance_history).route_layer(view_analytics.clone()))
         
         // Withdrawal management
-        .route("/api/v1/merchant/withdrawals", post(merchant_handlers::create_withdrawal))
+        .route("/api/v1/merchant/withdrawals", post(merchant_handlers::create_withdrawal).route_layer(step_up.clone()).route_layer(approve_withdrawals.clone()))
         .route("/api/v1/merchant/withdrawals", get(merchant_handlers::list_withdrawals))
         .route("/api/v1/merchant/withdrawals/:withdrawal_id", get(merchant_handlers::get_withdrawal))
-        .route("/api/v1/merchant/withdrawals/:withdrawal_id/cancel", post(merchant_handlers::cancel_withdrawal))
-        .route("/api/v1/merchant/withdrawals/:withdrawal_id/process", post(wallet_management::process_withdrawal))
+        .route("/api/v1/merchant/withdrawals/:withdrawal_id/cancel", post(merchant_handlers::cancel_withdrawal).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/withdrawals/:withdrawal_id/process", post(wallet_management::process_withdrawal).route_layer(step_up.clone()).route_layer(approve_withdrawals.clone()))
         
         // Wallet management
         .route("/api/v1/merchant/wallets", get(wallet_management::get_wallet_configs))
-        .route("/api/v1/merchant/wallets", put(merchant_handlers::set_wallet))
-        .route("/api/v1/merchant/wallets/configure-address", post(wallet_management::configure_address_only_wallet))
-        .route("/api/v1/merchant/wallets/generate", post(wallet_management::generate_wallet))
-        .route("/api/v1/merchant/wallets/import", post(wallet_management::import_wallet))
-        .route("/api/v1/merchant/wallets/export-key", post(wallet_management::export_private_key))
+        .route("/api/v1/merchant/wallets", put(merchant_handlers::set_wallet).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/wallets/configure-address", post(wallet_management::configure_address_only_wallet).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/wallets/generate", post(wallet_management::generate_wallet).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/wallets/import", post(wallet_management::import_wallet).route_layer(approve_withdrawals.clone()))
+        .route("/api/v1/merchant/wallets/export-key", post(wallet_management::export_private_key).route_layer(step_up.clone()).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/wallets/export-key", get(wallet_management::list_key_exports).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/wallets/export-key/:export_id/release", post(wallet_management::release_private_key).route_layer(step_up.clone()).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/wallets/export-key/:export_id/cancel", post(wallet_management::cancel_key_export).route_layer(owner_only.clone()))
         .route("/api/v1/merchant/wallets/gas-check", get(wallet_management::check_gas_requirements))
         .route("/api/v1/merchant/wallets/gas-estimates", get(wallet_management::get_gas_estimates))
         .route("/api/v1/merchant/wallets/withdrawal-capability/:crypto_type", get(wallet_management::check_withdrawal_capability))
         
         // Security settings (merchant's own security preferences)
         .route("/api/v1/merchant/security/settings", get(security_monitoring::get_security_settings))
-        .route("/api/v1/merchant/security/settings", put(security_monitoring::update_security_settings))
+        .route("/api/v1/merchant/security/settings", put(security_monitoring::update_security_settings).route_layer(owner_only.clone()))
         .route("/api/v1/merchant/security/events", get(security_monitoring::get_security_events))
         .route("/api/v1/merchant/security/alerts", get(security_monitoring::get_security_alerts))
-        .route("/api/v1/merchant/security/alerts/:alert_id/acknowledge", post(security_monitoring::acknowledge_security_alert))
+        .route("/api/v1/merchant/security/alerts/:alert_id/acknowledge", post(security_monitoring::acknowledge_security_alert).route_layer(manage_users.clone()))
         .route("/api/v1/merchant/security/balance-alerts", get(security_monitoring::get_balance_alerts))
-        .route("/api/v1/merchant/security/balance-alerts/:alert_id/resolve", post(security_monitoring::resolve_balance_alert))
+        .route("/api/v1/merchant/security/balance-alerts/:alert_id/resolve", post(security_monitoring::resolve_balance_alert).route_layer(manage_users.clone()))
         .route("/api/v1/merchant/security/gas-check", get(security_monitoring::check_gas_balances))
+
+        // Two-factor authentication
+        .route("/api/v1/merchant/security/2fa", get(merchant_handlers::get_two_factor_status))
+        .route("/api/v1/merchant/security/2fa/setup", post(merchant_handlers::setup_two_factor).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/security/2fa/enable", post(merchant_handlers::enable_two_factor).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/security/2fa/disable", post(merchant_handlers::disable_two_factor).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/security/2fa/recovery-codes", post(merchant_handlers::regenerate_recovery_codes).route_layer(owner_only.clone()))
         
         // IP whitelist management
-        .route("/api/v1/merchant/ip-whitelist", put(merchant_handlers::set_ip_whitelist))
+        .route("/api/v1/merchant/ip-whitelist", put(merchant_handlers::set_ip_whitelist).route_layer(step_up.clone()).route_layer(owner_only.clone()))
         .route("/api/v1/merchant/ip-whitelist", get(merchant_handlers::get_ip_whitelist))
         
         // Invoice management
-        .route("/api/v1/merchant/invoices", post(merchant_handlers::create_invoice))
+        .route("/api/v1/merchant/invoices", post(merchant_handlers::create_invoice).route_layer(manage_payments.clone()))
         .route("/api/v1/merchant/invoices", get(merchant_handlers::list_invoices))
         .route("/api/v1/merchant/invoices/:invoice_id", get(merchant_handlers::get_invoice))
         
         // Sandbox testing
-        .route("/api/v1/merchant/sandbox/enable", post(merchant_handlers::enable_sandbox))
-        .route("/api/v1/merchant/sandbox/payments/:payment_id/simulate", post(merchant_handlers::simulate_payment))
+        .route("/api/v1/merchant/sandbox/enable", post(merchant_handlers::enable_sandbox).route_layer(owner_only.clone()))
+        .route("/api/v1/merchant/sandbox/payments/:payment_id/simulate", post(merchant_handlers::simulate_payment).route_layer(manage_payments.clone()))
         
+        // Team management
+        .merge(team_routes)
+
+        // Record mutating calls; runs innermost so the merchant is known
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            audit::audit_middleware,
+        ))
+
+        // Per-key quotas, applied once the merchant is known
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::rate_limit_middleware,
+        ))
+
+        // Merchant-wide and per-key IP allowlists
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            ip_whitelist::ip_whitelist_middleware,
+        ))
+
         // Apply merchant API key authentication
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
@@ -3,7 +3,7 @@
 
 use crate::api::{handlers, merchant_handlers, merchant_routes, admin_routes, status, blog, careers};
 use crate::api::state::AppState;
-use crate::middleware::{auth, ip_whitelist, logging, rate_limit};
+use crate::middleware::{auth, client_ip, ip_whitelist, logging, permissions, rate_limit};
 use axum::{
     http::{
         header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
@@ -16,22 +16,34 @@ use axum::{
 use tower_http::cors::CorsLayer;
 
 pub fn create_router(state: AppState) -> Router {
-    // Create rate limiter with config
-    let rate_limiter = rate_limit::create_rate_limiter(state.config.rate_limit_requests_per_minute);
-
     // Public routes (no auth required)
     let public_routes = Router::new()
         .route("/health", get(handlers::health_check))
         .route("/pay/:link_id", get(handlers::payment_page))
         .route("/pay/:link_id/status", get(handlers::payment_status))
+        .route("/pay/:link_id/events", get(handlers::payment_events))
         .route("/api/v1/merchant/register", post(merchant_handlers::register_merchant))
         .route("/api/v1/merchant/login", post(merchant_handlers::login_merchant))
+        .route("/api/v1/merchant/verify-email", post(merchant_handlers::verify_email))
+        .route("/api/v1/merchant/password-reset", post(merchant_handlers::request_password_reset))
+        .route("/api/v1/merchant/password-reset/confirm", post(merchant_handlers::confirm_password_reset))
+        .merge(
+            Router::new()
+                .route("/api/v1/merchant/team/login", post(merchant_handlers::team_login))
+                .route("/api/v1/merchant/team/invitations/accept", post(merchant_handlers::accept_team_invitation))
+                .route_layer(axum_middleware::from_fn_with_state(state.clone(), permissions::require_multi_user)),
+        )
         .route("/api/v1/currencies/supported", get(handlers::get_supported_currencies))
         .route("/api/v1/status", get(status::get_system_status))
         .route("/api/v1/blog", get(blog::get_blog_posts))
         .route("/api/v1/careers", get(careers::get_careers))
         .route("/api/v1/contact", post(handlers::submit_contact_form))
-        .route("/api/v1/pricing", get(handlers::get_pricing_info));
+        .route("/api/v1/pricing", get(handlers::get_pricing_info))
+        // Public routes are limited per client IP
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            rate_limit::public_rate_limit_middleware,
+        ));
 
     // Combine all routers with CORS
     let cors = CorsLayer::new()
@@ -48,6 +60,10 @@ pub fn create_router(state: AppState) -> Router {
     public_routes
         .merge(merchant_routes::create_merchant_router(state.clone()))
         .merge(admin_routes::create_admin_router(state.clone()))
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            client_ip::resolve_client_ip,
+        ))
         .layer(cors)
         .with_state(state)
 }
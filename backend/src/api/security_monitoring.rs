@@ -1,5 +1,6 @@
 use crate::api::state::AppState;
 use crate::middleware::auth::MerchantContext;
+use crate::services::audit_service::AuditChange;
 use axum::{
     extract::{Extension, State},
     http::StatusCode,
@@ -112,26 +113,35 @@ pub async fn check_gas_balances(
         "total": 0
     })))
 }
+fn default_security_settings() -> Value {
+    json!({
+        "alerts_enabled": true,
+        "monitoring_enabled": true
+    })
+}
+
 pub async fn get_security_settings(
     State(_state): State<AppState>,
     Extension(_context): Extension<MerchantContext>,
 ) -> Result<Json<Value>, StatusCode> {
     // Simplified - return default settings
     Ok(Json(json!({
-        "settings": {
-            "alerts_enabled": true,
-            "monitoring_enabled": true
-        }
+        "settings": default_security_settings()
     })))
 }
 
 pub async fn update_security_settings(
     State(_state): State<AppState>,
-    Extension(_context): Extension<MerchantContext>,
-) -> Result<Json<Value>, StatusCode> {
-    // Simplified - return success
-    Ok(Json(json!({
+    Extension(context): Extension<MerchantContext>,
+    settings: Option<Json<Value>>,
+) -> Result<(Extension<AuditChange>, Json<Value>), StatusCode> {
+    // Simplified - settings are not stored yet, so the defaults are always the previous value
+    let change = AuditChange::new("security_settings", &context.merchant_id.to_string())
+        .with_before(default_security_settings())
+        .with_after(settings.map(|Json(settings)| settings).unwrap_or(Value::Null));
+
+    Ok((Extension(change), Json(json!({
         "success": true,
         "message": "Settings updated"
-    })))
+    }))))
 }
@@ -2,14 +2,22 @@
 // Shared application state
 
 use crate::config::Config;
+use crate::middleware::client_ip::ClientIpResolver;
 use crate::services::{
     analytics_service::AnalyticsService,
     merchant_service::MerchantService,
     payment_service::PaymentService,
+    payment_policy_service::PaymentPolicyService,
+    payment_stream_service::PaymentStreamService,
     refund_service::RefundService,
     sandbox_service::SandboxService,
     admin_service::AdminService,
     webhook_service::WebhookService,
+    webhook_egress::EgressPolicy,
+    webhook_endpoint_service::WebhookEndpointService,
+    webhook_delivery_service::WebhookDeliveryService,
+    webhook_test_service::WebhookTestService,
+    event_service::EventService,
     ip_whitelist_service::IpWhitelistService,
     audit_service::AuditService,
     balance_service::BalanceService,
@@ -18,6 +26,17 @@ use crate::services::{
     currency_service::CurrencyService,
     price_service::PriceService,
     volume_tracking_service::VolumeTrackingService,
+    two_factor_service::TwoFactorService,
+    account_lockout_service::AccountLockoutService,
+    rate_limit_service::RateLimitService,
+    key_rotation_service::KeyRotationService,
+    key_export_service::KeyExportService,
+    screening_service::ScreeningService,
+    aml_service::AmlService,
+    onboarding_service::OnboardingService,
+    notification_service::NotificationService,
+    email_token_service::EmailTokenService,
+    multi_user_service::MultiUserService,
 };
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -28,11 +47,17 @@ pub struct AppState {
     pub config: Config,
     pub merchant_service: Arc<MerchantService>,
     pub payment_service: Arc<PaymentService>,
+    pub payment_policy_service: Arc<PaymentPolicyService>,
+    pub payment_stream: PaymentStreamService,
     pub refund_service: Arc<RefundService>,
     pub analytics_service: Arc<AnalyticsService>,
     pub sandbox_service: Arc<SandboxService>,
     pub admin_service: Arc<AdminService>,
     pub webhook_service: Arc<WebhookService>,
+    pub webhook_endpoint_service: Arc<WebhookEndpointService>,
+    pub webhook_delivery_service: Arc<WebhookDeliveryService>,
+    pub webhook_test_service: Arc<WebhookTestService>,
+    pub event_service: Arc<EventService>,
     pub ip_whitelist_service: Arc<IpWhitelistService>,
     pub audit_service: Arc<AuditService>,
     pub balance_service: Arc<BalanceService>,
@@ -41,6 +66,18 @@ pub struct AppState {
     pub currency_service: Arc<CurrencyService>,
     pub price_service: Arc<PriceService>,
     pub volume_tracking_service: Arc<VolumeTrackingService>,
+    pub two_factor_service: Arc<TwoFactorService>,
+    pub account_lockout_service: Arc<AccountLockoutService>,
+    pub rate_limit_service: Arc<RateLimitService>,
+    pub key_rotation_service: Arc<KeyRotationService>,
+    pub key_export_service: Arc<KeyExportService>,
+    pub screening_service: Arc<ScreeningService>,
+    pub aml_service: Arc<AmlService>,
+    pub onboarding_service: Arc<OnboardingService>,
+    pub notification_service: Arc<NotificationService>,
+    pub email_token_service: Arc<EmailTokenService>,
+    pub multi_user_service: Arc<MultiUserService>,
+    pub client_ip_resolver: Arc<ClientIpResolver>,
 }
 
 impl AppState {
@@ -48,29 +85,69 @@ impl AppState {
         db_pool: PgPool,
         config: Config,
     ) -> Self {
-        let webhook_service = Arc::new(WebhookService::new(db_pool.clone(), config.webhook_signing_key.clone()));
+        let webhook_service = Arc::new(
+            WebhookService::new(db_pool.clone())
+                .with_timeout(std::time::Duration::from_secs(config.webhook_timeout_seconds))
+                .with_egress_policy(EgressPolicy::from_config(&config)),
+        );
         
         let price_service = Arc::new(PriceService::new());
         price_service.start_background_polling();
         
         let balance_service = Arc::new(BalanceService::new(db_pool.clone(), price_service.clone()));
 
+        let payment_stream = PaymentStreamService::from_config(db_pool.clone(), &config);
+        payment_stream.start_relay();
+
+        let screening_service = ScreeningService::from_config(db_pool.clone(), &config);
+
         Self {
             merchant_service: Arc::new(MerchantService::new(db_pool.clone(), config.clone())),
-            payment_service: Arc::new(PaymentService::new(db_pool.clone(), &config.payment_page_base_url, price_service.clone(), &config.webhook_signing_key, config.clone())),
-            refund_service: Arc::new(RefundService::new(db_pool.clone(), webhook_service.clone())),
+            payment_service: Arc::new(
+                PaymentService::new(db_pool.clone(), &config.payment_page_base_url, price_service.clone(), config.clone())
+                    .with_payment_stream(payment_stream.clone()),
+            ),
+            payment_policy_service: Arc::new(PaymentPolicyService::new(db_pool.clone())),
+            refund_service: Arc::new(RefundService::new(db_pool.clone()).with_screening(screening_service.clone())),
             analytics_service: Arc::new(AnalyticsService::new(db_pool.clone())),
             sandbox_service: Arc::new(SandboxService::new(db_pool.clone())),
             admin_service: Arc::new(AdminService::new(db_pool.clone())),
             webhook_service: webhook_service.clone(),
+            webhook_endpoint_service: Arc::new(
+                WebhookEndpointService::new(db_pool.clone())
+                    .with_egress_policy(EgressPolicy::from_config(&config)),
+            ),
+            webhook_delivery_service: Arc::new(WebhookDeliveryService::new(db_pool.clone())),
+            webhook_test_service: Arc::new(WebhookTestService::new(db_pool.clone(), webhook_service.clone())),
+            event_service: Arc::new(EventService::new(db_pool.clone())),
             ip_whitelist_service: Arc::new(IpWhitelistService::new(db_pool.clone())),
             audit_service: Arc::new(AuditService::new(db_pool.clone())),
             balance_service: balance_service.clone(),
-            withdrawal_service: Arc::new(WithdrawalService::new(db_pool.clone())),
-            wallet_config_service: Arc::new(WalletConfigService::new(db_pool.clone())),
+            withdrawal_service: Arc::new(
+                WithdrawalService::new(db_pool.clone())
+                    .with_screening(screening_service.clone())
+                    .with_volume_tracking(VolumeTrackingService::from_config(db_pool.clone(), &config)),
+            ),
+            wallet_config_service: Arc::new(WalletConfigService::from_config(db_pool.clone(), &config)),
             currency_service: Arc::new(CurrencyService::new(db_pool.clone())),
             price_service,
-            volume_tracking_service: Arc::new(VolumeTrackingService::new(db_pool.clone())),
+            volume_tracking_service: Arc::new(VolumeTrackingService::from_config(db_pool.clone(), &config)),
+            payment_stream,
+            two_factor_service: Arc::new(
+                TwoFactorService::new(db_pool.clone(), config.two_factor_enabled)
+                    .with_required(config.two_factor_required),
+            ),
+            account_lockout_service: Arc::new(AccountLockoutService::from_config(db_pool.clone(), &config)),
+            rate_limit_service: Arc::new(RateLimitService::from_config(db_pool.clone(), &config)),
+            key_rotation_service: Arc::new(KeyRotationService::from_config(db_pool.clone(), &config)),
+            key_export_service: Arc::new(KeyExportService::from_config(db_pool.clone(), &config)),
+            screening_service: Arc::new(screening_service),
+            aml_service: Arc::new(AmlService::from_config(db_pool.clone(), &config)),
+            onboarding_service: Arc::new(OnboardingService::from_config(db_pool.clone(), &config)),
+            notification_service: Arc::new(NotificationService::new(db_pool.clone())),
+            email_token_service: Arc::new(EmailTokenService::from_config(db_pool.clone(), &config)),
+            multi_user_service: Arc::new(MultiUserService::from_config(db_pool.clone(), &config)),
+            client_ip_resolver: Arc::new(ClientIpResolver::from_config(&config)),
             config,
             db_pool,
         }
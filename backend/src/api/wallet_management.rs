@@ -3,9 +3,10 @@
 
 use crate::api::state::AppState;
 use crate::middleware::auth::MerchantContext;
+use crate::middleware::client_ip::ClientIp;
 use crate::services::wallet_config_service::{
-    WalletConfigService, ConfigureWalletRequest, GenerateWalletRequest, 
-    ImportWalletRequest, ExportKeyRequest, GasValidationResult
+    ConfigureWalletRequest, GenerateWalletRequest, 
+    ImportWalletRequest, GasValidationResult
 };
 use crate::services::withdrawal_processor::WithdrawalProcessor;
 use crate::payment::models::CryptoType;
@@ -27,7 +28,7 @@ pub async fn get_wallet_configs(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     match wallet_service.get_wallet_configs(context.merchant_id).await {
         Ok(configs) => (StatusCode::OK, Json(json!({
@@ -45,7 +46,7 @@ pub async fn configure_address_only_wallet(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<ConfigureAddressRequest>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     // Map network to appropriate crypto type
     let crypto_type = match req.network.to_lowercase().as_str() {
@@ -80,12 +81,12 @@ pub async fn generate_wallet(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<GenerateWalletRequest>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     match wallet_service.generate_wallet(context.merchant_id, req).await {
         Ok(response) => (StatusCode::CREATED, Json(json!({
             "wallet": response,
-            "message": "Wallet generated successfully. Save the private key securely - it won't be shown again."
+            "message": "Wallet generated successfully. The private key is stored encrypted and can be exported through a delayed key export."
         }))).into_response(),
         Err(e) => (StatusCode::BAD_REQUEST, Json(json!({
             "error": e.to_string()
@@ -98,7 +99,7 @@ pub async fn import_wallet(
     Extension(context): Extension<MerchantContext>,
     Json(req): Json<ImportWalletRequest>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     match wallet_service.import_wallet(context.merchant_id, req).await {
         Ok(config) => (StatusCode::OK, Json(json!({
@@ -114,18 +115,59 @@ pub async fn import_wallet(
 pub async fn export_private_key(
     State(state): State<AppState>,
     Extension(context): Extension<MerchantContext>,
+    client_ip: Option<Extension<ClientIp>>,
     Json(req): Json<ExportKeyRequest>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
-    
-    match wallet_service.export_private_key(context.merchant_id, req).await {
-        Ok(private_key) => (StatusCode::OK, Json(json!({
-            "private_key": private_key,
-            "warning": " Keep this private key secure. Anyone with access can control your funds."
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+
+    match state.key_export_service.request_export(context.merchant_id, req.crypto_type, &req.password, ip.as_deref()).await {
+        Ok(export) => (StatusCode::ACCEPTED, Json(json!({
+            "export": export,
+            "message": "Key export requested. A notification was sent to your email; the key can be released after the cooling-off period."
         }))).into_response(),
-        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({
-            "error": e.to_string()
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_key_exports(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+) -> impl IntoResponse {
+    match state.key_export_service.list_exports(context.merchant_id).await {
+        Ok(exports) => (StatusCode::OK, Json(json!({ "exports": exports }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn release_private_key(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    client_ip: Option<Extension<ClientIp>>,
+    Path(export_id): Path<String>,
+    Json(req): Json<ReleaseKeyRequest>,
+) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+
+    match state.key_export_service.release_export(context.merchant_id, &export_id, &req.passphrase, ip.as_deref()).await {
+        Ok(keystore) => (StatusCode::OK, Json(json!({
+            "keystore": keystore,
+            "warning": "Keep this keystore and its passphrase secure. Anyone with both can control your funds."
         }))).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn cancel_key_export(
+    State(state): State<AppState>,
+    Extension(context): Extension<MerchantContext>,
+    client_ip: Option<Extension<ClientIp>>,
+    Path(export_id): Path<String>,
+) -> impl IntoResponse {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip.to_string());
+
+    match state.key_export_service.cancel_export(context.merchant_id, &export_id, ip.as_deref()).await {
+        Ok(export) => (StatusCode::OK, Json(json!({ "export": export }))).into_response(),
+        Err(e) => e.into_response(),
     }
 }
 
@@ -138,7 +180,7 @@ pub async fn check_gas_requirements(
     Extension(context): Extension<MerchantContext>,
     Query(params): Query<GasCheckQuery>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     match wallet_service.validate_gas_for_withdrawal(
         context.merchant_id,
@@ -202,7 +244,7 @@ pub async fn check_withdrawal_capability(
     Extension(context): Extension<MerchantContext>,
     Path(crypto_type): Path<CryptoType>,
 ) -> impl IntoResponse {
-    let wallet_service = WalletConfigService::new(state.db_pool.clone());
+    let wallet_service = state.wallet_config_service.clone();
     
     match wallet_service.can_withdraw(context.merchant_id, crypto_type, rust_decimal::Decimal::ZERO).await {
         Ok(can_withdraw) => {
@@ -257,6 +299,19 @@ pub struct ConfigureAddressRequest {
     pub address: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExportKeyRequest {
+    pub crypto_type: CryptoType,
+    /// Account password, for re-authentication
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseKeyRequest {
+    /// Passphrase the keystore is encrypted with
+    pub passphrase: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GasCheckQuery {
     pub crypto_type: CryptoType,
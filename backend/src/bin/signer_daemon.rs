@@ -0,0 +1,57 @@
+// Signer Daemon
+// Holds deposit and wallet keys and signs forwarding transactions for the API over a Unix socket
+//
+// Runs with its own ENCRYPTION_KEY/ENCRYPTION_KEKS, which the API server does not
+// have; the private key columns are only readable under these KEKs.
+
+use fiddupay::{
+    config::Config,
+    services::key_rotation_service::{KeyRotationService, SIGNING_KEY_COLUMNS},
+    services::signer::{self, LocalSigner, SignerPolicy},
+};
+use sqlx::postgres::PgPoolOptions;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+
+    let config = Config::from_env()?;
+    let policy = SignerPolicy::from_env(&config)?;
+
+    let db_pool = PgPoolOptions::new()
+        .max_connections(5)
+        .connect(&config.database_url)
+        .await?;
+
+    // Move keys onto the daemon's current KEK, e.g. off the KEK once shared with the API
+    let key_rotation = KeyRotationService::with_columns(db_pool.clone(), SIGNING_KEY_COLUMNS.to_vec());
+    match key_rotation.rewrap_all().await {
+        Ok(report) if report.rewrapped > 0 || report.failed > 0 => tracing::info!(
+            "Re-wrapped {} signing keys onto KEK {} ({} failed)",
+            report.rewrapped, report.current_key_id, report.failed
+        ),
+        Ok(_) => {}
+        Err(e) => tracing::error!("Failed to re-wrap signing keys: {}", e),
+    }
+
+    // Replace a socket left behind by a previous run
+    let socket_path = &config.signer_socket_path;
+    if std::fs::metadata(socket_path).is_ok() {
+        std::fs::remove_file(socket_path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(socket_path)?;
+    // Only the owner (the API server's user) may connect
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600))?;
+
+    tracing::info!("Signer daemon listening on {}", socket_path);
+    signer::serve(listener, Arc::new(LocalSigner::new(db_pool)), Arc::new(policy)).await?;
+
+    Ok(())
+}
@@ -71,6 +71,11 @@ pub struct Config {
     pub encryption_key: String,
     pub webhook_signing_key: String,
     pub jwt_secret: String,
+    /// `local` signs in process; `remote` sends signing requests to the signer daemon
+    pub signer_mode: String,
+    pub signer_socket_path: String,
+    /// Cooling-off period between a private key export request and its release
+    pub key_export_delay_hours: u64,
 
     // Password Security
     pub password_min_length: u32,
@@ -89,6 +94,12 @@ pub struct Config {
     pub rate_limit_requests_per_minute: u32,
     pub rate_limit_burst_size: u32,
     pub rate_limit_per_api_key: bool,
+    /// Share of the merchant-wide quota one API key may use, in percent
+    pub rate_limit_per_api_key_percent: u32,
+    pub rate_limit_write_requests_per_minute: u32,
+    pub rate_limit_public_requests_per_minute: u32,
+    /// Proxy IPs or CIDRs whose X-Forwarded-For/Forwarded headers are trusted
+    pub trusted_proxies: Vec<String>,
 
     // Payment Settings
     pub default_payment_expiration_minutes: u32,
@@ -100,28 +111,64 @@ pub struct Config {
 
     // Daily Volume Limits
     pub daily_volume_limit_non_kyc_usd: rust_decimal::Decimal,
+    pub daily_volume_limit_basic_kyc_usd: rust_decimal::Decimal,
+    /// `None` means fully verified merchants have no daily limit
+    pub daily_volume_limit_full_kyc_usd: Option<rust_decimal::Decimal>,
+    /// "rolling" (last 24 hours) or "calendar" (since midnight UTC)
+    pub daily_volume_limit_window: String,
 
     // Merchant Settings
     pub merchant_registration_enabled: bool,
     pub merchant_email_verification_required: bool,
+    /// Verify-email and password-reset links point at this dashboard
+    pub dashboard_base_url: String,
+    pub email_verification_ttl_hours: u64,
+    pub password_reset_ttl_minutes: u64,
     pub merchant_kyc_required: bool,
     pub merchant_auto_approval: bool,
+    /// `local` keeps onboarding documents under `kyc_document_dir`; `s3` uses an S3-compatible bucket
+    pub kyc_document_storage: String,
+    pub kyc_document_dir: String,
+    pub kyc_document_max_bytes: usize,
+    pub kyc_s3_endpoint: String,
+    pub kyc_s3_bucket: String,
+    pub kyc_s3_region: String,
+    pub kyc_s3_access_key_id: String,
+    pub kyc_s3_secret_access_key: String,
 
     // Webhook Settings
     pub webhook_timeout_seconds: u64,
     pub webhook_max_retries: u32,
     pub webhook_retry_delay_seconds: u64,
     pub webhook_signature_required: bool,
+    /// Hosts, IPs or CIDRs webhooks may reach despite the egress policy (ignored in production)
+    pub webhook_test_allowlist: Vec<String>,
 
     // Withdrawal Settings
     pub withdrawal_enabled: bool,
     pub withdrawal_auto_approval_limit_usd: rust_decimal::Decimal,
 
+    // Compliance Screening
+    /// Screen payer and withdrawal/refund destination addresses against sanctions and deny lists
+    pub sanctions_screening_enabled: bool,
+    /// OFAC SDN list (SDN.XML or SDN.CSV) imported at startup and daily
+    pub ofac_sdn_file: Option<String>,
+    /// Local deny list, one `address[,currency[,reason]]` per line
+    pub sanctions_list_file: Option<String>,
+    /// Run the AML transaction monitoring rules
+    pub aml_monitoring_enabled: bool,
+    pub aml_monitoring_interval_seconds: u64,
+
     // Feature Flags
     pub two_factor_enabled: bool,
+    /// Refuse sensitive operations until the merchant has enabled 2FA
+    pub two_factor_required: bool,
     pub deposit_address_enabled: bool,
     pub invoice_enabled: bool,
     pub multi_user_enabled: bool,
+    /// How long team invitations and team member sessions stay valid
+    pub team_invitation_ttl_hours: u64,
+    pub team_session_ttl_hours: u64,
     pub analytics_enabled: bool,
     pub maintenance_mode: bool,
 
@@ -251,10 +298,17 @@ impl Config {
                 .unwrap_or_else(|_| "15".to_string())
                 .parse()?,
 
-            // Security - All required, no defaults
-            encryption_key: env::var("ENCRYPTION_KEY")?,
+            // Security - All required, no defaults (ENCRYPTION_KEY is optional with SIGNER_MODE=remote)
+            encryption_key: env::var("ENCRYPTION_KEY").unwrap_or_default(),
             webhook_signing_key: env::var("WEBHOOK_SIGNING_KEY")?,
             jwt_secret: env::var("JWT_SECRET")?,
+            signer_mode: env::var("SIGNER_MODE")
+                .unwrap_or_else(|_| "local".to_string()),
+            signer_socket_path: env::var("SIGNER_SOCKET_PATH")
+                .unwrap_or_else(|_| "/run/fiddupay/signer.sock".to_string()),
+            key_export_delay_hours: env::var("KEY_EXPORT_DELAY_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()?,
 
             // Password Security
             password_min_length: env::var("PASSWORD_MIN_LENGTH")
@@ -297,6 +351,21 @@ impl Config {
             rate_limit_per_api_key: env::var("RATE_LIMIT_PER_API_KEY")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
+            rate_limit_per_api_key_percent: env::var("RATE_LIMIT_PER_API_KEY_PERCENT")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()?,
+            rate_limit_write_requests_per_minute: env::var("RATE_LIMIT_WRITE_REQUESTS_PER_MINUTE")
+                .unwrap_or_else(|_| "50".to_string())
+                .parse()?,
+            rate_limit_public_requests_per_minute: env::var("RATE_LIMIT_PUBLIC_REQUESTS_PER_MINUTE")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
 
             // Payment Settings
             default_payment_expiration_minutes: env::var("DEFAULT_PAYMENT_EXPIRATION_MINUTES")
@@ -316,6 +385,15 @@ impl Config {
             daily_volume_limit_non_kyc_usd: env::var("DAILY_VOLUME_LIMIT_NON_KYC_USD")
                 .unwrap_or_else(|_| "1000.00".to_string())
                 .parse()?,
+            daily_volume_limit_basic_kyc_usd: env::var("DAILY_VOLUME_LIMIT_BASIC_KYC_USD")
+                .unwrap_or_else(|_| "25000.00".to_string())
+                .parse()?,
+            daily_volume_limit_full_kyc_usd: match env::var("DAILY_VOLUME_LIMIT_FULL_KYC_USD") {
+                Ok(v) if !v.trim().is_empty() => Some(v.trim().parse()?),
+                _ => None,
+            },
+            daily_volume_limit_window: env::var("DAILY_VOLUME_LIMIT_WINDOW")
+                .unwrap_or_else(|_| "rolling".to_string()),
 
             // Merchant Settings
             merchant_registration_enabled: env::var("MERCHANT_REGISTRATION_ENABLED")
@@ -324,19 +402,42 @@ impl Config {
             merchant_email_verification_required: env::var("MERCHANT_EMAIL_VERIFICATION_REQUIRED")
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()?,
+            dashboard_base_url: env::var("DASHBOARD_BASE_URL")
+                .or_else(|_| env::var("FRONTEND_URL"))
+                .unwrap_or_else(|_| "http://localhost:3000".to_string()),
+            email_verification_ttl_hours: env::var("EMAIL_VERIFICATION_TTL_HOURS")
+                .unwrap_or_else(|_| "48".to_string())
+                .parse()?,
+            password_reset_ttl_minutes: env::var("PASSWORD_RESET_TTL_MINUTES")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()?,
             merchant_kyc_required: env::var("MERCHANT_KYC_REQUIRED")
                 .unwrap_or_else(|_| "false".to_string())
                 .parse()?,
             merchant_auto_approval: env::var("MERCHANT_AUTO_APPROVAL")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
+            kyc_document_storage: env::var("KYC_DOCUMENT_STORAGE")
+                .unwrap_or_else(|_| "local".to_string()),
+            kyc_document_dir: env::var("KYC_DOCUMENT_DIR")
+                .unwrap_or_else(|_| "./data/kyc-documents".to_string()),
+            kyc_document_max_bytes: env::var("KYC_DOCUMENT_MAX_BYTES")
+                .unwrap_or_else(|_| "10485760".to_string())
+                .parse()?,
+            kyc_s3_endpoint: env::var("KYC_S3_ENDPOINT")
+                .unwrap_or_else(|_| "https://s3.amazonaws.com".to_string()),
+            kyc_s3_bucket: env::var("KYC_S3_BUCKET").unwrap_or_default(),
+            kyc_s3_region: env::var("KYC_S3_REGION")
+                .unwrap_or_else(|_| "us-east-1".to_string()),
+            kyc_s3_access_key_id: env::var("KYC_S3_ACCESS_KEY_ID").unwrap_or_default(),
+            kyc_s3_secret_access_key: env::var("KYC_S3_SECRET_ACCESS_KEY").unwrap_or_default(),
 
             // Webhook Settings
             webhook_timeout_seconds: env::var("WEBHOOK_TIMEOUT_SECONDS")
                 .unwrap_or_else(|_| "10".to_string())
                 .parse()?,
             webhook_max_retries: env::var("WEBHOOK_MAX_RETRIES")
-                .unwrap_or_else(|_| "5".to_string())
+                .unwrap_or_else(|_| "18".to_string())
                 .parse()?,
             webhook_retry_delay_seconds: env::var("WEBHOOK_RETRY_DELAY_SECONDS")
                 .unwrap_or_else(|_| "5".to_string())
@@ -344,6 +445,12 @@ impl Config {
             webhook_signature_required: env::var("WEBHOOK_SIGNATURE_REQUIRED")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
+            webhook_test_allowlist: env::var("WEBHOOK_TEST_ALLOWLIST")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
 
             // Withdrawal Settings
             withdrawal_enabled: env::var("WITHDRAWAL_ENABLED")
@@ -353,10 +460,26 @@ impl Config {
                 .unwrap_or_else(|_| "1000.00".to_string())
                 .parse()?,
 
+            // Compliance Screening
+            sanctions_screening_enabled: env::var("SANCTIONS_SCREENING_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            ofac_sdn_file: env::var("OFAC_SDN_FILE").ok().filter(|p| !p.is_empty()),
+            sanctions_list_file: env::var("SANCTIONS_LIST_FILE").ok().filter(|p| !p.is_empty()),
+            aml_monitoring_enabled: env::var("AML_MONITORING_ENABLED")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()?,
+            aml_monitoring_interval_seconds: env::var("AML_MONITORING_INTERVAL_SECONDS")
+                .unwrap_or_else(|_| "300".to_string())
+                .parse()?,
+
             // Feature Flags
             two_factor_enabled: env::var("TWO_FACTOR_ENABLED")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
+            two_factor_required: env::var("TWO_FACTOR_REQUIRED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()?,
             deposit_address_enabled: env::var("DEPOSIT_ADDRESS_ENABLED")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
@@ -366,6 +489,12 @@ impl Config {
             multi_user_enabled: env::var("MULTI_USER_ENABLED")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
+            team_invitation_ttl_hours: env::var("TEAM_INVITATION_TTL_HOURS")
+                .unwrap_or_else(|_| "72".to_string())
+                .parse()?,
+            team_session_ttl_hours: env::var("TEAM_SESSION_TTL_HOURS")
+                .unwrap_or_else(|_| "12".to_string())
+                .parse()?,
             analytics_enabled: env::var("ANALYTICS_ENABLED")
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()?,
@@ -387,8 +516,15 @@ impl Config {
             return Err("DATABASE_URL is required".to_string());
         }
 
+        // With a remote signer the API only encrypts its own secrets, which may
+        // use ENCRYPTION_KEKS, a KEK file or a KMS instead of the shared key
         if self.encryption_key.is_empty() {
-            return Err("ENCRYPTION_KEY is required".to_string());
+            if self.signer_mode != "remote" {
+                return Err("ENCRYPTION_KEY is required".to_string());
+            }
+            crate::utils::key_provider::provider_from_env().map_err(|e| {
+                format!("SIGNER_MODE=remote needs ENCRYPTION_KEY or another KEK provider for the API's own secrets: {}", e)
+            })?;
         }
 
         if self.webhook_signing_key.is_empty() {
@@ -427,6 +563,10 @@ impl Config {
             return Err("COINBASE_PRICE_API_URL is required".to_string());
         }
 
+        if !(1..=100).contains(&self.rate_limit_per_api_key_percent) {
+            return Err("RATE_LIMIT_PER_API_KEY_PERCENT must be between 1 and 100".to_string());
+        }
+
         if self.payment_page_base_url.is_empty() {
             return Err("PAYMENT_PAGE_BASE_URL is required".to_string());
         }
@@ -435,6 +575,23 @@ impl Config {
             return Err("JWT_SECRET is required".to_string());
         }
 
+        if !matches!(self.signer_mode.as_str(), "local" | "remote") {
+            return Err("SIGNER_MODE must be local or remote".to_string());
+        }
+
+        match self.kyc_document_storage.as_str() {
+            "local" => {}
+            "s3" => {
+                if self.kyc_s3_bucket.is_empty()
+                    || self.kyc_s3_access_key_id.is_empty()
+                    || self.kyc_s3_secret_access_key.is_empty()
+                {
+                    return Err("KYC_S3_BUCKET, KYC_S3_ACCESS_KEY_ID and KYC_S3_SECRET_ACCESS_KEY are required for s3 document storage".to_string());
+                }
+            }
+            _ => return Err("KYC_DOCUMENT_STORAGE must be local or s3".to_string()),
+        }
+
         Ok(())
     }
 }
@@ -485,6 +642,9 @@ impl Default for Config {
             encryption_key: "test_key_32_bytes_long_for_tests".to_string(),
             webhook_signing_key: "test_webhook_key".to_string(),
             jwt_secret: "test_jwt_secret".to_string(),
+            signer_mode: "local".to_string(),
+            signer_socket_path: "/run/fiddupay/signer.sock".to_string(),
+            key_export_delay_hours: 24,
             password_min_length: 8,
             password_require_uppercase: true,
             password_require_lowercase: true,
@@ -497,25 +657,52 @@ impl Default for Config {
             rate_limit_requests_per_minute: 100,
             rate_limit_burst_size: 20,
             rate_limit_per_api_key: true,
+            rate_limit_per_api_key_percent: 50,
+            rate_limit_write_requests_per_minute: 50,
+            rate_limit_public_requests_per_minute: 60,
+            trusted_proxies: Vec::new(),
             default_payment_expiration_minutes: 15,
             payment_cleanup_interval_hours: 24,
             payment_page_base_url: "http://localhost:3000".to_string(),
             default_fee_percentage: rust_decimal::Decimal::new(75, 4), // 0.0075 = 0.75%
             daily_volume_limit_non_kyc_usd: rust_decimal::Decimal::new(100000, 2), // 1000.00
+            daily_volume_limit_basic_kyc_usd: rust_decimal::Decimal::new(2500000, 2), // 25000.00
+            daily_volume_limit_full_kyc_usd: None,
+            daily_volume_limit_window: "rolling".to_string(),
             merchant_registration_enabled: true,
             merchant_email_verification_required: true,
+            dashboard_base_url: "http://localhost:3000".to_string(),
+            email_verification_ttl_hours: 48,
+            password_reset_ttl_minutes: 60,
             merchant_kyc_required: false,
             merchant_auto_approval: false,
+            kyc_document_storage: "local".to_string(),
+            kyc_document_dir: "./data/kyc-documents".to_string(),
+            kyc_document_max_bytes: 10 * 1024 * 1024,
+            kyc_s3_endpoint: "https://s3.amazonaws.com".to_string(),
+            kyc_s3_bucket: String::new(),
+            kyc_s3_region: "us-east-1".to_string(),
+            kyc_s3_access_key_id: String::new(),
+            kyc_s3_secret_access_key: String::new(),
             webhook_timeout_seconds: 30,
             webhook_max_retries: 3,
             webhook_retry_delay_seconds: 5,
             webhook_signature_required: true,
+            webhook_test_allowlist: Vec::new(),
             withdrawal_enabled: true,
             withdrawal_auto_approval_limit_usd: rust_decimal::Decimal::new(100000, 2), // 1000.00
+            sanctions_screening_enabled: true,
+            ofac_sdn_file: None,
+            sanctions_list_file: None,
+            aml_monitoring_enabled: true,
+            aml_monitoring_interval_seconds: 300,
             two_factor_enabled: false,
+            two_factor_required: false,
             deposit_address_enabled: true,
             invoice_enabled: true,
             multi_user_enabled: false,
+            team_invitation_ttl_hours: 72,
+            team_session_ttl_hours: 12,
             analytics_enabled: true,
             maintenance_mode: false,
             environment: "test".to_string(),
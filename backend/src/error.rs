@@ -2,7 +2,7 @@
 // Centralized error handling for the gateway
 
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -65,6 +65,9 @@ pub enum ServiceError {
     #[error("Invalid refund amount: {0}")]
     InvalidRefundAmount(String),
 
+    #[error("Invalid payment state transition: {0}")]
+    InvalidStateTransition(String),
+
     #[error("Internal server error: {0}")]
     Internal(String),
 
@@ -74,6 +77,18 @@ pub enum ServiceError {
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
+    #[error("Two-factor authentication required: {0}")]
+    TwoFactorRequired(String),
+
+    #[error("Daily volume limit exceeded: {0}")]
+    DailyVolumeLimitExceeded(String),
+
+    #[error("Invalid or expired token: {0}")]
+    InvalidToken(String),
+
+    #[error("Too many failed attempts, retry after {0} seconds")]
+    TooManyAttempts(u64),
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
@@ -92,6 +107,11 @@ pub struct ErrorDetail {
 
 impl IntoResponse for ServiceError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            ServiceError::TooManyAttempts(secs) => Some(secs),
+            _ => None,
+        };
+
         let (status, code, message) = match self {
             ServiceError::InvalidApiKey => (
                 StatusCode::UNAUTHORIZED,
@@ -153,6 +173,11 @@ impl IntoResponse for ServiceError {
                 "INVALID_REFUND_AMOUNT",
                 msg.as_str(),
             ),
+            ServiceError::InvalidStateTransition(ref msg) => (
+                StatusCode::CONFLICT,
+                "INVALID_STATE_TRANSITION",
+                msg.as_str(),
+            ),
             ServiceError::Database(_) | ServiceError::DatabaseError(_) | ServiceError::Json(_) | ServiceError::Internal(_) | ServiceError::InternalError(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "INTERNAL_SERVER_ERROR",
@@ -173,6 +198,26 @@ impl IntoResponse for ServiceError {
                 "FORBIDDEN",
                 msg.as_str(),
             ),
+            ServiceError::TwoFactorRequired(ref msg) => (
+                StatusCode::FORBIDDEN,
+                "TWO_FACTOR_REQUIRED",
+                msg.as_str(),
+            ),
+            ServiceError::DailyVolumeLimitExceeded(ref msg) => (
+                StatusCode::FORBIDDEN,
+                "DAILY_VOLUME_LIMIT_EXCEEDED",
+                msg.as_str(),
+            ),
+            ServiceError::InvalidToken(ref msg) => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_TOKEN",
+                msg.as_str(),
+            ),
+            ServiceError::TooManyAttempts(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "TOO_MANY_ATTEMPTS",
+                "Too many failed attempts, try again later",
+            ),
             ServiceError::NotFound(ref msg) => (
                 StatusCode::NOT_FOUND,
                 "NOT_FOUND",
@@ -198,7 +243,11 @@ impl IntoResponse for ServiceError {
             },
         };
 
-        (status, Json(error_response)).into_response()
+        let mut response = (status, Json(error_response)).into_response();
+        if let Some(secs) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
 
@@ -21,6 +21,8 @@ pub struct EmailConfig {
     pub smtp_port: Option<u16>,
     pub smtp_username: Option<String>,
     pub smtp_password: Option<String>,
+    /// "tls", "starttls" or "none" (local SMTP sink, no authentication)
+    pub smtp_tls: Option<String>,
 }
 
 impl FeatureFlags {
@@ -67,6 +69,7 @@ impl EmailConfig {
             smtp_port: env::var("SMTP_PORT").ok().and_then(|s| s.parse().ok()),
             smtp_username: env::var("SMTP_USERNAME").ok(),
             smtp_password: env::var("SMTP_PASSWORD").ok(),
+            smtp_tls: env::var("SMTP_TLS").ok(),
         }
     }
 }
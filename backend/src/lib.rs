@@ -11,6 +11,7 @@ pub mod config;
 pub mod background_tasks;
 pub mod utils;
 pub mod feature_flags;
+pub mod webhook_verifier;
 pub mod performance;
 pub mod performance_advanced;
 // pub mod simple_tests; // Removed during test cleanup
@@ -58,9 +58,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Start background tasks
     tracing::info!(" Starting background tasks...");
-    let background_tasks = Arc::new(BackgroundTasks::new(
+    let background_tasks = Arc::new(BackgroundTasks::from_config(
         db_pool.clone(),
-        config.webhook_signing_key.clone(),
+        &config,
     ));
     background_tasks.start();
     tracing::info!(" Background tasks started");
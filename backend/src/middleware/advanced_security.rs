@@ -14,6 +14,7 @@ use uuid::Uuid;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 use chrono::{DateTime, Utc, Duration};
+use crate::middleware::client_ip::ClientIp;
 
 /// Advanced security middleware combining all remaining features
 pub struct AdvancedSecurityMiddleware {
@@ -191,7 +192,7 @@ pub async fn advanced_security_middleware(
 ) -> Result<Response, impl IntoResponse> {
     // Extract request info
     let api_key = extract_api_key(&headers)?;
-    let ip_address = extract_ip_address(&headers);
+    let ip_address = extract_ip_address(&request);
     let endpoint = request.uri().path().to_string();
 
     // 1. Validate API key format
@@ -260,13 +261,13 @@ fn extract_api_key(headers: &HeaderMap) -> Result<String, (StatusCode, axum::Jso
         ))
 }
 
-fn extract_ip_address(headers: &HeaderMap) -> String {
-    headers
-        .get("x-forwarded-for")
-        .or_else(|| headers.get("x-real-ip"))
-        .and_then(|value| value.to_str().ok())
-        .unwrap_or("unknown")
-        .to_string()
+/// Client IP resolved by `client_ip::resolve_client_ip`; raw forwarding headers are not trusted
+fn extract_ip_address(request: &Request) -> String {
+    request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
 /// API Version Security Manager
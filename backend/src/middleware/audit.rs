@@ -0,0 +1,273 @@
+// Audit Middleware
+// Records every mutating merchant and admin API call in the audit log
+
+use crate::api::state::AppState;
+use crate::middleware::admin_auth::AdminContext;
+use crate::middleware::auth::MerchantContext;
+use crate::middleware::client_ip::ClientIp;
+use crate::services::audit_service::{AuditChange, AuditEntry};
+use axum::{
+    body::{self, Body},
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use prometheus::{register_int_counter, IntCounter};
+use serde_json::{json, Value};
+use std::sync::LazyLock;
+use tracing::{error, warn};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Audit entries that could not be written, also reported in the admin system health
+pub static AUDIT_WRITE_FAILURES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("fiddupay_audit_write_failures_total", "Audit log entries that could not be written")
+        .expect("audit failure counter is registered once")
+});
+
+/// Largest JSON body copied into the log
+const MAX_LOGGED_BODY_BYTES: usize = 64 * 1024;
+
+/// Body fields whose values are never logged; matched as substrings of the key
+///
+/// Keys are compared lowercased with `_` and `-` removed, so `private_key`
+/// and `privateKey` both match.
+const REDACTED_FIELDS: &[&str] = &[
+    "password", "passphrase", "privatekey", "secret", "token", "apikey", "mnemonic",
+];
+
+/// One-time code fields, matched exactly so `country_code` or `currency_code` stay readable
+const REDACTED_CODE_FIELDS: &[&str] = &[
+    "code", "totpcode", "recoverycode", "twofactorcode", "2facode", "backupcode", "otp",
+];
+
+/// Audit middleware for authenticated routers
+///
+/// Must run after the merchant or admin auth middleware, which provides
+/// the actor. Reads (GET, HEAD, OPTIONS) are not recorded. Each call gets
+/// a request id (the caller's `X-Request-ID` if valid), returned in the
+/// response. Handlers that change state can attach an [`AuditChange`]
+/// to their response to record the entity and a before/after diff.
+pub async fn audit_middleware(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return with_request_id(next.run(request).await, &request_id);
+    }
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let ip = request.extensions().get::<ClientIp>().map(|ClientIp(ip)| ip.to_string());
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let merchant = request.extensions().get::<MerchantContext>().cloned();
+    let admin = request.extensions().get::<AdminContext>().cloned();
+
+    let (request, body) = capture_json_body(request).await;
+    let response = next.run(request).await;
+
+    let change = response.extensions().get::<AuditChange>().cloned();
+    let mut details = json!({
+        "method": method,
+        "path": path,
+        "status": response.status().as_u16(),
+        "request": body,
+    });
+    if let Some(change) = &change {
+        details["changes"] = change.diff();
+    }
+
+    let action_type: String = format!("{} {}", method, route).chars().take(100).collect();
+    let mut entry = AuditEntry::new(&action_type)
+        .with_ip(ip.as_deref())
+        .with_user_agent(user_agent.as_deref())
+        .with_request_id(&request_id)
+        .with_details(details);
+
+    entry = match (&admin, merchant) {
+        (Some(admin), _) => {
+            let role = if admin.permissions.iter().any(|p| p == "all") { "super_admin" } else { "admin" };
+            entry.with_actor("admin", Some(admin.admin_id.to_string()), Some(role))
+        }
+        (None, Some(MerchantContext { merchant_id, user: Some(user), .. })) => entry
+            .with_merchant(merchant_id)
+            .with_actor("merchant_user", Some(user.user_id.to_string()), Some(&user.role.as_str().to_lowercase())),
+        (None, Some(context)) => entry
+            .with_merchant(context.merchant_id)
+            .with_actor("merchant", Some(context.merchant_id.to_string()), Some("merchant")),
+        (None, None) => entry,
+    };
+    if let Some(change) = change {
+        entry = entry.with_entity(&change.entity_type, &change.entity_id);
+        // Admin changes to a merchant's data belong in that merchant's chain
+        if let Some(merchant_id) = change.merchant_id {
+            entry = entry.with_merchant(merchant_id);
+        }
+    }
+
+    // One retry covers a lost connection or a conflict on the chain head
+    if let Err(e) = state.audit_service.record(&entry).await {
+        warn!("Retrying audit log write for {} {} (request {}): {}", method, path, request_id, e);
+        if let Err(e) = state.audit_service.record(&entry).await {
+            AUDIT_WRITE_FAILURES.inc();
+            error!(
+                alert = "audit_write_failed",
+                "Audit log entry lost for {} {} (request {}, status {}): {}",
+                method, path, request_id, response.status().as_u16(), e
+            );
+        }
+    }
+
+    with_request_id(response, &request_id)
+}
+
+fn with_request_id(mut response: Response, request_id: &str) -> Response {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 100
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Copy a small JSON body for the log, leaving the request intact
+///
+/// Other bodies (uploads, unknown length) are passed through untouched.
+async fn capture_json_body(request: Request) -> (Request, Value) {
+    let is_json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+
+    match length {
+        Some(0) | None => return (request, Value::Null),
+        Some(length) if is_json && length <= MAX_LOGGED_BODY_BYTES => {}
+        Some(_) => return (request, json!("[not logged]")),
+    }
+
+    let (parts, body) = request.into_parts();
+    match body::to_bytes(body, MAX_LOGGED_BODY_BYTES).await {
+        Ok(bytes) => {
+            let logged = serde_json::from_slice::<Value>(&bytes)
+                .map(|mut value| {
+                    redact(&mut value);
+                    value
+                })
+                .unwrap_or_else(|_| json!("[invalid JSON]"));
+            (Request::from_parts(parts, Body::from(bytes)), logged)
+        }
+        // Content-Length was checked, so this is a client that stopped sending
+        Err(_) => (Request::from_parts(parts, Body::empty()), json!("[unreadable]")),
+    }
+}
+
+fn is_redacted(key: &str) -> bool {
+    let key: String = key
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect();
+    REDACTED_FIELDS.iter().any(|name| key.contains(name))
+        || REDACTED_CODE_FIELDS.contains(&key.as_str())
+}
+
+/// Replace the values of sensitive fields, at any depth
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if is_redacted(key) {
+                    *field = json!("[REDACTED]");
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_sensitive_fields_at_any_depth() {
+        let mut body = json!({
+            "crypto_type": "ETH",
+            "password": "hunter2",
+            "wallet": { "private_key": "abc", "address": "0x1" },
+            "users": [{ "email": "a@b.c", "twoFactorCode": "123456" }]
+        });
+        redact(&mut body);
+
+        assert_eq!(body["crypto_type"], "ETH");
+        assert_eq!(body["password"], "[REDACTED]");
+        assert_eq!(body["wallet"]["private_key"], "[REDACTED]");
+        assert_eq!(body["wallet"]["address"], "0x1");
+        assert_eq!(body["users"][0]["twoFactorCode"], "[REDACTED]");
+    }
+
+    #[test]
+    fn test_code_fields_are_matched_exactly() {
+        let mut body = json!({
+            "code": "123456",
+            "totp_code": "123456",
+            "recovery_code": "abcd-efgh",
+            "privateKey": "abc",
+            "country_code": "US",
+            "currency_code": "USD",
+            "postal_code": "10001",
+        });
+        redact(&mut body);
+
+        assert_eq!(body["code"], "[REDACTED]");
+        assert_eq!(body["totp_code"], "[REDACTED]");
+        assert_eq!(body["recovery_code"], "[REDACTED]");
+        assert_eq!(body["privateKey"], "[REDACTED]");
+        assert_eq!(body["country_code"], "US");
+        assert_eq!(body["currency_code"], "USD");
+        assert_eq!(body["postal_code"], "10001");
+    }
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("4f1c2d3e-aaaa-bbbb-cccc-000000000000"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"x".repeat(101)));
+    }
+}
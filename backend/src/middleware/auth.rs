@@ -1,7 +1,12 @@
 // Authentication Middleware
-// API key authentication
+// API key and team member session authentication
 
 use crate::api::state::AppState;
+use crate::error::ServiceError;
+use crate::middleware::client_ip::ClientIp;
+use crate::services::account_lockout_service::AttemptKind;
+use crate::services::onboarding_service::Capability;
+use crate::services::multi_user_service::{Permission, UserRole, SESSION_TOKEN_PREFIX};
 use axum::{
     extract::{Request, State},
     http::{HeaderMap, StatusCode},
@@ -14,8 +19,31 @@ use serde_json::json;
 #[derive(Clone)]
 pub struct MerchantContext {
     pub merchant_id: i64,
+    /// API key, or the session token of a team member
     pub api_key: String,
     pub sandbox_mode: bool,
+    /// Team member making the request; `None` for the account owner's API key
+    pub user: Option<TeamMemberContext>,
+}
+
+/// Team member authenticated by a session token
+#[derive(Clone)]
+pub struct TeamMemberContext {
+    pub user_id: i32,
+    pub email: String,
+    pub role: UserRole,
+}
+
+impl MerchantContext {
+    /// Whether the request was made with the account owner's API key
+    pub fn is_owner(&self) -> bool {
+        self.user.is_none()
+    }
+
+    /// Whether the caller's role grants `permission`; the owner holds every permission
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.user.as_ref().is_none_or(|user| user.role.allows(permission))
+    }
 }
 
 /// Extract API key from Authorization header
@@ -36,7 +64,12 @@ fn extract_api_key(headers: &HeaderMap) -> Option<String> {
 
 /// Authentication middleware
 /// 
-/// Validates API key and attaches merchant context to request
+/// Validates API key and attaches merchant context to request. A team
+/// member's session token (`mus_...`) is accepted in place of the API key
+/// and identifies the member. Invalid keys count as failed attempts against
+/// the client IP, which is throttled and then locked out like a failing login.
+/// Live API keys (`live_...`) are refused while the merchant's onboarding
+/// does not allow live mode.
 /// 
 /// # Requirements
 /// * 7.1: Authenticate requests with valid API key
@@ -46,8 +79,14 @@ pub async fn auth_middleware(
     headers: HeaderMap,
     mut request: Request,
     next: Next,
-) -> Result<Response, impl IntoResponse> {
-    
+) -> Result<Response, Response> {
+    let ip = request.extensions().get::<ClientIp>().map(|ClientIp(ip)| ip.to_string());
+    state
+        .account_lockout_service
+        .check_lockout(AttemptKind::ApiKey, None, ip.as_deref())
+        .await
+        .map_err(IntoResponse::into_response)?;
+
     // Extract API key from header
     let api_key = match extract_api_key(&headers) {
         Some(key) => {
@@ -60,21 +99,43 @@ pub async fn auth_middleware(
                     "error": "Missing or invalid Authorization header",
                     "message": "Expected format: Authorization: Bearer <api_key>"
                 }))
-            ));
+            ).into_response());
         }
     };
 
-    
-    // Authenticate with merchant service
-    match state.merchant_service.authenticate(&api_key).await {
-        Ok(merchant) => {
-            
-            // Create merchant context
-            let context = MerchantContext {
-                merchant_id: merchant.id,
-                api_key: api_key.clone(),
-                sandbox_mode: merchant.sandbox_mode,
-            };
+    // Team member sessions, then API keys
+    let authenticated = if api_key.starts_with(SESSION_TOKEN_PREFIX) {
+        if !state.config.multi_user_enabled {
+            return Err(ServiceError::Unauthorized("Team member sessions are disabled".to_string()).into_response());
+        }
+        state.multi_user_service.authenticate_session(&api_key).await.map(|session| MerchantContext {
+            merchant_id: session.user.merchant_id,
+            api_key: api_key.clone(),
+            sandbox_mode: session.sandbox_mode,
+            user: Some(TeamMemberContext {
+                user_id: session.user.id,
+                role: UserRole::parse(&session.user.role).unwrap_or(UserRole::User),
+                email: session.user.email,
+            }),
+        })
+    } else {
+        state.merchant_service.authenticate(&api_key).await.map(|merchant| MerchantContext {
+            merchant_id: merchant.id,
+            api_key: api_key.clone(),
+            sandbox_mode: merchant.sandbox_mode,
+            user: None,
+        })
+    };
+
+    match authenticated {
+        Ok(context) => {
+            if context.api_key.starts_with("live_") {
+                state
+                    .onboarding_service
+                    .require(context.merchant_id, Capability::LiveMode)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+            }
 
             // Attach context to request extensions
             request.extensions_mut().insert(context);
@@ -82,14 +143,27 @@ pub async fn auth_middleware(
             // Continue to next middleware/handler
             Ok(next.run(request).await)
         }
-        Err(e) => {
+        Err(e @ (ServiceError::Database(_) | ServiceError::DatabaseError(_) | ServiceError::Internal(_))) => {
+            // Lookup failures say nothing about the key, so they do not count as attempts
+            tracing::error!("API key authentication failed: {}", e);
+            Err(e.into_response())
+        }
+        Err(_) => {
+            if let Err(e) = state
+                .account_lockout_service
+                .record_failed_attempt(AttemptKind::ApiKey, None, ip.as_deref())
+                .await
+            {
+                tracing::warn!("Failed to record API key failure: {}", e);
+            }
+
             Err((
                 StatusCode::UNAUTHORIZED,
                 axum::Json(json!({
                     "error": "Invalid API key",
                     "message": "The provided API key is not valid"
                 }))
-            ))
+            ).into_response())
         }
     }
 }
@@ -0,0 +1,263 @@
+// Client IP Middleware
+// Resolves the client IP, honouring forwarding headers only from trusted proxies
+
+use crate::api::state::AppState;
+use crate::config::Config;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use ipnetwork::IpNetwork;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{error, warn};
+
+/// IP of the client that made the request, as resolved by [`resolve_client_ip`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Resolves client IPs from the peer address and forwarding headers
+///
+/// `Forwarded` (RFC 7239), or `X-Forwarded-For` if absent, is read from
+/// right to left, skipping hops that are trusted proxies; the first
+/// untrusted address is the client. Headers from untrusted peers are
+/// ignored, since anyone can set them.
+///
+/// Forwarding headers arriving while no proxy is trusted usually mean a
+/// reverse proxy is in front but `TRUSTED_PROXIES` was left empty, so every
+/// client would share the proxy's address; this is logged as an error once.
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    trusted_proxies: Vec<IpNetwork>,
+    reported_untrusted_forwarding: Arc<AtomicBool>,
+}
+
+impl ClientIpResolver {
+    pub fn new(trusted_proxies: Vec<IpNetwork>) -> Self {
+        Self {
+            trusted_proxies,
+            reported_untrusted_forwarding: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Resolver trusting `Config::trusted_proxies`; invalid entries are skipped
+    pub fn from_config(config: &Config) -> Self {
+        let trusted_proxies = config
+            .trusted_proxies
+            .iter()
+            .filter_map(|entry| match parse_network(entry) {
+                Some(network) => Some(network),
+                None => {
+                    warn!("Ignoring invalid trusted proxy: {}", entry);
+                    None
+                }
+            })
+            .collect();
+
+        Self::new(trusted_proxies)
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|network| network.contains(ip))
+    }
+
+    /// Client IP for a request received from `peer`
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = canonical_ip(peer);
+        if !self.is_trusted(peer) {
+            if self.trusted_proxies.is_empty() && is_forwarded(headers) {
+                self.report_untrusted_forwarding(peer);
+            }
+            return peer;
+        }
+
+        let hops = match header_values(headers, "forwarded") {
+            Some(values) => parse_forwarded(&values),
+            None => match header_values(headers, "x-forwarded-for") {
+                Some(values) => values.split(',').map(parse_hop).collect(),
+                None => return peer,
+            },
+        };
+
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client = canonical_ip(ip);
+                    if !self.is_trusted(client) {
+                        break;
+                    }
+                }
+                // Obfuscated or malformed hop: the last proxy we trust is as far as we can see
+                None => break,
+            }
+        }
+
+        client
+    }
+
+    fn report_untrusted_forwarding(&self, peer: IpAddr) {
+        if !self.reported_untrusted_forwarding.swap(true, Ordering::Relaxed) {
+            error!(
+                "Ignoring forwarding headers from {}: TRUSTED_PROXIES is empty, so every request \
+                 behind this proxy shares its IP for rate limits and lockouts. \
+                 Set TRUSTED_PROXIES to the proxy's address (e.g. 127.0.0.1/32,::1/128)",
+                peer
+            );
+        }
+    }
+}
+
+fn is_forwarded(headers: &HeaderMap) -> bool {
+    headers.contains_key("forwarded") || headers.contains_key("x-forwarded-for")
+}
+
+/// Resolve the client IP and attach it to the request as [`ClientIp`]
+///
+/// Layered on the whole router so every other middleware and handler sees
+/// the same address. Without connect info there is nothing to resolve.
+pub async fn resolve_client_ip(
+    State(state): State<AppState>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(addr)) = connect_info {
+        let ip = state.client_ip_resolver.resolve(addr.ip(), request.headers());
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+
+    next.run(request).await
+}
+
+/// IPv4-mapped IPv6 addresses are treated as the IPv4 address they carry
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// Parse an IP or CIDR block; a bare IP is a single-address network
+pub fn parse_network(entry: &str) -> Option<IpNetwork> {
+    let entry = entry.trim();
+    if entry.contains('/') {
+        entry.parse::<IpNetwork>().ok()
+    } else {
+        entry.parse::<IpAddr>().ok().map(|ip| IpNetwork::from(canonical_ip(ip)))
+    }
+}
+
+/// All values of a header joined with commas, as if sent on one line
+fn header_values(headers: &HeaderMap, name: &str) -> Option<String> {
+    let values: Vec<&str> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+    (!values.is_empty()).then(|| values.join(","))
+}
+
+/// `for=` addresses of a `Forwarded` header, oldest hop first
+fn parse_forwarded(value: &str) -> Vec<Option<IpAddr>> {
+    value
+        .split(',')
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_hop(node))
+        })
+        .collect()
+}
+
+/// An address from a forwarding header: `1.2.3.4`, `1.2.3.4:80`,
+/// `2001:db8::1`, `"[2001:db8::1]:443"`
+fn parse_hop(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.rsplit_once(':')?.0.parse::<std::net::Ipv4Addr>().ok().map(IpAddr::V4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn resolver() -> ClientIpResolver {
+        ClientIpResolver::new(vec![
+            parse_network("10.0.0.0/8").unwrap(),
+            parse_network("fd00::/8").unwrap(),
+        ])
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_headers_from_untrusted_peers_are_ignored() {
+        let spoofed = headers("x-forwarded-for", "1.1.1.1");
+        assert_eq!(resolver().resolve(ip("203.0.113.9"), &spoofed), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn test_forwarding_without_trusted_proxies_is_reported_once() {
+        let unconfigured = ClientIpResolver::new(Vec::new());
+        let forwarded = headers("x-forwarded-for", "198.51.100.7");
+
+        assert_eq!(unconfigured.resolve(ip("127.0.0.1"), &HeaderMap::new()), ip("127.0.0.1"));
+        assert!(!unconfigured.reported_untrusted_forwarding.load(Ordering::Relaxed));
+
+        assert_eq!(unconfigured.resolve(ip("127.0.0.1"), &forwarded), ip("127.0.0.1"));
+        assert!(unconfigured.reported_untrusted_forwarding.load(Ordering::Relaxed));
+
+        // Spoofed headers are still ignored once proxies are configured
+        let configured = resolver();
+        configured.resolve(ip("203.0.113.9"), &forwarded);
+        assert!(!configured.reported_untrusted_forwarding.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_forwarded_for_is_read_right_to_left_past_trusted_hops() {
+        // The client prepended a fake address; 198.51.100.7 is what our proxies saw
+        let chain = headers("x-forwarded-for", "1.1.1.1, 198.51.100.7, 10.1.2.3");
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &chain), ip("198.51.100.7"));
+
+        let all_trusted = headers("x-forwarded-for", "10.9.9.9");
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &all_trusted), ip("10.9.9.9"));
+    }
+
+    #[test]
+    fn test_forwarded_header_takes_precedence() {
+        let mut both = headers("forwarded", r#"for=192.0.2.60;proto=http, for="[2001:db8::1]:443""#);
+        both.insert("x-forwarded-for", HeaderValue::from_static("1.1.1.1"));
+        assert_eq!(resolver().resolve(ip("fd00::1"), &both), ip("2001:db8::1"));
+
+        let obfuscated = headers("forwarded", "for=192.0.2.60, for=_hidden");
+        assert_eq!(resolver().resolve(ip("10.0.0.1"), &obfuscated), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_addresses_are_canonical() {
+        assert_eq!(canonical_ip(ip("::ffff:10.0.0.1")), ip("10.0.0.1"));
+        assert_eq!(parse_hop("192.0.2.1:8080"), Some(ip("192.0.2.1")));
+        assert!(parse_network("10.0.0.0/8").unwrap().contains(canonical_ip(ip("::ffff:10.2.3.4"))));
+        assert!(parse_network("not-an-ip").is_none());
+    }
+}
@@ -3,82 +3,64 @@
 
 use crate::api::state::AppState;
 use crate::middleware::auth::MerchantContext;
+use crate::middleware::client_ip::ClientIp;
+use crate::error::ServiceError;
 use axum::{
-    extract::{ConnectInfo, Request, State},
-    http::StatusCode,
+    extract::{Request, State},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use serde_json::json;
-use std::net::SocketAddr;
 
 /// IP whitelist middleware
-/// 
+///
+/// Must run after `auth_middleware`. Checks the client IP against the
+/// merchant-wide allowlist and the allowlist of the API key used, and
+/// records every rejection in the audit log.
+///
 /// # Requirements
 /// * 18.2: Reject requests from non-whitelisted IPs when enabled
 /// * 18.3: Return 403 for non-whitelisted IPs
 /// * 18.7: Allow all IPs when whitelist is empty
 pub async fn ip_whitelist_middleware(
     State(state): State<AppState>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     request: Request,
     next: Next,
-) -> Result<Response, impl IntoResponse> {
+) -> Response {
     // Get merchant context (must run after auth middleware)
-    let merchant_context = request.extensions().get::<MerchantContext>().cloned();
-    
-    if let Some(context) = merchant_context {
-        // Get IP whitelist for merchant
-        let whitelist = match sqlx::query!(
-            "SELECT ip_address FROM ip_whitelist WHERE merchant_id = $1 AND is_active = true",
-            context.merchant_id
-        )
-        .fetch_all(&state.db_pool)
-        .await
-        {
-            Ok(ips) => ips,
-            Err(e) => {
-                tracing::error!("Failed to fetch IP whitelist: {}", e);
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    axum::Json(json!({
-                        "error": "Internal server error",
-                        "message": "Failed to check IP whitelist"
-                    }))
-                ));
-            }
-        };
+    let Some(context) = request.extensions().get::<MerchantContext>().cloned() else {
+        return next.run(request).await;
+    };
+    let client_ip = request.extensions().get::<ClientIp>().map(|ClientIp(ip)| *ip);
 
-        // If whitelist is empty, allow all IPs
-        if whitelist.is_empty() {
-            return Ok(next.run(request).await);
+    let allowlists = match state
+        .ip_whitelist_service
+        .allowlists(context.merchant_id, &context.api_key)
+        .await
+    {
+        Ok(allowlists) => allowlists,
+        Err(e) => {
+            tracing::error!("Failed to fetch IP whitelist: {}", e);
+            return e.into_response();
         }
+    };
 
-        // Check if request IP is in whitelist
-        let request_ip = addr.ip().to_string();
-        let is_whitelisted = whitelist.iter().any(|entry| {
-            // Simple IP matching (could be enhanced with CIDR support)
-            entry.ip_address == request_ip
-        });
-
-        if !is_whitelisted {
-            // Log rejected request
-            tracing::warn!(
-                "IP {} rejected for merchant {} (not in whitelist)",
-                request_ip,
-                context.merchant_id
-            );
+    if allowlists.allows(client_ip) {
+        return next.run(request).await;
+    }
 
-            return Err((
-                StatusCode::FORBIDDEN,
-                axum::Json(json!({
-                    "error": "IP not whitelisted",
-                    "message": "Your IP address is not authorized to access this resource"
-                }))
-            ));
-        }
+    let request_ip = client_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+    tracing::warn!(
+        "IP {} rejected for merchant {} (not in whitelist)",
+        request_ip,
+        context.merchant_id
+    );
+    if let Err(e) = state
+        .ip_whitelist_service
+        .log_rejected_request(context.merchant_id, &request_ip, request.uri().path())
+        .await
+    {
+        tracing::error!("Failed to log rejected request: {}", e);
     }
 
-    // IP is whitelisted or no merchant context, continue
-    Ok(next.run(request).await)
+    ServiceError::IpNotWhitelisted.into_response()
 }
@@ -1,6 +1,7 @@
 // Middleware modules
 
 pub mod auth;
+pub mod client_ip;
 pub mod admin_auth;
 pub mod rate_limit;
 pub mod ip_whitelist;
@@ -9,3 +10,6 @@ pub mod validation;
 pub mod per_key_rate_limit;
 pub mod csrf;
 pub mod advanced_security;
+pub mod two_factor;
+pub mod audit;
+pub mod permissions;
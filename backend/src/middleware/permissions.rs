@@ -0,0 +1,119 @@
+// Permission Middleware
+// Role checks for team members on merchant routes
+
+use crate::api::state::AppState;
+use crate::error::ServiceError;
+use crate::middleware::auth::MerchantContext;
+use crate::services::multi_user_service::Permission;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Team routes, only while `MULTI_USER_ENABLED` is set
+pub async fn require_multi_user(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.config.multi_user_enabled {
+        return ServiceError::Forbidden("Team management is disabled".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Routes only the account owner's API key may use, such as credentials
+pub async fn require_owner(request: Request, next: Next) -> Response {
+    match request.extensions().get::<MerchantContext>() {
+        Some(context) if context.is_owner() => next.run(request).await,
+        Some(_) => ServiceError::Forbidden("Only the account owner can do this".to_string()).into_response(),
+        None => ServiceError::Unauthorized("Authentication required".to_string()).into_response(),
+    }
+}
+
+pub async fn require_manage_users(request: Request, next: Next) -> Response {
+    require(Permission::ManageUsers, request, next).await
+}
+
+pub async fn require_approve_withdrawals(request: Request, next: Next) -> Response {
+    require(Permission::ApproveWithdrawals, request, next).await
+}
+
+pub async fn require_view_analytics(request: Request, next: Next) -> Response {
+    require(Permission::ViewAnalytics, request, next).await
+}
+
+pub async fn require_manage_payments(request: Request, next: Next) -> Response {
+    require(Permission::ManagePayments, request, next).await
+}
+
+/// Refuse team members whose role lacks `permission`
+///
+/// Must run after auth middleware; the owner's API key passes and requests
+/// without a merchant context are refused.
+async fn require(permission: Permission, request: Request, next: Next) -> Response {
+    let Some(context) = request.extensions().get::<MerchantContext>() else {
+        return ServiceError::Unauthorized("Authentication required".to_string()).into_response();
+    };
+    if !context.allows(permission) {
+        if let Some(user) = &context.user {
+            tracing::warn!(
+                "Team member {} ({}) of merchant {} lacks {} for {}",
+                user.user_id,
+                user.role.as_str(),
+                context.merchant_id,
+                permission.as_str(),
+                request.uri().path()
+            );
+        }
+        return ServiceError::Forbidden(format!(
+            "Your role does not have the {} permission",
+            permission.as_str()
+        ))
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::auth::TeamMemberContext;
+    use crate::services::multi_user_service::UserRole;
+    use axum::{body::Body, http::StatusCode, middleware, routing::post, Router};
+    use tower::Service;
+
+    fn context(role: Option<UserRole>) -> MerchantContext {
+        MerchantContext {
+            merchant_id: 1,
+            api_key: "test".to_string(),
+            sandbox_mode: false,
+            user: role.map(|role| TeamMemberContext {
+                user_id: 7,
+                email: "member@example.com".to_string(),
+                role,
+            }),
+        }
+    }
+
+    async fn status(mut app: Router, role: Option<UserRole>) -> StatusCode {
+        let mut request = Request::post("/").body(Body::empty()).unwrap();
+        request.extensions_mut().insert(context(role));
+        app.call(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_read_only_members_are_refused_mutations() {
+        let manage_payments = Router::new()
+            .route("/", post(|| async { "ok" }).route_layer(middleware::from_fn(require_manage_payments)));
+        let manage_users = Router::new()
+            .route("/", post(|| async { "ok" }).route_layer(middleware::from_fn(require_manage_users)));
+
+        assert_eq!(status(manage_payments.clone(), Some(UserRole::User)).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(manage_users.clone(), Some(UserRole::User)).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(manage_payments, Some(UserRole::Moderator)).await, StatusCode::OK);
+        assert_eq!(status(manage_users.clone(), Some(UserRole::Moderator)).await, StatusCode::FORBIDDEN);
+        assert_eq!(status(manage_users, None).await, StatusCode::OK);
+    }
+}
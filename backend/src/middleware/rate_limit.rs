@@ -1,56 +1,74 @@
 // Rate Limiting Middleware
-// Limits requests per merchant per time window
+// Limits requests per API key or merchant, and per client IP on public routes
 
+use crate::api::state::AppState;
+use crate::error::ServiceError;
 use crate::middleware::auth::MerchantContext;
+use crate::middleware::client_ip::ClientIp;
+use crate::services::rate_limit_service::{RateLimitDecision, RouteClass, WINDOW};
 use axum::{
-    extract::Request,
-    http::StatusCode,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue},
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use governor::{
-    clock::DefaultClock,
-    state::{InMemoryState, NotKeyed},
-    Quota, RateLimiter,
-};
-use serde_json::json;
-use std::num::NonZeroU32;
-use std::sync::Arc;
-
-/// Rate limiter instance
-pub type AppRateLimiter = Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>;
-
-/// Create a rate limiter with config
-pub fn create_rate_limiter(requests_per_minute: u32) -> AppRateLimiter {
-    let quota = Quota::per_minute(NonZeroU32::new(requests_per_minute).unwrap_or(NonZeroU32::new(100).unwrap()));
-    Arc::new(RateLimiter::direct(quota))
-}
 
-/// Rate limiting middleware
-/// 
+/// Rate limiting middleware for authenticated routes
+///
+/// Must run after `auth_middleware`. Reads and writes are counted
+/// separately, against the merchant's plan quota.
+///
 /// # Requirements
-/// * 7.3: Limit requests to 100 per minute per API key
+/// * 7.3: Limit requests per API key
 /// * 7.4: Return 429 when rate limit exceeded
 pub async fn rate_limit_middleware(
-    limiter: AppRateLimiter,
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(context) = request.extensions().get::<MerchantContext>() else {
+        return next.run(request).await;
+    };
+
+    let class = RouteClass::for_method(request.method());
+    let decision = state.rate_limit_service.check_merchant(context, class).await;
+    respond(decision, request, next).await
+}
+
+/// Rate limiting middleware for public routes, counted per client IP
+pub async fn public_rate_limit_middleware(
+    State(state): State<AppState>,
     request: Request,
     next: Next,
-) -> Result<Response, impl IntoResponse> {
-    // Check rate limit
-    match limiter.check() {
-        Ok(_) => {
-            // Within rate limit, continue
-            Ok(next.run(request).await)
-        }
-        Err(_) => {
-            // Rate limit exceeded
-            Err((
-                StatusCode::TOO_MANY_REQUESTS,
-                axum::Json(json!({
-                    "error": "Rate limit exceeded",
-                    "message": "Too many requests. Limit: 100 requests per minute"
-                }))
-            ))
-        }
+) -> Response {
+    let Some(ClientIp(ip)) = request.extensions().get::<ClientIp>().copied() else {
+        return next.run(request).await;
+    };
+
+    let decision = state.rate_limit_service.check_ip(&ip.to_string()).await;
+    respond(decision, request, next).await
+}
+
+async fn respond(decision: RateLimitDecision, request: Request, next: Next) -> Response {
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        ServiceError::RateLimitExceeded.into_response()
+    };
+
+    set_rate_limit_headers(response.headers_mut(), &decision);
+    response
+}
+
+/// Add `RateLimit-*` headers, and `Retry-After` when the request was refused
+pub fn set_rate_limit_headers(headers: &mut HeaderMap, decision: &RateLimitDecision) {
+    headers.insert("ratelimit-limit", HeaderValue::from(decision.limit));
+    headers.insert("ratelimit-remaining", HeaderValue::from(decision.remaining));
+    headers.insert("ratelimit-reset", HeaderValue::from(decision.reset_secs));
+    if let Ok(policy) = HeaderValue::from_str(&format!("{};w={}", decision.limit, WINDOW.as_secs())) {
+        headers.insert("ratelimit-policy", policy);
+    }
+    if let Some(retry_after) = decision.retry_after_secs {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
     }
 }
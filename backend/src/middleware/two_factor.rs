@@ -0,0 +1,44 @@
+// Two-Factor Step-Up Middleware
+// Requires a fresh 2FA code for sensitive merchant operations
+
+use crate::api::state::AppState;
+use crate::error::ServiceError;
+use crate::middleware::auth::MerchantContext;
+use crate::services::two_factor_service::STEP_UP_HEADER;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Step-up middleware for sensitive routes
+///
+/// Reads the code from the `X-2FA-Code` header and checks it with
+/// `TwoFactorService::verify_step_up`. Must run after auth middleware;
+/// requests without a merchant context are refused.
+pub async fn require_step_up(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(context) = request.extensions().get::<MerchantContext>().cloned() else {
+        return ServiceError::Unauthorized("Authentication required".to_string()).into_response();
+    };
+
+    let code = request
+        .headers()
+        .get(STEP_UP_HEADER)
+        .and_then(|v| v.to_str().ok());
+
+    if let Err(e) = state.two_factor_service.verify_step_up(context.merchant_id, code).await {
+        tracing::warn!(
+            "Step-up verification failed for merchant {} on {}: {}",
+            context.merchant_id,
+            request.uri().path(),
+            e
+        );
+        return e.into_response();
+    }
+
+    next.run(request).await
+}
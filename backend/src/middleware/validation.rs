@@ -179,34 +179,86 @@ pub fn validate_webhook_url(url: &str) -> Result<(), validator::ValidationError>
 }
 
 /// Check if host is private IP or localhost
-fn is_private_or_localhost(host: &str) -> bool {
+///
+/// Covers localhost names, cloud metadata hostnames and every address
+/// rejected by [`is_blocked_ip`]. Hostnames are not resolved here.
+pub fn is_private_or_localhost(host: &str) -> bool {
     use std::net::IpAddr;
-    
-    // Check for localhost names
-    if host == "localhost" || host == "127.0.0.1" || host == "::1" {
+
+    let host = host.trim_end_matches('.').to_lowercase();
+
+    // Check for localhost and metadata names
+    if host == "localhost"
+        || host.ends_with(".localhost")
+        || host == "metadata"
+        || host == "metadata.google.internal"
+    {
         return true;
     }
-    
-    // Parse as IP and check if private
-    if let Ok(ip) = host.parse::<IpAddr>() {
-        match ip {
-            IpAddr::V4(ipv4) => {
-                let octets = ipv4.octets();
-                // Private IPv4 ranges
-                matches!(octets[0], 10) ||
-                (octets[0] == 172 && (16..=31).contains(&octets[1])) ||
-                (octets[0] == 192 && octets[1] == 168) ||
-                octets[0] == 127 // Loopback
+
+    // Parse as IP (IPv6 hosts may come bracketed from a URL)
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    match literal.parse::<IpAddr>() {
+        Ok(ip) => is_blocked_ip(ip),
+        Err(_) => false,
+    }
+}
+
+/// Check if an address must never receive server-side requests
+///
+/// Rejects unspecified, loopback, private, carrier-grade NAT, link-local
+/// (including the 169.254.169.254 metadata service), documentation,
+/// benchmarking, multicast and reserved ranges. IPv6 addresses that embed an
+/// IPv4 address (mapped, NAT64, 6to4) are judged by the embedded address.
+pub fn is_blocked_ip(ip: std::net::IpAddr) -> bool {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn blocked_v4(ip: Ipv4Addr) -> bool {
+        let octets = ip.octets();
+        ip.is_unspecified()
+            || ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_broadcast()
+            || ip.is_documentation()
+            || ip.is_multicast()
+            || octets[0] == 0 // "This network"
+            || (octets[0] == 100 && (64..=127).contains(&octets[1])) // Carrier-grade NAT
+            || (octets[0] == 192 && octets[1] == 0 && octets[2] == 0) // IETF protocol assignments
+            || (octets[0] == 198 && (18..=19).contains(&octets[1])) // Benchmarking
+            || octets[0] >= 240 // Reserved
+    }
+
+    match ip {
+        IpAddr::V4(ipv4) => blocked_v4(ipv4),
+        IpAddr::V6(ipv6) => {
+            if let Some(mapped) = ipv6.to_ipv4_mapped() {
+                return blocked_v4(mapped);
+            }
+
+            let segments = ipv6.segments();
+            // NAT64 (64:ff9b::/96) carries the IPv4 address in the last 32 bits
+            if segments[0] == 0x64 && segments[1] == 0xff9b && segments[2..6] == [0, 0, 0, 0] {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return blocked_v4(Ipv4Addr::new(a, b, c, d));
             }
-            IpAddr::V6(ipv6) => {
-                // Private IPv6 ranges and loopback
-                ipv6.is_loopback() || 
-                ipv6.segments()[0] == 0xfc00 || // Unique local
-                ipv6.segments()[0] == 0xfd00    // Unique local
+            // 6to4 (2002::/16) carries the IPv4 address in bits 16..48
+            if segments[0] == 0x2002 {
+                let [a, b] = segments[1].to_be_bytes();
+                let [c, d] = segments[2].to_be_bytes();
+                return blocked_v4(Ipv4Addr::new(a, b, c, d));
             }
+
+            ipv6.is_unspecified()
+                || ipv6.is_loopback()
+                || ipv6.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00 // Unique local (fc00::/7)
+                || (segments[0] & 0xffc0) == 0xfe80 // Link-local (fe80::/10)
+                || (segments[0] & 0xffc0) == 0xfec0 // Site-local (fec0::/10)
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8) // Documentation
+                || segments[..6] == [0, 0, 0, 0, 0, 0] // IPv4-compatible (deprecated)
         }
-    } else {
-        false
     }
 }
 
@@ -238,4 +290,31 @@ mod tests {
         assert!(!is_private_or_localhost("8.8.8.8"));
         assert!(!is_private_or_localhost("example.com"));
     }
+
+    #[test]
+    fn test_blocked_ip_ranges() {
+        use std::net::IpAddr;
+
+        let blocked = [
+            "0.0.0.0", "169.254.169.254", "100.100.100.200", "172.31.255.255", "224.0.0.1",
+            "255.255.255.255", "::", "::1", "fe80::1", "fd00:ec2::254", "ff02::1",
+            "::ffff:127.0.0.1", "64:ff9b::a9fe:a9fe", "2002:c0a8:0101::1",
+        ];
+        for ip in blocked {
+            assert!(is_blocked_ip(ip.parse::<IpAddr>().unwrap()), "{} should be blocked", ip);
+        }
+
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(!is_blocked_ip(ip.parse::<IpAddr>().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn test_private_host_names_and_bracketed_ipv6() {
+        assert!(is_private_or_localhost("api.localhost"));
+        assert!(is_private_or_localhost("metadata.google.internal"));
+        assert!(is_private_or_localhost("[::1]"));
+        assert!(is_private_or_localhost("[fe80::1]"));
+        assert!(!is_private_or_localhost("[2606:4700:4700::1111]"));
+    }
 }
@@ -11,6 +11,9 @@ use crate::payment::models::PaymentStatus;
 /// Webhook payload sent to merchant endpoints
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebhookPayload {
+    /// Id of the recorded event ("evt_..."), for deduplication and backfill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<String>,
     pub event_type: String,  // "payment.confirmed", "payment.expired", "refund.completed"
     pub payment_id: String,
     pub merchant_id: i64,
@@ -19,6 +22,9 @@ pub struct WebhookPayload {
     pub crypto_type: String,
     pub transaction_hash: Option<String>,
     pub timestamp: i64,
+    /// Event-specific details, e.g. amounts received for underpaid payments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
 }
 
 /// Webhook delivery record for tracking delivery attempts
@@ -48,6 +54,7 @@ mod tests {
     #[test]
     fn test_webhook_payload_creation() {
         let payload = WebhookPayload {
+            event_id: None,
             event_type: "payment.confirmed".to_string(),
             payment_id: "pay_abc123".to_string(),
             merchant_id: 1i64,
@@ -56,6 +63,7 @@ mod tests {
             crypto_type: "USDT_BEP20".to_string(),
             transaction_hash: Some("0xabc123".to_string()),
             timestamp: 1234567890,
+            data: None,
         };
 
         assert_eq!(payload.event_type, "payment.confirmed");
@@ -71,6 +79,7 @@ mod tests {
     #[test]
     fn test_webhook_payload_serialization() {
         let payload = WebhookPayload {
+            event_id: None,
             event_type: "payment.expired".to_string(),
             payment_id: "pay_xyz789".to_string(),
             merchant_id: 1i64,
@@ -79,6 +88,7 @@ mod tests {
             crypto_type: "SOL".to_string(),
             transaction_hash: None,
             timestamp: 9876543210,
+            data: None,
         };
 
         let json = serde_json::to_string(&payload).unwrap();
@@ -93,6 +103,31 @@ mod tests {
         assert_eq!(deserialized.status, payload.status);
     }
 
+    #[test]
+    fn test_webhook_payload_event_id_round_trip() {
+        let payload = WebhookPayload {
+            event_id: Some("evt_abc123".to_string()),
+            event_type: "payment.confirmed".to_string(),
+            payment_id: "pay_abc123".to_string(),
+            merchant_id: 1i64,
+            status: PaymentStatus::Confirmed,
+            amount: Decimal::new(100, 0),
+            crypto_type: "SOL".to_string(),
+            transaction_hash: None,
+            timestamp: 1234567890,
+            data: None,
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["event_id"], "evt_abc123");
+        assert!(json.get("data").is_none());
+
+        // Payloads queued before event ids existed still deserialize
+        let legacy = r#"{"event_type":"payment.confirmed","payment_id":"pay_1","merchant_id":1,"status":"Confirmed","amount":"1","crypto_type":"SOL","transaction_hash":null,"timestamp":1}"#;
+        let parsed: WebhookPayload = serde_json::from_str(legacy).unwrap();
+        assert!(parsed.event_id.is_none());
+    }
+
     #[test]
     fn test_webhook_delivery_creation() {
         let delivery = WebhookDelivery {
@@ -195,6 +230,7 @@ mod tests {
 
         for event_type in event_types {
             let payload = WebhookPayload {
+                event_id: None,
                 event_type: event_type.to_string(),
                 payment_id: "pay_test".to_string(),
                 merchant_id: 1i64,
@@ -203,6 +239,7 @@ mod tests {
                 crypto_type: "SOL".to_string(),
                 transaction_hash: None,
                 timestamp: 1234567890,
+                data: None,
             };
 
             assert_eq!(payload.event_type, event_type);
@@ -237,6 +274,7 @@ mod tests {
     #[test]
     fn test_webhook_payload_without_transaction_hash() {
         let payload = WebhookPayload {
+            event_id: None,
             event_type: "payment.expired".to_string(),
             payment_id: "pay_expired".to_string(),
             merchant_id: 1i64,
@@ -245,6 +283,7 @@ mod tests {
             crypto_type: "USDT_POLYGON".to_string(),
             transaction_hash: None,
             timestamp: 1234567890,
+            data: None,
         };
 
         assert!(payload.transaction_hash.is_none());
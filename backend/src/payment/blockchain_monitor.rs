@@ -162,15 +162,17 @@ impl BlockchainMonitor for EvmMonitor {
         // Check if transaction succeeded
         let success = self.check_transaction_success(tx_hash).await?;
 
-        // Get actual block timestamp if block number is available
-        let timestamp = if let Some(block_num) = block_number {
-            self.get_block_timestamp(block_num).await
-                .unwrap_or_else(|e| {
-                    warn!("Failed to get block timestamp: {}, using current time", e);
-                    chrono::Utc::now()
-                })
-        } else {
-            chrono::Utc::now()
+        // Get actual block timestamp if block number is available; an unknown
+        // timestamp stays None so callers don't mistake it for the current time
+        let timestamp = match block_number {
+            Some(block_num) => match self.get_block_timestamp(block_num).await {
+                Ok(timestamp) => Some(timestamp),
+                Err(e) => {
+                    warn!("Failed to get block timestamp: {}", e);
+                    None
+                }
+            },
+            None => None,
         };
 
         Ok(BlockchainTransaction {
@@ -180,7 +182,7 @@ impl BlockchainMonitor for EvmMonitor {
             amount,
             confirmations,
             block_number,
-            timestamp: Some(timestamp),
+            timestamp,
             success,
         })
     }
@@ -10,3 +10,4 @@ pub mod verifier;
 pub mod processor;
 pub mod price_fetcher;
 pub mod fee_calculator;
+pub mod state_machine;
@@ -7,7 +7,7 @@ use chrono::{DateTime, Utc};
 
 /// Payment status enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
-#[sqlx(type_name = "payment_status", rename_all = "UPPERCASE")]
+#[sqlx(type_name = "payment_status", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum PaymentStatus {
     Pending,
     Confirmed,
@@ -15,6 +15,11 @@ pub enum PaymentStatus {
     Expired,
     Confirming,
     Refunded,
+    Cancelled,
+    Underpaid,
+    Overpaid,
+    PartiallyRefunded,
+    PaidLate,
 }
 
 impl PaymentStatus {
@@ -26,9 +31,94 @@ impl PaymentStatus {
             "EXPIRED" => PaymentStatus::Expired,
             "CONFIRMING" => PaymentStatus::Confirming,
             "REFUNDED" => PaymentStatus::Refunded,
+            "CANCELLED" => PaymentStatus::Cancelled,
+            "UNDERPAID" => PaymentStatus::Underpaid,
+            "OVERPAID" => PaymentStatus::Overpaid,
+            "PARTIALLY_REFUNDED" => PaymentStatus::PartiallyRefunded,
+            "PAID_LATE" => PaymentStatus::PaidLate,
             _ => PaymentStatus::Pending, // Default fallback
         }
     }
+
+    /// Value stored in `payment_transactions.status`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "PENDING",
+            PaymentStatus::Confirmed => "CONFIRMED",
+            PaymentStatus::Failed => "FAILED",
+            PaymentStatus::Expired => "EXPIRED",
+            PaymentStatus::Confirming => "CONFIRMING",
+            PaymentStatus::Refunded => "REFUNDED",
+            PaymentStatus::Cancelled => "CANCELLED",
+            PaymentStatus::Underpaid => "UNDERPAID",
+            PaymentStatus::Overpaid => "OVERPAID",
+            PaymentStatus::PartiallyRefunded => "PARTIALLY_REFUNDED",
+            PaymentStatus::PaidLate => "PAID_LATE",
+        }
+    }
+
+    /// Webhook event emitted when a payment enters this status
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "payment.created",
+            PaymentStatus::Confirmed => "payment.confirmed",
+            PaymentStatus::Failed => "payment.failed",
+            PaymentStatus::Expired => "payment.expired",
+            PaymentStatus::Confirming => "payment.confirming",
+            PaymentStatus::Refunded => "payment.refunded",
+            PaymentStatus::Cancelled => "payment.cancelled",
+            PaymentStatus::Underpaid => "payment.underpaid",
+            PaymentStatus::Overpaid => "payment.overpaid",
+            PaymentStatus::PartiallyRefunded => "payment.partially_refunded",
+            PaymentStatus::PaidLate => "payment.paid_late",
+        }
+    }
+
+    /// Whether no further transitions are possible from this status
+    ///
+    /// Expired payments are not terminal: funds that still arrive move them
+    /// to `PaidLate` for the merchant to accept or refund.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            PaymentStatus::Failed
+                | PaymentStatus::Refunded
+                | PaymentStatus::Cancelled
+        )
+    }
+
+    /// Whether funds for this payment have been settled to the merchant
+    pub fn is_settled(&self) -> bool {
+        matches!(
+            self,
+            PaymentStatus::Confirmed | PaymentStatus::Overpaid | PaymentStatus::PartiallyRefunded
+        )
+    }
+
+    /// Legal moves of the payment state machine
+    ///
+    /// Self-transitions are only legal where they carry new information
+    /// (another top-up on an underpaid payment, another partial refund).
+    pub fn can_transition_to(&self, to: PaymentStatus) -> bool {
+        use PaymentStatus::*;
+
+        matches!(
+            (self, to),
+            (Pending, Confirming | Confirmed | Underpaid | Overpaid | Failed | Expired | Cancelled)
+                | (Confirming, Confirmed | Underpaid | Overpaid | Failed | Expired)
+                | (Underpaid, Underpaid | Confirming | Confirmed | Overpaid | Failed | Expired | Cancelled | Refunded)
+                | (Confirmed | Overpaid, Refunded | PartiallyRefunded)
+                | (PartiallyRefunded, PartiallyRefunded | Refunded)
+                | (Expired, PaidLate)
+                | (PaidLate, PaidLate | Confirmed | Refunded | PartiallyRefunded)
+        )
+    }
+}
+
+impl fmt::Display for PaymentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Cryptocurrency type enumeration (5 supported payment methods)
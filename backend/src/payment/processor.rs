@@ -8,7 +8,12 @@ use sqlx::PgPool;
 use tracing::info;
 
 use crate::error::ServiceError;
-use crate::services::{merchant_service::MerchantService, price_service::PriceService};
+use crate::services::{
+    merchant_service::MerchantService,
+    onboarding_service::{Capability, OnboardingService},
+    price_service::PriceService,
+    volume_tracking_service::VolumeTrackingService,
+};
 use std::sync::Arc;
 use super::models::{CreatePaymentRequest, PaymentResponse, PaymentStatus, CryptoType};
 
@@ -18,6 +23,8 @@ pub struct PaymentProcessor {
     db_pool: PgPool,
     price_service: Arc<PriceService>,
     merchant_service: MerchantService,
+    volume_tracking: VolumeTrackingService,
+    onboarding: OnboardingService,
     payment_page_base_url: String,
 }
 
@@ -26,6 +33,8 @@ impl PaymentProcessor {
         Self {
             db_pool: db_pool.clone(),
             price_service,
+            volume_tracking: VolumeTrackingService::from_config(db_pool.clone(), &config),
+            onboarding: OnboardingService::from_config(db_pool.clone(), &config),
             merchant_service: MerchantService::new(db_pool, config),
             payment_page_base_url,
         }
@@ -140,6 +149,17 @@ impl PaymentProcessor {
             return Err(ServiceError::ValidationError("Either amount or amount_usd must be provided".to_string()));
         };
 
+        let mut tx = self.db_pool.begin().await?;
+
+        // Live payments need onboarding to allow live mode and count towards
+        // the merchant's daily volume limit; the merchant stays locked until
+        // the payment is stored
+        if !is_sandbox {
+            self.onboarding.require(merchant_id, Capability::LiveMode).await?;
+            VolumeTrackingService::lock_merchant(&mut tx, merchant_id).await?;
+            self.volume_tracking.check_transaction(merchant_id, amount_usd).await?;
+        }
+
         // Calculate expiration time
         let expiration_minutes = request.expiration_minutes.unwrap_or(15);
         let expires_at = Utc::now() + Duration::minutes(expiration_minutes as i64);
@@ -169,7 +189,7 @@ impl PaymentProcessor {
         let required_confirmations = request.crypto_type.required_confirmations() as i32;
         
         // Determine if partial payments are enabled
-        let partial_payments_enabled = false; // Simplified for now
+        let partial_payments_enabled = request.partial_payments_enabled.unwrap_or(false);
         
         // Store payment in database
         let payment = sqlx::query_as!(
@@ -178,9 +198,10 @@ impl PaymentProcessor {
             INSERT INTO payment_transactions (
                 payment_id, merchant_id, crypto_type, amount, amount_usd, to_address,
                 status, expires_at, fee_percentage, fee_amount, fee_amount_usd, network,
-                required_confirmations, webhook_url, description
+                required_confirmations, webhook_url, description,
+                partial_payments_enabled, remaining_balance
             )
-            VALUES ($1, $2, $3, $4, $5, $6, 'PENDING', $7, $8, $9, $10, $11, $12, $13, $14)
+            VALUES ($1, $2, $3, $4, $5, $6, 'PENDING', $7, $8, $9, $10, $11, $12, $13, $14, $15, $4)
             RETURNING id, payment_id, merchant_id, crypto_type, amount, amount_usd, to_address,
                      status, expires_at, created_at, confirmed_at, description, metadata,
                      confirmations, required_confirmations
@@ -198,11 +219,14 @@ impl PaymentProcessor {
             request.crypto_type.network(),
             1, // required_confirmations
             request.webhook_url,
-            request.description
+            request.description,
+            partial_payments_enabled
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         // Generate payment link and QR code
         let payment_link = format!("{}/pay/{}", 
             std::env::var("PAYMENT_PAGE_BASE_URL").unwrap_or_else(|_| "http://localhost:8080".to_string()),
@@ -200,10 +200,9 @@ impl SolanaMonitor {
             amount,
             confirmations,
             block_number: Some(tx_result.slot),
-            timestamp: chrono::DateTime::from_timestamp(
-                tx_result.block_time.unwrap_or(0) as i64,
-                0
-            ),
+            timestamp: tx_result
+                .block_time
+                .and_then(|block_time| chrono::DateTime::from_timestamp(block_time, 0)),
             success,
         })
     }
@@ -0,0 +1,362 @@
+// Payment State Machine
+// Single entry point for payment status changes
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::info;
+
+use super::models::PaymentStatus;
+use crate::error::ServiceError;
+use crate::models::webhook::WebhookPayload;
+use crate::services::email_templates::PaymentConfirmed;
+use crate::services::payment_stream_service::PAYMENT_TRANSITIONS_CHANNEL;
+use crate::services::{notification_service, outbox};
+
+/// Who triggered a payment status transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionActor {
+    System,
+    Customer,
+    Merchant(i64),
+    Admin(i64),
+}
+
+impl std::fmt::Display for TransitionActor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransitionActor::System => write!(f, "system"),
+            TransitionActor::Customer => write!(f, "customer"),
+            TransitionActor::Merchant(id) => write!(f, "merchant:{}", id),
+            TransitionActor::Admin(id) => write!(f, "admin:{}", id),
+        }
+    }
+}
+
+/// A requested status change for a payment
+#[derive(Debug, Clone)]
+pub struct PaymentTransition {
+    pub to: PaymentStatus,
+    pub actor: TransitionActor,
+    pub reason: Option<String>,
+    pub transaction_hash: Option<String>,
+}
+
+impl PaymentTransition {
+    pub fn new(to: PaymentStatus, actor: TransitionActor) -> Self {
+        Self {
+            to,
+            actor,
+            reason: None,
+            transaction_hash: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn with_transaction_hash(mut self, transaction_hash: impl Into<String>) -> Self {
+        self.transaction_hash = Some(transaction_hash.into());
+        self
+    }
+}
+
+/// Result of a transition that has been written to the database
+#[derive(Debug, Clone)]
+pub struct AppliedTransition {
+    pub payment_id: i64,
+    pub public_payment_id: String,
+    pub merchant_id: i64,
+    pub from: PaymentStatus,
+    pub to: PaymentStatus,
+    pub amount: rust_decimal::Decimal,
+    pub crypto_type: String,
+    pub transaction_hash: Option<String>,
+    pub total_paid: rust_decimal::Decimal,
+    pub remaining_balance: Option<rust_decimal::Decimal>,
+    pub overpaid_amount: rust_decimal::Decimal,
+    pub amount_usd: rust_decimal::Decimal,
+    pub late_amount_usd: Option<rust_decimal::Decimal>,
+    pub late_exchange_rate: Option<rust_decimal::Decimal>,
+    /// False when the payment was already in the target status
+    pub changed: bool,
+}
+
+/// Row of `payment_status_history`
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PaymentStatusHistory {
+    pub from_status: String,
+    pub to_status: String,
+    pub actor: String,
+    pub reason: Option<String>,
+    pub transaction_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Apply a transition inside an existing database transaction
+///
+/// Locks the payment row, rejects illegal moves, records the change in
+/// `payment_status_history`, queues the matching `payment.*` event in the
+/// outbox and notifies the live payment stream. Re-applying the current status
+/// is a no-op unless the state machine allows that self-transition. The event
+/// and the stream notification are only delivered if the caller commits.
+pub async fn apply_transition(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: i64,
+    transition: &PaymentTransition,
+) -> Result<AppliedTransition, ServiceError> {
+    let current = sqlx::query!(
+        r#"
+        SELECT payment_id, merchant_id, status, amount, crypto_type, transaction_hash,
+               total_paid, remaining_balance, overpaid_amount,
+               amount_usd, late_amount_usd, late_exchange_rate
+        FROM payment_transactions
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        payment_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(ServiceError::PaymentNotFound)?;
+
+    let from = PaymentStatus::from_string(&current.status);
+    let to = transition.to;
+
+    if from == to && !from.can_transition_to(to) {
+        return Ok(AppliedTransition {
+            payment_id,
+            public_payment_id: current.payment_id,
+            merchant_id: current.merchant_id,
+            from,
+            to,
+            amount: current.amount,
+            crypto_type: current.crypto_type,
+            transaction_hash: current.transaction_hash,
+            total_paid: current.total_paid,
+            remaining_balance: current.remaining_balance,
+            overpaid_amount: current.overpaid_amount,
+            amount_usd: current.amount_usd,
+            late_amount_usd: current.late_amount_usd,
+            late_exchange_rate: current.late_exchange_rate,
+            changed: false,
+        });
+    }
+
+    if !from.can_transition_to(to) {
+        return Err(ServiceError::InvalidStateTransition(format!(
+            "Payment {} cannot move from {} to {}",
+            current.payment_id, from, to
+        )));
+    }
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE payment_transactions
+        SET status = $1::varchar,
+            confirmed_at = CASE WHEN $1::varchar = 'CONFIRMED' THEN NOW() ELSE confirmed_at END,
+            transaction_hash = COALESCE($2, transaction_hash)
+        WHERE id = $3
+        RETURNING transaction_hash
+        "#,
+        to.as_str(),
+        transition.transaction_hash.as_deref(),
+        payment_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO payment_status_history (
+            payment_id, merchant_id, from_status, to_status, actor, reason, transaction_hash
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        payment_id,
+        current.merchant_id,
+        from.as_str(),
+        to.as_str(),
+        transition.actor.to_string(),
+        transition.reason.as_deref(),
+        transition.transaction_hash.as_deref()
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    info!(
+        "Payment {} transitioned {} -> {} by {}",
+        current.payment_id, from, to, transition.actor
+    );
+
+    let applied = AppliedTransition {
+        payment_id,
+        public_payment_id: current.payment_id,
+        merchant_id: current.merchant_id,
+        from,
+        to,
+        amount: current.amount,
+        crypto_type: current.crypto_type,
+        transaction_hash: updated.transaction_hash,
+        total_paid: current.total_paid,
+        remaining_balance: current.remaining_balance,
+        overpaid_amount: current.overpaid_amount,
+        amount_usd: current.amount_usd,
+        late_amount_usd: current.late_amount_usd,
+        late_exchange_rate: current.late_exchange_rate,
+        changed: true,
+    };
+
+    outbox::enqueue_payment_event(tx, payment_id, transition_payload(&applied)).await?;
+
+    // Postgres holds the notification until commit, whoever applied the transition
+    sqlx::query!("SELECT pg_notify($1, $2)", PAYMENT_TRANSITIONS_CHANNEL, payment_id.to_string())
+        .execute(&mut **tx)
+        .await?;
+
+    if applied.to == PaymentStatus::Confirmed {
+        let notice = PaymentConfirmed {
+            payment_id: applied.public_payment_id.clone(),
+            amount: applied.amount.normalize().to_string(),
+            crypto_type: applied.crypto_type.clone(),
+        };
+        notification_service::enqueue_notification(tx, applied.merchant_id, &notice).await?;
+    }
+
+    Ok(applied)
+}
+
+/// Webhook payload describing an applied transition
+fn transition_payload(applied: &AppliedTransition) -> WebhookPayload {
+    WebhookPayload {
+        event_id: None,
+        event_type: applied.to.event_type().to_string(),
+        payment_id: applied.public_payment_id.clone(),
+        merchant_id: applied.merchant_id,
+        status: applied.to,
+        amount: applied.amount,
+        crypto_type: applied.crypto_type.clone(),
+        transaction_hash: applied.transaction_hash.clone(),
+        timestamp: Utc::now().timestamp(),
+        data: amount_details(applied),
+    }
+}
+
+/// Amounts attached to underpayment, overpayment and late-payment events
+fn amount_details(applied: &AppliedTransition) -> Option<serde_json::Value> {
+    match applied.to {
+        PaymentStatus::PaidLate => Some(serde_json::json!({
+            "amount_received": applied.total_paid.to_string(),
+            "original_amount_usd": applied.amount_usd.to_string(),
+            "current_amount_usd": applied.late_amount_usd.unwrap_or_default().to_string(),
+            "exchange_rate": applied.late_exchange_rate.unwrap_or_default().to_string(),
+        })),
+        PaymentStatus::Underpaid | PaymentStatus::Overpaid => Some(serde_json::json!({
+            "amount_received": applied.total_paid.to_string(),
+            "remaining_balance": applied.remaining_balance.unwrap_or_default().to_string(),
+            "overpaid_amount": applied.overpaid_amount.to_string(),
+        })),
+        _ => None,
+    }
+}
+
+/// Transition a payment in its own database transaction
+pub async fn transition_payment(
+    db_pool: &PgPool,
+    payment_id: i64,
+    transition: PaymentTransition,
+) -> Result<AppliedTransition, ServiceError> {
+    let mut tx = db_pool.begin().await?;
+    let applied = apply_transition(&mut tx, payment_id, &transition).await?;
+    tx.commit().await?;
+
+    Ok(applied)
+}
+
+/// Fetch the transition history of a payment, oldest first
+pub async fn get_status_history(
+    db_pool: &PgPool,
+    payment_id: i64,
+) -> Result<Vec<PaymentStatusHistory>, ServiceError> {
+    let history = sqlx::query_as!(
+        PaymentStatusHistory,
+        r#"
+        SELECT from_status, to_status, actor, reason, transaction_hash, created_at
+        FROM payment_status_history
+        WHERE payment_id = $1
+        ORDER BY created_at ASC, id ASC
+        "#,
+        payment_id
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PaymentStatus::*;
+
+    const ALL: [PaymentStatus; 11] = [
+        Pending, Confirming, Confirmed, Failed, Expired, Refunded,
+        Cancelled, Underpaid, Overpaid, PartiallyRefunded, PaidLate,
+    ];
+
+    #[test]
+    fn test_happy_path_transitions() {
+        assert!(Pending.can_transition_to(Confirming));
+        assert!(Confirming.can_transition_to(Confirmed));
+        assert!(Confirmed.can_transition_to(PartiallyRefunded));
+        assert!(PartiallyRefunded.can_transition_to(Refunded));
+    }
+
+    #[test]
+    fn test_expiry_uses_expired_status() {
+        assert!(Pending.can_transition_to(Expired));
+        assert!(Confirming.can_transition_to(Expired));
+        assert!(!Confirmed.can_transition_to(Expired));
+    }
+
+    #[test]
+    fn test_late_funds_reopen_expired_payment() {
+        assert!(Expired.can_transition_to(PaidLate));
+        assert!(PaidLate.can_transition_to(Confirmed));
+        assert!(PaidLate.can_transition_to(Refunded));
+        assert!(!Expired.can_transition_to(Confirmed));
+        assert!(PaidLate.can_transition_to(PartiallyRefunded));
+    }
+
+    #[test]
+    fn test_terminal_states_have_no_exits() {
+        for from in ALL.iter().filter(|s| s.is_terminal()) {
+            for to in ALL {
+                assert!(!from.can_transition_to(to), "{} -> {} should be illegal", from, to);
+            }
+        }
+    }
+
+    #[test]
+    fn test_confirmed_cannot_be_reopened() {
+        assert!(!Confirmed.can_transition_to(Pending));
+        assert!(!Confirmed.can_transition_to(Failed));
+        assert!(!Confirmed.can_transition_to(Confirmed));
+    }
+
+    #[test]
+    fn test_status_string_round_trip() {
+        for status in ALL {
+            assert_eq!(PaymentStatus::from_string(status.as_str()), status);
+        }
+    }
+
+    #[test]
+    fn test_transition_actor_display() {
+        assert_eq!(TransitionActor::System.to_string(), "system");
+        assert_eq!(TransitionActor::Merchant(7).to_string(), "merchant:7");
+        assert_eq!(TransitionActor::Admin(1).to_string(), "admin:1");
+    }
+}
@@ -1,32 +1,51 @@
 // Payment Verification Service
 // Verifies cryptocurrency payments and updates payment status
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
-use std::str::FromStr;
-use sqlx::{PgPool, Postgres, Transaction};
+use sqlx::PgPool;
 use tracing::{info, warn, error};
-use serde_json::json;
 
 use super::models::{PaymentTransaction, PaymentStatus, CryptoType, BlockchainTransaction};
 use super::blockchain_monitor::get_blockchain_monitor;
-use crate::services::webhook_service::WebhookService;
+use super::state_machine::{self, PaymentTransition, TransitionActor};
+use crate::services::payment_policy_service::{AmountOutcome, OverpaymentAction, PaymentPolicyService};
+use crate::services::payment_stream_service::PaymentStreamService;
+use crate::services::price_service::PriceService;
+use crate::services::refund_service::RefundService;
+use crate::services::screening_service::{ScreeningOutcome, ScreeningService, SubjectType};
+use std::sync::Arc;
 
 pub struct PaymentVerifier {
     db_pool: PgPool,
-    webhook_service: WebhookService,
+    policy_service: PaymentPolicyService,
+    payment_stream: PaymentStreamService,
+    price_service: Arc<PriceService>,
+    screening: ScreeningService,
     config: crate::config::Config,
 }
 
 impl PaymentVerifier {
-    pub fn new(db_pool: PgPool, webhook_service: WebhookService, config: crate::config::Config) -> Self {
+    pub fn new(
+        db_pool: PgPool,
+        price_service: Arc<PriceService>,
+        config: crate::config::Config,
+    ) -> Self {
         Self {
+            policy_service: PaymentPolicyService::new(db_pool.clone()),
+            payment_stream: PaymentStreamService::new(db_pool.clone()),
+            screening: ScreeningService::from_config(db_pool.clone(), &config),
             db_pool,
-            webhook_service,
+            price_service,
             config,
         }
     }
 
+    pub fn with_payment_stream(mut self, payment_stream: PaymentStreamService) -> Self {
+        self.payment_stream = payment_stream;
+        self
+    }
+
     /// Verify a payment using public payment_id and transaction hash
     /// 
     /// This is the public API method that accepts the payment_id string (e.g., "pay_abc123")
@@ -38,8 +57,8 @@ impl PaymentVerifier {
     /// * `merchant_id` - ID of the merchant requesting verification
     /// 
     /// # Returns
-    /// * `Ok(true)` if payment is confirmed
-    /// * `Ok(false)` if payment is pending more confirmations
+    /// * `Ok(true)` if payment is settled (confirmed or overpaid)
+    /// * `Ok(false)` if payment is pending more confirmations or awaiting a top-up
     /// * `Err` if verification fails
     pub async fn verify_payment(
         &self,
@@ -68,7 +87,12 @@ impl PaymentVerifier {
         }
 
         // Delegate to internal verification method
-        self.verify_payment_by_hash(payment.id, transaction_hash, merchant_id).await
+        let result = self.verify_payment_by_hash(payment.id, transaction_hash, merchant_id).await;
+
+        // Status and confirmation count may have changed even if verification failed
+        self.payment_stream.publish(payment.id).await;
+
+        result
     }
 
     /// Verify a payment using user-provided transaction hash
@@ -76,7 +100,7 @@ impl PaymentVerifier {
     /// 
     /// # Requirements
     /// * 3.1: Verify transaction hash exists on blockchain
-    /// * 3.2: Confirm amount matches expected payment amount
+    /// * 3.2: Compare amount received against expected payment amount
     /// * 3.3: Confirm recipient address matches merchant's wallet
     /// * 3.4: Mark payment as confirmed when sufficient confirmations received
     /// * 3.5: Reject verification if transaction hash is invalid or doesn't match
@@ -111,6 +135,23 @@ impl PaymentVerifier {
             ).into());
         }
 
+        // Top-ups are recorded in partial_payments, so check those too
+        let recorded_for = sqlx::query_scalar!(
+            "SELECT payment_id FROM partial_payments WHERE transaction_hash = $1",
+            transaction_hash
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        if let Some(existing_id) = recorded_for {
+            if existing_id != payment_id {
+                return Err(format!(
+                    "Transaction hash already used for payment #{}. Each transaction can only be used once.",
+                    existing_id
+                ).into());
+            }
+        }
+
         // 2. Get payment from database and verify merchant ownership
         let payment = sqlx::query_as::<_, PaymentTransaction>(
             r#"
@@ -131,19 +172,24 @@ impl PaymentVerifier {
             ).into());
         }
 
-        // 4. Check if payment is already confirmed
-        if payment.status == "CONFIRMED" {
+        // 4. Check if payment is already settled
+        let status = PaymentStatus::from_string(&payment.status);
+        if status.is_settled() {
             info!(" Payment {} already confirmed", payment_id);
             return Ok(true);
         }
 
-        // 5. Check if payment has expired
-        if payment.expires_at < Utc::now() {
-            self.mark_payment_failed(payment_id, "Payment expired").await?;
-            return Err("Payment has expired. Please create a new payment request.".into());
+        if status.is_terminal() {
+            return Err(format!("Payment is {} and can no longer be verified", status).into());
         }
 
-        // 6. Fetch blockchain transaction using the provided hash
+        // This transaction was already counted towards the payment
+        if recorded_for.is_some() {
+            info!(" Transaction {} already recorded for payment {}", transaction_hash, payment_id);
+            return Ok(false);
+        }
+
+        // 5. Fetch blockchain transaction using the provided hash
         // Parse crypto type from string
         let crypto_type = CryptoType::from_string(&payment.crypto_type);
 
@@ -156,10 +202,33 @@ impl PaymentVerifier {
             .await
             .map_err(|e| format!("Failed to fetch transaction from {}: {}", monitor.blockchain_name(), e))?;
 
-        // 7. Verify transaction details match payment (Requirements 3.2, 3.3, 3.5)
-        if !self.validate_transaction(&payment, &blockchain_tx)? {
-            self.mark_payment_failed(payment_id, "Transaction validation failed").await?;
-            return Err("Transaction validation failed: amount or address mismatch".into());
+        // 6. Funds are late only if they reached the chain after the payment expired
+        let already_expired = matches!(status, PaymentStatus::Expired | PaymentStatus::PaidLate);
+        let recorded_hash = sqlx::query_scalar!(
+            "SELECT transaction_hash FROM payment_transactions WHERE id = $1",
+            payment_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        let seen_before_expiry = recorded_hash.as_deref() == Some(transaction_hash);
+        let is_late = arrived_late(
+            already_expired,
+            payment.expires_at,
+            blockchain_tx.timestamp,
+            seen_before_expiry,
+            Utc::now(),
+        );
+        if is_late && !already_expired {
+            self.mark_payment_expired(payment_id).await?;
+        }
+
+        // 7. Verify transaction details match payment (Requirements 3.3, 3.5)
+        if !self.validate_transaction(&payment, &blockchain_tx) {
+            if is_late {
+                return Err("Payment has expired. Please create a new payment request.".into());
+            }
+            self.mark_payment_failed(payment_id, "Transaction validation failed", Some(transaction_hash)).await?;
+            return Err("Transaction validation failed: transaction failed or address mismatch".into());
         }
 
         // 8. Update payment with transaction details
@@ -169,11 +238,7 @@ impl PaymentVerifier {
             SET transaction_hash = $1,
                 from_address = $2,
                 confirmations = $3,
-                block_number = $4,
-                status = CASE
-                    WHEN $3 >= required_confirmations THEN 'CONFIRMED'
-                    ELSE 'CONFIRMING'
-                END
+                block_number = $4
             WHERE id = $5
             "#
         )
@@ -185,13 +250,64 @@ impl PaymentVerifier {
         .execute(&self.db_pool)
         .await?;
 
-        // 9. If enough confirmations, confirm the payment (Requirements 3.4, 3.7)
+        // 9. Funds from a sanctioned or deny-listed sender wait for compliance review
+        match self.screening.screen(
+            merchant_id,
+            SubjectType::Payment,
+            &payment.payment_id,
+            &blockchain_tx.from_address,
+            Some(transaction_hash),
+        ).await? {
+            ScreeningOutcome::Clear => {}
+            ScreeningOutcome::Held(hold) => {
+                warn!("Payment {} held for compliance review ({})", payment_id, hold.hold_id);
+                return Ok(false);
+            }
+            ScreeningOutcome::Rejected(_) => {
+                return Err("Payment was rejected by compliance review".into());
+            }
+        }
+
+        // 10. Late funds are held for the merchant to accept or refund
+        if is_late {
+            if (blockchain_tx.confirmations as i32) >= payment.required_confirmations.unwrap_or(1) {
+                self.record_late_payment(payment_id, transaction_hash, blockchain_tx.amount, crypto_type).await?;
+            } else {
+                info!("⏳ Late transaction {} for payment {} awaiting confirmations ({}/{})",
+                    transaction_hash,
+                    payment_id,
+                    blockchain_tx.confirmations,
+                    payment.required_confirmations.unwrap_or(1)
+                );
+            }
+            return Ok(false);
+        }
+
+        // 11. If enough confirmations, settle the amount received (Requirements 3.2, 3.4, 3.7)
         if (blockchain_tx.confirmations as i32) >= payment.required_confirmations.unwrap_or(1) {
-            self.confirm_payment(payment_id, merchant_id).await?;
-            info!(" Payment {} confirmed with {} confirmations for merchant {}!",
-                payment_id, blockchain_tx.confirmations, merchant_id);
-            return Ok(true);
+            let amount_usd = if payment.amount.is_zero() {
+                Decimal::ZERO
+            } else {
+                payment.amount_usd / payment.amount * blockchain_tx.amount
+            };
+
+            let settled = self
+                .record_partial_payment(payment_id, transaction_hash, blockchain_tx.amount, amount_usd)
+                .await?;
+
+            if settled {
+                self.log_fee_recorded(payment_id, merchant_id).await?;
+                info!(" Payment {} settled with {} confirmations for merchant {}!",
+                    payment_id, blockchain_tx.confirmations, merchant_id);
+            }
+            return Ok(settled);
         } else {
+            state_machine::transition_payment(
+                &self.db_pool,
+                payment_id,
+                PaymentTransition::new(PaymentStatus::Confirming, TransitionActor::System)
+                    .with_transaction_hash(transaction_hash),
+            ).await?;
             info!("⏳ Payment {} confirming ({}/{} confirmations)",
                 payment_id,
                 blockchain_tx.confirmations,
@@ -203,19 +319,21 @@ impl PaymentVerifier {
 
     /// Validate blockchain transaction matches payment request
     /// 
+    /// The amount is not checked here; mismatches are handled by the merchant's
+    /// payment policy once the transaction is confirmed.
+    /// 
     /// # Requirements
-    /// * 3.2: Confirm amount matches expected payment amount (with 0.1% tolerance)
     /// * 3.3: Confirm recipient address matches merchant's wallet
     /// * 3.5: Reject verification if transaction doesn't match payment details
     fn validate_transaction(
         &self,
         payment: &PaymentTransaction,
         blockchain_tx: &BlockchainTransaction,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> bool {
         // Check transaction was successful
         if !blockchain_tx.success {
             warn!("Transaction {} failed on blockchain", blockchain_tx.hash);
-            return Ok(false);
+            return false;
         }
 
         // Check recipient address matches merchant's wallet (Requirement 3.3)
@@ -224,40 +342,25 @@ impl PaymentVerifier {
                 payment.to_address,
                 blockchain_tx.to_address
             );
-            return Ok(false);
-        }
-
-        // Check amount matches (allow 0.1% tolerance for fees) (Requirement 3.2)
-        let amount_diff = (blockchain_tx.amount - payment.amount).abs();
-        let tolerance = payment.amount * Decimal::from_str("0.001")?; // 0.1%
-
-        if amount_diff > tolerance {
-            warn!("Amount mismatch: expected {}, got {} (diff: {})",
-                payment.amount,
-                blockchain_tx.amount,
-                amount_diff
-            );
-            return Ok(false);
+            return false;
         }
 
-        Ok(true)
+        true
     }
 
-    /// Mark payment as confirmed and trigger webhooks
+    /// Log the fees recorded for a settled payment
     /// 
     /// # Requirements
-    /// * 3.7: Update payment status to completed when confirmed
-    /// * 4.2: Send webhook notification when payment status changes to confirmed
     /// * 6.3: Record fee amounts when payment is confirmed
-    async fn confirm_payment(
+    async fn log_fee_recorded(
         &self,
         payment_id: i64,
         merchant_id: i64,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Fetch payment to get fee information for logging (Requirement 6.3)
+        // Fee amounts are already stored from payment creation and remain in the record
         let payment = sqlx::query!(
             r#"
-            SELECT fee_amount, fee_amount_usd, fee_percentage, amount, amount_usd
+            SELECT fee_amount, fee_amount_usd, fee_percentage
             FROM payment_transactions
             WHERE id = $1
             "#,
@@ -265,23 +368,7 @@ impl PaymentVerifier {
         )
         .fetch_one(&self.db_pool)
         .await?;
-        
-        // Update payment status to CONFIRMED (Requirement 3.7)
-        // Fee amounts are already stored from payment creation and remain in the record
-        sqlx::query(
-            r#"
-            UPDATE payment_transactions
-            SET status = 'CONFIRMED',
-                confirmed_at = $1
-            WHERE id = $2
-            "#
-        )
-        .bind(Utc::now())
-        .bind(payment_id)
-        .execute(&self.db_pool)
-        .await?;
 
-        // Log fee recording for audit trail (Requirement 6.3)
         info!(
             " Payment {} confirmed for merchant {} - Fee recorded: {} crypto (${}) at {}% rate",
             payment_id,
@@ -291,26 +378,6 @@ impl PaymentVerifier {
             payment.fee_percentage
         );
 
-        // Trigger webhook notification
-        let webhook_payload = crate::models::webhook::WebhookPayload {
-            event_type: "payment.confirmed".to_string(),
-            payment_id: payment_id.to_string(),
-            merchant_id,
-            status: crate::payment::models::PaymentStatus::Confirmed,
-            amount: payment.amount,
-            crypto_type: "SOL".to_string(), // Would need to fetch from payment record
-            transaction_hash: Some("tx_hash".to_string()), // Would need transaction hash parameter
-            timestamp: chrono::Utc::now().timestamp(),
-        };
-        
-        if let Err(e) = self.webhook_service.queue_webhook(
-            merchant_id,
-            payment_id,
-            webhook_payload
-        ).await {
-            warn!("Failed to queue webhook for payment {}: {}", payment_id, e);
-        }
-
         Ok(())
     }
 
@@ -319,28 +386,51 @@ impl PaymentVerifier {
         &self,
         payment_id: i64,
         reason: &str,
+        transaction_hash: Option<&str>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        sqlx::query(
-            r#"
-            UPDATE payment_transactions
-            SET status = 'FAILED'
-            WHERE id = $1
-            "#
-        )
-        .bind(payment_id)
-        .execute(&self.db_pool)
-        .await?;
+        let mut transition = PaymentTransition::new(PaymentStatus::Failed, TransitionActor::System)
+            .with_reason(reason);
+        if let Some(hash) = transaction_hash {
+            transition = transition.with_transaction_hash(hash);
+        }
+
+        state_machine::transition_payment(&self.db_pool, payment_id, transition).await?;
 
         warn!(" Payment {} marked as failed: {}", payment_id, reason);
         Ok(())
     }
 
-    /// Record a partial payment
+    /// Mark payment as expired
+    async fn mark_payment_expired(
+        &self,
+        payment_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        state_machine::transition_payment(
+            &self.db_pool,
+            payment_id,
+            PaymentTransition::new(PaymentStatus::Expired, TransitionActor::System)
+                .with_reason("Payment expired before verification"),
+        ).await?;
+
+        warn!(" Payment {} marked as expired", payment_id);
+        Ok(())
+    }
+
+    /// Record funds received for a payment and settle it per the merchant policy
+    /// 
+    /// Every transaction is stored in `partial_payments`, so top-ups accumulate in
+    /// `total_paid`. The total is then compared against the requested amount:
+    /// within tolerance confirms, a surplus confirms as overpaid with the
+    /// difference recorded as refundable, and a shortfall follows the merchant's
+    /// underpayment action.
     /// 
     /// # Requirements
     /// * 20.2: Track total amount paid across multiple transactions
     /// * 20.3: Update remaining balance
     /// * 20.4: Mark payment as completed when total >= required amount
+    /// 
+    /// # Returns
+    /// * `Ok(true)` if the payment is settled (confirmed or overpaid)
     pub async fn record_partial_payment(
         &self,
         payment_id: i64,
@@ -365,15 +455,15 @@ impl PaymentVerifier {
         .execute(&mut *tx)
         .await?;
 
-        // Update payment total_paid and remaining_balance
+        // Update payment total_paid, remaining_balance and overpaid_amount
         let payment = sqlx::query!(
             r#"
             UPDATE payment_transactions
             SET total_paid = total_paid + $1,
-                remaining_balance = remaining_balance - $1,
-                expires_at = expires_at + INTERVAL '15 minutes'
+                remaining_balance = GREATEST(amount - (total_paid + $1), 0),
+                overpaid_amount = GREATEST((total_paid + $1) - amount, 0)
             WHERE id = $2
-            RETURNING amount, total_paid, remaining_balance
+            RETURNING payment_id, merchant_id, amount, total_paid, partial_payments_enabled
             "#,
             amount,
             payment_id
@@ -381,24 +471,190 @@ impl PaymentVerifier {
         .fetch_one(&mut *tx)
         .await?;
 
-        // Check if payment is now complete
-        let is_complete = payment.total_paid >= payment.amount;
-        
-        if is_complete {
+        let policy = self.policy_service.get_policy(payment.merchant_id).await?;
+        let outcome = policy.classify(payment.amount, payment.total_paid);
+        let status = policy.status_for(outcome, payment.partial_payments_enabled);
+
+        // Keep the payment open long enough for the customer to top up
+        if status == PaymentStatus::Underpaid {
             sqlx::query!(
-                "UPDATE payment_transactions SET status = 'CONFIRMED', confirmed_at = $1 WHERE id = $2",
-                chrono::Utc::now(),
+                r#"
+                UPDATE payment_transactions
+                SET expires_at = GREATEST(expires_at, NOW() + make_interval(mins => $1))
+                WHERE id = $2
+                "#,
+                policy.top_up_window_minutes,
                 payment_id
             )
             .execute(&mut *tx)
             .await?;
         }
 
+        let reason = match outcome {
+            AmountOutcome::Exact => "Amount received matches the requested amount".to_string(),
+            AmountOutcome::Underpaid { remaining } => format!("Underpaid, {} remaining", remaining),
+            AmountOutcome::Overpaid { surplus } => format!("Overpaid by {}", surplus),
+        };
+        let transition = PaymentTransition::new(status, TransitionActor::System)
+            .with_reason(reason)
+            .with_transaction_hash(transaction_hash);
+        state_machine::apply_transition(&mut tx, payment_id, &transition).await?;
+
+        tx.commit().await?;
+
+        info!(" Payment recorded for payment {}: {} (total: {}/{}) -> {}", 
+            payment_id, amount, payment.total_paid, payment.amount, status);
+
+        if let AmountOutcome::Overpaid { surplus } = outcome {
+            if policy.overpayment_action == OverpaymentAction::AutoRefund {
+                self.refund_surplus(payment.merchant_id, payment.payment_id, surplus).await;
+            }
+        }
+
+        Ok(status.is_settled())
+    }
+
+    /// Record funds received after a payment expired
+    /// 
+    /// The funds are repriced at the current exchange rate and the payment moves
+    /// to `PaidLate`, which emits `payment.paid_late` so the merchant can accept
+    /// or refund them.
+    async fn record_late_payment(
+        &self,
+        payment_id: i64,
+        transaction_hash: &str,
+        amount: Decimal,
+        crypto_type: CryptoType,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Reprice at the current rate (USDT is pegged)
+        let exchange_rate = if crypto_type.as_str() == "USDT" {
+            Decimal::ONE
+        } else {
+            let price = self.price_service
+                .get_price(crypto_type)
+                .await
+                .map_err(|e| format!("Failed to fetch price: {}", e))?;
+            Decimal::from_f64_retain(price).ok_or("Invalid price conversion")?
+        };
+        let amount_usd = amount * exchange_rate;
+
+        let mut tx = self.db_pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO partial_payments (payment_id, transaction_hash, amount, amount_usd, confirmations, status, created_at)
+            VALUES ($1, $2, $3, $4, 0, 'CONFIRMED', $5)
+            "#,
+            payment_id,
+            transaction_hash,
+            amount,
+            amount_usd,
+            chrono::Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE payment_transactions
+            SET total_paid = total_paid + $1,
+                late_amount_usd = COALESCE(late_amount_usd, 0) + $2,
+                late_exchange_rate = $3,
+                late_received_at = NOW()
+            WHERE id = $4
+            "#,
+            amount,
+            amount_usd,
+            exchange_rate,
+            payment_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let transition = PaymentTransition::new(PaymentStatus::PaidLate, TransitionActor::System)
+            .with_reason(format!("Funds received after expiry, repriced at {} USD", exchange_rate))
+            .with_transaction_hash(transaction_hash);
+        state_machine::apply_transition(&mut tx, payment_id, &transition).await?;
+
         tx.commit().await?;
 
-        info!(" Partial payment recorded for payment {}: {} (total: {}/{})", 
-            payment_id, amount, payment.total_paid, payment.amount);
+        warn!(" Payment {} received {} after expiry (${} at current rate), awaiting merchant decision",
+            payment_id, amount, amount_usd);
+
+        Ok(())
+    }
+
+    /// Create a pending refund for the surplus of an overpaid payment
+    async fn refund_surplus(&self, merchant_id: i64, payment_id: String, surplus: Decimal) {
+        let refund_service = RefundService::new(self.db_pool.clone());
+
+        match refund_service
+            .create_refund(merchant_id, payment_id.clone(), Some(surplus), "Automatic refund of overpaid amount".to_string())
+            .await
+        {
+            Ok(refund) => info!(" Created refund {} for overpayment of {} on payment {}", refund.refund_id, surplus, payment_id),
+            Err(e) => error!("Failed to refund overpayment on payment {}: {}", payment_id, e),
+        }
+    }
+}
+
+/// Whether a transaction counts as arriving after `expires_at`.
+///
+/// The block timestamp decides when it is known, so a transfer mined in time but
+/// verified after expiry still settles the payment. Without one, a transaction
+/// already recorded on the still-open payment was seen in time; anything else
+/// falls back to the wall clock.
+fn arrived_late(
+    already_expired: bool,
+    expires_at: DateTime<Utc>,
+    tx_timestamp: Option<DateTime<Utc>>,
+    seen_before_expiry: bool,
+    now: DateTime<Utc>,
+) -> bool {
+    if already_expired {
+        return true;
+    }
+    match tx_timestamp {
+        Some(timestamp) => timestamp > expires_at,
+        None if seen_before_expiry => false,
+        None => now > expires_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn tx_before_expiry_verified_after_is_not_late() {
+        let expires_at = Utc::now() - Duration::minutes(10);
+        let mined_at = expires_at - Duration::minutes(1);
+
+        assert!(!arrived_late(false, expires_at, Some(mined_at), false, Utc::now()));
+    }
+
+    #[test]
+    fn tx_after_expiry_is_late() {
+        let expires_at = Utc::now() - Duration::minutes(10);
+        let mined_at = expires_at + Duration::minutes(1);
+
+        assert!(arrived_late(false, expires_at, Some(mined_at), false, Utc::now()));
+    }
+
+    #[test]
+    fn unknown_timestamp_uses_pre_expiry_state() {
+        let expires_at = Utc::now() - Duration::minutes(10);
+
+        assert!(!arrived_late(false, expires_at, None, true, Utc::now()));
+        assert!(arrived_late(false, expires_at, None, false, Utc::now()));
+        assert!(!arrived_late(false, Utc::now() + Duration::minutes(10), None, false, Utc::now()));
+    }
+
+    #[test]
+    fn already_expired_payment_stays_late() {
+        let expires_at = Utc::now() + Duration::minutes(10);
 
-        Ok(is_complete)
+        assert!(arrived_late(true, expires_at, Some(Utc::now()), true, Utc::now()));
     }
 }
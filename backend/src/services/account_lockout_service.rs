@@ -1,7 +1,104 @@
+// Account Lockout Service
+// Brute-force protection for authentication and public form endpoints
+
+use crate::config::Config;
 use crate::error::ServiceError;
-use chrono::{Duration, Utc};
-use sqlx::PgPool;
+use crate::services::email_templates::AccountLocked;
+use crate::services::notification_service;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::warn;
+
+/// Longest delay imposed between failed attempts, in seconds
+const MAX_PROGRESSIVE_DELAY_SECS: i64 = 60;
+
+/// An IP is shared by everyone behind the same NAT, so it gets this many
+/// times the per-email allowance for authentication failures
+const IP_ATTEMPT_MULTIPLIER: u32 = 4;
+
+/// Attempts are kept at least this long for investigation
+const ATTEMPT_RETENTION_HOURS: i64 = 24;
+
+/// Expired lockouts are kept this long as an audit trail
+const LOCKOUT_RETENTION_DAYS: i64 = 30;
+
+/// Endpoint family an attempt belongs to; each is counted separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptKind {
+    /// Dashboard login with email and password
+    Login,
+    /// Bearer API key on authenticated routes
+    ApiKey,
+    /// Public merchant registration
+    Register,
+    /// Public contact form
+    Contact,
+    /// Public password reset requests
+    PasswordReset,
+}
+
+impl AttemptKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttemptKind::Login => "login",
+            AttemptKind::ApiKey => "api_key",
+            AttemptKind::Register => "register",
+            AttemptKind::Contact => "contact",
+            AttemptKind::PasswordReset => "password_reset",
+        }
+    }
+
+    /// Whether failures slow down before the lockout threshold is reached
+    ///
+    /// Registration, contact and password reset submissions are not
+    /// guesses, so they are only capped.
+    fn is_progressive(&self) -> bool {
+        matches!(self, AttemptKind::Login | AttemptKind::ApiKey)
+    }
+}
+
+/// What an attempt is counted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttemptKey<'a> {
+    Email(&'a str),
+    Ip(&'a str),
+}
+
+impl AttemptKey<'_> {
+    fn key_type(&self) -> &'static str {
+        match self {
+            AttemptKey::Email(_) => "email",
+            AttemptKey::Ip(_) => "ip",
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            AttemptKey::Email(value) | AttemptKey::Ip(value) => value,
+        }
+    }
+}
+
+/// An active lockout, as listed for administrators
+#[derive(Debug, Serialize)]
+pub struct AccountLockout {
+    pub id: i64,
+    pub scope: String,
+    pub key_type: String,
+    pub key_value: String,
+    pub failed_attempts: i32,
+    pub locked_until: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
 
+/// Counts failed attempts per email and per IP and locks out repeat offenders
+///
+/// Failures are counted since the last success, last lockout or last admin
+/// unlock, within a window of `lockout_duration_minutes`. From the second
+/// failure, login and API key attempts are delayed progressively; reaching
+/// `max_attempts` locks the key for `lockout_duration_minutes` and, for a
+/// login email, notifies the account owner.
 pub struct AccountLockoutService {
     pool: PgPool,
     max_attempts: u32,
@@ -12,28 +109,414 @@ impl AccountLockoutService {
     pub fn new(pool: PgPool, max_attempts: u32, lockout_duration_minutes: i64) -> Self {
         Self {
             pool,
-            max_attempts,
-            lockout_duration_minutes,
+            max_attempts: max_attempts.max(1),
+            lockout_duration_minutes: lockout_duration_minutes.max(1),
         }
     }
 
-    pub async fn check_lockout(&self, _email: &str) -> Result<bool, ServiceError> {
-        // Simplified - no lockout for now
-        Ok(false)
+    /// Service using `Config::max_login_attempts` and `account_lockout_duration_minutes`
+    pub fn from_config(pool: PgPool, config: &Config) -> Self {
+        Self::new(
+            pool,
+            config.max_login_attempts,
+            config.account_lockout_duration_minutes as i64,
+        )
+    }
+
+    /// Refuse the attempt if its email or IP is locked out or throttled
+    ///
+    /// # Arguments
+    /// * `kind` - Endpoint family being accessed
+    /// * `email` - Account the attempt is for, if known
+    /// * `ip` - Client IP, if known
+    pub async fn check_lockout(
+        &self,
+        kind: AttemptKind,
+        email: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        let email = email.map(normalize_email);
+        if email.is_none() && ip.is_none() {
+            return Ok(());
+        }
+
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(locked_until)
+            FROM account_lockouts
+            WHERE scope = $1
+              AND unlocked_at IS NULL
+              AND locked_until > NOW()
+              AND ((key_type = 'email' AND key_value = $2) OR (key_type = 'ip' AND key_value = $3))
+            "#,
+            kind.as_str(),
+            email,
+            ip
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        match locked_until {
+            Some(until) => Err(ServiceError::TooManyAttempts(retry_after_secs(until, Utc::now()))),
+            None => Ok(()),
+        }
     }
 
-    pub async fn record_failed_attempt(&self, _email: &str, _ip: &str) -> Result<(), ServiceError> {
-        // Simplified - no recording for now
+    /// Record a failed attempt, throttling or locking out its email and IP
+    ///
+    /// # Arguments
+    /// * `kind` - Endpoint family that was accessed
+    /// * `email` - Account the attempt was for, if known
+    /// * `ip` - Client IP, if known
+    pub async fn record_failed_attempt(
+        &self,
+        kind: AttemptKind,
+        email: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        let email = email.map(normalize_email);
+        let keys = attempt_keys(email.as_deref(), ip);
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for key in keys {
+            self.count_failure(&mut tx, kind, key, ip).await?;
+        }
+        tx.commit().await?;
+
         Ok(())
     }
 
-    pub async fn record_successful_login(&self, _email: &str, _ip: &str) -> Result<(), ServiceError> {
-        // Simplified - no recording for now
+    /// Record a registration, contact form or password reset submission
+    ///
+    /// These endpoints have no failure to speak of, so every submission
+    /// counts toward the limit.
+    pub async fn record_submission(
+        &self,
+        kind: AttemptKind,
+        email: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        self.record_failed_attempt(kind, email, ip).await
+    }
+
+    /// Record a successful login, resetting the failure count for the email
+    ///
+    /// The IP count is left alone: a valid login for one account must not
+    /// reset a credential-stuffing run against others from the same address.
+    pub async fn record_successful_login(&self, email: &str, ip: Option<&str>) -> Result<(), ServiceError> {
+        let email = normalize_email(email);
+        let mut tx = self.pool.begin().await?;
+        insert_attempt(&mut tx, AttemptKind::Login, AttemptKey::Email(&email), ip, true).await?;
+        tx.commit().await?;
+
         Ok(())
     }
 
-    pub async fn cleanup_old_attempts(&self) -> Result<(), ServiceError> {
-        // Simplified - no cleanup needed
+    async fn count_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        kind: AttemptKind,
+        key: AttemptKey<'_>,
+        ip: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        insert_attempt(tx, kind, key, ip, false).await?;
+
+        let window_start = Utc::now() - Duration::minutes(self.lockout_duration_minutes);
+        let failures = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM login_attempts a
+            WHERE a.scope = $1 AND a.key_type = $2 AND a.key_value = $3
+              AND NOT a.succeeded
+              AND a.created_at > $4
+              AND a.created_at > COALESCE((
+                  SELECT MAX(created_at) FROM login_attempts
+                  WHERE scope = $1 AND key_type = $2 AND key_value = $3 AND succeeded
+              ), '-infinity'::timestamptz)
+              AND a.created_at > COALESCE((
+                  SELECT MAX(CASE WHEN is_throttle THEN unlocked_at ELSE COALESCE(unlocked_at, created_at) END)
+                  FROM account_lockouts
+                  WHERE scope = $1 AND key_type = $2 AND key_value = $3
+              ), '-infinity'::timestamptz)
+            "#,
+            kind.as_str(),
+            key.key_type(),
+            key.value(),
+            window_start
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        if failures >= i64::from(self.threshold(kind, key)) {
+            let locked_until = Utc::now() + Duration::minutes(self.lockout_duration_minutes);
+            insert_lockout(tx, kind, key, false, failures, locked_until).await?;
+            warn!(
+                "Locked out {} {} for {} after {} failed attempts",
+                kind.as_str(), key.key_type(), key.value(), failures
+            );
+
+            if let (AttemptKind::Login, AttemptKey::Email(email)) = (kind, key) {
+                notify_owner(tx, email, locked_until, ip).await?;
+            }
+        } else if kind.is_progressive() {
+            let delay = progressive_delay_secs(failures);
+            if delay > 0 {
+                let locked_until = Utc::now() + Duration::seconds(delay);
+                insert_lockout(tx, kind, key, true, failures, locked_until).await?;
+            }
+        }
+
         Ok(())
     }
+
+    /// Failures allowed for a key before it is locked out
+    fn threshold(&self, kind: AttemptKind, key: AttemptKey<'_>) -> u32 {
+        match key {
+            AttemptKey::Ip(_) if kind.is_progressive() => {
+                self.max_attempts.saturating_mul(IP_ATTEMPT_MULTIPLIER)
+            }
+            _ => self.max_attempts,
+        }
+    }
+
+    /// Active lockouts, excluding short progressive delays
+    pub async fn list_active_lockouts(&self) -> Result<Vec<AccountLockout>, ServiceError> {
+        let lockouts = sqlx::query_as!(
+            AccountLockout,
+            r#"
+            SELECT id, scope, key_type, key_value, failed_attempts, locked_until, created_at
+            FROM account_lockouts
+            WHERE unlocked_at IS NULL AND NOT is_throttle AND locked_until > NOW()
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(lockouts)
+    }
+
+    /// Lift every active lockout and delay on an email and/or IP
+    ///
+    /// Failures before the unlock no longer count toward the next lockout.
+    ///
+    /// # Arguments
+    /// * `email` - Email to unlock
+    /// * `ip` - IP to unlock
+    /// * `admin_id` - Administrator performing the unlock
+    ///
+    /// # Returns
+    /// Number of lockouts lifted
+    pub async fn unlock(
+        &self,
+        email: Option<&str>,
+        ip: Option<&str>,
+        admin_id: i64,
+    ) -> Result<u64, ServiceError> {
+        let email = email.map(normalize_email);
+        if email.is_none() && ip.is_none() {
+            return Err(ServiceError::ValidationError("Provide an email or an IP to unlock".to_string()));
+        }
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE account_lockouts
+            SET unlocked_at = NOW(), unlocked_by = $3
+            WHERE unlocked_at IS NULL
+              AND locked_until > NOW()
+              AND ((key_type = 'email' AND key_value = $1) OR (key_type = 'ip' AND key_value = $2))
+            "#,
+            email,
+            ip,
+            admin_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Delete old attempts, expired delays and lockouts past their retention
+    ///
+    /// # Returns
+    /// Number of rows deleted
+    pub async fn cleanup_old_attempts(&self) -> Result<u64, ServiceError> {
+        let attempt_cutoff = Utc::now()
+            - Duration::hours(ATTEMPT_RETENTION_HOURS).max(Duration::minutes(self.lockout_duration_minutes));
+
+        let attempts = sqlx::query!(
+            "DELETE FROM login_attempts WHERE created_at < $1",
+            attempt_cutoff
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let lockouts = sqlx::query!(
+            r#"
+            DELETE FROM account_lockouts
+            WHERE (is_throttle AND locked_until < $1)
+               OR locked_until < $2
+            "#,
+            attempt_cutoff,
+            Utc::now() - Duration::days(LOCKOUT_RETENTION_DAYS)
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(attempts.rows_affected() + lockouts.rows_affected())
+    }
+}
+
+async fn insert_attempt(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: AttemptKind,
+    key: AttemptKey<'_>,
+    ip: Option<&str>,
+    succeeded: bool,
+) -> Result<(), ServiceError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO login_attempts (scope, key_type, key_value, succeeded, ip_address)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        kind.as_str(),
+        key.key_type(),
+        key.value(),
+        succeeded,
+        ip
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn insert_lockout(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: AttemptKind,
+    key: AttemptKey<'_>,
+    is_throttle: bool,
+    failures: i64,
+    locked_until: DateTime<Utc>,
+) -> Result<(), ServiceError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO account_lockouts (scope, key_type, key_value, is_throttle, failed_attempts, locked_until)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        kind.as_str(),
+        key.key_type(),
+        key.value(),
+        is_throttle,
+        failures as i32,
+        locked_until
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Queue the lockout email, if the email belongs to a merchant
+async fn notify_owner(
+    tx: &mut Transaction<'_, Postgres>,
+    email: &str,
+    locked_until: DateTime<Utc>,
+    ip: Option<&str>,
+) -> Result<(), ServiceError> {
+    let merchant_id = sqlx::query_scalar!(
+        "SELECT id FROM merchants WHERE LOWER(email) = $1",
+        email
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(merchant_id) = merchant_id {
+        let notice = AccountLocked {
+            locked_until: locked_until.to_rfc3339(),
+            ip: ip.map(str::to_string),
+        };
+        notification_service::enqueue_notification(tx, merchant_id, &notice).await?;
+    }
+
+    Ok(())
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn attempt_keys<'a>(email: Option<&'a str>, ip: Option<&'a str>) -> Vec<AttemptKey<'a>> {
+    email
+        .filter(|e| !e.is_empty())
+        .map(AttemptKey::Email)
+        .into_iter()
+        .chain(ip.filter(|i| !i.is_empty()).map(AttemptKey::Ip))
+        .collect()
+}
+
+/// Delay after the given number of consecutive failures: none after the
+/// first, then doubling from 2 seconds up to a minute
+fn progressive_delay_secs(failures: i64) -> i64 {
+    if failures < 2 {
+        return 0;
+    }
+    let exponent = (failures - 1).min(6) as u32;
+    2_i64.pow(exponent).min(MAX_PROGRESSIVE_DELAY_SECS)
+}
+
+/// Whole seconds until `until`, at least one
+fn retry_after_secs(until: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    let millis = (until - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(max_attempts: u32) -> AccountLockoutService {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        AccountLockoutService::new(pool, max_attempts, 30)
+    }
+
+    #[test]
+    fn test_progressive_delay_doubles_up_to_a_minute() {
+        assert_eq!(progressive_delay_secs(1), 0);
+        assert_eq!(progressive_delay_secs(2), 2);
+        assert_eq!(progressive_delay_secs(3), 4);
+        assert_eq!(progressive_delay_secs(6), 32);
+        assert_eq!(progressive_delay_secs(7), 60);
+        assert_eq!(progressive_delay_secs(50), 60);
+    }
+
+    #[tokio::test]
+    async fn test_ip_threshold_is_looser_for_authentication() {
+        let service = service(5);
+
+        assert_eq!(service.threshold(AttemptKind::Login, AttemptKey::Email("a@b.c")), 5);
+        assert_eq!(service.threshold(AttemptKind::Login, AttemptKey::Ip("10.0.0.1")), 20);
+        assert_eq!(service.threshold(AttemptKind::ApiKey, AttemptKey::Ip("10.0.0.1")), 20);
+        assert_eq!(service.threshold(AttemptKind::Contact, AttemptKey::Ip("10.0.0.1")), 5);
+    }
+
+    #[test]
+    fn test_attempt_keys_skip_missing_values() {
+        assert_eq!(
+            attempt_keys(Some("a@b.c"), Some("10.0.0.1")),
+            vec![AttemptKey::Email("a@b.c"), AttemptKey::Ip("10.0.0.1")]
+        );
+        assert_eq!(attempt_keys(None, Some("10.0.0.1")), vec![AttemptKey::Ip("10.0.0.1")]);
+        assert!(attempt_keys(Some(""), None).is_empty());
+    }
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        let now = Utc::now();
+        assert_eq!(retry_after_secs(now + Duration::milliseconds(1500), now), 2);
+        assert_eq!(retry_after_secs(now, now), 1);
+    }
 }
@@ -7,7 +7,6 @@ use crate::services::{
     address_only_service::AddressOnlyService,
     gas_fee_service::GasFeeService,
     payment_monitor_service::PaymentMonitorService,
-    webhook_notification_service::WebhookNotificationService,
 };
 use sqlx::PgPool;
 use std::sync::Arc;
@@ -16,7 +15,6 @@ use tokio::task::JoinHandle;
 pub struct AddressOnlyManager {
     pub address_service: Arc<AddressOnlyService>,
     pub monitor_service: Arc<PaymentMonitorService>,
-    pub webhook_service: Arc<WebhookNotificationService>,
     db_pool: PgPool,
     monitor_handle: Option<JoinHandle<()>>,
 }
@@ -31,7 +29,6 @@ impl AddressOnlyManager {
             gas_service,
             config.clone(),
         ));
-        let webhook_service = Arc::new(WebhookNotificationService::new(db_pool.clone()));
         let monitor_service = Arc::new(PaymentMonitorService::new(
             db_pool.clone(),
             (*address_service).clone(),
@@ -41,7 +38,6 @@ impl AddressOnlyManager {
         Ok(Self {
             address_service,
             monitor_service,
-            webhook_service,
             db_pool,
             monitor_handle: None,
         })
@@ -76,11 +72,6 @@ impl AddressOnlyManager {
         Arc::clone(&self.address_service)
     }
 
-    /// Get webhook service for notifications
-    pub fn get_webhook_service(&self) -> Arc<WebhookNotificationService> {
-        Arc::clone(&self.webhook_service)
-    }
-
     /// Health check for all components
     pub async fn health_check(&self) -> Result<AddressOnlyHealthStatus, ServiceError> {
         // Check database connectivity
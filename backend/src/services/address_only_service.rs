@@ -3,7 +3,9 @@
 
 use crate::error::ServiceError;
 use crate::payment::models::CryptoType;
+use crate::services::blockchain_transaction_sender::BlockchainTransactionSender;
 use crate::services::gas_fee_service::GasFeeService;
+use crate::services::signer::signer_from_config;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
 use serde::{Deserialize, Serialize};
@@ -188,40 +190,55 @@ impl AddressOnlyService {
         .execute(&self.db_pool)
         .await?;
 
-        // Update payment status to completed
-        self.update_payment_status(&payment.payment_id, AddressOnlyStatus::Completed).await?;
+        // Mark completed and queue the merchant notification in one transaction
+        let mut tx = self.db_pool.begin().await?;
 
-        // Send webhook notification
-        if let Ok(updated_payment) = self.get_payment_by_id(&payment.payment_id).await {
-            let webhook_service = crate::services::webhook_notification_service::WebhookNotificationService::new(self.db_pool.clone());
-            let _ = webhook_service.notify_status_change(&updated_payment).await;
-        }
+        sqlx::query!(
+            "UPDATE address_only_payments SET status = $1, updated_at = NOW() WHERE payment_id = $2",
+            AddressOnlyStatus::Completed as i32,
+            payment.payment_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let mut object = serde_json::to_value(payment)?;
+        object["status"] = serde_json::to_value(AddressOnlyStatus::Completed)?;
+        object["customer_pays_fee"] = serde_json::json!(payment.customer_amount > payment.requested_amount);
+        object["forwarding_tx_hash"] = serde_json::json!(forwarding_tx_hash);
+        object["net_forwarding_amount"] = serde_json::json!(net_forwarding_amount);
+
+        crate::services::outbox::enqueue_event(
+            &mut tx,
+            payment.merchant_id,
+            "payment.forwarded",
+            &payment.payment_id,
+            serde_json::json!({ "object": object }),
+        )
+        .await?;
+
+        tx.commit().await?;
 
         Ok(())
     }
 
     /// Generate unique deposit address for payment tracking
+    ///
+    /// The signer creates the key and returns it encrypted under its own KEK.
     async fn generate_deposit_address(&self, crypto_type: CryptoType) -> Result<String, ServiceError> {
-        // Use existing KeyGenerator for real address generation
-        use crate::utils::keygen::KeyGenerator;
-        
-        let network = match crypto_type {
-            CryptoType::Eth => "ethereum",
-            CryptoType::Bnb => "bsc", 
-            CryptoType::Matic => "polygon",
-            CryptoType::Arb => "arbitrum",
-            CryptoType::Sol => "solana",
-            _ => return Err(ServiceError::ValidationError("Unsupported crypto type".to_string())),
-        };
+        if !matches!(
+            crypto_type,
+            CryptoType::Eth | CryptoType::Bnb | CryptoType::Matic | CryptoType::Arb | CryptoType::Sol
+        ) {
+            return Err(ServiceError::ValidationError("Unsupported crypto type".to_string()));
+        }
 
-        let wallet = match crypto_type {
-            CryptoType::Sol => KeyGenerator::generate_solana_wallet()?,
-            _ => KeyGenerator::generate_evm_wallet()?,
-        };
+        let wallet = signer_from_config(self.db_pool.clone(), &self.config)
+            .generate_key(crypto_type)
+            .await?;
 
-        // Store private key securely for later forwarding
+        // Store the encrypted key for later forwarding
         let payment_id = uuid::Uuid::new_v4().to_string();
-        self.store_deposit_keypair(&payment_id, &wallet.private_key, &wallet.address).await?;
+        self.store_deposit_keypair(&payment_id, &wallet.encrypted_private_key, &wallet.address).await?;
 
         Ok(wallet.address)
     }
@@ -237,18 +254,13 @@ impl AddressOnlyService {
         )
     }
 
-    /// Store deposit keypair securely for forwarding
+    /// Store an encrypted deposit key for forwarding
     async fn store_deposit_keypair(
         &self,
         payment_id: &str,
-        private_key: &str,
+        encrypted_key: &str,
         address: &str,
     ) -> Result<(), ServiceError> {
-        use crate::utils::encryption::encrypt_data;
-        
-        let encrypted_key = encrypt_data(private_key)
-            .map_err(|e| ServiceError::Internal(format!("Key encryption failed: {}", e)))?;
-
         sqlx::query!(
             "INSERT INTO deposit_keypairs (payment_id, address, encrypted_private_key) VALUES ($1, $2, $3)",
             payment_id,
@@ -268,13 +280,13 @@ impl AddressOnlyService {
         amount: Decimal,
         gas_estimate: &crate::services::gas_fee_service::GasFeeEstimate,
     ) -> Result<String, ServiceError> {
-        // Get private key for deposit address
-        let private_key = self.get_deposit_private_key(&payment.gateway_deposit_address).await?;
-        
+        // The signer holds the deposit address key
+        let from_address = &payment.gateway_deposit_address;
+
         match payment.crypto_type {
             CryptoType::Sol => {
                 self.send_solana_transaction(
-                    &private_key,
+                    from_address,
                     &payment.merchant_destination_address,
                     amount,
                 ).await
@@ -282,7 +294,7 @@ impl AddressOnlyService {
             _ => {
                 self.send_evm_transaction(
                     payment.crypto_type,
-                    &private_key,
+                    from_address,
                     &payment.merchant_destination_address,
                     amount,
                     gas_estimate,
@@ -294,46 +306,35 @@ impl AddressOnlyService {
     /// Send Solana transaction
     async fn send_solana_transaction(
         &self,
-        private_key: &str,
+        from_address: &str,
         to_address: &str,
         amount: Decimal,
     ) -> Result<String, ServiceError> {
-        let tx_sender = crate::services::blockchain_transaction_sender::BlockchainTransactionSender::new(self.config.clone());
-        tx_sender.send_native_transaction(CryptoType::Sol, private_key, to_address, amount, None).await
+        self.transaction_sender().send_native_transaction(CryptoType::Sol, from_address, to_address, amount, None).await
     }
 
     /// Send EVM transaction  
     async fn send_evm_transaction(
         &self,
         crypto_type: CryptoType,
-        private_key: &str,
+        from_address: &str,
         to_address: &str,
         amount: Decimal,
         gas_estimate: &crate::services::gas_fee_service::GasFeeEstimate,
     ) -> Result<String, ServiceError> {
-        let tx_sender = crate::services::blockchain_transaction_sender::BlockchainTransactionSender::new(self.config.clone());
-        
         // Convert gas price to U256
         let gas_price_wei = (gas_estimate.standard_fee * Decimal::new(1_000_000_000_000_000_000i64, 0))
             .to_u128()
             .map(web3::types::U256::from);
             
-        tx_sender.send_native_transaction(crypto_type, private_key, to_address, amount, gas_price_wei).await
+        self.transaction_sender().send_native_transaction(crypto_type, from_address, to_address, amount, gas_price_wei).await
     }
 
-    /// Get private key for deposit address
-    async fn get_deposit_private_key(&self, address: &str) -> Result<String, ServiceError> {
-        let record = sqlx::query!(
-            "SELECT encrypted_private_key FROM deposit_keypairs WHERE address = $1",
-            address
+    fn transaction_sender(&self) -> BlockchainTransactionSender {
+        BlockchainTransactionSender::new(
+            self.config.clone(),
+            signer_from_config(self.db_pool.clone(), &self.config),
         )
-        .fetch_optional(&self.db_pool)
-        .await?
-        .ok_or_else(|| ServiceError::NotFound("Deposit keypair not found".to_string()))?;
-
-        // For now, return a placeholder since we don't have decrypt_data
-        // In production, this would decrypt the stored key
-        Ok(format!("decrypted_key_for_{}", address))
     }
 
     /// Get merchant statistics for address-only payments
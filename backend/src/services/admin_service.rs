@@ -3,7 +3,7 @@
 
 use crate::error::ServiceError;
 use crate::models::merchant::Merchant;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
@@ -28,15 +28,25 @@ pub struct SecurityEvent {
     pub created_at: String,
 }
 
+/// Alert raised by transaction monitoring, handled as a case
 #[derive(Debug, Serialize)]
 pub struct SecurityAlert {
     pub alert_id: String,
+    pub merchant_id: Option<i64>,
     pub alert_type: String,
     pub severity: String,
     pub message: String,
+    pub details: serde_json::Value,
+    /// open, investigating or closed
+    pub status: String,
+    pub assigned_to: Option<i64>,
     pub acknowledged: bool,
-    pub acknowledged_at: Option<String>,
-    pub created_at: String,
+    pub acknowledged_at: Option<DateTime<Utc>>,
+    pub resolution: Option<String>,
+    pub closed_by: Option<i64>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
@@ -202,26 +212,43 @@ impl AdminService {
         ])
     }
 
-    /// Get security alerts (mock implementation)
+    /// Get security alerts that are not closed, newest first
     pub async fn get_security_alerts(&self) -> Result<Vec<SecurityAlert>, ServiceError> {
-        // Mock security alerts for testing
-        Ok(vec![
-            SecurityAlert {
-                alert_id: "alert_001".to_string(),
-                alert_type: "high_volume".to_string(),
-                severity: "high".to_string(),
-                message: "Unusual transaction volume detected".to_string(),
-                acknowledged: false,
-                acknowledged_at: None,
-                created_at: Utc::now().to_rfc3339(),
-            },
-        ])
+        let alerts = sqlx::query_as!(
+            SecurityAlert,
+            r#"
+            SELECT alert_id, merchant_id, alert_type, severity, message, details, status,
+                   assigned_to, acknowledged, acknowledged_at, resolution, closed_by, closed_at,
+                   created_at, updated_at
+            FROM security_alerts
+            WHERE status <> 'closed'
+            ORDER BY created_at DESC
+            LIMIT 100
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(alerts)
     }
 
     /// Acknowledge security alert
     pub async fn acknowledge_alert(&self, alert_id: &str) -> Result<(), ServiceError> {
-        // Mock implementation - in real system would update database
-        println!("Alert {} acknowledged", alert_id);
+        let updated = sqlx::query!(
+            r#"
+            UPDATE security_alerts
+            SET acknowledged = true, acknowledged_at = COALESCE(acknowledged_at, NOW()), updated_at = NOW()
+            WHERE alert_id = $1
+            "#,
+            alert_id
+        )
+        .execute(&self.db_pool)
+        .await?
+        .rows_affected();
+
+        if updated == 0 {
+            return Err(ServiceError::NotFound(format!("Alert {} not found", alert_id)));
+        }
         Ok(())
     }
 }
@@ -0,0 +1,1164 @@
+// AML Monitoring Service
+// Transaction monitoring rules, security alert cases and withdrawal freezes
+
+use chrono::{DateTime, Duration, Utc};
+use nanoid::nanoid;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::services::admin_service::SecurityAlert;
+use crate::services::audit_service::{AuditEntry, AuditService};
+
+pub const RULE_VELOCITY: &str = "velocity";
+pub const RULE_STRUCTURING: &str = "structuring";
+pub const RULE_RAPID_IN_OUT: &str = "rapid_in_out";
+pub const RULE_LARGE_TRANSFER: &str = "large_transfer";
+
+pub const SEVERITIES: &[&str] = &["low", "medium", "high", "critical"];
+
+/// Shortest history loaded for an evaluation run
+const MIN_LOOKBACK_HOURS: i64 = 24;
+
+/// Payment statuses whose funds were received
+const RECEIVED_PAYMENT_STATUSES: &[&str] = &["CONFIRMED", "OVERPAID", "PARTIALLY_REFUNDED", "REFUNDED", "PAID_LATE"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AmlRule {
+    pub rule_id: String,
+    pub enabled: bool,
+    pub severity: String,
+    pub freeze_withdrawals: bool,
+    pub params: JsonValue,
+    pub updated_by: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAmlRule {
+    pub enabled: Option<bool>,
+    pub severity: Option<String>,
+    pub freeze_withdrawals: Option<bool>,
+    pub params: Option<JsonValue>,
+}
+
+/// Too many payments from one payer address in a short window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VelocityParams {
+    pub window_minutes: i64,
+    pub max_transactions: usize,
+}
+
+/// Repeated transfers just under a reporting threshold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuringParams {
+    pub threshold_usd: Decimal,
+    /// Transfers within this percentage below the threshold count
+    pub margin_percent: Decimal,
+    pub window_hours: i64,
+    pub min_transactions: usize,
+}
+
+/// Funds withdrawn soon after they arrived
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RapidInOutParams {
+    pub window_minutes: i64,
+    /// Share of the inbound amount withdrawn within the window
+    pub min_ratio_percent: Decimal,
+    pub min_inbound_usd: Decimal,
+}
+
+/// Any single transfer at or above a threshold
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LargeTransferParams {
+    pub threshold_usd: Decimal,
+}
+
+/// Typed parameters of a rule
+#[derive(Debug, Clone)]
+pub enum RuleConfig {
+    Velocity(VelocityParams),
+    Structuring(StructuringParams),
+    RapidInOut(RapidInOutParams),
+    LargeTransfer(LargeTransferParams),
+}
+
+impl RuleConfig {
+    /// Parse and validate the stored parameters of a rule
+    pub fn parse(rule_id: &str, params: &JsonValue) -> Result<Self, ServiceError> {
+        fn typed<T: serde::de::DeserializeOwned>(rule_id: &str, params: &JsonValue) -> Result<T, ServiceError> {
+            serde_json::from_value(params.clone())
+                .map_err(|e| ServiceError::ValidationError(format!("Invalid parameters for rule {}: {}", rule_id, e)))
+        }
+
+        let config = match rule_id {
+            RULE_VELOCITY => RuleConfig::Velocity(typed(rule_id, params)?),
+            RULE_STRUCTURING => RuleConfig::Structuring(typed(rule_id, params)?),
+            RULE_RAPID_IN_OUT => RuleConfig::RapidInOut(typed(rule_id, params)?),
+            RULE_LARGE_TRANSFER => RuleConfig::LargeTransfer(typed(rule_id, params)?),
+            other => return Err(ServiceError::NotFound(format!("AML rule {} not found", other))),
+        };
+
+        let valid = match &config {
+            RuleConfig::Velocity(p) => p.window_minutes > 0 && p.max_transactions > 0,
+            RuleConfig::Structuring(p) => {
+                p.threshold_usd > Decimal::ZERO
+                    && p.margin_percent > Decimal::ZERO
+                    && p.margin_percent < Decimal::ONE_HUNDRED
+                    && p.window_hours > 0
+                    && p.min_transactions > 1
+            }
+            RuleConfig::RapidInOut(p) => {
+                p.window_minutes > 0 && p.min_ratio_percent > Decimal::ZERO && p.min_inbound_usd >= Decimal::ZERO
+            }
+            RuleConfig::LargeTransfer(p) => p.threshold_usd > Decimal::ZERO,
+        };
+        if !valid {
+            return Err(ServiceError::ValidationError(format!("Parameters for rule {} are out of range", rule_id)));
+        }
+
+        Ok(config)
+    }
+
+    /// History a rule needs to see
+    fn lookback(&self) -> Duration {
+        match self {
+            RuleConfig::Velocity(p) => Duration::minutes(p.window_minutes),
+            RuleConfig::Structuring(p) => Duration::hours(p.window_hours),
+            RuleConfig::RapidInOut(p) => Duration::minutes(p.window_minutes),
+            RuleConfig::LargeTransfer(_) => Duration::zero(),
+        }
+    }
+}
+
+/// An enabled rule ready for evaluation
+#[derive(Debug, Clone)]
+pub struct ActiveRule {
+    pub rule_id: String,
+    pub severity: String,
+    pub freeze_withdrawals: bool,
+    pub config: RuleConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Received payment
+    Inbound,
+    /// Withdrawal
+    Outbound,
+}
+
+/// A confirmed payment or a withdrawal, valued in USD
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub direction: Direction,
+    pub merchant_id: i64,
+    /// Public id (pay_..., wd_...)
+    pub reference: String,
+    /// Payer address for payments, destination for withdrawals
+    pub counterparty: Option<String>,
+    pub crypto_type: String,
+    pub amount_usd: Decimal,
+    pub at: DateTime<Utc>,
+}
+
+/// Activity matching a rule
+#[derive(Debug, Clone)]
+pub struct RuleHit {
+    pub rule_id: String,
+    pub severity: String,
+    pub freeze_withdrawals: bool,
+    pub merchant_id: i64,
+    pub dedupe_key: String,
+    pub message: String,
+    pub details: JsonValue,
+    pub last_activity_at: DateTime<Utc>,
+}
+
+/// Case status of a security alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseStatus {
+    Open,
+    Investigating,
+    Closed,
+}
+
+impl CaseStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaseStatus::Open => "open",
+            CaseStatus::Investigating => "investigating",
+            CaseStatus::Closed => "closed",
+        }
+    }
+
+    pub fn from_string(status: &str) -> Option<Self> {
+        match status {
+            "open" => Some(CaseStatus::Open),
+            "investigating" => Some(CaseStatus::Investigating),
+            "closed" => Some(CaseStatus::Closed),
+            _ => None,
+        }
+    }
+
+    /// Closed cases stay closed; new activity opens a new case
+    pub fn can_transition_to(&self, to: CaseStatus) -> bool {
+        matches!(
+            (self, to),
+            (CaseStatus::Open, CaseStatus::Investigating | CaseStatus::Closed)
+                | (CaseStatus::Investigating, CaseStatus::Open | CaseStatus::Closed)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityAlertEvent {
+    pub id: i64,
+    pub alert_id: String,
+    pub admin_id: Option<i64>,
+    pub from_status: Option<String>,
+    pub to_status: Option<String>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlertQuery {
+    pub status: Option<String>,
+    pub merchant_id: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MonitoringReport {
+    pub rules_evaluated: usize,
+    pub transfers_scanned: usize,
+    pub alerts_raised: usize,
+    pub alerts_updated: usize,
+    pub merchants_frozen: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WithdrawalFreeze {
+    pub merchant_id: i64,
+    pub frozen: bool,
+    pub reason: Option<String>,
+    pub frozen_at: Option<DateTime<Utc>>,
+    pub alert_id: Option<String>,
+}
+
+pub struct AmlService {
+    db_pool: PgPool,
+    enabled: bool,
+}
+
+impl AmlService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool, enabled: true }
+    }
+
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self {
+            db_pool,
+            enabled: config.aml_monitoring_enabled,
+        }
+    }
+
+    pub async fn list_rules(&self) -> Result<Vec<AmlRule>, ServiceError> {
+        let rules = sqlx::query_as!(AmlRule, "SELECT * FROM aml_rules ORDER BY rule_id")
+            .fetch_all(&self.db_pool)
+            .await?;
+
+        Ok(rules)
+    }
+
+    /// Change a rule; parameters are validated before they are stored
+    pub async fn update_rule(
+        &self,
+        rule_id: &str,
+        update: UpdateAmlRule,
+        admin_id: i64,
+    ) -> Result<AmlRule, ServiceError> {
+        if let Some(severity) = &update.severity {
+            if !SEVERITIES.contains(&severity.as_str()) {
+                return Err(ServiceError::ValidationError(format!("Unknown severity: {}", severity)));
+            }
+        }
+        if let Some(params) = &update.params {
+            RuleConfig::parse(rule_id, params)?;
+        }
+
+        sqlx::query_as!(
+            AmlRule,
+            r#"
+            UPDATE aml_rules
+            SET enabled = COALESCE($2, enabled),
+                severity = COALESCE($3, severity),
+                freeze_withdrawals = COALESCE($4, freeze_withdrawals),
+                params = COALESCE($5, params),
+                updated_by = $6,
+                updated_at = NOW()
+            WHERE rule_id = $1
+            RETURNING *
+            "#,
+            rule_id,
+            update.enabled,
+            update.severity,
+            update.freeze_withdrawals,
+            update.params,
+            admin_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("AML rule {} not found", rule_id)))
+    }
+
+    async fn active_rules(&self) -> Result<Vec<ActiveRule>, ServiceError> {
+        let rules = self.list_rules().await?;
+
+        Ok(rules
+            .into_iter()
+            .filter(|rule| rule.enabled)
+            .filter_map(|rule| match RuleConfig::parse(&rule.rule_id, &rule.params) {
+                Ok(config) => Some(ActiveRule {
+                    rule_id: rule.rule_id,
+                    severity: rule.severity,
+                    freeze_withdrawals: rule.freeze_withdrawals,
+                    config,
+                }),
+                Err(e) => {
+                    warn!("Skipping AML rule {}: {}", rule.rule_id, e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Received payments and withdrawals since `since`, oldest first
+    ///
+    /// Withdrawals have no USD amount; stablecoins count at par and other
+    /// currencies at the rate of the latest payment in that currency.
+    async fn load_transfers(&self, since: DateTime<Utc>) -> Result<Vec<Transfer>, ServiceError> {
+        let statuses: Vec<String> = RECEIVED_PAYMENT_STATUSES.iter().map(|s| s.to_string()).collect();
+
+        let payments = sqlx::query!(
+            r#"
+            SELECT merchant_id, payment_id, from_address, crypto_type,
+                   CASE WHEN total_paid > 0 THEN amount_usd * total_paid / amount ELSE amount_usd END AS "amount_usd!",
+                   COALESCE(confirmed_at, created_at) AS "at!"
+            FROM payment_transactions
+            WHERE status = ANY($1) AND COALESCE(confirmed_at, created_at) >= $2
+            "#,
+            &statuses,
+            since
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let withdrawals = sqlx::query!(
+            r#"
+            SELECT w.merchant_id, w.withdrawal_id, w.destination_address, w.crypto_type, w.created_at,
+                   COALESCE(
+                       CASE WHEN w.crypto_type LIKE 'USDT%' THEN w.amount END,
+                       w.amount * (
+                           SELECT p.amount_usd / p.amount FROM payment_transactions p
+                           WHERE p.crypto_type = w.crypto_type
+                           ORDER BY p.created_at DESC LIMIT 1
+                       )
+                   ) AS amount_usd
+            FROM withdrawals w
+            WHERE w.status NOT IN ('CANCELLED', 'REJECTED') AND w.created_at >= $1
+            "#,
+            since
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut transfers: Vec<Transfer> = payments
+            .into_iter()
+            .map(|p| Transfer {
+                direction: Direction::Inbound,
+                merchant_id: p.merchant_id,
+                reference: p.payment_id,
+                counterparty: p.from_address,
+                crypto_type: p.crypto_type,
+                amount_usd: p.amount_usd,
+                at: p.at,
+            })
+            .collect();
+
+        for w in withdrawals {
+            let Some(amount_usd) = w.amount_usd else {
+                warn!("No USD rate for withdrawal {}; skipped by AML rules", w.withdrawal_id);
+                continue;
+            };
+            transfers.push(Transfer {
+                direction: Direction::Outbound,
+                merchant_id: w.merchant_id,
+                reference: w.withdrawal_id,
+                counterparty: Some(w.destination_address),
+                crypto_type: w.crypto_type,
+                amount_usd,
+                at: w.created_at,
+            });
+        }
+
+        transfers.sort_by_key(|t| t.at);
+        Ok(transfers)
+    }
+
+    /// Run every enabled rule over recent activity and raise alerts
+    pub async fn evaluate(&self) -> Result<MonitoringReport, ServiceError> {
+        let mut report = MonitoringReport::default();
+        if !self.enabled {
+            return Ok(report);
+        }
+
+        let rules = self.active_rules().await?;
+        if rules.is_empty() {
+            return Ok(report);
+        }
+
+        let lookback = rules
+            .iter()
+            .map(|rule| rule.config.lookback())
+            .max()
+            .unwrap_or_else(Duration::zero)
+            .max(Duration::hours(MIN_LOOKBACK_HOURS));
+        let transfers = self.load_transfers(Utc::now() - lookback).await?;
+
+        report.rules_evaluated = rules.len();
+        report.transfers_scanned = transfers.len();
+
+        for hit in evaluate_rules(&rules, &transfers) {
+            match self.raise(&hit).await? {
+                RaiseOutcome::Raised { frozen } => {
+                    report.alerts_raised += 1;
+                    if frozen {
+                        report.merchants_frozen += 1;
+                    }
+                }
+                RaiseOutcome::Updated => report.alerts_updated += 1,
+                RaiseOutcome::AlreadyReviewed => {}
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Record a rule hit as a new case, or refresh the open case for it
+    async fn raise(&self, hit: &RuleHit) -> Result<RaiseOutcome, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let alert_id = format!("alert_{}", nanoid!(16));
+        let saved = sqlx::query!(
+            r#"
+            INSERT INTO security_alerts (
+                alert_id, merchant_id, alert_type, severity, message, details, dedupe_key, last_activity_at
+            )
+            SELECT $1, $2, $3, $4, $5, $6, $7::varchar, $8::timestamptz
+            WHERE NOT EXISTS (
+                SELECT 1 FROM security_alerts
+                WHERE dedupe_key = $7::varchar AND last_activity_at >= $8::timestamptz
+            )
+            ON CONFLICT (dedupe_key) WHERE status <> 'closed'
+            DO UPDATE SET message = EXCLUDED.message,
+                          details = EXCLUDED.details,
+                          last_activity_at = EXCLUDED.last_activity_at,
+                          updated_at = NOW()
+            RETURNING alert_id, (xmax = 0) AS "inserted!"
+            "#,
+            alert_id,
+            hit.merchant_id,
+            hit.rule_id,
+            hit.severity,
+            hit.message,
+            hit.details,
+            hit.dedupe_key,
+            hit.last_activity_at
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(saved) = saved else {
+            return Ok(RaiseOutcome::AlreadyReviewed);
+        };
+        if !saved.inserted {
+            tx.commit().await?;
+            return Ok(RaiseOutcome::Updated);
+        }
+
+        AuditService::record_in_tx(
+            &mut tx,
+            &AuditEntry::new("AML_ALERT")
+                .with_merchant(hit.merchant_id)
+                .with_entity("security_alert", &saved.alert_id)
+                .with_details(json!({
+                    "rule": hit.rule_id,
+                    "severity": hit.severity,
+                    "message": hit.message,
+                })),
+        ).await?;
+
+        let frozen = hit.freeze_withdrawals
+            && Self::freeze_in_tx(&mut tx, hit.merchant_id, &hit.message, Some(&saved.alert_id)).await?;
+
+        tx.commit().await?;
+        warn!("AML alert {} ({}) for merchant {}: {}", saved.alert_id, hit.rule_id, hit.merchant_id, hit.message);
+
+        Ok(RaiseOutcome::Raised { frozen })
+    }
+
+    pub async fn list_alerts(&self, query: &AlertQuery) -> Result<Vec<SecurityAlert>, ServiceError> {
+        let alerts = sqlx::query_as!(
+            SecurityAlert,
+            r#"
+            SELECT alert_id, merchant_id, alert_type, severity, message, details, status,
+                   assigned_to, acknowledged, acknowledged_at, resolution, closed_by, closed_at,
+                   created_at, updated_at
+            FROM security_alerts
+            WHERE ($1::text IS NULL OR status = $1)
+              AND ($2::bigint IS NULL OR merchant_id = $2)
+            ORDER BY created_at DESC
+            LIMIT $3 OFFSET $4
+            "#,
+            query.status,
+            query.merchant_id,
+            query.limit.unwrap_or(50).clamp(1, 500),
+            query.offset.unwrap_or(0).max(0)
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(alerts)
+    }
+
+    pub async fn get_alert(&self, alert_id: &str) -> Result<(SecurityAlert, Vec<SecurityAlertEvent>), ServiceError> {
+        let alert = sqlx::query_as!(
+            SecurityAlert,
+            r#"
+            SELECT alert_id, merchant_id, alert_type, severity, message, details, status,
+                   assigned_to, acknowledged, acknowledged_at, resolution, closed_by, closed_at,
+                   created_at, updated_at
+            FROM security_alerts WHERE alert_id = $1
+            "#,
+            alert_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Alert {} not found", alert_id)))?;
+
+        let events = sqlx::query_as!(
+            SecurityAlertEvent,
+            "SELECT * FROM security_alert_events WHERE alert_id = $1 ORDER BY id",
+            alert_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok((alert, events))
+    }
+
+    /// Take an alert: marks it acknowledged, assigns it and starts the investigation
+    pub async fn acknowledge(&self, alert_id: &str, admin_id: i64) -> Result<SecurityAlert, ServiceError> {
+        let (alert, _) = self.get_alert(alert_id).await?;
+        let mut tx = self.db_pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE security_alerts
+            SET acknowledged = true,
+                acknowledged_at = COALESCE(acknowledged_at, NOW()),
+                assigned_to = COALESCE(assigned_to, $2),
+                updated_at = NOW()
+            WHERE alert_id = $1
+            "#,
+            alert_id,
+            admin_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if alert.status == CaseStatus::Open.as_str() {
+            Self::set_status(&mut tx, &alert, CaseStatus::Investigating, admin_id, Some("Acknowledged")).await?;
+        }
+
+        tx.commit().await?;
+        Ok(self.get_alert(alert_id).await?.0)
+    }
+
+    /// Move a case through open, investigating and closed
+    ///
+    /// Closing requires a note, which becomes the resolution.
+    pub async fn update_case(
+        &self,
+        alert_id: &str,
+        to: CaseStatus,
+        admin_id: i64,
+        note: Option<&str>,
+    ) -> Result<SecurityAlert, ServiceError> {
+        if to == CaseStatus::Closed && note.is_none_or(|n| n.trim().is_empty()) {
+            return Err(ServiceError::ValidationError("A resolution note is required to close a case".to_string()));
+        }
+
+        let (alert, _) = self.get_alert(alert_id).await?;
+        let mut tx = self.db_pool.begin().await?;
+        Self::set_status(&mut tx, &alert, to, admin_id, note).await?;
+        tx.commit().await?;
+
+        Ok(self.get_alert(alert_id).await?.0)
+    }
+
+    async fn set_status(
+        tx: &mut Transaction<'_, Postgres>,
+        alert: &SecurityAlert,
+        to: CaseStatus,
+        admin_id: i64,
+        note: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        let from = CaseStatus::from_string(&alert.status).unwrap_or(CaseStatus::Open);
+        if !from.can_transition_to(to) {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Alert {} cannot move from {} to {}",
+                alert.alert_id,
+                from.as_str(),
+                to.as_str()
+            )));
+        }
+
+        let closing = to == CaseStatus::Closed;
+        let updated = sqlx::query!(
+            r#"
+            UPDATE security_alerts
+            SET status = $2,
+                assigned_to = COALESCE(assigned_to, $3),
+                resolution = CASE WHEN $4 THEN $5 ELSE resolution END,
+                closed_by = CASE WHEN $4 THEN $3 ELSE closed_by END,
+                closed_at = CASE WHEN $4 THEN NOW() ELSE closed_at END,
+                updated_at = NOW()
+            WHERE alert_id = $1 AND status = $6
+            "#,
+            alert.alert_id,
+            to.as_str(),
+            admin_id,
+            closing,
+            note,
+            from.as_str()
+        )
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        if updated == 0 {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Alert {} changed while it was being updated",
+                alert.alert_id
+            )));
+        }
+
+        Self::record_event(tx, &alert.alert_id, admin_id, Some(from), Some(to), note).await
+    }
+
+    /// Add a note to a case without changing its status
+    pub async fn add_note(&self, alert_id: &str, admin_id: i64, note: &str) -> Result<(), ServiceError> {
+        if note.trim().is_empty() {
+            return Err(ServiceError::ValidationError("Note cannot be empty".to_string()));
+        }
+        self.get_alert(alert_id).await?;
+
+        let mut tx = self.db_pool.begin().await?;
+        Self::record_event(&mut tx, alert_id, admin_id, None, None, Some(note)).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn record_event(
+        tx: &mut Transaction<'_, Postgres>,
+        alert_id: &str,
+        admin_id: i64,
+        from: Option<CaseStatus>,
+        to: Option<CaseStatus>,
+        note: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO security_alert_events (alert_id, admin_id, from_status, to_status, note)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            alert_id,
+            admin_id,
+            from.map(|s| s.as_str()),
+            to.map(|s| s.as_str()),
+            note
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn withdrawal_freeze(&self, merchant_id: i64) -> Result<WithdrawalFreeze, ServiceError> {
+        sqlx::query_as!(
+            WithdrawalFreeze,
+            r#"
+            SELECT id AS merchant_id, withdrawals_frozen AS frozen, withdrawals_frozen_reason AS reason,
+                   withdrawals_frozen_at AS frozen_at, withdrawals_frozen_alert_id AS alert_id
+            FROM merchants WHERE id = $1
+            "#,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)
+    }
+
+    /// Stop a merchant's withdrawals pending review
+    pub async fn freeze_withdrawals(
+        &self,
+        merchant_id: i64,
+        reason: &str,
+        alert_id: Option<&str>,
+    ) -> Result<WithdrawalFreeze, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+        Self::freeze_in_tx(&mut tx, merchant_id, reason, alert_id).await?;
+        tx.commit().await?;
+
+        self.withdrawal_freeze(merchant_id).await
+    }
+
+    /// Returns whether the merchant was newly frozen
+    async fn freeze_in_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+        reason: &str,
+        alert_id: Option<&str>,
+    ) -> Result<bool, ServiceError> {
+        let frozen = sqlx::query!(
+            r#"
+            UPDATE merchants
+            SET withdrawals_frozen = true,
+                withdrawals_frozen_reason = $2,
+                withdrawals_frozen_at = NOW(),
+                withdrawals_frozen_alert_id = $3
+            WHERE id = $1 AND NOT withdrawals_frozen
+            "#,
+            merchant_id,
+            reason,
+            alert_id
+        )
+        .execute(&mut **tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if frozen {
+            AuditService::record_in_tx(
+                tx,
+                &AuditEntry::new("WITHDRAWALS_FROZEN")
+                    .with_merchant(merchant_id)
+                    .with_entity("merchant", &merchant_id.to_string())
+                    .with_details(json!({ "reason": reason, "alert_id": alert_id })),
+            ).await?;
+            info!("Withdrawals frozen for merchant {}: {}", merchant_id, reason);
+        }
+
+        Ok(frozen)
+    }
+
+    /// Lift a withdrawal freeze after review
+    pub async fn unfreeze_withdrawals(
+        &self,
+        merchant_id: i64,
+        admin_id: i64,
+        note: Option<&str>,
+    ) -> Result<WithdrawalFreeze, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let lifted = sqlx::query!(
+            r#"
+            UPDATE merchants
+            SET withdrawals_frozen = false,
+                withdrawals_frozen_reason = NULL,
+                withdrawals_frozen_at = NULL,
+                withdrawals_frozen_alert_id = NULL
+            WHERE id = $1 AND withdrawals_frozen
+            "#,
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if lifted {
+            AuditService::record_in_tx(
+                &mut tx,
+                &AuditEntry::new("WITHDRAWALS_UNFROZEN")
+                    .with_merchant(merchant_id)
+                    .with_actor("admin", Some(admin_id.to_string()), None)
+                    .with_entity("merchant", &merchant_id.to_string())
+                    .with_details(json!({ "note": note })),
+            ).await?;
+        }
+
+        tx.commit().await?;
+        self.withdrawal_freeze(merchant_id).await
+    }
+
+    /// USD threshold of the large transfer rule
+    pub async fn large_transfer_threshold(&self) -> Result<Decimal, ServiceError> {
+        let params = sqlx::query_scalar!("SELECT params FROM aml_rules WHERE rule_id = $1", RULE_LARGE_TRANSFER)
+            .fetch_optional(&self.db_pool)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("AML rule {} not found", RULE_LARGE_TRANSFER)))?;
+
+        match RuleConfig::parse(RULE_LARGE_TRANSFER, &params)? {
+            RuleConfig::LargeTransfer(params) => Ok(params.threshold_usd),
+            _ => unreachable!("large_transfer parses to LargeTransfer"),
+        }
+    }
+
+    /// Withdrawals of the last `hours` at or above the large transfer threshold
+    pub async fn large_withdrawals(&self, hours: i32) -> Result<Vec<Transfer>, ServiceError> {
+        let threshold = self.large_transfer_threshold().await?;
+        let transfers = self.load_transfers(Utc::now() - Duration::hours(hours as i64)).await?;
+
+        Ok(transfers
+            .into_iter()
+            .filter(|t| t.direction == Direction::Outbound && t.amount_usd >= threshold)
+            .collect())
+    }
+}
+
+enum RaiseOutcome {
+    Raised { frozen: bool },
+    Updated,
+    AlreadyReviewed,
+}
+
+/// Evaluate rules over transfers sorted by time
+pub fn evaluate_rules(rules: &[ActiveRule], transfers: &[Transfer]) -> Vec<RuleHit> {
+    let mut hits = Vec::new();
+
+    for rule in rules {
+        let found = match &rule.config {
+            RuleConfig::Velocity(p) => velocity(p, transfers),
+            RuleConfig::Structuring(p) => structuring(p, transfers),
+            RuleConfig::RapidInOut(p) => rapid_in_out(p, transfers),
+            RuleConfig::LargeTransfer(p) => large_transfers(p, transfers),
+        };
+        hits.extend(found.into_iter().map(|finding| RuleHit {
+            rule_id: rule.rule_id.clone(),
+            severity: rule.severity.clone(),
+            freeze_withdrawals: rule.freeze_withdrawals,
+            merchant_id: finding.merchant_id,
+            dedupe_key: format!("{}:{}", rule.rule_id, finding.subject),
+            message: finding.message,
+            details: finding.details,
+            last_activity_at: finding.last_activity_at,
+        }));
+    }
+
+    hits
+}
+
+/// What a rule found, before the rule's settings are attached
+struct Finding {
+    merchant_id: i64,
+    /// Identifies the case together with the rule
+    subject: String,
+    message: String,
+    details: JsonValue,
+    last_activity_at: DateTime<Utc>,
+}
+
+/// Latest span of at most `window` holding `min_count` or more transfers, as (start, end) indices
+fn latest_dense_window(transfers: &[&Transfer], window: Duration, min_count: usize) -> Option<(usize, usize)> {
+    let mut start = 0;
+    let mut found = None;
+    for end in 0..transfers.len() {
+        while transfers[end].at - transfers[start].at > window {
+            start += 1;
+        }
+        if end - start + 1 >= min_count {
+            found = Some((start, end));
+        }
+    }
+    found
+}
+
+fn references(transfers: &[&Transfer]) -> Vec<String> {
+    transfers.iter().map(|t| t.reference.clone()).collect()
+}
+
+fn velocity(params: &VelocityParams, transfers: &[Transfer]) -> Vec<Finding> {
+    let mut by_payer: BTreeMap<(i64, &str), Vec<&Transfer>> = BTreeMap::new();
+    for transfer in transfers.iter().filter(|t| t.direction == Direction::Inbound) {
+        if let Some(payer) = transfer.counterparty.as_deref().filter(|a| !a.is_empty()) {
+            by_payer.entry((transfer.merchant_id, payer)).or_default().push(transfer);
+        }
+    }
+
+    let window = Duration::minutes(params.window_minutes);
+    by_payer
+        .into_iter()
+        .filter_map(|((merchant_id, payer), list)| {
+            let (start, end) = latest_dense_window(&list, window, params.max_transactions + 1)?;
+            let matched = &list[start..=end];
+            Some(Finding {
+                merchant_id,
+                subject: format!("{}:{}", merchant_id, payer),
+                message: format!(
+                    "{} payments from {} within {} minutes",
+                    matched.len(), payer, params.window_minutes
+                ),
+                details: json!({
+                    "payer_address": payer,
+                    "count": matched.len(),
+                    "window_minutes": params.window_minutes,
+                    "max_transactions": params.max_transactions,
+                    "references": references(matched),
+                }),
+                last_activity_at: matched[matched.len() - 1].at,
+            })
+        })
+        .collect()
+}
+
+fn structuring(params: &StructuringParams, transfers: &[Transfer]) -> Vec<Finding> {
+    let band_from = params.threshold_usd * (Decimal::ONE_HUNDRED - params.margin_percent) / Decimal::ONE_HUNDRED;
+
+    let mut by_merchant: BTreeMap<i64, Vec<&Transfer>> = BTreeMap::new();
+    for transfer in transfers
+        .iter()
+        .filter(|t| t.amount_usd >= band_from && t.amount_usd < params.threshold_usd)
+    {
+        by_merchant.entry(transfer.merchant_id).or_default().push(transfer);
+    }
+
+    let window = Duration::hours(params.window_hours);
+    by_merchant
+        .into_iter()
+        .filter_map(|(merchant_id, list)| {
+            let (start, end) = latest_dense_window(&list, window, params.min_transactions)?;
+            let matched = &list[start..=end];
+            let total: Decimal = matched.iter().map(|t| t.amount_usd).sum();
+            Some(Finding {
+                merchant_id,
+                subject: merchant_id.to_string(),
+                message: format!(
+                    "{} transfers between ${} and ${} within {} hours",
+                    matched.len(), band_from.round_dp(2), params.threshold_usd, params.window_hours
+                ),
+                details: json!({
+                    "count": matched.len(),
+                    "total_usd": total.round_dp(2),
+                    "threshold_usd": params.threshold_usd,
+                    "band_from_usd": band_from.round_dp(2),
+                    "window_hours": params.window_hours,
+                    "references": references(matched),
+                }),
+                last_activity_at: matched[matched.len() - 1].at,
+            })
+        })
+        .collect()
+}
+
+fn rapid_in_out(params: &RapidInOutParams, transfers: &[Transfer]) -> Vec<Finding> {
+    let mut by_merchant: BTreeMap<i64, Vec<&Transfer>> = BTreeMap::new();
+    for transfer in transfers {
+        by_merchant.entry(transfer.merchant_id).or_default().push(transfer);
+    }
+
+    let window = Duration::minutes(params.window_minutes);
+    by_merchant
+        .into_iter()
+        .filter_map(|(merchant_id, list)| {
+            // Latest withdrawal that, with the others in its window, drains the funds received
+            list.iter()
+                .enumerate()
+                .rev()
+                .filter(|(_, t)| t.direction == Direction::Outbound)
+                .find_map(|(index, outbound)| {
+                    let in_window: Vec<&Transfer> = list[..=index]
+                        .iter()
+                        .copied()
+                        .filter(|t| outbound.at - t.at <= window)
+                        .collect();
+                    let inbound: Decimal = in_window
+                        .iter()
+                        .filter(|t| t.direction == Direction::Inbound)
+                        .map(|t| t.amount_usd)
+                        .sum();
+                    let outbound_total: Decimal = in_window
+                        .iter()
+                        .filter(|t| t.direction == Direction::Outbound)
+                        .map(|t| t.amount_usd)
+                        .sum();
+
+                    let drained = inbound > Decimal::ZERO
+                        && inbound >= params.min_inbound_usd
+                        && outbound_total * Decimal::ONE_HUNDRED >= inbound * params.min_ratio_percent;
+                    drained.then(|| (outbound.at, inbound, outbound_total, references(&in_window)))
+                })
+                .map(|(at, inbound, outbound, refs)| Finding {
+                    merchant_id,
+                    subject: merchant_id.to_string(),
+                    message: format!(
+                        "${} withdrawn within {} minutes of receiving ${}",
+                        outbound.round_dp(2), params.window_minutes, inbound.round_dp(2)
+                    ),
+                    details: json!({
+                        "inbound_usd": inbound.round_dp(2),
+                        "outbound_usd": outbound.round_dp(2),
+                        "window_minutes": params.window_minutes,
+                        "min_ratio_percent": params.min_ratio_percent,
+                        "references": refs,
+                    }),
+                    last_activity_at: at,
+                })
+        })
+        .collect()
+}
+
+fn large_transfers(params: &LargeTransferParams, transfers: &[Transfer]) -> Vec<Finding> {
+    transfers
+        .iter()
+        .filter(|t| t.amount_usd >= params.threshold_usd)
+        .map(|t| {
+            let kind = match t.direction {
+                Direction::Inbound => "payment",
+                Direction::Outbound => "withdrawal",
+            };
+            Finding {
+                merchant_id: t.merchant_id,
+                subject: t.reference.clone(),
+                message: format!("Large {} {} of ${}", kind, t.reference, t.amount_usd.round_dp(2)),
+                details: json!({
+                    "reference": t.reference,
+                    "direction": t.direction,
+                    "counterparty": t.counterparty,
+                    "amount_usd": t.amount_usd.round_dp(2),
+                    "threshold_usd": params.threshold_usd,
+                }),
+                last_activity_at: t.at,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(direction: Direction, reference: &str, payer: &str, usd: i64, minutes: i64) -> Transfer {
+        Transfer {
+            direction,
+            merchant_id: 1,
+            reference: reference.to_string(),
+            counterparty: Some(payer.to_string()),
+            crypto_type: "USDT_ETH".to_string(),
+            amount_usd: Decimal::from(usd),
+            at: DateTime::<Utc>::from_timestamp(1_700_000_000, 0).unwrap() + Duration::minutes(minutes),
+        }
+    }
+
+    fn rule(rule_id: &str, params: JsonValue) -> ActiveRule {
+        ActiveRule {
+            rule_id: rule_id.to_string(),
+            severity: "high".to_string(),
+            freeze_withdrawals: true,
+            config: RuleConfig::parse(rule_id, &params).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_velocity_per_payer_address() {
+        let rules = [rule(RULE_VELOCITY, json!({ "window_minutes": 60, "max_transactions": 2 }))];
+        let transfers = vec![
+            transfer(Direction::Inbound, "pay_1", "0xaaa", 10, 0),
+            transfer(Direction::Inbound, "pay_2", "0xbbb", 10, 5),
+            transfer(Direction::Inbound, "pay_3", "0xaaa", 10, 10),
+            transfer(Direction::Inbound, "pay_4", "0xaaa", 10, 50),
+            // Outside the window of the first three
+            transfer(Direction::Inbound, "pay_5", "0xbbb", 10, 120),
+        ];
+
+        let hits = evaluate_rules(&rules, &transfers);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].dedupe_key, "velocity:1:0xaaa");
+        assert_eq!(hits[0].details["count"], 3);
+        assert_eq!(hits[0].last_activity_at, transfers[3].at);
+    }
+
+    #[test]
+    fn test_structuring_just_under_threshold() {
+        let rules = [rule(
+            RULE_STRUCTURING,
+            json!({ "threshold_usd": 10000, "margin_percent": 10, "window_hours": 24, "min_transactions": 3 }),
+        )];
+        let mut transfers = vec![
+            transfer(Direction::Inbound, "pay_1", "0xaaa", 9500, 0),
+            transfer(Direction::Inbound, "pay_2", "0xbbb", 9900, 60),
+            // At the threshold and well below it: not structuring
+            transfer(Direction::Inbound, "pay_3", "0xccc", 10000, 90),
+            transfer(Direction::Inbound, "pay_4", "0xddd", 5000, 100),
+        ];
+        assert!(evaluate_rules(&rules, &transfers).is_empty());
+
+        transfers.push(transfer(Direction::Outbound, "wd_1", "0xeee", 9100, 120));
+        let hits = evaluate_rules(&rules, &transfers);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].details["references"], json!(["pay_1", "pay_2", "wd_1"]));
+    }
+
+    #[test]
+    fn test_rapid_in_out_and_large_transfer() {
+        let rules = [
+            rule(RULE_RAPID_IN_OUT, json!({ "window_minutes": 60, "min_ratio_percent": 80, "min_inbound_usd": 1000 })),
+            rule(RULE_LARGE_TRANSFER, json!({ "threshold_usd": 5000 })),
+        ];
+        let transfers = vec![
+            transfer(Direction::Inbound, "pay_1", "0xaaa", 3000, 0),
+            transfer(Direction::Outbound, "wd_1", "0xfff", 1000, 20),
+            transfer(Direction::Outbound, "wd_2", "0xfff", 1500, 40),
+            transfer(Direction::Inbound, "pay_2", "0xbbb", 6000, 300),
+        ];
+
+        let hits = evaluate_rules(&rules, &transfers);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].dedupe_key, "rapid_in_out:1");
+        assert_eq!(hits[0].last_activity_at, transfers[2].at);
+        assert_eq!(hits[1].dedupe_key, "large_transfer:pay_2");
+    }
+
+    #[test]
+    fn test_rule_params_are_validated() {
+        assert!(RuleConfig::parse(RULE_VELOCITY, &json!({ "window_minutes": 60 })).is_err());
+        assert!(RuleConfig::parse(RULE_STRUCTURING, &json!({
+            "threshold_usd": 10000, "margin_percent": 150, "window_hours": 24, "min_transactions": 3
+        })).is_err());
+        assert!(matches!(
+            RuleConfig::parse("unknown", &json!({})),
+            Err(ServiceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_case_status_transitions() {
+        assert!(CaseStatus::Open.can_transition_to(CaseStatus::Investigating));
+        assert!(CaseStatus::Investigating.can_transition_to(CaseStatus::Closed));
+        assert!(CaseStatus::Investigating.can_transition_to(CaseStatus::Open));
+        assert!(!CaseStatus::Closed.can_transition_to(CaseStatus::Open));
+        assert!(!CaseStatus::Open.can_transition_to(CaseStatus::Open));
+    }
+}
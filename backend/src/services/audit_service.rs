@@ -1,16 +1,33 @@
-use sqlx::PgPool;
-use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use serde_json::{json, Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
 use crate::error::ServiceError;
 
+/// Chain of events that do not belong to a merchant
+pub const PLATFORM_TENANT: i64 = 0;
+
+/// `prev_hash` of the first row of a chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Rows read per query while verifying a chain
+const VERIFY_PAGE_SIZE: i64 = 1000;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuditLog {
     pub id: i64,
     pub merchant_id: Option<i64>,
     pub action_type: String,
+    pub actor_type: Option<String>,
+    pub actor_id: Option<String>,
+    pub actor_role: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
     pub ip_address: Option<String>,
+    pub request_id: Option<String>,
     pub details: Option<JsonValue>,
+    pub hash: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -22,6 +39,303 @@ pub struct AuditLogQuery {
     pub limit: Option<i64>,
 }
 
+/// An event to append to the audit log
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// Merchant the event concerns; also selects the chain
+    pub merchant_id: Option<i64>,
+    pub action_type: String,
+    /// "merchant", "admin" or "system"
+    pub actor_type: String,
+    pub actor_id: Option<String>,
+    pub actor_role: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub details: Option<JsonValue>,
+}
+
+impl AuditEntry {
+    /// System event; use the builder methods to fill in the rest
+    pub fn new(action_type: &str) -> Self {
+        Self {
+            merchant_id: None,
+            action_type: action_type.to_string(),
+            actor_type: "system".to_string(),
+            actor_id: None,
+            actor_role: None,
+            entity_type: None,
+            entity_id: None,
+            ip_address: None,
+            user_agent: None,
+            request_id: None,
+            details: None,
+        }
+    }
+
+    pub fn with_merchant(mut self, merchant_id: i64) -> Self {
+        self.merchant_id = Some(merchant_id);
+        self
+    }
+
+    pub fn with_actor(mut self, actor_type: &str, actor_id: Option<String>, actor_role: Option<&str>) -> Self {
+        self.actor_type = actor_type.to_string();
+        self.actor_id = actor_id;
+        self.actor_role = actor_role.map(str::to_string);
+        self
+    }
+
+    pub fn with_entity(mut self, entity_type: &str, entity_id: &str) -> Self {
+        self.entity_type = Some(entity_type.to_string());
+        self.entity_id = Some(entity_id.to_string());
+        self
+    }
+
+    pub fn with_ip(mut self, ip_address: Option<&str>) -> Self {
+        self.ip_address = ip_address.map(str::to_string);
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: Option<&str>) -> Self {
+        self.user_agent = user_agent.map(str::to_string);
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: JsonValue) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Change a handler made, attached to its response for the audit middleware
+///
+/// ```ignore
+/// let change = AuditChange::new("payment", &payment_id)
+///     .with_before(json!({ "status": previous }))
+///     .with_after(json!({ "status": "CONFIRMED" }));
+/// (Extension(change), Json(body)).into_response()
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AuditChange {
+    /// Merchant whose chain records the change, when the actor is an admin
+    pub merchant_id: Option<i64>,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub before: Option<JsonValue>,
+    pub after: Option<JsonValue>,
+}
+
+impl AuditChange {
+    pub fn new(entity_type: &str, entity_id: &str) -> Self {
+        Self {
+            entity_type: entity_type.to_string(),
+            entity_id: entity_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_merchant(mut self, merchant_id: i64) -> Self {
+        self.merchant_id = Some(merchant_id);
+        self
+    }
+
+    pub fn with_before(mut self, before: JsonValue) -> Self {
+        self.before = Some(before);
+        self
+    }
+
+    pub fn with_after(mut self, after: JsonValue) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Fields that differ between `before` and `after`
+    pub fn diff(&self) -> JsonValue {
+        diff(
+            self.before.as_ref().unwrap_or(&JsonValue::Null),
+            self.after.as_ref().unwrap_or(&JsonValue::Null),
+        )
+    }
+}
+
+/// Top-level fields that differ, as `{"field": {"before": .., "after": ..}}`
+///
+/// Values that are not both objects are compared whole.
+pub fn diff(before: &JsonValue, after: &JsonValue) -> JsonValue {
+    match (before, after) {
+        (JsonValue::Object(before), JsonValue::Object(after)) => {
+            let mut changes = Map::new();
+            for key in before.keys().chain(after.keys()) {
+                let old = before.get(key).unwrap_or(&JsonValue::Null);
+                let new = after.get(key).unwrap_or(&JsonValue::Null);
+                if old != new && !changes.contains_key(key) {
+                    changes.insert(key.clone(), json!({ "before": old, "after": new }));
+                }
+            }
+            JsonValue::Object(changes)
+        }
+        _ if before == after => json!({}),
+        _ => json!({ "before": before, "after": after }),
+    }
+}
+
+/// Result of checking one tenant's chain
+#[derive(Debug, Serialize)]
+pub struct ChainVerification {
+    pub tenant_id: i64,
+    pub intact: bool,
+    /// Chained rows whose hashes were recomputed
+    pub verified_rows: u64,
+    /// Rows written before chaining was introduced
+    pub legacy_rows: u64,
+    pub last_id: Option<i64>,
+    /// First row that does not match the chain
+    pub first_broken_id: Option<i64>,
+    pub error: Option<String>,
+}
+
+/// A row as stored, with everything its hash covers
+#[derive(Debug, Clone)]
+struct ChainedRow {
+    id: i64,
+    tenant_id: i64,
+    merchant_id: Option<i64>,
+    action_type: String,
+    actor_type: Option<String>,
+    actor_id: Option<String>,
+    actor_role: Option<String>,
+    entity_type: Option<String>,
+    entity_id: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    request_id: Option<String>,
+    details: Option<JsonValue>,
+    created_at: DateTime<Utc>,
+    prev_hash: Option<String>,
+    hash: Option<String>,
+}
+
+impl ChainedRow {
+    /// Hash over the previous hash and the canonical JSON of the row
+    ///
+    /// serde_json orders object keys, so the same content always gives the
+    /// same text; timestamps use microseconds, the precision Postgres keeps.
+    fn compute_hash(&self, prev_hash: &str) -> String {
+        let content = json!({
+            "id": self.id,
+            "tenant_id": self.tenant_id,
+            "merchant_id": self.merchant_id,
+            "action_type": self.action_type,
+            "actor_type": self.actor_type,
+            "actor_id": self.actor_id,
+            "actor_role": self.actor_role,
+            "entity_type": self.entity_type,
+            "entity_id": self.entity_id,
+            "ip_address": self.ip_address,
+            "user_agent": self.user_agent,
+            "request_id": self.request_id,
+            "details": self.details,
+            "created_at": self.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        });
+
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(content.to_string().as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+/// Walks a chain in id order and reports the first inconsistency
+struct ChainVerifier {
+    expected_prev: String,
+    verified_rows: u64,
+    legacy_rows: u64,
+    last_id: Option<i64>,
+    broken: Option<(i64, String)>,
+}
+
+impl ChainVerifier {
+    fn new() -> Self {
+        Self {
+            expected_prev: GENESIS_HASH.to_string(),
+            verified_rows: 0,
+            legacy_rows: 0,
+            last_id: None,
+            broken: None,
+        }
+    }
+
+    fn push(&mut self, row: &ChainedRow) {
+        if self.broken.is_some() {
+            return;
+        }
+
+        let (prev_hash, hash) = match (&row.prev_hash, &row.hash) {
+            (Some(prev_hash), Some(hash)) => (prev_hash, hash),
+            // Unchained rows are only expected before the chain starts
+            _ if self.verified_rows == 0 => {
+                self.legacy_rows += 1;
+                return;
+            }
+            _ => {
+                self.broken = Some((row.id, "Row is missing its chain hash".to_string()));
+                return;
+            }
+        };
+
+        if *prev_hash != self.expected_prev {
+            self.broken = Some((row.id, "Previous hash does not match; a row was removed or reordered".to_string()));
+        } else if row.compute_hash(prev_hash) != *hash {
+            self.broken = Some((row.id, "Row content does not match its hash".to_string()));
+        } else {
+            self.expected_prev = hash.clone();
+            self.verified_rows += 1;
+            self.last_id = Some(row.id);
+        }
+    }
+
+    /// Compare the end of the chain with the recorded head
+    fn finish(self, tenant_id: i64, head: Option<(Option<i64>, Option<String>)>) -> ChainVerification {
+        let mut broken = self.broken;
+
+        if broken.is_none() {
+            if let Some((head_id, head_hash)) = head {
+                let head_hash = head_hash.unwrap_or_else(|| GENESIS_HASH.to_string());
+                if head_id != self.last_id || head_hash != self.expected_prev {
+                    broken = Some((
+                        head_id.or(self.last_id).unwrap_or_default(),
+                        "Chain ends before its recorded head; rows were removed".to_string(),
+                    ));
+                }
+            }
+        }
+
+        ChainVerification {
+            tenant_id,
+            intact: broken.is_none(),
+            verified_rows: self.verified_rows,
+            legacy_rows: self.legacy_rows,
+            last_id: self.last_id,
+            first_broken_id: broken.as_ref().map(|(id, _)| *id),
+            error: broken.map(|(_, error)| error),
+        }
+    }
+}
+
+/// Append-only, hash-chained audit log
+///
+/// Every row stores the hash of the previous row of its tenant (the
+/// merchant, or [`PLATFORM_TENANT`]), so editing, removing or reordering
+/// rows breaks the chain; [`AuditService::verify_chain`] detects it. The
+/// table also rejects updates and deletes.
 pub struct AuditService {
     pool: PgPool,
 }
@@ -38,27 +352,195 @@ impl AuditService {
         ip_address: Option<&str>,
         details: Option<JsonValue>,
     ) -> Result<(), ServiceError> {
+        let mut entry = AuditEntry::new(action_type)
+            .with_merchant(merchant_id)
+            .with_ip(ip_address);
+        entry.details = details;
+
+        self.record(&entry).await?;
+        Ok(())
+    }
+
+    /// Append an entry to its tenant's chain
+    ///
+    /// # Returns
+    /// Id of the new row
+    pub async fn record(&self, entry: &AuditEntry) -> Result<i64, ServiceError> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::record_in_tx(&mut tx, entry).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Append an entry as part of a larger transaction
+    ///
+    /// The tenant's chain stays locked until the transaction ends.
+    pub async fn record_in_tx(tx: &mut Transaction<'_, Postgres>, entry: &AuditEntry) -> Result<i64, ServiceError> {
+        let tenant_id = entry.merchant_id.unwrap_or(PLATFORM_TENANT);
+
         sqlx::query!(
-            "INSERT INTO audit_logs (merchant_id, action_type, ip_address, details) VALUES ($1, $2, $3, $4)",
-            merchant_id,
-            action_type,
-            ip_address,
-            details
+            "INSERT INTO audit_chain_heads (tenant_id) VALUES ($1) ON CONFLICT (tenant_id) DO NOTHING",
+            tenant_id
         )
-        .execute(&self.pool)
+        .execute(&mut **tx)
         .await?;
 
-        Ok(())
+        let head = sqlx::query!(
+            "SELECT last_hash FROM audit_chain_heads WHERE tenant_id = $1 FOR UPDATE",
+            tenant_id
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+        let prev_hash = head.last_hash.unwrap_or_else(|| GENESIS_HASH.to_string());
+
+        let id = sqlx::query_scalar!("SELECT nextval('audit_logs_id_seq')")
+            .fetch_one(&mut **tx)
+            .await?
+            .ok_or_else(|| ServiceError::InternalError("Audit log sequence returned no id".to_string()))?;
+
+        let now = Utc::now();
+        let row = ChainedRow {
+            id,
+            tenant_id,
+            merchant_id: entry.merchant_id,
+            action_type: entry.action_type.clone(),
+            actor_type: Some(entry.actor_type.clone()),
+            actor_id: entry.actor_id.clone(),
+            actor_role: entry.actor_role.clone(),
+            entity_type: entry.entity_type.clone(),
+            entity_id: entry.entity_id.clone(),
+            ip_address: entry.ip_address.clone(),
+            user_agent: entry.user_agent.clone(),
+            request_id: entry.request_id.clone(),
+            details: entry.details.clone(),
+            created_at: DateTime::from_timestamp_micros(now.timestamp_micros()).unwrap_or(now),
+            prev_hash: Some(prev_hash.clone()),
+            hash: None,
+        };
+        let hash = row.compute_hash(&prev_hash);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (
+                id, tenant_id, merchant_id, action_type, actor_type, actor_id, actor_role,
+                entity_type, entity_id, ip_address, user_agent, request_id, details,
+                created_at, prev_hash, hash
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            "#,
+            row.id,
+            row.tenant_id,
+            row.merchant_id,
+            row.action_type,
+            row.actor_type,
+            row.actor_id,
+            row.actor_role,
+            row.entity_type,
+            row.entity_id,
+            row.ip_address,
+            row.user_agent,
+            row.request_id,
+            row.details,
+            row.created_at,
+            prev_hash,
+            hash
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE audit_chain_heads SET last_id = $1, last_hash = $2, updated_at = NOW() WHERE tenant_id = $3",
+            id,
+            hash,
+            tenant_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Recompute a tenant's chain from its first row
+    pub async fn verify_chain(&self, tenant_id: i64) -> Result<ChainVerification, ServiceError> {
+        let mut verifier = ChainVerifier::new();
+        let mut after_id = 0i64;
+
+        loop {
+            let rows = sqlx::query!(
+                r#"
+                SELECT id, tenant_id, merchant_id, action_type, actor_type, actor_id, actor_role,
+                       entity_type, entity_id, ip_address, user_agent, request_id, details,
+                       created_at, prev_hash, hash
+                FROM audit_logs
+                WHERE tenant_id = $1 AND id > $2
+                ORDER BY id
+                LIMIT $3
+                "#,
+                tenant_id,
+                after_id,
+                VERIFY_PAGE_SIZE
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            let Some(last) = rows.last() else { break };
+            after_id = last.id;
+
+            for r in rows {
+                verifier.push(&ChainedRow {
+                    id: r.id,
+                    tenant_id: r.tenant_id,
+                    merchant_id: r.merchant_id,
+                    action_type: r.action_type,
+                    actor_type: r.actor_type,
+                    actor_id: r.actor_id,
+                    actor_role: r.actor_role,
+                    entity_type: r.entity_type,
+                    entity_id: r.entity_id,
+                    ip_address: r.ip_address,
+                    user_agent: r.user_agent,
+                    request_id: r.request_id,
+                    details: r.details,
+                    created_at: r.created_at,
+                    prev_hash: r.prev_hash,
+                    hash: r.hash,
+                });
+            }
+        }
+
+        let head = sqlx::query!(
+            "SELECT last_id, last_hash FROM audit_chain_heads WHERE tenant_id = $1",
+            tenant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .map(|h| (h.last_id, h.last_hash));
+
+        Ok(verifier.finish(tenant_id, head))
+    }
+
+    /// Verify the chain of every tenant that has one
+    pub async fn verify_all(&self) -> Result<Vec<ChainVerification>, ServiceError> {
+        let tenants = sqlx::query_scalar!("SELECT tenant_id FROM audit_chain_heads ORDER BY tenant_id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut results = Vec::with_capacity(tenants.len());
+        for tenant_id in tenants {
+            results.push(self.verify_chain(tenant_id).await?);
+        }
+        Ok(results)
     }
 
     pub async fn get_logs(&self, merchant_id: i64, query: AuditLogQuery) -> Result<Vec<AuditLog>, ServiceError> {
         let limit = query.limit.unwrap_or(100).min(1000);
-        
+
         let logs = if let Some(action_type) = query.action_type {
             sqlx::query_as!(
                 AuditLog,
                 r#"
-                SELECT id, merchant_id, action_type, ip_address, details, created_at
+                SELECT id, merchant_id, action_type, actor_type, actor_id, actor_role, entity_type, entity_id,
+                       ip_address, request_id, details, hash, created_at
                 FROM audit_logs
                 WHERE merchant_id = $1
                   AND action_type = $2
@@ -79,7 +561,8 @@ impl AuditService {
             sqlx::query_as!(
                 AuditLog,
                 r#"
-                SELECT id, merchant_id, action_type, ip_address, details, created_at
+                SELECT id, merchant_id, action_type, actor_type, actor_id, actor_role, entity_type, entity_id,
+                       ip_address, request_id, details, hash, created_at
                 FROM audit_logs
                 WHERE merchant_id = $1
                   AND ($2::timestamptz IS NULL OR created_at >= $2)
@@ -99,3 +582,108 @@ impl AuditService {
         Ok(logs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(ids: std::ops::RangeInclusive<i64>) -> Vec<ChainedRow> {
+        let mut prev = GENESIS_HASH.to_string();
+        ids
+            .map(|id| {
+                let mut row = ChainedRow {
+                    id,
+                    tenant_id: 7,
+                    merchant_id: Some(7),
+                    action_type: "POST /api/v1/merchant/withdrawals".to_string(),
+                    actor_type: Some("merchant".to_string()),
+                    actor_id: Some("7".to_string()),
+                    actor_role: Some("merchant".to_string()),
+                    entity_type: None,
+                    entity_id: None,
+                    ip_address: Some("203.0.113.5".to_string()),
+                    user_agent: None,
+                    request_id: Some(format!("req-{}", id)),
+                    details: Some(json!({ "status": 201, "amount": "1.5" })),
+                    created_at: DateTime::from_timestamp_micros(1_700_000_000_000_000 + id).unwrap(),
+                    prev_hash: Some(prev.clone()),
+                    hash: None,
+                };
+                let hash = row.compute_hash(&prev);
+                row.hash = Some(hash.clone());
+                prev = hash;
+                row
+            })
+            .collect()
+    }
+
+    fn verify(rows: &[ChainedRow], head: Option<(Option<i64>, Option<String>)>) -> ChainVerification {
+        let mut verifier = ChainVerifier::new();
+        rows.iter().for_each(|row| verifier.push(row));
+        verifier.finish(7, head)
+    }
+
+    fn head_of(rows: &[ChainedRow]) -> Option<(Option<i64>, Option<String>)> {
+        rows.last().map(|row| (Some(row.id), row.hash.clone()))
+    }
+
+    #[test]
+    fn test_intact_chain_verifies() {
+        let rows = chain(1..=5);
+        let result = verify(&rows, head_of(&rows));
+        assert!(result.intact);
+        assert_eq!(result.verified_rows, 5);
+        assert_eq!(result.last_id, Some(5));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let rows = chain(1..=5);
+        let head = head_of(&rows);
+
+        let mut edited = rows.clone();
+        edited[2].details = Some(json!({ "status": 201, "amount": "150" }));
+        assert_eq!(verify(&edited, head.clone()).first_broken_id, Some(3));
+
+        let mut removed = rows.clone();
+        removed.remove(1);
+        assert_eq!(verify(&removed, head.clone()).first_broken_id, Some(3));
+
+        // Dropping the newest rows leaves a valid prefix that no longer reaches the head
+        let truncated = verify(&rows[..3], head);
+        assert!(!truncated.intact);
+    }
+
+    #[test]
+    fn test_legacy_rows_only_before_chain() {
+        let mut legacy = chain(1..=1).remove(0);
+        legacy.prev_hash = None;
+        legacy.hash = None;
+
+        let mut rows = vec![legacy.clone()];
+        rows.extend(chain(2..=3));
+        let result = verify(&rows, head_of(&rows));
+        assert!(result.intact);
+        assert_eq!(result.legacy_rows, 1);
+        assert_eq!(result.verified_rows, 2);
+
+        // An unchained row after the chain started was written around it
+        let head = head_of(&rows);
+        legacy.id = 4;
+        rows.push(legacy);
+        assert_eq!(verify(&rows, head).first_broken_id, Some(4));
+    }
+
+    #[test]
+    fn test_diff_lists_changed_fields() {
+        let changes = diff(
+            &json!({ "status": "PENDING", "amount": "10" }),
+            &json!({ "status": "CONFIRMED", "amount": "10", "reason": "manual" }),
+        );
+        assert_eq!(changes, json!({
+            "status": { "before": "PENDING", "after": "CONFIRMED" },
+            "reason": { "before": null, "after": "manual" }
+        }));
+        assert_eq!(diff(&json!("a"), &json!("a")), json!({}));
+    }
+}
@@ -1,4 +1,6 @@
 use crate::error::ServiceError;
+use crate::services::aml_service::AmlService;
+use crate::services::audit_service::{AuditEntry, AuditService};
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
@@ -28,9 +30,23 @@ impl BalanceMonitoringService {
         Ok(vec![])
     }
 
-    pub async fn check_large_withdrawals(&self, _hours: i32) -> Result<Vec<BalanceAlert>, ServiceError> {
-        // Simplified - return empty for now
-        Ok(vec![])
+    /// Withdrawals of the last `hours` at or above the AML large transfer threshold
+    pub async fn check_large_withdrawals(&self, hours: i32) -> Result<Vec<BalanceAlert>, ServiceError> {
+        let aml = AmlService::new(self.db_pool.clone());
+        let threshold = aml.large_transfer_threshold().await?;
+        let withdrawals = aml.large_withdrawals(hours).await?;
+
+        Ok(withdrawals
+            .into_iter()
+            .map(|w| BalanceAlert {
+                merchant_id: w.merchant_id,
+                alert_type: "large_withdrawal".to_string(),
+                crypto_type: w.crypto_type,
+                current_balance: w.amount_usd,
+                threshold,
+                created_at: w.at,
+            })
+            .collect())
     }
 
     pub async fn send_balance_alert(&self, alert: BalanceAlert) -> Result<(), ServiceError> {
@@ -42,14 +58,10 @@ impl BalanceMonitoringService {
             "threshold": alert.threshold
         });
 
-        sqlx::query!(
-            "INSERT INTO audit_logs (merchant_id, action_type, details) VALUES ($1, $2, $3)",
-            alert.merchant_id,
-            "BALANCE_ALERT",
-            details
-        )
-        .execute(&self.db_pool)
-        .await?;
+        let entry = AuditEntry::new("BALANCE_ALERT")
+            .with_merchant(alert.merchant_id)
+            .with_details(details);
+        AuditService::new(self.db_pool.clone()).record(&entry).await?;
 
         Ok(())
     }
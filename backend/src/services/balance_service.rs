@@ -2,11 +2,12 @@
 
 use crate::error::ServiceError;
 use crate::payment::models::CryptoType;
+use crate::services::outbox;
 use crate::services::price_service::PriceService;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -121,6 +122,8 @@ impl BalanceService {
         // Ensure balance record exists
         self.initialize_balance(merchant_id, crypto_type).await?;
 
+        let mut tx = self.db_pool.begin().await?;
+
         match balance_type {
             "available" => {
                 sqlx::query!(
@@ -135,7 +138,7 @@ impl BalanceService {
                     merchant_id,
                     crypto_type as CryptoType
                 )
-                .execute(&self.db_pool)
+                .execute(&mut *tx)
                 .await?;
             }
             "pending" => {
@@ -152,7 +155,7 @@ impl BalanceService {
                     merchant_id,
                     crypto_type as CryptoType
                 )
-                .execute(&self.db_pool)
+                .execute(&mut *tx)
                 .await?;
             }
             "total" => {
@@ -166,7 +169,7 @@ impl BalanceService {
                     merchant_id,
                     crypto_type as CryptoType
                 )
-                .execute(&self.db_pool)
+                .execute(&mut *tx)
                 .await?;
             }
             _ => {
@@ -176,6 +179,9 @@ impl BalanceService {
             }
         }
 
+        Self::enqueue_balance_updated(&mut tx, merchant_id, crypto_type).await?;
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -185,6 +191,8 @@ impl BalanceService {
         crypto_type: CryptoType,
         amount: Decimal,
     ) -> Result<(), ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
         sqlx::query!(
             r#"
             UPDATE merchant_balances 
@@ -198,9 +206,43 @@ impl BalanceService {
             merchant_id,
             crypto_type as CryptoType
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
+        .await?;
+
+        Self::enqueue_balance_updated(&mut tx, merchant_id, crypto_type).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Queue a balance.updated event with the balance row as seen by `tx`
+    async fn enqueue_balance_updated(
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+        crypto_type: CryptoType,
+    ) -> Result<(), ServiceError> {
+        let snapshot = sqlx::query_scalar!(
+            r#"
+            SELECT to_jsonb(mb) - 'id' AS "snapshot!"
+            FROM merchant_balances mb
+            WHERE merchant_id = $1 AND crypto_type = $2
+            "#,
+            merchant_id,
+            crypto_type as CryptoType
+        )
+        .fetch_optional(&mut **tx)
         .await?;
 
+        if let Some(snapshot) = snapshot {
+            outbox::enqueue_event(
+                tx,
+                merchant_id,
+                "balance.updated",
+                &crypto_type.to_string(),
+                serde_json::json!({ "object": snapshot }),
+            ).await?;
+        }
+
         Ok(())
     }
 
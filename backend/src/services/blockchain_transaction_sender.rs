@@ -3,43 +3,48 @@
 
 use crate::error::ServiceError;
 use crate::payment::models::CryptoType;
+use crate::services::signer::{EvmTransaction, Signer};
 use rust_decimal::Decimal;
 use rust_decimal::prelude::ToPrimitive;
+use std::sync::Arc;
 use web3::{
     transports::Http,
-    types::{Address, TransactionParameters, U256},
-    signing::Key,
+    types::{Address, U256},
     Web3,
 };
 
 pub struct BlockchainTransactionSender {
     config: crate::config::Config,
+    signer: Arc<dyn Signer>,
 }
 
 impl BlockchainTransactionSender {
-    pub fn new(config: crate::config::Config) -> Self {
-        Self { config }
+    pub fn new(config: crate::config::Config, signer: Arc<dyn Signer>) -> Self {
+        Self { config, signer }
     }
 
     /// Send native currency transaction
+    ///
+    /// # Arguments
+    /// * `from_address` - Deposit address whose key the signer uses
     pub async fn send_native_transaction(
         &self,
         crypto_type: CryptoType,
-        private_key: &str,
+        from_address: &str,
         to_address: &str,
         amount: Decimal,
         gas_price: Option<U256>,
     ) -> Result<String, ServiceError> {
         match crypto_type {
-            CryptoType::Sol => self.send_solana_transaction_placeholder(private_key, to_address, amount).await,
-            _ => self.send_evm_transaction(crypto_type, private_key, to_address, amount, gas_price).await,
+            CryptoType::Sol => self.send_solana_transaction_placeholder(from_address, to_address, amount).await,
+            _ => self.send_evm_transaction(crypto_type, from_address, to_address, amount, gas_price).await,
         }
     }
 
     /// Send Solana transaction (placeholder - requires solana-sdk)
     async fn send_solana_transaction_placeholder(
         &self,
-        _private_key: &str,
+        _from_address: &str,
         _to_address: &str,
         amount: Decimal,
     ) -> Result<String, ServiceError> {
@@ -53,7 +58,7 @@ impl BlockchainTransactionSender {
     async fn send_evm_transaction(
         &self,
         crypto_type: CryptoType,
-        private_key: &str,
+        from_address: &str,
         to_address: &str,
         amount: Decimal,
         gas_price: Option<U256>,
@@ -71,19 +76,9 @@ impl BlockchainTransactionSender {
             .map_err(|e| ServiceError::Internal(format!("Failed to create transport: {}", e)))?;
         let web3 = Web3::new(transport);
 
-        // Parse private key
-        let private_key = private_key.strip_prefix("0x").unwrap_or(private_key);
-        let key_bytes = hex::decode(private_key)
-            .map_err(|_| ServiceError::ValidationError("Invalid private key hex".to_string()))?;
-        
-        // Use web3's SecretKey type directly
-        let secret_key_bytes: [u8; 32] = key_bytes.try_into()
-            .map_err(|_| ServiceError::ValidationError("Invalid key length".to_string()))?;
-        let secret_key = web3::signing::SecretKey::from_slice(&secret_key_bytes)
-            .map_err(|_| ServiceError::ValidationError("Invalid private key".to_string()))?;
-
-        // Get sender address from secret key
-        let from_address = (&secret_key).address();
+        // Parse sender address
+        let from: Address = from_address.parse()
+            .map_err(|_| ServiceError::ValidationError("Invalid sender address".to_string()))?;
 
         // Parse destination address
         let to_address: Address = to_address.parse()
@@ -96,7 +91,7 @@ impl BlockchainTransactionSender {
 
         // Get nonce
         let nonce = web3.eth()
-            .transaction_count(from_address, None)
+            .transaction_count(from, None)
             .await
             .map_err(|e| ServiceError::Internal(format!("Failed to get nonce: {}", e)))?;
 
@@ -109,21 +104,21 @@ impl BlockchainTransactionSender {
                 .map_err(|e| ServiceError::Internal(format!("Failed to get gas price: {}", e)))?,
         };
 
-        // Create transaction parameters
-        let tx_params = TransactionParameters {
-            nonce: Some(nonce),
-            to: Some(to_address),
+        let chain_id = web3.eth()
+            .chain_id()
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Failed to get chain id: {}", e)))?;
+
+        let transaction = EvmTransaction {
+            chain_id: chain_id.as_u64(),
+            nonce,
+            to: to_address,
             value: U256::from(wei_amount),
-            gas_price: Some(gas_price),
+            gas_price,
             gas: U256::from(21000), // Standard gas limit for ETH transfer
-            data: web3::types::Bytes::default(),
-            ..Default::default()
         };
 
-        let signed_tx = web3.accounts()
-            .sign_transaction(tx_params, &secret_key)
-            .await
-            .map_err(|e| ServiceError::Internal(format!("Failed to sign transaction: {}", e)))?;
+        let signed_tx = self.signer.sign_evm_transaction(from_address, &transaction).await?;
 
         let tx_hash = web3.eth()
             .send_raw_transaction(signed_tx.raw_transaction)
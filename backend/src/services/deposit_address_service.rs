@@ -89,19 +89,6 @@ impl DepositAddressService {
         })
     }
 
-    pub async fn get_private_key(&self, payment_id: &str) -> Result<String, ServiceError> {
-        let record = sqlx::query!(
-            "SELECT private_key_encrypted FROM deposit_addresses WHERE payment_id = $1",
-            payment_id
-        )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| ServiceError::NotFound("Deposit address not found".to_string()))?;
-
-        self.encryption.decrypt(&record.private_key_encrypted)
-            .map_err(|e| ServiceError::InternalError(format!("Decryption failed: {}", e)))
-    }
-
     pub async fn mark_as_used(&self, payment_id: &str, forward_tx_hash: &str) -> Result<(), ServiceError> {
         sqlx::query!(
             "UPDATE deposit_addresses SET status = 'USED', forwarded_at = NOW(), forward_tx_hash = $2
@@ -1,15 +1,49 @@
 use serde::Serialize;
 use crate::error::ServiceError;
-use tracing::{info, warn, error};
-use lettre::{Message, SmtpTransport, Transport};
+use tracing::{info, error};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::message::header::ContentType;
+use lettre::message::MultiPart;
 
+/// A rendered email, ready to queue or send
+///
+/// Built by the templates in [`crate::services::email_templates`].
 #[derive(Debug, Serialize)]
 pub struct EmailTemplate {
     pub to: String,
     pub subject: String,
+    /// Plain-text body
     pub body: String,
+    /// HTML alternative of `body`
+    pub html_body: Option<String>,
+    /// Template the email was rendered from
+    pub template: Option<String>,
+    pub locale: Option<String>,
+}
+
+/// How the SMTP connection is secured (`SMTP_TLS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// TLS from the first byte, usually port 465
+    Tls,
+    /// Upgrade with STARTTLS, usually port 587
+    StartTls,
+    /// Plain connection without authentication, for a local sink such as Mailpit
+    None,
+}
+
+impl SmtpTls {
+    /// Mode from `SMTP_TLS` ("tls", "starttls", "none"); unset picks by port
+    pub fn from_setting(setting: Option<&str>, port: Option<u16>) -> Self {
+        match setting.map(|s| s.trim().to_lowercase()).as_deref() {
+            Some("tls") => SmtpTls::Tls,
+            Some("none") => SmtpTls::None,
+            Some("starttls") => SmtpTls::StartTls,
+            _ if port == Some(465) => SmtpTls::Tls,
+            _ => SmtpTls::StartTls,
+        }
+    }
 }
 
 pub struct EmailService {
@@ -19,6 +53,7 @@ pub struct EmailService {
     smtp_port: Option<u16>,
     smtp_username: Option<String>,
     smtp_password: Option<String>,
+    smtp_tls: SmtpTls,
 }
 
 impl EmailService {
@@ -33,6 +68,7 @@ impl EmailService {
         Self {
             enabled,
             from_email,
+            smtp_tls: SmtpTls::from_setting(None, smtp_port),
             smtp_host,
             smtp_port,
             smtp_username,
@@ -40,110 +76,79 @@ impl EmailService {
         }
     }
 
-    pub async fn send_payment_confirmed(&self, to: &str, payment_id: &str, amount: &str, crypto: &str) -> Result<(), ServiceError> {
-        if !self.enabled {
-            info!(" Email disabled - would send payment confirmation to {}", to);
-            return Ok(());
-        }
-
-        let subject = format!("Payment Confirmed - {}", payment_id);
-        let body = format!(
-            "Your payment has been confirmed!\n\n\
-             Payment ID: {}\n\
-             Amount: {} {}\n\n\
-             Thank you for your payment.",
-            payment_id, amount, crypto
-        );
-
-        self.send_email(to, &subject, &body).await
-    }
-
-    pub async fn send_withdrawal_completed(&self, to: &str, withdrawal_id: &str, amount: &str, crypto: &str) -> Result<(), ServiceError> {
-        if !self.enabled {
-            info!(" Email disabled - would send withdrawal notification to {}", to);
-            return Ok(());
-        }
-
-        let subject = format!("Withdrawal Completed - {}", withdrawal_id);
-        let body = format!(
-            "Your withdrawal has been completed!\n\n\
-             Withdrawal ID: {}\n\
-             Amount: {} {}\n\n\
-             Funds have been sent to your wallet.",
-            withdrawal_id, amount, crypto
-        );
-
-        self.send_email(to, &subject, &body).await
+    /// Secure the SMTP connection differently
+    pub fn with_smtp_tls(mut self, smtp_tls: SmtpTls) -> Self {
+        self.smtp_tls = smtp_tls;
+        self
     }
 
-    pub async fn send_invoice(&self, to: &str, invoice_id: &str, total: &str, due_date: &str) -> Result<(), ServiceError> {
-        if !self.enabled {
-            info!(" Email disabled - would send invoice to {}", to);
-            return Ok(());
-        }
-
-        let subject = format!("Invoice {} - Due {}", invoice_id, due_date);
-        let body = format!(
-            "You have received an invoice.\n\n\
-             Invoice ID: {}\n\
-             Total: ${}\n\
-             Due Date: {}\n\n\
-             Please pay at your earliest convenience.",
-            invoice_id, total, due_date
-        );
-
-        self.send_email(to, &subject, &body).await
+    /// Build from `EMAIL_ENABLED` and the SMTP settings in the environment
+    pub fn from_env() -> Self {
+        let flags = crate::feature_flags::FeatureFlags::from_env();
+        let config = crate::feature_flags::EmailConfig::from_env();
+        let smtp_tls = SmtpTls::from_setting(config.smtp_tls.as_deref(), config.smtp_port);
+
+        Self::new(
+            flags.email_enabled,
+            config.from,
+            config.smtp_host,
+            config.smtp_port,
+            config.smtp_username,
+            config.smtp_password,
+        )
+        .with_smtp_tls(smtp_tls)
     }
 
-    pub async fn send_2fa_enabled(&self, to: &str) -> Result<(), ServiceError> {
+    /// Send a rendered email, such as one taken from the outbox
+    pub async fn send_template(&self, template: &EmailTemplate) -> Result<(), ServiceError> {
         if !self.enabled {
-            info!(" Email disabled - would send 2FA notification to {}", to);
+            info!(" Email disabled - would send \"{}\" to {}", template.subject, template.to);
             return Ok(());
         }
 
-        let subject = "Two-Factor Authentication Enabled";
-        let body = "Two-factor authentication has been enabled on your account.\n\n\
-                    If you did not make this change, please contact support immediately.";
-
-        self.send_email(to, subject, body).await
+        self.send_email(template).await
     }
 
-    async fn send_email(&self, to: &str, subject: &str, body: &str) -> Result<(), ServiceError> {
-        if !self.enabled {
-            return Ok(());
-        }
-
-        // Check if SMTP is configured
+    async fn send_email(&self, template: &EmailTemplate) -> Result<(), ServiceError> {
         let smtp_host = self.smtp_host.as_ref()
             .ok_or_else(|| ServiceError::InternalError("SMTP host not configured".to_string()))?;
-        let smtp_username = self.smtp_username.as_ref()
-            .ok_or_else(|| ServiceError::InternalError("SMTP username not configured".to_string()))?;
-        let smtp_password = self.smtp_password.as_ref()
-            .ok_or_else(|| ServiceError::InternalError("SMTP password not configured".to_string()))?;
 
-        // Build email
-        let email = Message::builder()
+        // Build email, with an HTML alternative when the template has one
+        let builder = Message::builder()
             .from(self.from_email.parse()
                 .map_err(|e| ServiceError::InternalError(format!("Invalid from email: {}", e)))?)
-            .to(to.parse()
+            .to(template.to.parse()
                 .map_err(|e| ServiceError::InternalError(format!("Invalid to email: {}", e)))?)
-            .subject(subject)
-            .header(ContentType::TEXT_PLAIN)
-            .body(body.to_string())
-            .map_err(|e| ServiceError::InternalError(format!("Email build failed: {}", e)))?;
+            .subject(&template.subject);
+        let email = match &template.html_body {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(template.body.clone(), html.clone())),
+            None => builder.header(ContentType::TEXT_PLAIN).body(template.body.clone()),
+        }
+        .map_err(|e| ServiceError::InternalError(format!("Email build failed: {}", e)))?;
 
         // Create SMTP transport
-        let creds = Credentials::new(smtp_username.clone(), smtp_password.clone());
-        
-        let mailer = SmtpTransport::relay(smtp_host)
-            .map_err(|e| ServiceError::InternalError(format!("SMTP relay failed: {}", e)))?
-            .credentials(creds)
-            .build();
+        let mut transport = match self.smtp_tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(smtp_host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(smtp_host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(smtp_host)),
+        }
+        .map_err(|e| ServiceError::InternalError(format!("SMTP relay failed: {}", e)))?;
+        if let Some(port) = self.smtp_port {
+            transport = transport.port(port);
+        }
+        if self.smtp_tls != SmtpTls::None {
+            let smtp_username = self.smtp_username.as_ref()
+                .ok_or_else(|| ServiceError::InternalError("SMTP username not configured".to_string()))?;
+            let smtp_password = self.smtp_password.as_ref()
+                .ok_or_else(|| ServiceError::InternalError("SMTP password not configured".to_string()))?;
+            transport = transport.credentials(Credentials::new(smtp_username.clone(), smtp_password.clone()));
+        }
+        let mailer = transport.build();
 
         // Send email
-        match mailer.send(&email) {
+        match mailer.send(email).await {
             Ok(_) => {
-                info!(" Email sent: {} -> {} | {}", self.from_email, to, subject);
+                info!(" Email sent: {} -> {} | {}", self.from_email, template.to, template.subject);
                 Ok(())
             }
             Err(e) => {
@@ -153,3 +158,17 @@ impl EmailService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smtp_tls_setting() {
+        assert_eq!(SmtpTls::from_setting(Some("none"), Some(1025)), SmtpTls::None);
+        assert_eq!(SmtpTls::from_setting(Some("STARTTLS"), Some(465)), SmtpTls::StartTls);
+        assert_eq!(SmtpTls::from_setting(None, Some(465)), SmtpTls::Tls);
+        assert_eq!(SmtpTls::from_setting(None, Some(587)), SmtpTls::StartTls);
+        assert_eq!(SmtpTls::from_setting(Some(""), None), SmtpTls::StartTls);
+    }
+}
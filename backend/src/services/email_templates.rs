@@ -0,0 +1,464 @@
+// Email Templates
+// Localized HTML and plain-text emails rendered with askama from templates/emails
+
+use askama::Template;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ServiceError;
+use crate::services::email_service::EmailTemplate;
+use crate::services::notification_service::NotificationCategory;
+
+/// Language an email is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// Supported locale for a language tag such as "es" or "es-MX"
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_']).next().unwrap_or_default().to_lowercase();
+        Self::ALL.into_iter().find(|l| l.as_str() == language)
+    }
+
+    /// Like [`Locale::parse`], falling back to English
+    pub fn from_string(tag: &str) -> Self {
+        Self::parse(tag).unwrap_or_default()
+    }
+}
+
+/// Translated strings of one locale, used by the templates as `t.get("key")`
+#[derive(Debug, Clone, Copy)]
+pub struct Catalog {
+    locale: Locale,
+}
+
+impl Catalog {
+    pub fn new(locale: Locale) -> Self {
+        Self { locale }
+    }
+
+    /// The `lang` attribute of rendered HTML
+    pub fn lang(&self) -> &'static str {
+        self.locale.as_str()
+    }
+
+    /// Translation of `key`, falling back to English and then to the key itself
+    pub fn get<'a>(&self, key: &'a str) -> &'a str {
+        let strings = match self.locale {
+            Locale::En => EN,
+            Locale::Es => ES,
+        };
+        lookup(strings, key)
+            .or_else(|| lookup(EN, key))
+            .unwrap_or(key)
+    }
+}
+
+fn lookup(strings: &'static [(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    strings.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+}
+
+const EN: &[(&str, &str)] = &[
+    ("brand", "FidduPay"),
+    ("footer", "You are receiving this email because of activity on your FidduPay merchant account."),
+    ("footer.preferences", "Choose which notifications you receive under notification preferences in your dashboard."),
+    ("unknown", "unknown"),
+    ("link_expires", "This link expires at"),
+    ("label.amount", "Amount"),
+    ("label.available_at", "Key available from"),
+    ("label.business", "Business"),
+    ("label.changed_at", "Changed at"),
+    ("label.enabled_at", "Enabled at"),
+    ("label.endpoint_id", "Endpoint ID"),
+    ("label.export_id", "Export ID"),
+    ("label.failing_since", "Failing since"),
+    ("label.invited_by", "Invited by"),
+    ("label.invoice_id", "Invoice ID"),
+    ("label.ip", "IP address"),
+    ("label.locked_until", "Locked until"),
+    ("label.payment_id", "Payment ID"),
+    ("label.role", "Role"),
+    ("label.total", "Total"),
+    ("label.url", "URL"),
+    ("label.wallet", "Wallet"),
+    ("label.withdrawal_id", "Withdrawal ID"),
+    ("verify_email.subject", "Verify your email address"),
+    ("verify_email.intro", "Thanks for signing up. Confirm your email address to continue setting up your merchant account."),
+    ("verify_email.action", "Verify email address"),
+    ("verify_email.ignore", "If you did not create an account, you can ignore this email."),
+    ("password_reset.subject", "Reset your password"),
+    ("password_reset.intro", "We received a request to reset the password of your merchant account."),
+    ("password_reset.action", "Reset password"),
+    ("password_reset.ignore", "If you did not request a password reset, ignore this email. Your password will not change."),
+    ("password_changed.subject", "Your password was changed"),
+    ("password_changed.intro", "The password of your merchant account was just changed."),
+    ("password_changed.warning", "If you did not make this change, contact support immediately and rotate your API keys."),
+    ("account_locked.subject", "Account Temporarily Locked"),
+    ("account_locked.intro", "Sign-in to your account has been locked after repeated failed login attempts."),
+    ("account_locked.advice", "If these attempts were not yours, change your password once the lock expires and enable two-factor authentication. Contact support to have the lock lifted early."),
+    ("key_export_requested.subject", "Private Key Export Requested"),
+    ("key_export_requested.intro", "An export of the private key of one of your wallets was requested."),
+    ("key_export_requested.advice", "The key will only be released after this cooling-off period. If you did not request this export, cancel it from the dashboard immediately, change your password and rotate your API keys."),
+    ("webhook_endpoint_disabled.subject", "Webhook Endpoint Disabled"),
+    ("webhook_endpoint_disabled.intro", "One of your webhook endpoints has been disabled because deliveries to it have been failing."),
+    ("webhook_endpoint_disabled.advice", "Pending deliveries are paused and resume when the endpoint is re-enabled. Deliveries that exhausted their retries are in the dead-letter queue and can be redelivered from the dashboard."),
+    ("payment_confirmed.subject", "Payment Confirmed"),
+    ("payment_confirmed.intro", "A payment to your account has been confirmed."),
+    ("withdrawal_completed.subject", "Withdrawal Completed"),
+    ("withdrawal_completed.intro", "Your withdrawal has been completed and the funds have been sent to your wallet."),
+    ("invoice_paid.subject", "Invoice Paid"),
+    ("invoice_paid.intro", "One of your invoices has been paid."),
+    ("two_factor_enabled.subject", "Two-Factor Authentication Enabled"),
+    ("two_factor_enabled.intro", "Two-factor authentication has been enabled on your account."),
+    ("two_factor_enabled.warning", "If you did not make this change, please contact support immediately."),
+    ("team_invitation.subject", "You have been invited to join"),
+    ("team_invitation.intro", "You have been invited to join a merchant team on FidduPay. Accept the invitation to choose your password and sign in."),
+    ("team_invitation.action", "Accept invitation"),
+    ("team_invitation.ignore", "If you were not expecting this invitation, you can ignore this email."),
+];
+
+const ES: &[(&str, &str)] = &[
+    ("footer", "Recibes este correo por la actividad de tu cuenta de comercio en FidduPay."),
+    ("footer.preferences", "Elige qué notificaciones recibes en las preferencias de notificación de tu panel."),
+    ("unknown", "desconocida"),
+    ("link_expires", "Este enlace caduca el"),
+    ("label.amount", "Importe"),
+    ("label.available_at", "Clave disponible desde"),
+    ("label.business", "Empresa"),
+    ("label.changed_at", "Fecha del cambio"),
+    ("label.enabled_at", "Fecha de activación"),
+    ("label.endpoint_id", "ID del endpoint"),
+    ("label.export_id", "ID de exportación"),
+    ("label.failing_since", "Con fallos desde"),
+    ("label.invited_by", "Invitación de"),
+    ("label.invoice_id", "ID de factura"),
+    ("label.ip", "Dirección IP"),
+    ("label.locked_until", "Bloqueada hasta"),
+    ("label.payment_id", "ID de pago"),
+    ("label.role", "Rol"),
+    ("label.total", "Total"),
+    ("label.url", "URL"),
+    ("label.wallet", "Monedero"),
+    ("label.withdrawal_id", "ID de retiro"),
+    ("verify_email.subject", "Verifica tu dirección de correo"),
+    ("verify_email.intro", "Gracias por registrarte. Confirma tu dirección de correo para seguir configurando tu cuenta de comercio."),
+    ("verify_email.action", "Verificar correo"),
+    ("verify_email.ignore", "Si no has creado una cuenta, puedes ignorar este correo."),
+    ("password_reset.subject", "Restablece tu contraseña"),
+    ("password_reset.intro", "Hemos recibido una solicitud para restablecer la contraseña de tu cuenta de comercio."),
+    ("password_reset.action", "Restablecer contraseña"),
+    ("password_reset.ignore", "Si no has solicitado el cambio, ignora este correo. Tu contraseña no cambiará."),
+    ("password_changed.subject", "Tu contraseña ha cambiado"),
+    ("password_changed.intro", "Se acaba de cambiar la contraseña de tu cuenta de comercio."),
+    ("password_changed.warning", "Si no has hecho este cambio, contacta con soporte de inmediato y rota tus claves de API."),
+    ("account_locked.subject", "Cuenta bloqueada temporalmente"),
+    ("account_locked.intro", "El inicio de sesión en tu cuenta se ha bloqueado tras varios intentos fallidos."),
+    ("account_locked.advice", "Si estos intentos no fueron tuyos, cambia tu contraseña cuando termine el bloqueo y activa la autenticación en dos pasos. Contacta con soporte para levantar el bloqueo antes."),
+    ("key_export_requested.subject", "Solicitud de exportación de clave privada"),
+    ("key_export_requested.intro", "Se ha solicitado exportar la clave privada de uno de tus monederos."),
+    ("key_export_requested.advice", "La clave solo se entregará tras este periodo de espera. Si no has solicitado la exportación, cancélala desde el panel de inmediato, cambia tu contraseña y rota tus claves de API."),
+    ("webhook_endpoint_disabled.subject", "Endpoint de webhook desactivado"),
+    ("webhook_endpoint_disabled.intro", "Uno de tus endpoints de webhook se ha desactivado porque las entregas están fallando."),
+    ("webhook_endpoint_disabled.advice", "Las entregas pendientes quedan en pausa y se reanudan al reactivar el endpoint. Las que agotaron sus reintentos están en la cola de mensajes fallidos y pueden reenviarse desde el panel."),
+    ("payment_confirmed.subject", "Pago confirmado"),
+    ("payment_confirmed.intro", "Se ha confirmado un pago a tu cuenta."),
+    ("withdrawal_completed.subject", "Retiro completado"),
+    ("withdrawal_completed.intro", "Tu retiro se ha completado y los fondos se han enviado a tu monedero."),
+    ("invoice_paid.subject", "Factura pagada"),
+    ("invoice_paid.intro", "Se ha pagado una de tus facturas."),
+    ("two_factor_enabled.subject", "Autenticación en dos pasos activada"),
+    ("two_factor_enabled.intro", "Se ha activado la autenticación en dos pasos en tu cuenta."),
+    ("two_factor_enabled.warning", "Si no has hecho este cambio, contacta con soporte de inmediato."),
+    ("team_invitation.subject", "Te han invitado a unirte a"),
+    ("team_invitation.intro", "Te han invitado a unirte a un equipo de comercio en FidduPay. Acepta la invitación para elegir tu contraseña e iniciar sesión."),
+    ("team_invitation.action", "Aceptar invitación"),
+    ("team_invitation.ignore", "Si no esperabas esta invitación, puedes ignorar este correo."),
+];
+
+/// An email that can be rendered in any supported locale
+pub trait EmailMessage: Send + Sync {
+    /// Template name, stored with the queued email
+    fn template(&self) -> &'static str;
+
+    /// Which preference decides whether merchant users receive it
+    fn category(&self) -> NotificationCategory;
+
+    /// Subject, HTML and plain-text body for `to`
+    fn render(&self, to: &str, locale: Locale) -> Result<EmailTemplate, ServiceError>;
+}
+
+/// Implement [`EmailMessage`] with `emails/<name>.html` and `emails/<name>.txt`
+///
+/// The templates see the message as `m` and the catalog as `t`; the message
+/// type provides `fn subject(&self, t: Catalog) -> String`.
+macro_rules! email_message {
+    ($message:ident, $name:literal, $category:expr, $html:tt, $text:tt) => {
+        impl EmailMessage for $message {
+            fn template(&self) -> &'static str {
+                $name
+            }
+
+            fn category(&self) -> NotificationCategory {
+                $category
+            }
+
+            fn render(&self, to: &str, locale: Locale) -> Result<EmailTemplate, ServiceError> {
+                #[derive(Template)]
+                #[template(path = $html)]
+                struct Html<'a> {
+                    t: Catalog,
+                    m: &'a $message,
+                }
+
+                #[derive(Template)]
+                #[template(path = $text)]
+                struct Text<'a> {
+                    t: Catalog,
+                    m: &'a $message,
+                }
+
+                let t = Catalog::new(locale);
+                let html = Html { t, m: self }.render().map_err(render_error)?;
+                let text = Text { t, m: self }.render().map_err(render_error)?;
+
+                Ok(EmailTemplate {
+                    to: to.to_string(),
+                    subject: self.subject(t),
+                    body: text,
+                    html_body: Some(html),
+                    template: Some($name.to_string()),
+                    locale: Some(locale.as_str().to_string()),
+                })
+            }
+        }
+    };
+}
+
+fn render_error(e: askama::Error) -> ServiceError {
+    ServiceError::Internal(format!("Email template rendering failed: {}", e))
+}
+
+/// Link that confirms a merchant's email address
+#[derive(Debug, Clone)]
+pub struct VerifyEmail {
+    pub link: String,
+    pub expires_at: String,
+}
+
+impl VerifyEmail {
+    fn subject(&self, t: Catalog) -> String {
+        t.get("verify_email.subject").to_string()
+    }
+}
+
+email_message!(VerifyEmail, "verify_email", NotificationCategory::Security, "emails/verify_email.html", "emails/verify_email.txt");
+
+/// Link that sets a new password
+#[derive(Debug, Clone)]
+pub struct PasswordReset {
+    pub link: String,
+    pub expires_at: String,
+    pub ip: Option<String>,
+}
+
+impl PasswordReset {
+    fn subject(&self, t: Catalog) -> String {
+        t.get("password_reset.subject").to_string()
+    }
+}
+
+email_message!(PasswordReset, "password_reset", NotificationCategory::Security, "emails/password_reset.html", "emails/password_reset.txt");
+
+#[derive(Debug, Clone)]
+pub struct PasswordChanged {
+    pub changed_at: String,
+    pub ip: Option<String>,
+}
+
+impl PasswordChanged {
+    fn subject(&self, t: Catalog) -> String {
+        t.get("password_changed.subject").to_string()
+    }
+}
+
+email_message!(PasswordChanged, "password_changed", NotificationCategory::Security, "emails/password_changed.html", "emails/password_changed.txt");
+
+#[derive(Debug, Clone)]
+pub struct AccountLocked {
+    pub locked_until: String,
+    pub ip: Option<String>,
+}
+
+impl AccountLocked {
+    fn subject(&self, t: Catalog) -> String {
+        t.get("account_locked.subject").to_string()
+    }
+}
+
+email_message!(AccountLocked, "account_locked", NotificationCategory::Security, "emails/account_locked.html", "emails/account_locked.txt");
+
+#[derive(Debug, Clone)]
+pub struct KeyExportRequested {
+    pub crypto_type: String,
+    pub export_id: String,
+    pub available_at: String,
+    pub ip: Option<String>,
+}
+
+impl KeyExportRequested {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} - {}", t.get("key_export_requested.subject"), self.crypto_type)
+    }
+}
+
+email_message!(KeyExportRequested, "key_export_requested", NotificationCategory::Security, "emails/key_export_requested.html", "emails/key_export_requested.txt");
+
+#[derive(Debug, Clone)]
+pub struct WebhookEndpointDisabled {
+    pub endpoint_id: String,
+    pub url: String,
+    pub failing_since: String,
+}
+
+impl WebhookEndpointDisabled {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} - {}", t.get("webhook_endpoint_disabled.subject"), self.endpoint_id)
+    }
+}
+
+email_message!(WebhookEndpointDisabled, "webhook_endpoint_disabled", NotificationCategory::Webhooks, "emails/webhook_endpoint_disabled.html", "emails/webhook_endpoint_disabled.txt");
+
+#[derive(Debug, Clone)]
+pub struct PaymentConfirmed {
+    pub payment_id: String,
+    pub amount: String,
+    pub crypto_type: String,
+}
+
+impl PaymentConfirmed {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} - {}", t.get("payment_confirmed.subject"), self.payment_id)
+    }
+}
+
+email_message!(PaymentConfirmed, "payment_confirmed", NotificationCategory::Payments, "emails/payment_confirmed.html", "emails/payment_confirmed.txt");
+
+#[derive(Debug, Clone)]
+pub struct WithdrawalCompleted {
+    pub withdrawal_id: String,
+    pub amount: String,
+    pub crypto_type: String,
+}
+
+impl WithdrawalCompleted {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} - {}", t.get("withdrawal_completed.subject"), self.withdrawal_id)
+    }
+}
+
+email_message!(WithdrawalCompleted, "withdrawal_completed", NotificationCategory::Withdrawals, "emails/withdrawal_completed.html", "emails/withdrawal_completed.txt");
+
+#[derive(Debug, Clone)]
+pub struct InvoicePaid {
+    pub invoice_id: String,
+    pub total: String,
+    pub currency: String,
+    pub payment_id: String,
+}
+
+impl InvoicePaid {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} - {}", t.get("invoice_paid.subject"), self.invoice_id)
+    }
+}
+
+email_message!(InvoicePaid, "invoice_paid", NotificationCategory::Invoices, "emails/invoice_paid.html", "emails/invoice_paid.txt");
+
+#[derive(Debug, Clone)]
+pub struct TwoFactorEnabled {
+    pub enabled_at: String,
+}
+
+impl TwoFactorEnabled {
+    fn subject(&self, t: Catalog) -> String {
+        t.get("two_factor_enabled.subject").to_string()
+    }
+}
+
+email_message!(TwoFactorEnabled, "two_factor_enabled", NotificationCategory::Security, "emails/two_factor_enabled.html", "emails/two_factor_enabled.txt");
+
+/// Link that lets an invited team member join a merchant
+#[derive(Debug, Clone)]
+pub struct TeamInvitation {
+    pub business_name: String,
+    pub role: String,
+    pub invited_by: Option<String>,
+    pub link: String,
+    pub expires_at: String,
+}
+
+impl TeamInvitation {
+    fn subject(&self, t: Catalog) -> String {
+        format!("{} {}", t.get("team_invitation.subject"), self.business_name)
+    }
+}
+
+email_message!(TeamInvitation, "team_invitation", NotificationCategory::Security, "emails/team_invitation.html", "emails/team_invitation.txt");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_parse_and_catalog_fallback() {
+        assert_eq!(Locale::parse("es-MX"), Some(Locale::Es));
+        assert_eq!(Locale::parse("EN"), Some(Locale::En));
+        assert_eq!(Locale::parse("de"), None);
+        assert_eq!(Locale::from_string("de"), Locale::En);
+
+        let es = Catalog::new(Locale::Es);
+        assert_eq!(es.get("label.amount"), "Importe");
+        // Missing translations fall back to English, then to the key
+        assert_eq!(es.get("brand"), "FidduPay");
+        assert_eq!(es.get("no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_every_english_key_is_translated() {
+        for (key, _) in EN.iter().filter(|(k, _)| *k != "brand") {
+            assert!(lookup(ES, key).is_some(), "missing es translation for {}", key);
+        }
+    }
+
+    #[test]
+    fn test_render_escapes_html_only() {
+        let message = WebhookEndpointDisabled {
+            endpoint_id: "we_1".to_string(),
+            url: "https://example.com/hook?a=1&b=<2>".to_string(),
+            failing_since: "2026-10-18T00:00:00Z".to_string(),
+        };
+
+        let email = message.render("ops@example.com", Locale::Es).unwrap();
+        assert_eq!(email.subject, "Endpoint de webhook desactivado - we_1");
+        assert_eq!(email.template.as_deref(), Some("webhook_endpoint_disabled"));
+        assert_eq!(email.locale.as_deref(), Some("es"));
+        assert!(email.body.contains("https://example.com/hook?a=1&b=<2>"));
+        let html = email.html_body.unwrap();
+        assert!(html.contains("lang=\"es\""));
+        assert!(html.contains("&lt;2&gt;"));
+        assert!(!html.contains("<2>"));
+    }
+}
@@ -0,0 +1,378 @@
+// Email Token Service
+// Signed, expiring links for email verification and password reset
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::info;
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::services::email_templates::{EmailMessage, PasswordChanged, PasswordReset, VerifyEmail};
+use crate::services::merchant_service::MerchantService;
+use crate::services::onboarding_service::{OnboardingService, OnboardingStatus};
+use crate::services::{notification_service, outbox};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Shortest password accepted on reset, as on registration
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// What a token may be used for
+///
+/// Team invitations and sessions are signed the same way but kept out of
+/// `email_tokens`; see [`crate::services::multi_user_service`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    TeamInvite,
+    TeamSession,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+            TokenPurpose::TeamInvite => "team_invite",
+            TokenPurpose::TeamSession => "team_session",
+        }
+    }
+}
+
+/// Issues and redeems the tokens behind verify-email and password-reset links
+///
+/// A token is `{token_id}.{expires}.{signature}`, signed with HMAC-SHA256 so
+/// forged or altered links are refused before the database is consulted.
+/// Each token is stored once and can be redeemed once; issuing a new token
+/// for the same purpose supersedes the old ones.
+pub struct EmailTokenService {
+    db_pool: PgPool,
+    signing_key: String,
+    dashboard_base_url: String,
+    verification_ttl: Duration,
+    reset_ttl: Duration,
+}
+
+impl EmailTokenService {
+    pub fn new(db_pool: PgPool, signing_key: &str, dashboard_base_url: &str) -> Self {
+        Self {
+            db_pool,
+            signing_key: signing_key.to_string(),
+            dashboard_base_url: dashboard_base_url.trim_end_matches('/').to_string(),
+            verification_ttl: Duration::hours(48),
+            reset_ttl: Duration::minutes(60),
+        }
+    }
+
+    /// Service signing with `jwt_secret`, linking to `dashboard_base_url`
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self::new(db_pool, &config.jwt_secret, &config.dashboard_base_url)
+            .with_ttls(
+                Duration::hours(config.email_verification_ttl_hours.max(1) as i64),
+                Duration::minutes(config.password_reset_ttl_minutes.max(1) as i64),
+            )
+    }
+
+    /// How long verification and reset links stay valid
+    pub fn with_ttls(mut self, verification_ttl: Duration, reset_ttl: Duration) -> Self {
+        self.verification_ttl = verification_ttl;
+        self.reset_ttl = reset_ttl;
+        self
+    }
+
+    /// Email a verification link to a merchant who has not verified yet
+    pub async fn send_verification(&self, merchant_id: i64) -> Result<(), ServiceError> {
+        let merchant = sqlx::query!(
+            "SELECT email, onboarding_status FROM merchants WHERE id = $1 AND is_active = true",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+
+        if merchant.onboarding_status != "registered" {
+            return Err(ServiceError::InvalidStateTransition(
+                "Email address is already verified".to_string(),
+            ));
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        let (token, expires_at) =
+            self.issue(&mut tx, merchant_id, &merchant.email, TokenPurpose::VerifyEmail).await?;
+        let message = VerifyEmail {
+            link: format!("{}/verify-email?token={}", self.dashboard_base_url, token),
+            expires_at: format_time(expires_at),
+        };
+        self.queue(&mut tx, merchant_id, &merchant.email, &message).await?;
+        tx.commit().await?;
+
+        info!("Queued email verification for merchant {}", merchant_id);
+        Ok(())
+    }
+
+    /// Redeem a verification token and move the merchant's onboarding on
+    ///
+    /// Both happen in one transaction, so a failure leaves the token unused.
+    pub async fn verify_email(&self, token: &str) -> Result<OnboardingStatus, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+        let merchant_id = self.redeem(&mut tx, token, TokenPurpose::VerifyEmail).await?;
+        let status = OnboardingService::mark_email_verified_in_tx(&mut tx, merchant_id).await?;
+        tx.commit().await?;
+
+        info!("Merchant {} verified their email address", merchant_id);
+        Ok(status)
+    }
+
+    /// Email a reset link if `email` belongs to an active merchant
+    ///
+    /// Unknown addresses are ignored without an error so the endpoint does not
+    /// reveal which addresses have accounts.
+    pub async fn request_password_reset(&self, email: &str, ip: Option<&str>) -> Result<(), ServiceError> {
+        let merchant = sqlx::query!(
+            "SELECT id, email FROM merchants WHERE LOWER(email) = LOWER($1) AND is_active = true",
+            email.trim()
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        let Some(merchant) = merchant else {
+            info!("Password reset requested for unknown email");
+            return Ok(());
+        };
+
+        let mut tx = self.db_pool.begin().await?;
+        let (token, expires_at) =
+            self.issue(&mut tx, merchant.id, &merchant.email, TokenPurpose::ResetPassword).await?;
+        let message = PasswordReset {
+            link: format!("{}/reset-password?token={}", self.dashboard_base_url, token),
+            expires_at: format_time(expires_at),
+            ip: ip.map(str::to_string),
+        };
+        self.queue(&mut tx, merchant.id, &merchant.email, &message).await?;
+        tx.commit().await?;
+
+        info!("Queued password reset for merchant {}", merchant.id);
+        Ok(())
+    }
+
+    /// Redeem a reset token and set the new password
+    ///
+    /// The owner is told about the change. Other outstanding reset links stop
+    /// working.
+    pub async fn reset_password(&self, token: &str, new_password: &str, ip: Option<&str>) -> Result<(), ServiceError> {
+        if new_password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(ServiceError::ValidationError(format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            )));
+        }
+        let password_hash = MerchantService::hash_password(new_password)?;
+
+        let mut tx = self.db_pool.begin().await?;
+        let merchant_id = self.redeem(&mut tx, token, TokenPurpose::ResetPassword).await?;
+
+        sqlx::query!(
+            "UPDATE merchants SET password_hash = $1, updated_at = NOW() WHERE id = $2",
+            password_hash,
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        supersede(&mut tx, merchant_id, TokenPurpose::ResetPassword).await?;
+
+        let notice = PasswordChanged {
+            changed_at: format_time(Utc::now()),
+            ip: ip.map(str::to_string),
+        };
+        notification_service::enqueue_notification(&mut tx, merchant_id, &notice).await?;
+        tx.commit().await?;
+
+        info!("Merchant {} reset their password", merchant_id);
+        Ok(())
+    }
+
+    /// Store a new token, superseding earlier ones for the same purpose
+    async fn issue(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+        email: &str,
+        purpose: TokenPurpose,
+    ) -> Result<(String, DateTime<Utc>), ServiceError> {
+        let ttl = match purpose {
+            TokenPurpose::VerifyEmail => self.verification_ttl,
+            TokenPurpose::ResetPassword => self.reset_ttl,
+            TokenPurpose::TeamInvite | TokenPurpose::TeamSession => {
+                return Err(ServiceError::Internal(format!("{} tokens are not email tokens", purpose.as_str())))
+            }
+        };
+        // Whole seconds, as carried in the token
+        let expires_at = Utc
+            .timestamp_opt((Utc::now() + ttl).timestamp(), 0)
+            .single()
+            .ok_or_else(|| ServiceError::Internal("Token expiry out of range".to_string()))?;
+        let token_id = nanoid::nanoid!(32);
+
+        supersede(tx, merchant_id, purpose).await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO email_tokens (token_id, merchant_id, purpose, email, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            token_id,
+            merchant_id,
+            purpose.as_str(),
+            email,
+            expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok((sign_token(&self.signing_key, purpose, &token_id, expires_at), expires_at))
+    }
+
+    /// Mark a token used and return its merchant
+    ///
+    /// Tokens sent to an address the merchant has since changed are refused.
+    async fn redeem(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token: &str,
+        purpose: TokenPurpose,
+    ) -> Result<i64, ServiceError> {
+        let token_id = verify_token(&self.signing_key, purpose, token, Utc::now())?;
+
+        sqlx::query_scalar!(
+            r#"
+            UPDATE email_tokens t
+            SET used_at = NOW()
+            FROM merchants m
+            WHERE t.token_id = $1
+              AND t.purpose = $2
+              AND t.used_at IS NULL
+              AND t.expires_at > NOW()
+              AND m.id = t.merchant_id
+              AND m.is_active = true
+              AND LOWER(m.email) = LOWER(t.email)
+            RETURNING t.merchant_id
+            "#,
+            token_id,
+            purpose.as_str()
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| ServiceError::InvalidToken("This link is invalid, expired or already used".to_string()))
+    }
+
+    /// Queue a token email in the owner's locale
+    async fn queue(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+        email: &str,
+        message: &dyn EmailMessage,
+    ) -> Result<(), ServiceError> {
+        let locale = notification_service::owner_locale(&mut **tx, merchant_id).await?;
+        outbox::enqueue_email(&mut **tx, Some(merchant_id), &message.render(email, locale)?).await
+    }
+}
+
+/// Retire a merchant's unused tokens for `purpose`
+async fn supersede(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    purpose: TokenPurpose,
+) -> Result<(), ServiceError> {
+    sqlx::query!(
+        "UPDATE email_tokens SET used_at = NOW() WHERE merchant_id = $1 AND purpose = $2 AND used_at IS NULL",
+        merchant_id,
+        purpose.as_str()
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+pub(crate) fn format_time(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+fn signature(key: &str, purpose: TokenPurpose, token_id: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}.{}.{}", purpose.as_str(), token_id, expires).as_bytes());
+    mac
+}
+
+/// `{token_id}.{expires}.{signature}` for a link
+pub(crate) fn sign_token(key: &str, purpose: TokenPurpose, token_id: &str, expires_at: DateTime<Utc>) -> String {
+    let expires = expires_at.timestamp();
+    let mac = signature(key, purpose, token_id, expires);
+    format!("{}.{}.{}", token_id, expires, hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Check a token's signature and expiry, returning its ID
+pub(crate) fn verify_token(key: &str, purpose: TokenPurpose, token: &str, now: DateTime<Utc>) -> Result<String, ServiceError> {
+    let invalid = || ServiceError::InvalidToken("This link is invalid, expired or already used".to_string());
+
+    let mut parts = token.trim().split('.');
+    let (Some(token_id), Some(expires), Some(sig), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let expires: i64 = expires.parse().map_err(|_| invalid())?;
+    let sig = hex::decode(sig).map_err(|_| invalid())?;
+
+    signature(key, purpose, token_id, expires)
+        .verify_slice(&sig)
+        .map_err(|_| invalid())?;
+    if expires <= now.timestamp() {
+        return Err(invalid());
+    }
+
+    Ok(token_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "test_jwt_secret";
+
+    #[test]
+    fn test_token_round_trip() {
+        let now = Utc::now();
+        let token = sign_token(KEY, TokenPurpose::VerifyEmail, "abc123", now + Duration::hours(1));
+
+        assert_eq!(verify_token(KEY, TokenPurpose::VerifyEmail, &token, now).unwrap(), "abc123");
+    }
+
+    #[test]
+    fn test_token_rejects_tampering_and_other_purposes() {
+        let now = Utc::now();
+        let token = sign_token(KEY, TokenPurpose::ResetPassword, "abc123", now + Duration::hours(1));
+
+        assert!(verify_token(KEY, TokenPurpose::VerifyEmail, &token, now).is_err());
+        assert!(verify_token("other_key", TokenPurpose::ResetPassword, &token, now).is_err());
+
+        let forged = token.replacen("abc123", "abc124", 1);
+        assert!(verify_token(KEY, TokenPurpose::ResetPassword, &forged, now).is_err());
+
+        let (head, _) = token.rsplit_once('.').unwrap();
+        let extended = format!("{}.{}", head.replace(&(now + Duration::hours(1)).timestamp().to_string(), "9999999999"), "00");
+        assert!(verify_token(KEY, TokenPurpose::ResetPassword, &extended, now).is_err());
+        assert!(verify_token(KEY, TokenPurpose::ResetPassword, "not-a-token", now).is_err());
+    }
+
+    #[test]
+    fn test_token_expires() {
+        let now = Utc::now();
+        let token = sign_token(KEY, TokenPurpose::VerifyEmail, "abc123", now + Duration::minutes(5));
+
+        assert!(verify_token(KEY, TokenPurpose::VerifyEmail, &token, now + Duration::minutes(6)).is_err());
+    }
+}
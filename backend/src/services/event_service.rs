@@ -0,0 +1,226 @@
+// Event Service
+// Durable log of domain events (payment.*, refund.*, withdrawal.*, invoice.*, balance.*, security.*)
+
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::error::ServiceError;
+
+/// API version stamped on every event snapshot
+pub const API_VERSION: &str = "2024-01-01";
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// A recorded domain event
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    #[serde(rename = "id")]
+    pub event_id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub api_version: String,
+    pub object_type: String,
+    pub object_id: String,
+    pub data: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters for listing events
+#[derive(Debug, Default, Deserialize)]
+pub struct EventQuery {
+    /// Comma-separated event types; `payment.*` matches every payment event
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+    /// Return events older than this event id
+    pub starting_after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One page of events, newest first
+#[derive(Debug, Serialize)]
+pub struct EventList {
+    pub data: Vec<Event>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// Object type of an event, taken from the prefix of its type
+///
+/// `"payment.confirmed"` → `"payment"`
+pub fn object_type_of(event_type: &str) -> &str {
+    event_type.split('.').next().unwrap_or(event_type)
+}
+
+/// Record an event using any executor (pool or open transaction)
+///
+/// # Arguments
+/// * `executor` - Pool or transaction to write with
+/// * `merchant_id` - Merchant the event belongs to
+/// * `event_type` - Event type (e.g., "payment.confirmed")
+/// * `object_id` - Public ID of the object the event is about
+/// * `data` - Snapshot of the object
+pub async fn record_event<'e, E: PgExecutor<'e>>(
+    executor: E,
+    merchant_id: i64,
+    event_type: &str,
+    object_id: &str,
+    data: serde_json::Value,
+) -> Result<Event, ServiceError> {
+    let event_id = format!("evt_{}", nanoid!(24));
+
+    let event = sqlx::query_as!(
+        Event,
+        r#"
+        INSERT INTO events (event_id, merchant_id, event_type, api_version, object_type, object_id, data)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING event_id, event_type, api_version, object_type, object_id, data, created_at
+        "#,
+        event_id,
+        merchant_id,
+        event_type,
+        API_VERSION,
+        object_type_of(event_type),
+        object_id,
+        data
+    )
+    .fetch_one(executor)
+    .await?;
+
+    Ok(event)
+}
+
+/// Convert a type filter into SQL LIKE patterns
+///
+/// `payment.*` becomes `payment.%`; literal `_` and `%` are escaped.
+fn type_patterns(types: &str) -> Vec<String> {
+    types
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            let escaped = t.replace('\\', "\\\\").replace('_', "\\_").replace('%', "\\%");
+            match escaped.strip_suffix(".*") {
+                Some(prefix) => format!("{}.%", prefix),
+                None => escaped,
+            }
+        })
+        .collect()
+}
+
+pub struct EventService {
+    db_pool: PgPool,
+}
+
+impl EventService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// List a merchant's events, newest first, with cursor pagination
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `query` - Type filters, cursor and page size
+    pub async fn list_events(
+        &self,
+        merchant_id: i64,
+        query: EventQuery,
+    ) -> Result<EventList, ServiceError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        // Resolve the cursor to its position in the log
+        let cursor = match &query.starting_after {
+            Some(event_id) => Some(
+                sqlx::query_scalar!(
+                    "SELECT id FROM events WHERE event_id = $1 AND merchant_id = $2",
+                    event_id,
+                    merchant_id
+                )
+                .fetch_optional(&self.db_pool)
+                .await?
+                .ok_or_else(|| ServiceError::ValidationError("Invalid starting_after cursor".to_string()))?,
+            ),
+            None => None,
+        };
+
+        let patterns = query.types.as_deref().map(type_patterns).filter(|p| !p.is_empty());
+
+        let mut events = sqlx::query_as!(
+            Event,
+            r#"
+            SELECT event_id, event_type, api_version, object_type, object_id, data, created_at
+            FROM events
+            WHERE merchant_id = $1
+              AND ($2::bigint IS NULL OR id < $2)
+              AND ($3::text[] IS NULL OR event_type LIKE ANY($3))
+            ORDER BY id DESC
+            LIMIT $4
+            "#,
+            merchant_id,
+            cursor,
+            patterns.as_deref(),
+            limit + 1
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let has_more = events.len() as i64 > limit;
+        events.truncate(limit as usize);
+        let next_cursor = if has_more {
+            events.last().map(|e| e.event_id.clone())
+        } else {
+            None
+        };
+
+        Ok(EventList {
+            data: events,
+            has_more,
+            next_cursor,
+        })
+    }
+
+    /// Get a single event by its public ID
+    pub async fn get_event(&self, merchant_id: i64, event_id: &str) -> Result<Event, ServiceError> {
+        sqlx::query_as!(
+            Event,
+            r#"
+            SELECT event_id, event_type, api_version, object_type, object_id, data, created_at
+            FROM events
+            WHERE event_id = $1 AND merchant_id = $2
+            "#,
+            event_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Event not found".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_type_of() {
+        assert_eq!(object_type_of("payment.confirmed"), "payment");
+        assert_eq!(object_type_of("security.alert"), "security");
+        assert_eq!(object_type_of("balance"), "balance");
+    }
+
+    #[test]
+    fn test_type_patterns_wildcard_and_escaping() {
+        assert_eq!(
+            type_patterns("payment.*, refund.completed"),
+            vec!["payment.%".to_string(), "refund.completed".to_string()]
+        );
+        assert_eq!(
+            type_patterns("payment.partially_refunded"),
+            vec!["payment.partially\\_refunded".to_string()]
+        );
+        assert!(type_patterns(" , ").is_empty());
+    }
+}
@@ -1,9 +1,11 @@
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc, NaiveDate};
 use nanoid::nanoid;
 use crate::error::ServiceError;
+use crate::services::email_templates::InvoicePaid;
+use crate::services::{notification_service, outbox};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InvoiceItem {
@@ -41,6 +43,42 @@ pub struct Invoice {
     pub paid_at: Option<DateTime<Utc>>,
 }
 
+/// Load an invoice using any executor (pool or open transaction)
+async fn fetch_invoice<'e, E: PgExecutor<'e>>(
+    executor: E,
+    merchant_id: i64,
+    invoice_id: &str,
+) -> Result<Invoice, ServiceError> {
+    let record = sqlx::query!(
+        r#"SELECT invoice_id, merchant_id, customer_email, customer_name, status, items, 
+                  subtotal, tax, total, payment_id, due_date, notes, created_at, paid_at
+           FROM invoices WHERE invoice_id = $1 AND merchant_id = $2"#,
+        invoice_id, merchant_id
+    )
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| ServiceError::NotFound("Invoice not found".to_string()))?;
+
+    let items: Vec<InvoiceItem> = serde_json::from_value(record.items)?;
+
+    Ok(Invoice {
+        invoice_id: record.invoice_id,
+        merchant_id: record.merchant_id,
+        customer_email: record.customer_email,
+        customer_name: record.customer_name,
+        status: record.status,
+        items,
+        subtotal: record.subtotal,
+        tax: record.tax,
+        total: record.total,
+        payment_id: record.payment_id,
+        due_date: record.due_date,
+        notes: record.notes,
+        created_at: record.created_at,
+        paid_at: record.paid_at,
+    })
+}
+
 pub struct InvoiceService {
     pool: PgPool,
 }
@@ -59,6 +97,8 @@ impl InvoiceService {
         let invoice_id = format!("inv_{}", nanoid!(12));
         let items_json = serde_json::to_value(&req.items)?;
 
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query!(
             r#"INSERT INTO invoices 
                (invoice_id, merchant_id, customer_email, customer_name, items, subtotal, tax, total, due_date, notes)
@@ -66,41 +106,26 @@ impl InvoiceService {
             invoice_id, merchant_id, req.customer_email, req.customer_name,
             items_json, subtotal, tax, total, req.due_date, req.notes
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
-        self.get_invoice(merchant_id, &invoice_id).await
+        let invoice = fetch_invoice(&mut *tx, merchant_id, &invoice_id).await?;
+
+        outbox::enqueue_event(
+            &mut tx,
+            merchant_id,
+            "invoice.created",
+            &invoice.invoice_id,
+            serde_json::json!({ "object": &invoice }),
+        ).await?;
+
+        tx.commit().await?;
+
+        Ok(invoice)
     }
 
     pub async fn get_invoice(&self, merchant_id: i64, invoice_id: &str) -> Result<Invoice, ServiceError> {
-        let record = sqlx::query!(
-            r#"SELECT invoice_id, merchant_id, customer_email, customer_name, status, items, 
-                      subtotal, tax, total, payment_id, due_date, notes, created_at, paid_at
-               FROM invoices WHERE invoice_id = $1 AND merchant_id = $2"#,
-            invoice_id, merchant_id
-        )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or_else(|| ServiceError::NotFound("Invoice not found".to_string()))?;
-
-        let items: Vec<InvoiceItem> = serde_json::from_value(record.items)?;
-
-        Ok(Invoice {
-            invoice_id: record.invoice_id,
-            merchant_id: record.merchant_id,
-            customer_email: record.customer_email,
-            customer_name: record.customer_name,
-            status: record.status,
-            items,
-            subtotal: record.subtotal,
-            tax: record.tax,
-            total: record.total,
-            payment_id: record.payment_id,
-            due_date: record.due_date,
-            notes: record.notes,
-            created_at: record.created_at,
-            paid_at: record.paid_at,
-        })
+        fetch_invoice(&self.pool, merchant_id, invoice_id).await
     }
 
     pub async fn list_invoices(&self, merchant_id: i64, limit: i64) -> Result<Vec<Invoice>, ServiceError> {
@@ -135,13 +160,37 @@ impl InvoiceService {
     }
 
     pub async fn mark_as_paid(&self, invoice_id: &str, payment_id: &str) -> Result<(), ServiceError> {
-        sqlx::query!(
-            "UPDATE invoices SET status = 'PAID', payment_id = $2, paid_at = NOW() WHERE invoice_id = $1",
+        let mut tx = self.pool.begin().await?;
+
+        let paid = sqlx::query!(
+            "UPDATE invoices SET status = 'PAID', payment_id = $2, paid_at = NOW() WHERE invoice_id = $1 RETURNING merchant_id, currency",
             invoice_id, payment_id
         )
-        .execute(&self.pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        if let Some(paid) = paid {
+            let merchant_id = paid.merchant_id;
+            let invoice = fetch_invoice(&mut *tx, merchant_id, invoice_id).await?;
+            outbox::enqueue_event(
+                &mut tx,
+                merchant_id,
+                "invoice.paid",
+                invoice_id,
+                serde_json::json!({ "object": &invoice }),
+            ).await?;
+
+            let notice = InvoicePaid {
+                invoice_id: invoice_id.to_string(),
+                total: invoice.total.to_string(),
+                currency: paid.currency,
+                payment_id: payment_id.to_string(),
+            };
+            notification_service::enqueue_notification(&mut tx, merchant_id, &notice).await?;
+        }
+
+        tx.commit().await?;
+
         Ok(())
     }
 }
@@ -1,7 +1,44 @@
 use sqlx::PgPool;
 use std::net::IpAddr;
 use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use crate::error::ServiceError;
+use crate::middleware::client_ip::{canonical_ip, parse_network};
+use crate::services::audit_service::{AuditEntry, AuditService};
+
+/// Maximum entries per allowlist (merchant-wide, or per API key)
+const MAX_ENTRIES: usize = 10;
+
+/// Which allowlist an update applies to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IpWhitelistScope {
+    /// Every API key of the merchant
+    #[default]
+    Merchant,
+    /// Only the API key making the request
+    ApiKey,
+}
+
+/// Merchant-wide and API key allowlists that apply to a request
+#[derive(Debug, Default)]
+pub struct IpAllowlists {
+    pub merchant: Vec<IpNetwork>,
+    pub api_key: Vec<IpNetwork>,
+}
+
+impl IpAllowlists {
+    /// An empty list allows every IP; a non-empty one must contain the client.
+    /// When both lists are set, the client must be in both.
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        let ip = ip.map(canonical_ip);
+        let matches = |list: &[IpNetwork]| {
+            list.is_empty() || ip.is_some_and(|ip| list.iter().any(|network| network.contains(ip)))
+        };
+        matches(&self.merchant) && matches(&self.api_key)
+    }
+}
 
 pub struct IpWhitelistService {
     pool: PgPool,
@@ -12,73 +49,119 @@ impl IpWhitelistService {
         Self { pool }
     }
 
-    pub async fn set_whitelist(&self, merchant_id: i64, ip_addresses: Vec<String>) -> Result<(), ServiceError> {
-        if ip_addresses.len() > 10 {
-            return Err(ServiceError::ValidationError("Maximum 10 IP addresses allowed".to_string()));
+    /// Replace the merchant-wide allowlist, or the one for `api_key`
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Merchant the allowlist belongs to
+    /// * `ip_addresses` - IPv4/IPv6 addresses or CIDR blocks; empty allows every IP
+    /// * `api_key` - Key to scope the list to, or `None` for every key
+    pub async fn set_whitelist(
+        &self,
+        merchant_id: i64,
+        ip_addresses: Vec<String>,
+        api_key: Option<&str>,
+    ) -> Result<(), ServiceError> {
+        if ip_addresses.len() > MAX_ENTRIES {
+            return Err(ServiceError::ValidationError(format!("Maximum {} IP addresses allowed", MAX_ENTRIES)));
         }
 
         // Validate all IPs
-        for ip in &ip_addresses {
-            self.validate_ip(ip)?;
-        }
+        let entries = ip_addresses
+            .iter()
+            .map(|ip| self.validate_ip(ip).map(|_| ip.trim().to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let api_key_hash = api_key.map(hash_api_key);
+
+        let mut tx = self.pool.begin().await?;
 
         // Delete existing whitelist
-        sqlx::query!("DELETE FROM ip_whitelist WHERE merchant_id = $1", merchant_id)
-            .execute(&self.pool)
-            .await?;
+        sqlx::query!(
+            "DELETE FROM ip_whitelist WHERE merchant_id = $1 AND api_key_hash IS NOT DISTINCT FROM $2",
+            merchant_id,
+            api_key_hash
+        )
+        .execute(&mut *tx)
+        .await?;
 
         // Insert new whitelist
-        for ip in ip_addresses {
+        for ip in entries {
             sqlx::query!(
-                "INSERT INTO ip_whitelist (merchant_id, ip_address) VALUES ($1, $2)",
+                "INSERT INTO ip_whitelist (merchant_id, ip_address, api_key_hash) VALUES ($1, $2, $3)",
                 merchant_id,
-                ip
+                ip,
+                api_key_hash
             )
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
         }
 
+        tx.commit().await?;
+
         Ok(())
     }
 
+    /// Merchant-wide allowlist
     pub async fn get_whitelist(&self, merchant_id: i64) -> Result<Vec<String>, ServiceError> {
-        let records = sqlx::query!("SELECT ip_address FROM ip_whitelist WHERE merchant_id = $1", merchant_id)
-            .fetch_all(&self.pool)
-            .await?;
+        let records = sqlx::query!(
+            "SELECT ip_address FROM ip_whitelist WHERE merchant_id = $1 AND api_key_hash IS NULL ORDER BY id",
+            merchant_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
         Ok(records.into_iter().map(|r| r.ip_address).collect())
     }
 
-    pub async fn is_ip_allowed(&self, merchant_id: i64, ip: &str) -> Result<bool, ServiceError> {
-        let whitelist = self.get_whitelist(merchant_id).await?;
-        
-        // Empty whitelist = allow all
-        if whitelist.is_empty() {
-            return Ok(true);
-        }
+    /// Allowlist scoped to one API key
+    pub async fn get_api_key_whitelist(&self, merchant_id: i64, api_key: &str) -> Result<Vec<String>, ServiceError> {
+        let records = sqlx::query!(
+            "SELECT ip_address FROM ip_whitelist WHERE merchant_id = $1 AND api_key_hash = $2 ORDER BY id",
+            merchant_id,
+            hash_api_key(api_key)
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        let client_ip: IpAddr = ip.parse()
-            .map_err(|_| ServiceError::ValidationError("Invalid IP address".to_string()))?;
+        Ok(records.into_iter().map(|r| r.ip_address).collect())
+    }
+
+    /// Active allowlists that apply to requests made with `api_key`
+    pub async fn allowlists(&self, merchant_id: i64, api_key: &str) -> Result<IpAllowlists, ServiceError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT ip_address, api_key_hash IS NOT NULL AS "key_scoped!"
+            FROM ip_whitelist
+            WHERE merchant_id = $1 AND is_active = true
+              AND (api_key_hash IS NULL OR api_key_hash = $2)
+            "#,
+            merchant_id,
+            hash_api_key(api_key)
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        for entry in whitelist {
-            if entry.contains('/') {
-                // CIDR range
-                let network: IpNetwork = entry.parse()
-                    .map_err(|_| ServiceError::ValidationError("Invalid CIDR range".to_string()))?;
-                if network.contains(client_ip) {
-                    return Ok(true);
-                }
+        let mut allowlists = IpAllowlists::default();
+        for record in records {
+            let Some(network) = parse_network(&record.ip_address) else {
+                tracing::warn!("Skipping invalid IP whitelist entry {} for merchant {}", record.ip_address, merchant_id);
+                continue;
+            };
+            if record.key_scoped {
+                allowlists.api_key.push(network);
             } else {
-                // Single IP
-                let allowed_ip: IpAddr = entry.parse()
-                    .map_err(|_| ServiceError::ValidationError("Invalid IP address".to_string()))?;
-                if client_ip == allowed_ip {
-                    return Ok(true);
-                }
+                allowlists.merchant.push(network);
             }
         }
 
-        Ok(false)
+        Ok(allowlists)
+    }
+
+    /// Whether a request from `ip` with `api_key` passes the merchant's allowlists
+    pub async fn is_ip_allowed(&self, merchant_id: i64, api_key: &str, ip: &str) -> Result<bool, ServiceError> {
+        let client_ip: IpAddr = ip.parse()
+            .map_err(|_| ServiceError::ValidationError("Invalid IP address".to_string()))?;
+
+        Ok(self.allowlists(merchant_id, api_key).await?.allows(Some(client_ip)))
     }
 
     pub async fn log_rejected_request(&self, merchant_id: i64, ip: &str, endpoint: &str) -> Result<(), ServiceError> {
@@ -86,29 +169,72 @@ impl IpWhitelistService {
             "endpoint": endpoint,
             "reason": "Non-whitelisted IP"
         });
-        sqlx::query!(
-            "INSERT INTO audit_logs (merchant_id, action_type, ip_address, details) VALUES ($1, $2, $3, $4)",
-            merchant_id,
-            "IP_REJECTED",
-            ip,
-            details
-        )
-        .execute(&self.pool)
-        .await?;
+        let entry = AuditEntry::new("IP_REJECTED")
+            .with_merchant(merchant_id)
+            .with_ip(Some(ip))
+            .with_details(details);
+        AuditService::new(self.pool.clone()).record(&entry).await?;
 
         Ok(())
     }
 
     fn validate_ip(&self, ip: &str) -> Result<(), ServiceError> {
-        if ip.contains('/') {
-            // CIDR range
-            ip.parse::<IpNetwork>()
-                .map_err(|_| ServiceError::ValidationError(format!("Invalid CIDR range: {}", ip)))?;
-        } else {
-            // Single IP
-            ip.parse::<IpAddr>()
-                .map_err(|_| ServiceError::ValidationError(format!("Invalid IP address: {}", ip)))?;
+        match parse_network(ip) {
+            Some(_) => Ok(()),
+            None if ip.contains('/') => Err(ServiceError::ValidationError(format!("Invalid CIDR range: {}", ip))),
+            None => Err(ServiceError::ValidationError(format!("Invalid IP address: {}", ip))),
         }
-        Ok(())
+    }
+}
+
+/// Same SHA-256 hex digest as `merchants.api_key_hash`
+fn hash_api_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks(entries: &[&str]) -> Vec<IpNetwork> {
+        entries.iter().map(|e| parse_network(e).unwrap()).collect()
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_cidr_blocks_match_ipv4_and_ipv6() {
+        let lists = IpAllowlists {
+            merchant: networks(&["203.0.113.0/24", "2001:db8::/32", "198.51.100.7"]),
+            api_key: Vec::new(),
+        };
+
+        assert!(lists.allows(ip("203.0.113.200")));
+        assert!(lists.allows(ip("2001:db8:1234::1")));
+        assert!(lists.allows(ip("::ffff:198.51.100.7")));
+        assert!(!lists.allows(ip("203.0.114.1")));
+        assert!(!lists.allows(ip("2001:db9::1")));
+        assert!(!lists.allows(None));
+    }
+
+    #[test]
+    fn test_api_key_list_narrows_merchant_list() {
+        let lists = IpAllowlists {
+            merchant: networks(&["10.0.0.0/8"]),
+            api_key: networks(&["10.1.0.0/16"]),
+        };
+
+        assert!(lists.allows(ip("10.1.2.3")));
+        assert!(!lists.allows(ip("10.2.0.1")));
+        assert!(IpAllowlists::default().allows(None));
+    }
+
+    #[test]
+    fn test_api_key_hash_matches_merchant_hash_format() {
+        let hash = hash_api_key("sk_test_key");
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, format!("{:x}", Sha256::digest(b"sk_test_key")));
     }
 }
@@ -0,0 +1,455 @@
+// Key Export Service
+// Delayed release of wallet private keys as passphrase-encrypted keystores
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::payment::models::CryptoType;
+use crate::services::account_lockout_service::{AccountLockoutService, AttemptKind};
+use crate::services::email_templates::KeyExportRequested;
+use crate::services::notification_service;
+use crate::services::merchant_service::MerchantService;
+use crate::services::two_factor_service::TwoFactorService;
+use crate::services::wallet_security_service::WalletSecurityService;
+use crate::utils::encryption::decrypt_data;
+use crate::utils::keystore::{self, DEFAULT_ITERATIONS, MIN_PASSPHRASE_LENGTH};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::warn;
+
+/// How long a key stays available once the cooling-off delay has passed
+const RELEASE_WINDOW_HOURS: i64 = 24;
+
+/// A private key export request, as shown to the merchant
+#[derive(Debug, Serialize)]
+pub struct KeyExport {
+    pub export_id: String,
+    pub crypto_type: String,
+    pub status: String,
+    pub requested_at: DateTime<Utc>,
+    pub available_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub released_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+/// Exports wallet private keys only after re-authentication and a cancellable delay
+///
+/// A request needs 2FA to be enabled, the merchant's password (the code
+/// itself is checked by the route's step-up layer) and emails the merchant.
+/// Wrong passwords count toward the account's login lockout. The key can be released once,
+/// between `available_at` and `expires_at`, as a keystore encrypted with a
+/// passphrase the merchant chooses at release time. Every attempt,
+/// successful or not, is written to the wallet access log.
+///
+/// With `SIGNER_MODE=remote` the API cannot decrypt keys, and exports are refused.
+pub struct KeyExportService {
+    db_pool: PgPool,
+    delay: Duration,
+    release_window: Duration,
+    two_factor: TwoFactorService,
+    lockout: AccountLockoutService,
+    remote_signer: bool,
+}
+
+impl KeyExportService {
+    pub fn new(
+        db_pool: PgPool,
+        delay: Duration,
+        release_window: Duration,
+        two_factor: TwoFactorService,
+        lockout: AccountLockoutService,
+    ) -> Self {
+        Self { db_pool, delay, release_window, two_factor, lockout, remote_signer: false }
+    }
+
+    /// Refuse exports because keys are held by the signer daemon
+    pub fn with_remote_signer(mut self, remote_signer: bool) -> Self {
+        self.remote_signer = remote_signer;
+        self
+    }
+
+    /// Service using `Config::key_export_delay_hours`
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self::new(
+            db_pool.clone(),
+            Duration::hours(config.key_export_delay_hours as i64),
+            Duration::hours(RELEASE_WINDOW_HOURS),
+            TwoFactorService::new(db_pool.clone(), config.two_factor_enabled),
+            AccountLockoutService::from_config(db_pool, config),
+        )
+        .with_remote_signer(config.signer_mode == "remote")
+    }
+
+    fn ensure_keys_available(&self) -> Result<(), ServiceError> {
+        if self.remote_signer {
+            return Err(ServiceError::Forbidden(
+                "Private keys are held by the signer daemon and cannot be exported through the API".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Start the cooling-off period for exporting a wallet's key
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Merchant requesting the export
+    /// * `crypto_type` - Wallet whose key is exported
+    /// * `password` - Merchant's account password, for re-authentication
+    /// * `ip` - Client IP, for the access log and notification
+    pub async fn request_export(
+        &self,
+        merchant_id: i64,
+        crypto_type: CryptoType,
+        password: &str,
+        ip: Option<&str>,
+    ) -> Result<KeyExport, ServiceError> {
+        let crypto_type = crypto_type.to_string();
+        let result = self.create_export(merchant_id, &crypto_type, password, ip).await;
+
+        let details = match &result {
+            Ok(export) => format!("Key export {} requested for {}", export.export_id, crypto_type),
+            Err(e) => format!("Key export request for {} denied: {}", crypto_type, e),
+        };
+        self.log_attempt(merchant_id, ip, &details).await;
+
+        result
+    }
+
+    async fn create_export(
+        &self,
+        merchant_id: i64,
+        crypto_type: &str,
+        password: &str,
+        ip: Option<&str>,
+    ) -> Result<KeyExport, ServiceError> {
+        self.ensure_keys_available()?;
+
+        let merchant = sqlx::query!(
+            "SELECT email, password_hash FROM merchants WHERE id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+
+        if !self.two_factor.is_enabled(merchant_id).await? {
+            return Err(ServiceError::TwoFactorRequired(
+                "Two-factor authentication must be enabled to export a private key".to_string(),
+            ));
+        }
+
+        self.lockout.check_lockout(AttemptKind::Login, Some(&merchant.email), ip).await?;
+        let password_ok = merchant.password_hash
+            .as_deref()
+            .map(|hash| MerchantService::password_matches(hash, password))
+            .unwrap_or(false);
+        if !password_ok {
+            self.lockout.record_failed_attempt(AttemptKind::Login, Some(&merchant.email), ip).await?;
+            return Err(ServiceError::Unauthorized("Password is incorrect".to_string()));
+        }
+
+        if self.wallet_key(merchant_id, crypto_type).await?.is_none() {
+            return Err(ServiceError::WalletNotConfigured(format!(
+                "No private key is stored for the {} wallet",
+                crypto_type
+            )));
+        }
+
+        let pending = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM key_export_requests WHERE merchant_id = $1 AND crypto_type = $2 AND status = 'pending' AND expires_at > NOW()",
+            merchant_id,
+            crypto_type
+        )
+        .fetch_one(&self.db_pool)
+        .await?
+        .unwrap_or(0);
+        if pending > 0 {
+            return Err(ServiceError::ValidationError(format!(
+                "An export of the {} key is already pending",
+                crypto_type
+            )));
+        }
+
+        let export_id = format!("kex_{}", nanoid::nanoid!(24));
+        let available_at = Utc::now() + self.delay;
+        let expires_at = available_at + self.release_window;
+
+        let mut tx = self.db_pool.begin().await?;
+
+        let export = sqlx::query_as!(
+            KeyExport,
+            r#"
+            INSERT INTO key_export_requests (export_id, merchant_id, crypto_type, ip_address, available_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING export_id, crypto_type, status, requested_at, available_at, expires_at, released_at, cancelled_at
+            "#,
+            export_id,
+            merchant_id,
+            crypto_type,
+            ip,
+            available_at,
+            expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let notice = KeyExportRequested {
+            crypto_type: crypto_type.to_string(),
+            export_id: export.export_id.clone(),
+            available_at: export.available_at.to_rfc3339(),
+            ip: ip.map(str::to_string),
+        };
+        notification_service::enqueue_notification(&mut tx, merchant_id, &notice).await?;
+
+        tx.commit().await?;
+
+        Ok(export)
+    }
+
+    /// Export requests of a merchant, newest first
+    pub async fn list_exports(&self, merchant_id: i64) -> Result<Vec<KeyExport>, ServiceError> {
+        sqlx::query!(
+            "UPDATE key_export_requests SET status = 'expired' WHERE merchant_id = $1 AND status = 'pending' AND expires_at <= NOW()",
+            merchant_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        let exports = sqlx::query_as!(
+            KeyExport,
+            r#"
+            SELECT export_id, crypto_type, status, requested_at, available_at, expires_at, released_at, cancelled_at
+            FROM key_export_requests
+            WHERE merchant_id = $1
+            ORDER BY requested_at DESC
+            LIMIT 100
+            "#,
+            merchant_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(exports)
+    }
+
+    /// Cancel a pending export
+    pub async fn cancel_export(
+        &self,
+        merchant_id: i64,
+        export_id: &str,
+        ip: Option<&str>,
+    ) -> Result<KeyExport, ServiceError> {
+        let cancelled = sqlx::query_as!(
+            KeyExport,
+            r#"
+            UPDATE key_export_requests
+            SET status = 'cancelled', cancelled_at = NOW()
+            WHERE merchant_id = $1 AND export_id = $2 AND status = 'pending'
+            RETURNING export_id, crypto_type, status, requested_at, available_at, expires_at, released_at, cancelled_at
+            "#,
+            merchant_id,
+            export_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("No pending key export {}", export_id)));
+
+        let details = match &cancelled {
+            Ok(_) => format!("Key export {} cancelled", export_id),
+            Err(e) => format!("Key export {} cancellation failed: {}", export_id, e),
+        };
+        self.log_attempt(merchant_id, ip, &details).await;
+
+        cancelled
+    }
+
+    /// Release the key of an export whose cooling-off period has passed
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Merchant owning the export
+    /// * `export_id` - Export to release
+    /// * `passphrase` - Passphrase the keystore is encrypted with
+    /// * `ip` - Client IP, for the access log
+    ///
+    /// # Returns
+    /// An Ethereum V3 keystore, or the Solana equivalent for Solana wallets
+    pub async fn release_export(
+        &self,
+        merchant_id: i64,
+        export_id: &str,
+        passphrase: &str,
+        ip: Option<&str>,
+    ) -> Result<Value, ServiceError> {
+        let result = self.release(merchant_id, export_id, passphrase).await;
+
+        let details = match &result {
+            Ok(_) => format!("Key export {} released", export_id),
+            Err(e) => format!("Key export {} release denied: {}", export_id, e),
+        };
+        self.log_attempt(merchant_id, ip, &details).await;
+
+        result
+    }
+
+    async fn release(&self, merchant_id: i64, export_id: &str, passphrase: &str) -> Result<Value, ServiceError> {
+        self.ensure_keys_available()?;
+
+        if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+            return Err(ServiceError::ValidationError(format!(
+                "Passphrase must be at least {} characters",
+                MIN_PASSPHRASE_LENGTH
+            )));
+        }
+
+        let export = sqlx::query!(
+            "SELECT id, crypto_type, status, available_at, expires_at FROM key_export_requests WHERE merchant_id = $1 AND export_id = $2",
+            merchant_id,
+            export_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Key export {}", export_id)))?;
+
+        check_releasable(&export.status, export.available_at, export.expires_at, Utc::now())?;
+
+        let wallet = self.wallet_key(merchant_id, &export.crypto_type).await?
+            .ok_or_else(|| ServiceError::WalletNotConfigured(format!(
+                "No private key is stored for the {} wallet",
+                export.crypto_type
+            )))?;
+
+        // PBKDF2 with the full iteration count takes a while; keep it off the runtime
+        let passphrase = passphrase.to_string();
+        let keystore = tokio::task::spawn_blocking(move || {
+            let private_key = decrypt_data(&wallet.encrypted_private_key)?;
+            build_keystore(&wallet.network, &wallet.address, &private_key, &passphrase, DEFAULT_ITERATIONS)
+        })
+        .await
+        .map_err(|e| ServiceError::InternalError(format!("Keystore task failed: {}", e)))?
+        .map_err(|e| ServiceError::InternalError(format!("Failed to build keystore: {}", e)))?;
+
+        // Single use: only the first release of a pending export succeeds
+        let claimed = sqlx::query!(
+            "UPDATE key_export_requests SET status = 'released', released_at = NOW() WHERE id = $1 AND status = 'pending' RETURNING id",
+            export.id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        if claimed.is_none() {
+            return Err(ServiceError::InvalidStateTransition(
+                "Key export was released or cancelled concurrently".to_string(),
+            ));
+        }
+
+        Ok(keystore)
+    }
+
+    async fn wallet_key(&self, merchant_id: i64, crypto_type: &str) -> Result<Option<WalletKey>, ServiceError> {
+        let wallet = sqlx::query!(
+            "SELECT address, network, encrypted_private_key FROM merchant_wallets WHERE merchant_id = $1 AND crypto_type = $2 AND is_active = true",
+            merchant_id,
+            crypto_type
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(wallet.and_then(|w| {
+            w.encrypted_private_key.map(|encrypted_private_key| WalletKey {
+                address: w.address,
+                network: w.network,
+                encrypted_private_key,
+            })
+        }))
+    }
+
+    async fn log_attempt(&self, merchant_id: i64, ip: Option<&str>, details: &str) {
+        let security = WalletSecurityService::new(self.db_pool.clone());
+        if let Err(e) = security.log_wallet_access(merchant_id, ip.unwrap_or("unknown"), Some(details)).await {
+            warn!("Failed to log key export attempt for merchant {}: {}", merchant_id, e);
+        }
+    }
+}
+
+/// Stored key of a generated or imported wallet
+struct WalletKey {
+    address: String,
+    network: String,
+    encrypted_private_key: String,
+}
+
+/// Whether an export in `status` may be released at `now`
+fn check_releasable(
+    status: &str,
+    available_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), ServiceError> {
+    match status {
+        "pending" if now >= expires_at => Err(ServiceError::InvalidStateTransition(
+            "Key export has expired; request a new one".to_string(),
+        )),
+        "pending" if now < available_at => Err(ServiceError::Forbidden(format!(
+            "Key export is in its cooling-off period until {}",
+            available_at.to_rfc3339()
+        ))),
+        "pending" => Ok(()),
+        other => Err(ServiceError::InvalidStateTransition(format!(
+            "Key export is {}",
+            other
+        ))),
+    }
+}
+
+/// Keystore for a decrypted wallet key
+///
+/// Solana keys are stored as base58 64-byte keypairs, EVM keys as hex.
+fn build_keystore(
+    network: &str,
+    address: &str,
+    private_key: &str,
+    passphrase: &str,
+    iterations: u32,
+) -> Result<Value, String> {
+    if network.starts_with("SOLANA") {
+        let keypair = bs58::decode(private_key)
+            .into_vec()
+            .map_err(|_| "Stored Solana key is not base58".to_string())?;
+        keystore::solana_keystore(&keypair, address, passphrase, iterations)
+    } else {
+        let key = hex::decode(private_key.trim_start_matches("0x"))
+            .map_err(|_| "Stored EVM key is not hex".to_string())?;
+        keystore::evm_keystore(&key, address, passphrase, iterations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_only_within_window_and_once() {
+        let requested = Utc::now();
+        let available_at = requested + Duration::hours(24);
+        let expires_at = available_at + Duration::hours(RELEASE_WINDOW_HOURS);
+
+        assert!(matches!(
+            check_releasable("pending", available_at, expires_at, requested),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(check_releasable("pending", available_at, expires_at, available_at).is_ok());
+        assert!(check_releasable("pending", available_at, expires_at, expires_at).is_err());
+        assert!(check_releasable("released", available_at, expires_at, available_at).is_err());
+        assert!(check_releasable("cancelled", available_at, expires_at, available_at).is_err());
+    }
+
+    #[test]
+    fn test_build_keystore_by_network() {
+        let key = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let keystore = build_keystore("ETHEREUM", "0x14791697260E4c9A71f18484C9f997B308e59325", key, "correct horse battery", 1024).unwrap();
+        assert_eq!(keystore["version"], 3);
+
+        assert!(build_keystore("SOLANA", "11111111111111111111111111111111", key, "correct horse battery", 1024).is_err());
+    }
+}
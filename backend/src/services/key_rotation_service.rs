@@ -0,0 +1,211 @@
+// Key Rotation Service
+// Re-wraps stored secrets onto the current key-encryption key
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::utils::encryption::Encryption;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use tracing::warn;
+
+/// Rows re-wrapped per query
+const BATCH_SIZE: i64 = 200;
+
+/// A column holding values written by [`Encryption`]
+#[derive(Debug, Clone, Copy)]
+pub struct EncryptedColumn {
+    pub table: &'static str,
+    pub column: &'static str,
+}
+
+/// Private key columns, encrypted by the signer; all tables have an integer `id` key
+pub const SIGNING_KEY_COLUMNS: &[EncryptedColumn] = &[
+    EncryptedColumn { table: "deposit_addresses", column: "private_key_encrypted" },
+    EncryptedColumn { table: "deposit_keypairs", column: "encrypted_private_key" },
+    EncryptedColumn { table: "merchant_wallets", column: "encrypted_private_key" },
+];
+
+/// Secrets the API server encrypts for itself; all tables have an integer `id` key
+pub const API_SECRET_COLUMNS: &[EncryptedColumn] = &[
+    EncryptedColumn { table: "two_factor_auth", column: "secret_encrypted" },
+    EncryptedColumn { table: "two_factor_auth", column: "recovery_codes_encrypted" },
+    EncryptedColumn { table: "webhook_endpoints", column: "secret_encrypted" },
+    EncryptedColumn { table: "webhook_endpoints", column: "previous_secret_encrypted" },
+];
+
+/// Values of one column not yet on the current KEK
+#[derive(Debug, Clone, Serialize)]
+pub struct ColumnStatus {
+    pub table: &'static str,
+    pub column: &'static str,
+    pub stale: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    pub current_key_id: String,
+    pub columns: Vec<ColumnStatus>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RewrapReport {
+    pub current_key_id: String,
+    /// Values moved to the current KEK
+    pub rewrapped: u64,
+    /// Values that could not be decrypted or re-wrapped
+    pub failed: u64,
+    /// Values changed by someone else while being re-wrapped; picked up next run
+    pub skipped: u64,
+}
+
+/// Re-wraps the columns encrypted under this process's KEKs
+///
+/// With `SIGNER_MODE=remote` the private key columns use the signer daemon's
+/// own KEKs, so the API server only covers its own secrets and the daemon
+/// re-wraps the keys.
+pub struct KeyRotationService {
+    db_pool: PgPool,
+    columns: Vec<EncryptedColumn>,
+}
+
+impl KeyRotationService {
+    /// Service covering every encrypted column
+    pub fn new(db_pool: PgPool) -> Self {
+        Self::with_columns(db_pool, [API_SECRET_COLUMNS, SIGNING_KEY_COLUMNS].concat())
+    }
+
+    pub fn with_columns(db_pool: PgPool, columns: Vec<EncryptedColumn>) -> Self {
+        Self { db_pool, columns }
+    }
+
+    /// Service covering the columns this process encrypts under `SIGNER_MODE`
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        match config.signer_mode.as_str() {
+            "remote" => Self::with_columns(db_pool, API_SECRET_COLUMNS.to_vec()),
+            _ => Self::new(db_pool),
+        }
+    }
+
+    /// Current KEK and how many values in each column still use an older one
+    pub async fn status(&self) -> Result<EncryptionStatus, ServiceError> {
+        let current_key_id = encryption()?.current_key_id().map_err(ServiceError::Internal)?;
+        let prefix = envelope_prefix(&current_key_id);
+
+        let mut columns = Vec::with_capacity(self.columns.len());
+        for target in &self.columns {
+            let stale: i64 = sqlx::query_scalar(&format!(
+                "SELECT COUNT(*) FROM {table} WHERE {column} IS NOT NULL AND left({column}, length($1)) <> $1",
+                table = target.table,
+                column = target.column,
+            ))
+            .bind(&prefix)
+            .fetch_one(&self.db_pool)
+            .await?;
+
+            columns.push(ColumnStatus { table: target.table, column: target.column, stale });
+        }
+
+        Ok(EncryptionStatus { current_key_id, columns })
+    }
+
+    /// Move every stored value to the current KEK
+    ///
+    /// Envelope values only get their data key re-wrapped; values from
+    /// before envelope encryption are re-encrypted. Each update only applies
+    /// if the value is unchanged, so concurrent writes are never overwritten.
+    pub async fn rewrap_all(&self) -> Result<RewrapReport, ServiceError> {
+        let encryption = encryption()?;
+        let current_key_id = encryption.current_key_id().map_err(ServiceError::Internal)?;
+        let prefix = envelope_prefix(&current_key_id);
+        let mut report = RewrapReport { current_key_id, ..Default::default() };
+
+        for target in &self.columns {
+            let select = format!(
+                "SELECT id::bigint AS id, {column} AS value FROM {table} \
+                 WHERE {column} IS NOT NULL AND left({column}, length($1)) <> $1 AND id > $2 \
+                 ORDER BY id LIMIT $3",
+                table = target.table,
+                column = target.column,
+            );
+            let update = format!(
+                "UPDATE {table} SET {column} = $1 WHERE id = $2 AND {column} = $3",
+                table = target.table,
+                column = target.column,
+            );
+
+            // Walk by id so rows that fail are not selected again
+            let mut last_id = 0i64;
+            loop {
+                let rows = sqlx::query(&select)
+                    .bind(&prefix)
+                    .bind(last_id)
+                    .bind(BATCH_SIZE)
+                    .fetch_all(&self.db_pool)
+                    .await?;
+                if rows.is_empty() {
+                    break;
+                }
+
+                for row in rows {
+                    let id: i64 = row.try_get("id")?;
+                    let value: String = row.try_get("value")?;
+                    last_id = id;
+
+                    let rewrapped = match encryption.rewrap(&value) {
+                        Ok(Some(rewrapped)) => rewrapped,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            warn!("Cannot re-wrap {}.{} id {}: {}", target.table, target.column, id, e);
+                            report.failed += 1;
+                            continue;
+                        }
+                    };
+
+                    let updated = sqlx::query(&update)
+                        .bind(&rewrapped)
+                        .bind(id)
+                        .bind(&value)
+                        .execute(&self.db_pool)
+                        .await?
+                        .rows_affected();
+
+                    if updated == 1 {
+                        report.rewrapped += 1;
+                    } else {
+                        report.skipped += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+fn encryption() -> Result<Encryption, ServiceError> {
+    Encryption::new().map_err(|e| ServiceError::Internal(format!("Encryption unavailable: {}", e)))
+}
+
+/// Start of every value encrypted under `key_id`
+fn envelope_prefix(key_id: &str) -> String {
+    format!("v2:{}:", key_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::key_provider::{KeyProvider, Keyring};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_envelope_prefix_matches_encrypted_values() {
+        let keyring: Arc<dyn KeyProvider> = Arc::new(Keyring::new("kek_2024", &[(
+            "kek_2024".to_string(),
+            "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef".to_string(),
+        )]).unwrap());
+        let encrypted = Encryption::with_provider(keyring, None).encrypt("secret").unwrap();
+
+        assert!(encrypted.starts_with(&envelope_prefix("kek_2024")));
+        assert!(!encrypted.starts_with(&envelope_prefix("kek_2023")));
+    }
+}
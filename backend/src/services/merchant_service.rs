@@ -39,11 +39,18 @@ impl MerchantService {
         hasher.update(api_key.as_bytes());
         let api_key_hash = format!("{:x}", hasher.finalize());
         
+        // Onboarding starts at email verification unless it is not required
+        let (onboarding_status, email_verified_at) = if self.config.merchant_email_verification_required {
+            ("registered", None)
+        } else {
+            ("email_verified", Some(Utc::now()))
+        };
+
         // Create merchant in sandbox mode by default
         let merchant = sqlx::query_as::<_, Merchant>(
             r#"
-            INSERT INTO merchants (email, business_name, api_key_hash, fee_percentage, customer_pays_fee, is_active, sandbox_mode, kyc_verified, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            INSERT INTO merchants (email, business_name, api_key_hash, fee_percentage, customer_pays_fee, is_active, sandbox_mode, kyc_verified, created_at, updated_at, onboarding_status, email_verified_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             RETURNING id, email, business_name, api_key_hash, fee_percentage, customer_pays_fee, is_active, sandbox_mode, kyc_verified, created_at, updated_at
             "#
         )
@@ -57,6 +64,8 @@ impl MerchantService {
         .bind(false) // kyc_verified (default)
         .bind(Utc::now())
         .bind(Utc::now())
+        .bind(onboarding_status)
+        .bind(email_verified_at)
         .fetch_one(&self.db_pool)
         .await?;
         
@@ -66,6 +75,60 @@ impl MerchantService {
         })
     }
 
+    /// Store an Argon2 hash of the merchant's password
+    pub async fn set_password(&self, merchant_id: i64, password: &str) -> Result<(), ServiceError> {
+        let password_hash = Self::hash_password(password)?;
+
+        sqlx::query!(
+            "UPDATE merchants SET password_hash = $1, updated_at = NOW() WHERE id = $2",
+            password_hash,
+            merchant_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Argon2 hash of a password, as stored in `merchants.password_hash`
+    pub fn hash_password(password: &str) -> Result<String, ServiceError> {
+        use argon2::{Argon2, PasswordHasher};
+        use argon2::password_hash::{SaltString, rand_core::OsRng};
+
+        let salt = SaltString::generate(&mut OsRng);
+        Ok(Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| ServiceError::InternalError(format!("Password hashing failed: {}", e)))?
+            .to_string())
+    }
+
+    /// Check a password for re-authentication
+    ///
+    /// Merchants registered before passwords were stored have none, so
+    /// nothing matches for them.
+    pub async fn verify_password(&self, merchant_id: i64, password: &str) -> Result<bool, ServiceError> {
+        let record = sqlx::query!(
+            "SELECT password_hash FROM merchants WHERE id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(record
+            .and_then(|r| r.password_hash)
+            .map(|hash| Self::password_matches(&hash, password))
+            .unwrap_or(false))
+    }
+
+    /// Compare a password against a stored Argon2 hash
+    pub fn password_matches(password_hash: &str, password: &str) -> bool {
+        use argon2::{Argon2, PasswordHash, PasswordVerifier};
+
+        PasswordHash::new(password_hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    }
+
     /// Switch merchant environment (sandbox <-> live)
     pub async fn switch_environment(
         &self,
@@ -155,6 +218,8 @@ impl MerchantService {
         hasher.update(new_api_key.as_bytes());
         let new_api_key_hash = format!("{:x}", hasher.finalize());
         
+        let mut tx = self.db_pool.begin().await?;
+
         // Update the merchant with the new API key hash
         sqlx::query!(
             "UPDATE merchants SET api_key_hash = $1, updated_at = $2 WHERE id = $3",
@@ -162,8 +227,20 @@ impl MerchantService {
             Utc::now(),
             merchant_id
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
+
+        // The rotated key keeps the IP allowlist of the key it replaces
+        sqlx::query!(
+            "UPDATE ip_whitelist SET api_key_hash = $1 WHERE merchant_id = $2 AND api_key_hash = $3",
+            new_api_key_hash,
+            merchant_id,
+            old_api_key_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
         
         Ok(new_api_key)
     }
@@ -668,4 +745,17 @@ mod tests {
             assert!(result.is_ok(), "Failed for {:?}: {:?}", crypto_type, result);
         }
     }
+
+    #[test]
+    fn test_password_matches_argon2_hash() {
+        use argon2::{Argon2, PasswordHasher};
+        use argon2::password_hash::{SaltString, rand_core::OsRng};
+
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default().hash_password(b"correct horse", &salt).unwrap().to_string();
+
+        assert!(MerchantService::password_matches(&hash, "correct horse"));
+        assert!(!MerchantService::password_matches(&hash, "wrong horse"));
+        assert!(!MerchantService::password_matches("not a hash", "correct horse"));
+    }
 }
@@ -3,7 +3,15 @@
 
 pub mod merchant_service;
 pub mod payment_service;
+pub mod payment_policy_service;
 pub mod webhook_service;
+pub mod webhook_egress;
+pub mod webhook_endpoint_service;
+pub mod webhook_delivery_service;
+pub mod webhook_test_service;
+pub mod payment_stream_service;
+pub mod outbox;
+pub mod event_service;
 pub mod refund_service;
 pub mod analytics_service;
 pub mod sandbox_service;
@@ -18,14 +26,26 @@ pub mod email_service;
 pub mod currency_service;
 pub mod price_service;
 pub mod account_lockout_service;
+pub mod two_factor_service;
+pub mod key_rotation_service;
+pub mod key_export_service;
+pub mod screening_service;
+pub mod aml_service;
+pub mod onboarding_service;
+pub mod document_store;
+pub mod email_templates;
+pub mod notification_service;
+pub mod email_token_service;
+pub mod multi_user_service;
+pub mod rate_limit_service;
 pub mod security_monitoring_service;
 pub mod wallet_config_service;
 pub mod gas_fee_service;
 pub mod gas_websocket_service;
 pub mod address_only_service;
 pub mod payment_monitor_service;
-pub mod webhook_notification_service;
 pub mod blockchain_transaction_sender;
+pub mod signer;
 pub mod address_only_manager;
 pub mod withdrawal_processor;
 pub mod wallet_security_service;
@@ -1,9 +1,23 @@
-use sqlx::PgPool;
+// Multi-User Service
+// Merchant team members, email invitations and team member sessions
+
+use sqlx::{PgPool, Postgres, Transaction};
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
+use tracing::info;
+use crate::config::Config;
 use crate::error::ServiceError;
+use crate::services::email_templates::{EmailMessage, TeamInvitation};
+use crate::services::email_token_service::{format_time, sign_token, verify_token, TokenPurpose};
+use crate::services::{notification_service, outbox};
+
+/// Prefix that tells team member sessions apart from API keys
+pub const SESSION_TOKEN_PREFIX: &str = "mus_";
+
+/// Shortest password accepted for a team member, as on registration
+const MIN_PASSWORD_LENGTH: usize = 8;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum UserRole {
@@ -30,6 +44,25 @@ impl UserRole {
         }
     }
 
+    /// Role from its `user_role` value, e.g. "ADMIN"
+    pub fn parse(role: &str) -> Option<Self> {
+        match role.trim().to_uppercase().as_str() {
+            "SUPER_ADMIN" => Some(UserRole::SuperAdmin),
+            "ADMIN" => Some(UserRole::Admin),
+            "MODERATOR" => Some(UserRole::Moderator),
+            "MERCHANT" => Some(UserRole::Merchant),
+            "USER" => Some(UserRole::User),
+            _ => None,
+        }
+    }
+
+    /// Roles a merchant can give its team members
+    ///
+    /// `MERCHANT` belongs to the account owner and `SUPER_ADMIN` to platform staff.
+    pub fn is_team_role(&self) -> bool {
+        matches!(self, UserRole::Admin | UserRole::Moderator | UserRole::User)
+    }
+
     pub fn can_manage_users(&self) -> bool {
         matches!(self, UserRole::SuperAdmin | UserRole::Admin | UserRole::Merchant)
     }
@@ -41,6 +74,40 @@ impl UserRole {
     pub fn can_view_analytics(&self) -> bool {
         !matches!(self, UserRole::User)
     }
+
+    /// Create, cancel and verify payments; `USER` members only read them
+    pub fn can_manage_payments(&self) -> bool {
+        !matches!(self, UserRole::User)
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::ManageUsers => self.can_manage_users(),
+            Permission::ApproveWithdrawals => self.can_approve_withdrawals(),
+            Permission::ViewAnalytics => self.can_view_analytics(),
+            Permission::ManagePayments => self.can_manage_payments(),
+        }
+    }
+}
+
+/// What a merchant route may require of a team member's role
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ManageUsers,
+    ApproveWithdrawals,
+    ViewAnalytics,
+    ManagePayments,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ManageUsers => "manage_users",
+            Permission::ApproveWithdrawals => "approve_withdrawals",
+            Permission::ViewAnalytics => "view_analytics",
+            Permission::ManagePayments => "manage_payments",
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -61,13 +128,86 @@ pub struct CreateUserRequest {
     pub role: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct InviteUserRequest {
+    pub email: String,
+    pub role: String,
+}
+
+/// An invitation that has not been accepted or revoked yet
+#[derive(Debug, Serialize)]
+pub struct Invitation {
+    pub invitation_id: String,
+    pub email: String,
+    pub role: String,
+    /// Team member who sent it; `None` when the owner did
+    pub invited_by: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Bearer token identifying a team member on merchant routes
+#[derive(Debug, Serialize)]
+pub struct TeamSession {
+    pub session_token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: MerchantUser,
+}
+
+/// Team member behind a valid session token
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub user: MerchantUser,
+    pub sandbox_mode: bool,
+}
+
+/// Team members of a merchant, and how they join and sign in
+///
+/// Members are invited by email with a signed, expiring link and choose
+/// their password on accepting. They then sign in for a session token
+/// (`mus_...`), which is checked against the member's current state on
+/// every request, so removal takes effect immediately.
 pub struct MultiUserService {
     pool: PgPool,
+    signing_key: String,
+    dashboard_base_url: String,
+    invitation_ttl: Duration,
+    session_ttl: Duration,
 }
 
 impl MultiUserService {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            signing_key: String::new(),
+            dashboard_base_url: "http://localhost:3000".to_string(),
+            invitation_ttl: Duration::hours(72),
+            session_ttl: Duration::hours(12),
+        }
+    }
+
+    /// Service signing with `jwt_secret`, linking invitations to `dashboard_base_url`
+    pub fn from_config(pool: PgPool, config: &Config) -> Self {
+        Self::new(pool)
+            .with_signing(&config.jwt_secret, &config.dashboard_base_url)
+            .with_ttls(
+                Duration::hours(config.team_invitation_ttl_hours.max(1) as i64),
+                Duration::hours(config.team_session_ttl_hours.max(1) as i64),
+            )
+    }
+
+    /// Key that signs invitations and sessions, and the dashboard invitations link to
+    pub fn with_signing(mut self, signing_key: &str, dashboard_base_url: &str) -> Self {
+        self.signing_key = signing_key.to_string();
+        self.dashboard_base_url = dashboard_base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// How long invitations and sessions stay valid
+    pub fn with_ttls(mut self, invitation_ttl: Duration, session_ttl: Duration) -> Self {
+        self.invitation_ttl = invitation_ttl;
+        self.session_ttl = session_ttl;
+        self
     }
 
     pub async fn create_user(&self, merchant_id: i64, req: CreateUserRequest) -> Result<MerchantUser, ServiceError> {
@@ -122,36 +262,82 @@ impl MultiUserService {
         }).collect())
     }
 
-    pub async fn update_role(&self, merchant_id: i64, user_id: i32, new_role: &str) -> Result<(), ServiceError> {
-        sqlx::query(
-            "UPDATE merchant_users SET role = $3::user_role, updated_at = NOW()
-             WHERE id = $1 AND merchant_id = $2"
+    pub async fn get_user(&self, merchant_id: i64, user_id: i32) -> Result<MerchantUser, ServiceError> {
+        let record = sqlx::query!(
+            "SELECT id, merchant_id, email, role::text as \"role!\", is_active, last_login, created_at
+             FROM merchant_users WHERE id = $1 AND merchant_id = $2",
+            user_id,
+            merchant_id
         )
-        .bind(user_id)
-        .bind(merchant_id)
-        .bind(new_role)
-        .execute(&self.pool)
-        .await?;
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Merchant user not found".to_string()))?;
 
-        Ok(())
+        Ok(MerchantUser {
+            id: record.id,
+            merchant_id: record.merchant_id,
+            email: record.email,
+            role: record.role,
+            is_active: record.is_active,
+            last_login: record.last_login,
+            created_at: record.created_at,
+        })
+    }
+
+    /// Give an active team member another team role
+    pub async fn update_role(&self, merchant_id: i64, user_id: i32, new_role: &str) -> Result<MerchantUser, ServiceError> {
+        let role = team_role(new_role)?;
+
+        let record = sqlx::query!(
+            r#"
+            UPDATE merchant_users SET role = ($3::text)::user_role, updated_at = NOW()
+            WHERE id = $1 AND merchant_id = $2 AND is_active = true
+            RETURNING id, merchant_id, email, role::text as "role!", is_active, last_login, created_at
+            "#,
+            user_id,
+            merchant_id,
+            role.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Merchant user not found".to_string()))?;
+
+        info!("Merchant {} changed the role of team member {} to {}", merchant_id, user_id, record.role);
+        Ok(MerchantUser {
+            id: record.id,
+            merchant_id: record.merchant_id,
+            email: record.email,
+            role: record.role,
+            is_active: record.is_active,
+            last_login: record.last_login,
+            created_at: record.created_at,
+        })
     }
 
+    /// Remove a member from the team, ending their sessions
+    ///
+    /// The row is kept for the audit trail; the address can be invited again.
     pub async fn deactivate_user(&self, merchant_id: i64, user_id: i32) -> Result<(), ServiceError> {
-        sqlx::query!(
-            "UPDATE merchant_users SET is_active = false, updated_at = NOW()
-             WHERE id = $1 AND merchant_id = $2",
+        let result = sqlx::query!(
+            "UPDATE merchant_users SET is_active = false, session_version = session_version + 1, updated_at = NOW()
+             WHERE id = $1 AND merchant_id = $2 AND is_active = true",
             user_id, merchant_id
         )
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("Merchant user not found".to_string()));
+        }
+
+        info!("Merchant {} removed team member {}", merchant_id, user_id);
         Ok(())
     }
 
     pub async fn authenticate(&self, email: &str, password: &str) -> Result<MerchantUser, ServiceError> {
         let record = sqlx::query!(
             "SELECT id, merchant_id, email, password_hash, role::text as \"role!\", is_active, last_login, created_at
-             FROM merchant_users WHERE email = $1 AND is_active = true",
+             FROM merchant_users WHERE LOWER(email) = LOWER($1) AND is_active = true",
             email
         )
         .fetch_optional(&self.pool)
@@ -161,7 +347,7 @@ impl MultiUserService {
         // Verify password
         let parsed_hash = PasswordHash::new(&record.password_hash)
             .map_err(|e| ServiceError::InternalError(format!("Invalid hash: {}", e)))?;
-        
+
         Argon2::default()
             .verify_password(password.as_bytes(), &parsed_hash)
             .map_err(|_| ServiceError::Unauthorized("Invalid credentials".to_string()))?;
@@ -184,4 +370,362 @@ impl MultiUserService {
             created_at: record.created_at,
         })
     }
+
+    /// Check a team member's password and start a session
+    pub async fn login(&self, email: &str, password: &str) -> Result<TeamSession, ServiceError> {
+        let user = self.authenticate(email, password).await?;
+        self.start_session(user).await
+    }
+
+    /// Team member and merchant behind a session token
+    pub async fn authenticate_session(&self, token: &str) -> Result<AuthenticatedUser, ServiceError> {
+        let invalid = || ServiceError::Unauthorized("Invalid or expired session".to_string());
+        let (user_id, version) = parse_session_token(&self.signing_key, token, Utc::now()).ok_or_else(invalid)?;
+
+        let record = sqlx::query!(
+            r#"
+            SELECT u.id, u.merchant_id, u.email, u.role::text as "role!", u.is_active, u.last_login,
+                   u.created_at, m.sandbox_mode
+            FROM merchant_users u
+            JOIN merchants m ON m.id = u.merchant_id
+            WHERE u.id = $1 AND u.session_version = $2 AND u.is_active = true AND m.is_active = true
+            "#,
+            user_id,
+            version
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(invalid)?;
+
+        Ok(AuthenticatedUser {
+            user: MerchantUser {
+                id: record.id,
+                merchant_id: record.merchant_id,
+                email: record.email,
+                role: record.role,
+                is_active: record.is_active,
+                last_login: record.last_login,
+                created_at: record.created_at,
+            },
+            sandbox_mode: record.sandbox_mode,
+        })
+    }
+
+    /// End every session of a team member
+    pub async fn sign_out(&self, merchant_id: i64, user_id: i32) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE merchant_users SET session_version = session_version + 1, updated_at = NOW()
+             WHERE id = $1 AND merchant_id = $2",
+            user_id, merchant_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Email an invitation to join the merchant's team
+    ///
+    /// An open invitation to the same address is replaced.
+    pub async fn invite(
+        &self,
+        merchant_id: i64,
+        invited_by: Option<i32>,
+        req: InviteUserRequest,
+    ) -> Result<Invitation, ServiceError> {
+        let email = req.email.trim().to_lowercase();
+        if !email.contains('@') || email.len() > 255 {
+            return Err(ServiceError::ValidationError("Invalid email address".to_string()));
+        }
+        let role = team_role(&req.role)?;
+
+        let mut tx = self.pool.begin().await?;
+        let merchant = sqlx::query!(
+            "SELECT business_name, email FROM merchants WHERE id = $1 AND is_active = true",
+            merchant_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+        if merchant.email.eq_ignore_ascii_case(&email) {
+            return Err(ServiceError::ValidationError("The account owner is already on the team".to_string()));
+        }
+        let member_exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM merchant_users WHERE LOWER(email) = $1 AND is_active = true) as "exists!""#,
+            email
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if member_exists {
+            return Err(ServiceError::ValidationError(format!("{} is already a team member", email)));
+        }
+
+        sqlx::query!(
+            "UPDATE team_invitations SET revoked_at = NOW()
+             WHERE merchant_id = $1 AND LOWER(email) = $2 AND accepted_at IS NULL AND revoked_at IS NULL",
+            merchant_id,
+            email
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let expires_at = whole_seconds(Utc::now() + self.invitation_ttl)?;
+        let invitation_id = nanoid::nanoid!(32);
+        let record = sqlx::query!(
+            r#"
+            INSERT INTO team_invitations (invitation_id, merchant_id, email, role, invited_by, expires_at)
+            VALUES ($1, $2, $3, ($4::text)::user_role, $5, $6)
+            RETURNING created_at
+            "#,
+            invitation_id,
+            merchant_id,
+            email,
+            role.as_str(),
+            invited_by,
+            expires_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let inviter = match invited_by {
+            Some(user_id) => sqlx::query_scalar!("SELECT email FROM merchant_users WHERE id = $1", user_id)
+                .fetch_optional(&mut *tx)
+                .await?,
+            None => None,
+        };
+        let token = sign_token(&self.signing_key, TokenPurpose::TeamInvite, &invitation_id, expires_at);
+        let message = TeamInvitation {
+            business_name: merchant.business_name,
+            role: role.as_str().to_string(),
+            invited_by: inviter,
+            link: format!("{}/accept-invitation?token={}", self.dashboard_base_url, token),
+            expires_at: format_time(expires_at),
+        };
+        let locale = notification_service::owner_locale(&mut *tx, merchant_id).await?;
+        outbox::enqueue_email(&mut *tx, Some(merchant_id), &message.render(&email, locale)?).await?;
+        tx.commit().await?;
+
+        info!("Merchant {} invited {} as {}", merchant_id, email, role.as_str());
+        Ok(Invitation {
+            invitation_id,
+            email,
+            role: role.as_str().to_string(),
+            invited_by,
+            expires_at,
+            created_at: record.created_at,
+        })
+    }
+
+    /// Invitations that can still be accepted
+    pub async fn list_invitations(&self, merchant_id: i64) -> Result<Vec<Invitation>, ServiceError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT invitation_id, email, role::text as "role!", invited_by, expires_at, created_at
+            FROM team_invitations
+            WHERE merchant_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#,
+            merchant_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records.into_iter().map(|r| Invitation {
+            invitation_id: r.invitation_id,
+            email: r.email,
+            role: r.role,
+            invited_by: r.invited_by,
+            expires_at: r.expires_at,
+            created_at: r.created_at,
+        }).collect())
+    }
+
+    pub async fn revoke_invitation(&self, merchant_id: i64, invitation_id: &str) -> Result<(), ServiceError> {
+        let result = sqlx::query!(
+            "UPDATE team_invitations SET revoked_at = NOW()
+             WHERE invitation_id = $1 AND merchant_id = $2 AND accepted_at IS NULL AND revoked_at IS NULL",
+            invitation_id,
+            merchant_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::NotFound("Invitation not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Join a team with an invitation token, choosing a password
+    ///
+    /// A member who was removed from the same team is reactivated.
+    pub async fn accept_invitation(&self, token: &str, password: &str) -> Result<TeamSession, ServiceError> {
+        let invitation_id = verify_token(&self.signing_key, TokenPurpose::TeamInvite, token, Utc::now())?;
+        if password.len() < MIN_PASSWORD_LENGTH {
+            return Err(ServiceError::ValidationError(format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            )));
+        }
+        let password_hash = crate::services::merchant_service::MerchantService::hash_password(password)?;
+
+        let mut tx = self.pool.begin().await?;
+        let invitation = sqlx::query!(
+            r#"
+            UPDATE team_invitations i
+            SET accepted_at = NOW()
+            FROM merchants m
+            WHERE i.invitation_id = $1
+              AND i.accepted_at IS NULL
+              AND i.revoked_at IS NULL
+              AND i.expires_at > NOW()
+              AND m.id = i.merchant_id
+              AND m.is_active = true
+            RETURNING i.merchant_id, i.email, i.role::text as "role!"
+            "#,
+            invitation_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ServiceError::InvalidToken("This invitation is invalid, expired or already used".to_string()))?;
+
+        let user_id = upsert_member(&mut tx, invitation.merchant_id, &invitation.email, &password_hash, &invitation.role).await?;
+        tx.commit().await?;
+
+        info!("{} joined the team of merchant {} as {}", invitation.email, invitation.merchant_id, invitation.role);
+        let user = self.get_user(invitation.merchant_id, user_id).await?;
+        self.start_session(user).await
+    }
+
+    async fn start_session(&self, user: MerchantUser) -> Result<TeamSession, ServiceError> {
+        let version = sqlx::query_scalar!("SELECT session_version FROM merchant_users WHERE id = $1", user.id)
+            .fetch_one(&self.pool)
+            .await?;
+        let expires_at = whole_seconds(Utc::now() + self.session_ttl)?;
+
+        Ok(TeamSession {
+            session_token: session_token(&self.signing_key, user.id, version, expires_at),
+            expires_at,
+            user,
+        })
+    }
+}
+
+/// Role a team member may be given, or a validation error
+fn team_role(role: &str) -> Result<UserRole, ServiceError> {
+    UserRole::parse(role)
+        .filter(UserRole::is_team_role)
+        .ok_or_else(|| ServiceError::ValidationError(format!(
+            "Unsupported role '{}', expected one of: ADMIN, MODERATOR, USER",
+            role
+        )))
+}
+
+/// Create the member, or bring back one removed from the same team
+async fn upsert_member(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    email: &str,
+    password_hash: &str,
+    role: &str,
+) -> Result<i32, ServiceError> {
+    let existing = sqlx::query!(
+        "SELECT id, merchant_id, is_active FROM merchant_users WHERE LOWER(email) = LOWER($1) FOR UPDATE",
+        email
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    match existing {
+        None => Ok(sqlx::query_scalar!(
+            r#"
+            INSERT INTO merchant_users (merchant_id, email, password_hash, role)
+            VALUES ($1, $2, $3, ($4::text)::user_role)
+            RETURNING id
+            "#,
+            merchant_id,
+            email,
+            password_hash,
+            role
+        )
+        .fetch_one(&mut **tx)
+        .await?),
+        Some(user) if user.merchant_id == merchant_id && !user.is_active => {
+            sqlx::query!(
+                r#"
+                UPDATE merchant_users
+                SET password_hash = $2, role = ($3::text)::user_role, is_active = true,
+                    session_version = session_version + 1, updated_at = NOW()
+                WHERE id = $1
+                "#,
+                user.id,
+                password_hash,
+                role
+            )
+            .execute(&mut **tx)
+            .await?;
+            Ok(user.id)
+        }
+        Some(_) => Err(ServiceError::ValidationError(format!("{} already belongs to a team", email))),
+    }
+}
+
+fn whole_seconds(at: DateTime<Utc>) -> Result<DateTime<Utc>, ServiceError> {
+    Utc.timestamp_opt(at.timestamp(), 0)
+        .single()
+        .ok_or_else(|| ServiceError::Internal("Token expiry out of range".to_string()))
+}
+
+/// `mus_{user_id}-{version}.{expires}.{signature}`
+fn session_token(key: &str, user_id: i32, version: i32, expires_at: DateTime<Utc>) -> String {
+    let id = format!("{}-{}", user_id, version);
+    format!("{}{}", SESSION_TOKEN_PREFIX, sign_token(key, TokenPurpose::TeamSession, &id, expires_at))
+}
+
+/// User ID and session version of a valid, unexpired session token
+fn parse_session_token(key: &str, token: &str, now: DateTime<Utc>) -> Option<(i32, i32)> {
+    let signed = token.strip_prefix(SESSION_TOKEN_PREFIX)?;
+    let id = verify_token(key, TokenPurpose::TeamSession, signed, now).ok()?;
+    let (user_id, version) = id.split_once('-')?;
+    Some((user_id.parse().ok()?, version.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "test_jwt_secret";
+
+    #[test]
+    fn test_team_roles() {
+        assert_eq!(UserRole::parse("admin"), Some(UserRole::Admin));
+        assert_eq!(UserRole::parse("OWNER"), None);
+        assert!(team_role("MODERATOR").is_ok());
+        assert!(team_role("MERCHANT").is_err());
+        assert!(team_role("SUPER_ADMIN").is_err());
+    }
+
+    #[test]
+    fn test_role_permissions() {
+        assert!(UserRole::Admin.allows(Permission::ManageUsers));
+        assert!(UserRole::Admin.allows(Permission::ApproveWithdrawals));
+        assert!(!UserRole::Moderator.allows(Permission::ApproveWithdrawals));
+        assert!(UserRole::Moderator.allows(Permission::ViewAnalytics));
+        assert!(!UserRole::User.allows(Permission::ViewAnalytics));
+        assert!(!UserRole::User.allows(Permission::ManageUsers));
+        assert!(UserRole::Moderator.allows(Permission::ManagePayments));
+        assert!(!UserRole::User.allows(Permission::ManagePayments));
+    }
+
+    #[test]
+    fn test_session_token_round_trip() {
+        let now = Utc::now();
+        let token = session_token(KEY, 42, 3, now + Duration::hours(1));
+
+        assert!(token.starts_with(SESSION_TOKEN_PREFIX));
+        assert_eq!(parse_session_token(KEY, &token, now), Some((42, 3)));
+        assert_eq!(parse_session_token("other_key", &token, now), None);
+        assert_eq!(parse_session_token(KEY, &token, now + Duration::hours(2)), None);
+        assert_eq!(parse_session_token(KEY, token.trim_start_matches(SESSION_TOKEN_PREFIX), now), None);
+    }
 }
@@ -0,0 +1,373 @@
+// Notification Service
+// Per-user email preferences and fan-out of notification emails
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+
+use crate::error::ServiceError;
+use crate::services::email_templates::{EmailMessage, Locale};
+use crate::services::outbox;
+
+/// What a notification email is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    /// Account security notices, always sent to the account owner only
+    Security,
+    Payments,
+    Withdrawals,
+    Invoices,
+    Webhooks,
+}
+
+impl NotificationCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationCategory::Security => "security",
+            NotificationCategory::Payments => "payments",
+            NotificationCategory::Withdrawals => "withdrawals",
+            NotificationCategory::Invoices => "invoices",
+            NotificationCategory::Webhooks => "webhooks",
+        }
+    }
+}
+
+/// Email preferences of the account owner or one team member
+///
+/// Owners receive every category until they opt out; team members only
+/// receive the categories they opt in to.
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationPreferences {
+    /// `None` for the merchant account owner
+    pub merchant_user_id: Option<i32>,
+    pub locale: Locale,
+    pub payments: bool,
+    pub withdrawals: bool,
+    pub invoices: bool,
+    pub webhooks: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl NotificationPreferences {
+    /// Preferences of a user who never saved any
+    pub fn defaults(merchant_user_id: Option<i32>) -> Self {
+        let owner = merchant_user_id.is_none();
+        Self {
+            merchant_user_id,
+            locale: Locale::default(),
+            payments: owner,
+            withdrawals: owner,
+            invoices: owner,
+            webhooks: owner,
+            updated_at: None,
+        }
+    }
+
+    /// Whether this user receives emails of `category`
+    pub fn allows(&self, category: NotificationCategory) -> bool {
+        match category {
+            NotificationCategory::Security => self.merchant_user_id.is_none(),
+            NotificationCategory::Payments => self.payments,
+            NotificationCategory::Withdrawals => self.withdrawals,
+            NotificationCategory::Invoices => self.invoices,
+            NotificationCategory::Webhooks => self.webhooks,
+        }
+    }
+}
+
+/// Fields to change; omitted fields keep their current value
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    pub locale: Option<String>,
+    pub payments: Option<bool>,
+    pub withdrawals: Option<bool>,
+    pub invoices: Option<bool>,
+    pub webhooks: Option<bool>,
+}
+
+/// A queued email and its delivery status
+#[derive(Debug, Serialize)]
+pub struct EmailDelivery {
+    pub id: i64,
+    pub recipient: String,
+    pub subject: String,
+    pub template: Option<String>,
+    pub locale: Option<String>,
+    /// pending, sent or failed
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+struct PreferencesRow {
+    merchant_user_id: Option<i32>,
+    locale: Option<String>,
+    payments: Option<bool>,
+    withdrawals: Option<bool>,
+    invoices: Option<bool>,
+    webhooks: Option<bool>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl PreferencesRow {
+    /// Saved preferences, or the defaults when the user has no row
+    fn into_preferences(self) -> NotificationPreferences {
+        let defaults = NotificationPreferences::defaults(self.merchant_user_id);
+        NotificationPreferences {
+            merchant_user_id: self.merchant_user_id,
+            locale: self.locale.as_deref().map(Locale::from_string).unwrap_or(defaults.locale),
+            payments: self.payments.unwrap_or(defaults.payments),
+            withdrawals: self.withdrawals.unwrap_or(defaults.withdrawals),
+            invoices: self.invoices.unwrap_or(defaults.invoices),
+            webhooks: self.webhooks.unwrap_or(defaults.webhooks),
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+pub struct NotificationService {
+    db_pool: PgPool,
+}
+
+impl NotificationService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Preferences of the owner (`merchant_user_id` = `None`) or a team member
+    pub async fn get_preferences(
+        &self,
+        merchant_id: i64,
+        merchant_user_id: Option<i32>,
+    ) -> Result<NotificationPreferences, ServiceError> {
+        self.ensure_user(merchant_id, merchant_user_id).await?;
+
+        let row = sqlx::query_as!(
+            PreferencesRow,
+            r#"
+            SELECT merchant_user_id, locale AS "locale?", payments AS "payments?",
+                   withdrawals AS "withdrawals?", invoices AS "invoices?",
+                   webhooks AS "webhooks?", updated_at AS "updated_at?"
+            FROM notification_preferences
+            WHERE merchant_id = $1 AND merchant_user_id IS NOT DISTINCT FROM $2
+            "#,
+            merchant_id,
+            merchant_user_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row
+            .map(PreferencesRow::into_preferences)
+            .unwrap_or_else(|| NotificationPreferences::defaults(merchant_user_id)))
+    }
+
+    /// Save changes to a user's preferences
+    pub async fn update_preferences(
+        &self,
+        merchant_id: i64,
+        merchant_user_id: Option<i32>,
+        request: UpdatePreferencesRequest,
+    ) -> Result<NotificationPreferences, ServiceError> {
+        let mut preferences = self.get_preferences(merchant_id, merchant_user_id).await?;
+
+        if let Some(tag) = &request.locale {
+            preferences.locale = Locale::parse(tag).ok_or_else(|| {
+                let supported: Vec<&str> = Locale::ALL.iter().map(Locale::as_str).collect();
+                ServiceError::ValidationError(format!(
+                    "Unsupported locale '{}', expected one of: {}",
+                    tag,
+                    supported.join(", ")
+                ))
+            })?;
+        }
+        preferences.payments = request.payments.unwrap_or(preferences.payments);
+        preferences.withdrawals = request.withdrawals.unwrap_or(preferences.withdrawals);
+        preferences.invoices = request.invoices.unwrap_or(preferences.invoices);
+        preferences.webhooks = request.webhooks.unwrap_or(preferences.webhooks);
+
+        let updated_at = sqlx::query_scalar!(
+            r#"
+            INSERT INTO notification_preferences (
+                merchant_id, merchant_user_id, locale, payments, withdrawals, invoices, webhooks
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (merchant_id, COALESCE(merchant_user_id, 0)) DO UPDATE SET
+                locale = EXCLUDED.locale,
+                payments = EXCLUDED.payments,
+                withdrawals = EXCLUDED.withdrawals,
+                invoices = EXCLUDED.invoices,
+                webhooks = EXCLUDED.webhooks,
+                updated_at = NOW()
+            RETURNING updated_at
+            "#,
+            merchant_id,
+            merchant_user_id,
+            preferences.locale.as_str(),
+            preferences.payments,
+            preferences.withdrawals,
+            preferences.invoices,
+            preferences.webhooks
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        preferences.updated_at = Some(updated_at);
+
+        Ok(preferences)
+    }
+
+    /// Recently queued emails of a merchant, newest first
+    pub async fn list_emails(&self, merchant_id: i64, limit: i64) -> Result<Vec<EmailDelivery>, ServiceError> {
+        let emails = sqlx::query_as!(
+            EmailDelivery,
+            r#"
+            SELECT id, recipient, subject, template, locale, status, attempts,
+                   last_error, next_attempt_at, created_at, sent_at
+            FROM email_outbox
+            WHERE merchant_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+            "#,
+            merchant_id,
+            limit.clamp(1, 100)
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(emails)
+    }
+
+    /// Team members must belong to the merchant
+    async fn ensure_user(&self, merchant_id: i64, merchant_user_id: Option<i32>) -> Result<(), ServiceError> {
+        let Some(user_id) = merchant_user_id else {
+            return Ok(());
+        };
+
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM merchant_users WHERE id = $1 AND merchant_id = $2) AS "exists!""#,
+            user_id,
+            merchant_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        if !exists {
+            return Err(ServiceError::NotFound("Merchant user not found".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Locale the account owner reads email in
+pub async fn owner_locale<'e, E: PgExecutor<'e>>(executor: E, merchant_id: i64) -> Result<Locale, ServiceError> {
+    let locale = sqlx::query_scalar!(
+        "SELECT locale FROM notification_preferences WHERE merchant_id = $1 AND merchant_user_id IS NULL",
+        merchant_id
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(locale.as_deref().map(Locale::from_string).unwrap_or_default())
+}
+
+/// Queue a notification for every merchant user whose preferences allow it
+///
+/// Each recipient gets the email in their own locale. Nothing is sent if the
+/// transaction rolls back.
+pub async fn enqueue_notification(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    message: &dyn EmailMessage,
+) -> Result<usize, ServiceError> {
+    let recipients = sqlx::query!(
+        r#"
+        SELECT m.email AS "email!", NULL::int AS merchant_user_id,
+               p.locale AS "locale?", p.payments AS "payments?", p.withdrawals AS "withdrawals?",
+               p.invoices AS "invoices?", p.webhooks AS "webhooks?"
+        FROM merchants m
+        LEFT JOIN notification_preferences p ON p.merchant_id = m.id AND p.merchant_user_id IS NULL
+        WHERE m.id = $1
+        UNION ALL
+        SELECT u.email, u.id, p.locale, p.payments, p.withdrawals, p.invoices, p.webhooks
+        FROM merchant_users u
+        LEFT JOIN notification_preferences p ON p.merchant_id = u.merchant_id AND p.merchant_user_id = u.id
+        WHERE u.merchant_id = $1 AND u.is_active
+        "#,
+        merchant_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut queued = 0;
+    for recipient in recipients {
+        let preferences = PreferencesRow {
+            merchant_user_id: recipient.merchant_user_id,
+            locale: recipient.locale,
+            payments: recipient.payments,
+            withdrawals: recipient.withdrawals,
+            invoices: recipient.invoices,
+            webhooks: recipient.webhooks,
+            updated_at: None,
+        }
+        .into_preferences();
+
+        if preferences.allows(message.category()) {
+            let email = message.render(&recipient.email, preferences.locale)?;
+            outbox::enqueue_email(&mut **tx, Some(merchant_id), &email).await?;
+            queued += 1;
+        }
+    }
+
+    Ok(queued)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_opt_in_team_members_only_by_choice() {
+        let owner = NotificationPreferences::defaults(None);
+        assert!(owner.allows(NotificationCategory::Payments));
+        assert!(owner.allows(NotificationCategory::Security));
+
+        let member = NotificationPreferences::defaults(Some(7));
+        assert!(!member.allows(NotificationCategory::Payments));
+        assert!(!member.allows(NotificationCategory::Webhooks));
+    }
+
+    #[test]
+    fn test_security_notices_go_to_the_owner_only() {
+        let mut member = NotificationPreferences::defaults(Some(7));
+        member.payments = true;
+        assert!(member.allows(NotificationCategory::Payments));
+        assert!(!member.allows(NotificationCategory::Security));
+
+        let mut owner = NotificationPreferences::defaults(None);
+        owner.payments = false;
+        assert!(!owner.allows(NotificationCategory::Payments));
+        assert!(owner.allows(NotificationCategory::Security));
+    }
+
+    #[test]
+    fn test_saved_row_overrides_defaults() {
+        let preferences = PreferencesRow {
+            merchant_user_id: Some(3),
+            locale: Some("es".to_string()),
+            payments: Some(true),
+            withdrawals: None,
+            invoices: None,
+            webhooks: None,
+            updated_at: None,
+        }
+        .into_preferences();
+
+        assert_eq!(preferences.locale, Locale::Es);
+        assert!(preferences.payments);
+        assert!(!preferences.withdrawals);
+    }
+}
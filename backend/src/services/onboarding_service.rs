@@ -0,0 +1,894 @@
+// Onboarding Service
+// KYC/KYB state machine from registration to approval, and the capabilities each stage unlocks
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::services::document_store::{DocumentStore, LocalDocumentStore};
+use crate::services::volume_tracking_service::KycTier;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::sync::Arc;
+use tracing::info;
+
+/// Content types accepted for uploaded documents
+const ALLOWED_CONTENT_TYPES: &[&str] = &["application/pdf", "image/png", "image/jpeg"];
+
+/// Merchant onboarding stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStatus {
+    Registered,
+    EmailVerified,
+    BusinessInfoSubmitted,
+    DocumentsUploaded,
+    UnderReview,
+    Approved,
+    Rejected,
+}
+
+impl OnboardingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnboardingStatus::Registered => "registered",
+            OnboardingStatus::EmailVerified => "email_verified",
+            OnboardingStatus::BusinessInfoSubmitted => "business_info_submitted",
+            OnboardingStatus::DocumentsUploaded => "documents_uploaded",
+            OnboardingStatus::UnderReview => "under_review",
+            OnboardingStatus::Approved => "approved",
+            OnboardingStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "email_verified" => OnboardingStatus::EmailVerified,
+            "business_info_submitted" => OnboardingStatus::BusinessInfoSubmitted,
+            "documents_uploaded" => OnboardingStatus::DocumentsUploaded,
+            "under_review" => OnboardingStatus::UnderReview,
+            "approved" => OnboardingStatus::Approved,
+            "rejected" => OnboardingStatus::Rejected,
+            _ => OnboardingStatus::Registered,
+        }
+    }
+
+    /// Allowed moves; rejected merchants resubmit their details and documents
+    pub fn can_transition_to(&self, to: OnboardingStatus) -> bool {
+        use OnboardingStatus::*;
+        matches!(
+            (self, to),
+            (Registered, EmailVerified)
+                | (EmailVerified, BusinessInfoSubmitted)
+                | (BusinessInfoSubmitted, DocumentsUploaded)
+                | (DocumentsUploaded, UnderReview)
+                | (UnderReview, Approved)
+                | (UnderReview, Rejected)
+                | (Rejected, BusinessInfoSubmitted)
+                | (Rejected, DocumentsUploaded)
+        )
+    }
+
+    /// Business details and documents may be changed until review starts
+    pub fn is_editable(&self) -> bool {
+        matches!(
+            self,
+            OnboardingStatus::BusinessInfoSubmitted
+                | OnboardingStatus::DocumentsUploaded
+                | OnboardingStatus::Rejected
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    IdentityDocument,
+    BusinessRegistration,
+    ProofOfAddress,
+    Other,
+}
+
+impl DocumentType {
+    /// Documents needed before the merchant can ask for review
+    pub const REQUIRED: [DocumentType; 3] = [
+        DocumentType::IdentityDocument,
+        DocumentType::BusinessRegistration,
+        DocumentType::ProofOfAddress,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::IdentityDocument => "identity_document",
+            DocumentType::BusinessRegistration => "business_registration",
+            DocumentType::ProofOfAddress => "proof_of_address",
+            DocumentType::Other => "other",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "identity_document" => DocumentType::IdentityDocument,
+            "business_registration" => DocumentType::BusinessRegistration,
+            "proof_of_address" => DocumentType::ProofOfAddress,
+            _ => DocumentType::Other,
+        }
+    }
+}
+
+/// What a merchant may do at its current stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    pub sandbox: bool,
+    pub live_mode: bool,
+    pub withdrawals: bool,
+    /// Approved merchants move to a verified KYC tier with higher volume limits
+    pub higher_limits: bool,
+}
+
+impl Capabilities {
+    /// Without `MERCHANT_KYC_REQUIRED`, a verified email is enough for live
+    /// mode and withdrawals; with it, the merchant must be approved.
+    /// Rejected merchants lose both either way.
+    pub fn for_status(status: OnboardingStatus, kyc_required: bool) -> Self {
+        let approved = status == OnboardingStatus::Approved;
+        let unlocked = if kyc_required {
+            approved
+        } else {
+            !matches!(status, OnboardingStatus::Registered | OnboardingStatus::Rejected)
+        };
+
+        Self {
+            sandbox: true,
+            live_mode: unlocked,
+            withdrawals: unlocked,
+            higher_limits: approved,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    LiveMode,
+    Withdrawals,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BusinessInfoRequest {
+    pub legal_name: String,
+    pub business_type: String,
+    pub registration_number: Option<String>,
+    pub country: String,
+    pub address: String,
+    pub website: Option<String>,
+    pub contact_name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BusinessInfo {
+    pub legal_name: String,
+    pub business_type: String,
+    pub registration_number: Option<String>,
+    pub country: String,
+    pub address: String,
+    pub website: Option<String>,
+    pub contact_name: String,
+    pub submitted_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MerchantDocument {
+    pub document_id: String,
+    pub document_type: DocumentType,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub sha256: String,
+    #[serde(skip)]
+    pub storage_backend: String,
+    #[serde(skip)]
+    pub storage_key: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingEvent {
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub admin_id: Option<i64>,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Full onboarding state of a merchant
+#[derive(Debug, Clone, Serialize)]
+pub struct Onboarding {
+    pub merchant_id: i64,
+    pub status: OnboardingStatus,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub business_info: Option<BusinessInfo>,
+    pub documents: Vec<MerchantDocument>,
+    pub missing_documents: Vec<DocumentType>,
+    pub capabilities: Capabilities,
+    pub rejection_reason: Option<String>,
+    pub reviewed_by: Option<i64>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub history: Vec<OnboardingEvent>,
+}
+
+/// Row of the admin review queue
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingSummary {
+    pub merchant_id: i64,
+    pub email: String,
+    pub business_name: String,
+    pub status: OnboardingStatus,
+    pub legal_name: Option<String>,
+    pub country: Option<String>,
+    pub document_count: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct OnboardingService {
+    db_pool: PgPool,
+    store: Arc<dyn DocumentStore>,
+    kyc_required: bool,
+    auto_approval: bool,
+    max_document_bytes: usize,
+}
+
+impl OnboardingService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+            store: Arc::new(LocalDocumentStore::new("./data/kyc-documents")),
+            kyc_required: false,
+            auto_approval: false,
+            max_document_bytes: 10 * 1024 * 1024,
+        }
+    }
+
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self {
+            db_pool,
+            store: crate::services::document_store::document_store_from_config(config),
+            kyc_required: config.merchant_kyc_required,
+            auto_approval: config.merchant_auto_approval,
+            max_document_bytes: config.kyc_document_max_bytes,
+        }
+    }
+
+    pub fn with_store(mut self, store: Arc<dyn DocumentStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    pub fn max_document_bytes(&self) -> usize {
+        self.max_document_bytes
+    }
+
+    pub async fn status(&self, merchant_id: i64) -> Result<OnboardingStatus, ServiceError> {
+        let status = sqlx::query_scalar!(
+            "SELECT onboarding_status FROM merchants WHERE id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+
+        Ok(OnboardingStatus::from_string(&status))
+    }
+
+    pub async fn capabilities(&self, merchant_id: i64) -> Result<Capabilities, ServiceError> {
+        Ok(Capabilities::for_status(self.status(merchant_id).await?, self.kyc_required))
+    }
+
+    /// Fail unless the merchant's onboarding stage grants `capability`
+    pub async fn require(&self, merchant_id: i64, capability: Capability) -> Result<(), ServiceError> {
+        let status = self.status(merchant_id).await?;
+        let capabilities = Capabilities::for_status(status, self.kyc_required);
+        let (allowed, name) = match capability {
+            Capability::LiveMode => (capabilities.live_mode, "Live mode"),
+            Capability::Withdrawals => (capabilities.withdrawals, "Withdrawals"),
+        };
+
+        if allowed {
+            return Ok(());
+        }
+        let needed = if self.kyc_required { "an approved onboarding review" } else { "a verified email address" };
+        Err(ServiceError::Forbidden(format!(
+            "{} requires {} (onboarding status: {})",
+            name,
+            needed,
+            status.as_str()
+        )))
+    }
+
+    pub async fn get_onboarding(&self, merchant_id: i64) -> Result<Onboarding, ServiceError> {
+        let merchant = sqlx::query!(
+            r#"
+            SELECT onboarding_status, email_verified_at, onboarding_reviewed_by,
+                   onboarding_reviewed_at, onboarding_rejection_reason
+            FROM merchants WHERE id = $1
+            "#,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+
+        let business_info = sqlx::query_as!(
+            BusinessInfo,
+            r#"
+            SELECT legal_name, business_type, registration_number, country AS "country!",
+                   address, website, contact_name, submitted_at, updated_at
+            FROM merchant_business_profiles WHERE merchant_id = $1
+            "#,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        let documents = self.list_documents(merchant_id).await?;
+
+        let history = sqlx::query_as!(
+            OnboardingEvent,
+            r#"
+            SELECT from_status, to_status, admin_id, note, created_at
+            FROM merchant_onboarding_events
+            WHERE merchant_id = $1
+            ORDER BY id
+            "#,
+            merchant_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let status = OnboardingStatus::from_string(&merchant.onboarding_status);
+        Ok(Onboarding {
+            merchant_id,
+            status,
+            email_verified_at: merchant.email_verified_at,
+            business_info,
+            missing_documents: missing_documents(&documents),
+            documents,
+            capabilities: Capabilities::for_status(status, self.kyc_required),
+            rejection_reason: merchant.onboarding_rejection_reason,
+            reviewed_by: merchant.onboarding_reviewed_by,
+            reviewed_at: merchant.onboarding_reviewed_at,
+            history,
+        })
+    }
+
+    /// Latest upload of each document first
+    async fn list_documents(&self, merchant_id: i64) -> Result<Vec<MerchantDocument>, ServiceError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT document_id, document_type, file_name, content_type, size_bytes, sha256,
+                   storage_backend, storage_key, uploaded_at
+            FROM merchant_documents
+            WHERE merchant_id = $1
+            ORDER BY uploaded_at DESC, id DESC
+            "#,
+            merchant_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| MerchantDocument {
+                document_id: r.document_id,
+                document_type: DocumentType::from_string(&r.document_type),
+                file_name: r.file_name,
+                content_type: r.content_type,
+                size_bytes: r.size_bytes,
+                sha256: r.sha256,
+                storage_backend: r.storage_backend,
+                storage_key: r.storage_key,
+                uploaded_at: r.uploaded_at,
+            })
+            .collect())
+    }
+
+    /// Move a registered merchant to email verified
+    pub async fn mark_email_verified(&self, merchant_id: i64) -> Result<OnboardingStatus, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+        let status = Self::mark_email_verified_in_tx(&mut tx, merchant_id).await?;
+        tx.commit().await?;
+        Ok(status)
+    }
+
+    /// [`Self::mark_email_verified`] inside the caller's transaction, so it
+    /// commits or rolls back together with whatever verified the address
+    pub async fn mark_email_verified_in_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+    ) -> Result<OnboardingStatus, ServiceError> {
+        let status = sqlx::query_scalar!(
+            "SELECT onboarding_status FROM merchants WHERE id = $1 FOR UPDATE",
+            merchant_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+        let status = OnboardingStatus::from_string(&status);
+        if status != OnboardingStatus::Registered {
+            return Ok(status);
+        }
+
+        sqlx::query!(
+            "UPDATE merchants SET email_verified_at = NOW() WHERE id = $1",
+            merchant_id
+        )
+        .execute(&mut **tx)
+        .await?;
+        transition(tx, merchant_id, status, OnboardingStatus::EmailVerified, None, None).await?;
+
+        Ok(OnboardingStatus::EmailVerified)
+    }
+
+    /// Save business details; the first submission moves the merchant on
+    pub async fn submit_business_info(
+        &self,
+        merchant_id: i64,
+        request: BusinessInfoRequest,
+    ) -> Result<Onboarding, ServiceError> {
+        validate_business_info(&request)?;
+
+        let status = self.status(merchant_id).await?;
+        if status != OnboardingStatus::EmailVerified && !status.is_editable() {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Business details cannot be changed while onboarding is {}",
+                status.as_str()
+            )));
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO merchant_business_profiles (
+                merchant_id, legal_name, business_type, registration_number,
+                country, address, website, contact_name
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (merchant_id) DO UPDATE SET
+                legal_name = EXCLUDED.legal_name,
+                business_type = EXCLUDED.business_type,
+                registration_number = EXCLUDED.registration_number,
+                country = EXCLUDED.country,
+                address = EXCLUDED.address,
+                website = EXCLUDED.website,
+                contact_name = EXCLUDED.contact_name,
+                updated_at = NOW()
+            "#,
+            merchant_id,
+            request.legal_name.trim(),
+            request.business_type.trim(),
+            request.registration_number.as_deref().map(str::trim),
+            request.country.trim().to_uppercase(),
+            request.address.trim(),
+            request.website.as_deref().map(str::trim),
+            request.contact_name.trim(),
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if matches!(status, OnboardingStatus::EmailVerified | OnboardingStatus::Rejected) {
+            transition(&mut tx, merchant_id, status, OnboardingStatus::BusinessInfoSubmitted, None, None).await?;
+        }
+        tx.commit().await?;
+
+        self.get_onboarding(merchant_id).await
+    }
+
+    /// Store a document; once every required type is present the merchant
+    /// moves to documents uploaded
+    pub async fn upload_document(
+        &self,
+        merchant_id: i64,
+        document_type: DocumentType,
+        file_name: &str,
+        content_type: &str,
+        bytes: Vec<u8>,
+    ) -> Result<MerchantDocument, ServiceError> {
+        let status = self.status(merchant_id).await?;
+        if !status.is_editable() {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Documents cannot be uploaded while onboarding is {}",
+                status.as_str()
+            )));
+        }
+
+        let content_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        if !ALLOWED_CONTENT_TYPES.contains(&content_type.as_str()) {
+            return Err(ServiceError::ValidationError(format!(
+                "Unsupported document type {}; use PDF, PNG or JPEG",
+                content_type
+            )));
+        }
+        if bytes.is_empty() {
+            return Err(ServiceError::ValidationError("Document is empty".to_string()));
+        }
+        if bytes.len() > self.max_document_bytes {
+            return Err(ServiceError::ValidationError(format!(
+                "Document exceeds the maximum size of {} bytes",
+                self.max_document_bytes
+            )));
+        }
+
+        let file_name = sanitize_file_name(file_name);
+        let document_id = format!("doc_{}", nanoid::nanoid!(16));
+        let storage_key = format!("merchants/{}/{}-{}", merchant_id, document_id, file_name);
+        let sha256 = hex::encode(Sha256::digest(&bytes));
+        let size_bytes = bytes.len() as i64;
+
+        self.store.put(&storage_key, &content_type, bytes).await?;
+
+        let mut tx = self.db_pool.begin().await?;
+        let uploaded_at = sqlx::query_scalar!(
+            r#"
+            INSERT INTO merchant_documents (
+                document_id, merchant_id, document_type, file_name, content_type,
+                size_bytes, sha256, storage_backend, storage_key
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING uploaded_at
+            "#,
+            document_id,
+            merchant_id,
+            document_type.as_str(),
+            file_name,
+            content_type,
+            size_bytes,
+            sha256,
+            self.store.backend(),
+            storage_key
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if status == OnboardingStatus::BusinessInfoSubmitted {
+            let uploaded: Vec<String> = sqlx::query_scalar!(
+                "SELECT DISTINCT document_type FROM merchant_documents WHERE merchant_id = $1",
+                merchant_id
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+            let complete = DocumentType::REQUIRED
+                .iter()
+                .all(|required| uploaded.iter().any(|t| t == required.as_str()));
+            if complete {
+                transition(&mut tx, merchant_id, status, OnboardingStatus::DocumentsUploaded, None, None).await?;
+            }
+        }
+        tx.commit().await?;
+
+        info!("Stored {} document {} for merchant {}", document_type.as_str(), document_id, merchant_id);
+
+        Ok(MerchantDocument {
+            document_id,
+            document_type,
+            file_name,
+            content_type,
+            size_bytes,
+            sha256,
+            storage_backend: self.store.backend().to_string(),
+            storage_key,
+            uploaded_at,
+        })
+    }
+
+    /// Send the application for review, approving it at once under `MERCHANT_AUTO_APPROVAL`
+    pub async fn submit_for_review(&self, merchant_id: i64) -> Result<Onboarding, ServiceError> {
+        let status = self.status(merchant_id).await?;
+        let documents = self.list_documents(merchant_id).await?;
+        let missing = missing_documents(&documents);
+        if !missing.is_empty() {
+            let names: Vec<&str> = missing.iter().map(|d| d.as_str()).collect();
+            return Err(ServiceError::ValidationError(format!(
+                "Missing required documents: {}",
+                names.join(", ")
+            )));
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        // Resubmissions after a rejection may already have every document
+        let from = if matches!(status, OnboardingStatus::Rejected | OnboardingStatus::BusinessInfoSubmitted) {
+            transition(&mut tx, merchant_id, status, OnboardingStatus::DocumentsUploaded, None, None).await?;
+            OnboardingStatus::DocumentsUploaded
+        } else {
+            status
+        };
+        transition(&mut tx, merchant_id, from, OnboardingStatus::UnderReview, None, None).await?;
+
+        if self.auto_approval {
+            approve_in_tx(&mut tx, merchant_id, None, KycTier::Basic, Some("Approved automatically")).await?;
+        }
+        tx.commit().await?;
+
+        self.get_onboarding(merchant_id).await
+    }
+
+    /// Review queue, oldest update first
+    pub async fn list_reviews(
+        &self,
+        status: Option<OnboardingStatus>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<OnboardingSummary>, ServiceError> {
+        let status = status.unwrap_or(OnboardingStatus::UnderReview);
+        let rows = sqlx::query!(
+            r#"
+            SELECT m.id, m.email, m.business_name, m.onboarding_status, m.updated_at,
+                   p.legal_name AS "legal_name?", p.country AS "country?",
+                   (SELECT COUNT(*) FROM merchant_documents d WHERE d.merchant_id = m.id) AS "document_count!"
+            FROM merchants m
+            LEFT JOIN merchant_business_profiles p ON p.merchant_id = m.id
+            WHERE m.onboarding_status = $1
+            ORDER BY m.updated_at ASC
+            LIMIT $2 OFFSET $3
+            "#,
+            status.as_str(),
+            limit,
+            offset
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| OnboardingSummary {
+                merchant_id: r.id,
+                email: r.email,
+                business_name: r.business_name,
+                status: OnboardingStatus::from_string(&r.onboarding_status),
+                legal_name: r.legal_name,
+                country: r.country,
+                document_count: r.document_count,
+                updated_at: r.updated_at,
+            })
+            .collect())
+    }
+
+    /// Approve an application under review and raise the merchant's KYC tier
+    pub async fn approve(
+        &self,
+        merchant_id: i64,
+        admin_id: i64,
+        tier: KycTier,
+        note: Option<&str>,
+    ) -> Result<Onboarding, ServiceError> {
+        if tier == KycTier::Unverified {
+            return Err(ServiceError::ValidationError(
+                "Approved merchants need a basic or full KYC tier".to_string(),
+            ));
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        approve_in_tx(&mut tx, merchant_id, Some(admin_id), tier, note).await?;
+        tx.commit().await?;
+
+        self.get_onboarding(merchant_id).await
+    }
+
+    /// Reject an application under review; the merchant may resubmit
+    pub async fn reject(&self, merchant_id: i64, admin_id: i64, reason: &str) -> Result<Onboarding, ServiceError> {
+        if reason.trim().is_empty() {
+            return Err(ServiceError::ValidationError("A rejection reason is required".to_string()));
+        }
+
+        let mut tx = self.db_pool.begin().await?;
+        transition(
+            &mut tx,
+            merchant_id,
+            OnboardingStatus::UnderReview,
+            OnboardingStatus::Rejected,
+            Some(admin_id),
+            Some(reason),
+        )
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE merchants
+            SET onboarding_reviewed_by = $1, onboarding_reviewed_at = NOW(), onboarding_rejection_reason = $2
+            WHERE id = $3
+            "#,
+            admin_id,
+            reason,
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        self.get_onboarding(merchant_id).await
+    }
+
+    /// Document metadata and contents, for reviewers
+    pub async fn get_document(
+        &self,
+        merchant_id: i64,
+        document_id: &str,
+    ) -> Result<(MerchantDocument, Vec<u8>), ServiceError> {
+        let document = self
+            .list_documents(merchant_id)
+            .await?
+            .into_iter()
+            .find(|d| d.document_id == document_id)
+            .ok_or_else(|| ServiceError::NotFound("Document not found".to_string()))?;
+
+        if document.storage_backend != self.store.backend() {
+            return Err(ServiceError::Internal(format!(
+                "Document is stored in {} but the configured store is {}",
+                document.storage_backend,
+                self.store.backend()
+            )));
+        }
+        let bytes = self.store.get(&document.storage_key).await?;
+        Ok((document, bytes))
+    }
+}
+
+/// Compare-and-set the merchant's status and record the change
+async fn transition(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    from: OnboardingStatus,
+    to: OnboardingStatus,
+    admin_id: Option<i64>,
+    note: Option<&str>,
+) -> Result<(), ServiceError> {
+    if !from.can_transition_to(to) {
+        return Err(ServiceError::InvalidStateTransition(format!(
+            "Cannot move onboarding from {} to {}",
+            from.as_str(),
+            to.as_str()
+        )));
+    }
+
+    let result = sqlx::query!(
+        "UPDATE merchants SET onboarding_status = $1, updated_at = NOW() WHERE id = $2 AND onboarding_status = $3",
+        to.as_str(),
+        merchant_id,
+        from.as_str()
+    )
+    .execute(&mut **tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(ServiceError::InvalidStateTransition(format!(
+            "Onboarding is no longer {}",
+            from.as_str()
+        )));
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO merchant_onboarding_events (merchant_id, from_status, to_status, admin_id, note)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        merchant_id,
+        from.as_str(),
+        to.as_str(),
+        admin_id,
+        note
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn approve_in_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    admin_id: Option<i64>,
+    tier: KycTier,
+    note: Option<&str>,
+) -> Result<(), ServiceError> {
+    transition(tx, merchant_id, OnboardingStatus::UnderReview, OnboardingStatus::Approved, admin_id, note).await?;
+    sqlx::query!(
+        r#"
+        UPDATE merchants
+        SET kyc_tier = $1, kyc_verified = true,
+            onboarding_reviewed_by = $2, onboarding_reviewed_at = NOW(), onboarding_rejection_reason = NULL
+        WHERE id = $3
+        "#,
+        tier.as_str(),
+        admin_id,
+        merchant_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+fn missing_documents(documents: &[MerchantDocument]) -> Vec<DocumentType> {
+    DocumentType::REQUIRED
+        .iter()
+        .copied()
+        .filter(|required| !documents.iter().any(|d| d.document_type == *required))
+        .collect()
+}
+
+fn validate_business_info(request: &BusinessInfoRequest) -> Result<(), ServiceError> {
+    for (field, value) in [
+        ("legal_name", &request.legal_name),
+        ("business_type", &request.business_type),
+        ("address", &request.address),
+        ("contact_name", &request.contact_name),
+    ] {
+        if value.trim().is_empty() {
+            return Err(ServiceError::ValidationError(format!("{} is required", field)));
+        }
+    }
+
+    let country = request.country.trim();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(ServiceError::ValidationError(
+            "country must be an ISO 3166-1 alpha-2 code".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Keep file names safe to embed in storage keys
+fn sanitize_file_name(file_name: &str) -> String {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .take(100)
+        .collect();
+    let cleaned = cleaned.trim_start_matches('.');
+    if cleaned.is_empty() {
+        "document".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_onboarding_transitions() {
+        use OnboardingStatus::*;
+        assert!(Registered.can_transition_to(EmailVerified));
+        assert!(DocumentsUploaded.can_transition_to(UnderReview));
+        assert!(UnderReview.can_transition_to(Approved));
+        assert!(Rejected.can_transition_to(BusinessInfoSubmitted));
+
+        assert!(!Registered.can_transition_to(UnderReview));
+        assert!(!BusinessInfoSubmitted.can_transition_to(Approved));
+        assert!(!Approved.can_transition_to(Rejected));
+        assert!(!UnderReview.can_transition_to(DocumentsUploaded));
+    }
+
+    #[test]
+    fn test_capabilities_per_stage() {
+        use OnboardingStatus::*;
+
+        // Without mandatory KYC a verified email unlocks live mode
+        assert!(!Capabilities::for_status(Registered, false).live_mode);
+        assert!(Capabilities::for_status(EmailVerified, false).live_mode);
+        assert!(!Capabilities::for_status(Rejected, false).withdrawals);
+        assert!(!Capabilities::for_status(UnderReview, false).higher_limits);
+
+        // With mandatory KYC only approval does
+        assert!(!Capabilities::for_status(UnderReview, true).live_mode);
+        let approved = Capabilities::for_status(Approved, true);
+        assert!(approved.live_mode && approved.withdrawals && approved.higher_limits);
+    }
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("passport scan.pdf"), "passport_scan.pdf");
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_file_name("C:\\docs\\id.png"), "id.png");
+        assert_eq!(sanitize_file_name(".."), "document");
+    }
+}
@@ -0,0 +1,801 @@
+// Outbox
+// Transactional outbox and dispatcher for webhooks and emails
+
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use rand::Rng;
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::models::webhook::WebhookPayload;
+use crate::services::email_service::{EmailService, EmailTemplate};
+use crate::services::event_service::{self, Event};
+use crate::services::webhook_delivery_service::{self, WebhookDeliveryService};
+use crate::services::webhook_endpoint_service;
+use crate::services::webhook_service::{DeliveryError, WebhookService};
+use crate::utils::circuit_breaker::CircuitBreaker;
+
+/// Send attempts before a queued email is marked failed
+pub const MAX_EMAIL_ATTEMPTS: i32 = 5;
+
+/// Longest wait between two webhook attempts
+const MAX_RETRY_DELAY_SECONDS: u64 = 12 * 60 * 60;
+
+/// Deliveries still failing this long after they were queued are dead-lettered
+const RETRY_WINDOW_SECONDS: u64 = 3 * 24 * 60 * 60;
+
+/// Consecutive failures that open an endpoint's circuit
+const ENDPOINT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open endpoint circuit defers deliveries before a trial request
+const ENDPOINT_OPEN_SECONDS: u64 = 300;
+
+const WEBHOOK_BATCH_SIZE: i64 = 100;
+const EMAIL_BATCH_SIZE: i64 = 50;
+
+/// How long a claimed row is hidden from other dispatchers
+const CLAIM_LEASE_SECONDS: f64 = 300.0;
+
+/// Record an event and queue its webhooks inside the caller's transaction
+///
+/// Webhook subscribers receive the generic event envelope. Nothing is sent
+/// if the transaction rolls back.
+///
+/// # Arguments
+/// * `tx` - Transaction that makes the state change the event describes
+/// * `merchant_id` - Merchant the event belongs to
+/// * `event_type` - Event type (e.g., "withdrawal.completed")
+/// * `object_id` - Public ID of the object the event is about
+/// * `data` - Snapshot of the object
+pub async fn enqueue_event(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    event_type: &str,
+    object_id: &str,
+    data: serde_json::Value,
+) -> Result<Event, ServiceError> {
+    let event = event_service::record_event(&mut **tx, merchant_id, event_type, object_id, data).await?;
+    let body = event_envelope(&event, merchant_id);
+
+    queue_deliveries(tx, merchant_id, None, &event, body).await?;
+
+    Ok(event)
+}
+
+/// Record a payment event and queue its webhooks inside the caller's transaction
+///
+/// The event snapshot is the payment row as seen by the transaction; the
+/// webhook body is the payment [`WebhookPayload`] with its `event_id` set.
+///
+/// # Arguments
+/// * `tx` - Transaction that changed the payment
+/// * `payment_id` - Database ID of the payment
+/// * `payload` - Payment webhook payload
+pub async fn enqueue_payment_event(
+    tx: &mut Transaction<'_, Postgres>,
+    payment_id: i64,
+    mut payload: WebhookPayload,
+) -> Result<Event, ServiceError> {
+    let snapshot = sqlx::query_scalar!(
+        r#"SELECT to_jsonb(pt) - 'id' AS "snapshot!" FROM payment_transactions pt WHERE id = $1"#,
+        payment_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let mut data = json!({ "object": snapshot });
+    if let Some(details) = &payload.data {
+        data["details"] = details.clone();
+    }
+
+    let event = event_service::record_event(
+        &mut **tx,
+        payload.merchant_id,
+        &payload.event_type,
+        &payload.payment_id,
+        data,
+    )
+    .await?;
+    payload.event_id = Some(event.event_id.clone());
+
+    let body = serde_json::to_value(&payload)?;
+    queue_deliveries(tx, payload.merchant_id, Some(payment_id), &event, body).await?;
+
+    Ok(event)
+}
+
+/// Queue an email inside the caller's transaction (or directly on the pool)
+pub async fn enqueue_email<'e, E: PgExecutor<'e>>(
+    executor: E,
+    merchant_id: Option<i64>,
+    template: &EmailTemplate,
+) -> Result<(), ServiceError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_outbox (merchant_id, recipient, subject, body, html_body, template, locale)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        merchant_id,
+        template.to,
+        template.subject,
+        template.body,
+        template.html_body,
+        template.template,
+        template.locale
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Webhook body for events that are not about a payment
+pub(crate) fn event_envelope(event: &Event, merchant_id: i64) -> serde_json::Value {
+    json!({
+        "event_id": event.event_id,
+        "event_type": event.event_type,
+        "api_version": event.api_version,
+        "merchant_id": merchant_id,
+        "object_type": event.object_type,
+        "object_id": event.object_id,
+        "data": event.data,
+        "timestamp": event.created_at.timestamp(),
+    })
+}
+
+/// One delivery row per active endpoint subscribed to the event
+async fn queue_deliveries(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    payment_id: Option<i64>,
+    event: &Event,
+    body: serde_json::Value,
+) -> Result<(), ServiceError> {
+    let targets = webhook_endpoint_service::delivery_targets(&mut **tx, merchant_id, &event.event_type).await?;
+
+    for target in &targets {
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_deliveries (
+                merchant_id, payment_id, event_type, url, payload,
+                status, attempts, next_retry_at, created_at, event_id, endpoint_id
+            )
+            VALUES ($1, $2, $3, $4, $5, 'pending', 0, NOW(), NOW(), $6, $7)
+            "#,
+            merchant_id,
+            payment_id,
+            &event.event_type,
+            &target.url,
+            body,
+            &event.event_id,
+            target.id
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Delay before retry `attempt` (1-based): 1s, 2s, 4s, 8s, ...
+fn retry_delay_seconds(attempt: i32) -> i64 {
+    2_i64.pow(attempt.saturating_sub(1).clamp(0, 20) as u32)
+}
+
+/// When and how often failed webhook deliveries are retried
+///
+/// Uses full-jitter exponential backoff: the wait before retry `n` is drawn
+/// uniformly from `[0, min(max_delay, base_delay * 2^(n-1))]`. A longer
+/// `Retry-After` from the receiver wins, up to `max_delay`. With the default
+/// settings the retries span about three days; deliveries older than `window`
+/// are dead-lettered even if attempts remain.
+#[derive(Debug, Clone)]
+pub struct RetrySchedule {
+    /// Total attempts, including the first
+    pub max_attempts: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub window: Duration,
+}
+
+impl Default for RetrySchedule {
+    fn default() -> Self {
+        Self {
+            max_attempts: 19,
+            base_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(MAX_RETRY_DELAY_SECONDS),
+            window: Duration::from_secs(RETRY_WINDOW_SECONDS),
+        }
+    }
+}
+
+impl RetrySchedule {
+    /// Schedule from `webhook_max_retries` and `webhook_retry_delay_seconds`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_attempts: config.webhook_max_retries.min(i32::MAX as u32 - 1) as i32 + 1,
+            base_delay: Duration::from_secs(config.webhook_retry_delay_seconds.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// Upper bound of the wait before retry `attempt` (1-based)
+    fn ceiling(&self, attempt: i32) -> Duration {
+        let exponent = attempt.saturating_sub(1).clamp(0, 32) as u32;
+        self.base_delay
+            .checked_mul(2_u32.saturating_pow(exponent))
+            .map_or(self.max_delay, |d| d.min(self.max_delay))
+    }
+
+    /// Wait before retry `attempt`, honouring the receiver's `Retry-After`
+    pub fn next_delay<R: Rng>(&self, attempt: i32, retry_after: Option<Duration>, rng: &mut R) -> Duration {
+        let ceiling = self.ceiling(attempt).as_millis() as u64;
+        let jittered = Duration::from_millis(rng.gen_range(0..=ceiling));
+
+        match retry_after {
+            Some(requested) => jittered.max(requested.min(self.max_delay)),
+            None => jittered,
+        }
+    }
+
+    /// Whether a failed attempt leaves the delivery out of retries
+    fn is_exhausted(&self, attempt: i32, queued_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let age = (now - queued_at).to_std().unwrap_or(Duration::ZERO);
+        attempt >= self.max_attempts || age >= self.window
+    }
+}
+
+struct ClaimedDelivery {
+    id: i64,
+    merchant_id: i64,
+    event_type: String,
+    payload: serde_json::Value,
+    attempts: i32,
+    endpoint_id: Option<i64>,
+    event_id: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// Lease the next due webhook deliveries
+///
+/// A delivery is skipped while an earlier one for the same merchant and
+/// endpoint is still pending outside this batch (waiting to retry, leased by
+/// another dispatcher or held for a disabled endpoint).
+async fn claim_webhooks(db_pool: &PgPool, max_attempts: i32) -> Result<Vec<ClaimedDelivery>, ServiceError> {
+    let claimed = sqlx::query_as!(
+        ClaimedDelivery,
+        r#"
+        WITH candidates AS (
+            SELECT id, merchant_id, endpoint_id
+            FROM webhook_deliveries
+            WHERE status = 'pending'
+              AND next_retry_at <= NOW()
+              AND attempts < $1
+              AND (endpoint_id IS NULL
+                   OR endpoint_id IN (SELECT id FROM webhook_endpoints WHERE is_active = true))
+            ORDER BY id ASC
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE webhook_deliveries
+        SET next_retry_at = NOW() + make_interval(secs => $3)
+        WHERE id IN (
+            SELECT c.id
+            FROM candidates c
+            WHERE NOT EXISTS (
+                SELECT 1
+                FROM webhook_deliveries earlier
+                WHERE earlier.merchant_id = c.merchant_id
+                  AND earlier.endpoint_id IS NOT DISTINCT FROM c.endpoint_id
+                  AND earlier.status = 'pending'
+                  AND earlier.attempts < $1
+                  AND earlier.id < c.id
+                  AND earlier.id NOT IN (SELECT id FROM candidates)
+            )
+        )
+        RETURNING id, merchant_id, event_type, payload, attempts, endpoint_id, event_id, created_at
+        "#,
+        max_attempts,
+        WEBHOOK_BATCH_SIZE,
+        CLAIM_LEASE_SECONDS
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(claimed)
+}
+
+/// Delivers queued webhooks and emails
+///
+/// Rows are claimed with a lease so several dispatchers can run at once.
+/// Each merchant's webhooks are sent one at a time in the order their events
+/// were recorded; different merchants are served concurrently. While a
+/// delivery waits to be retried, later deliveries to the same endpoint are
+/// held back, so receivers see events in order. Delivering or dead-lettering
+/// the waiting delivery releases the rest.
+///
+/// Every endpoint has its own circuit breaker. While it is open, deliveries
+/// to the endpoint are pushed back without a request or an attempt being
+/// counted, so a dead endpoint cannot tie up the dispatcher.
+pub struct OutboxDispatcher {
+    db_pool: PgPool,
+    webhook_service: Arc<WebhookService>,
+    webhook_delivery_service: Arc<WebhookDeliveryService>,
+    email_service: Arc<EmailService>,
+    retry_schedule: RetrySchedule,
+    circuit_breakers: Mutex<HashMap<i64, Arc<CircuitBreaker>>>,
+}
+
+impl OutboxDispatcher {
+    pub fn new(
+        db_pool: PgPool,
+        webhook_service: Arc<WebhookService>,
+        email_service: Arc<EmailService>,
+    ) -> Self {
+        Self {
+            webhook_delivery_service: Arc::new(WebhookDeliveryService::new(db_pool.clone())),
+            db_pool,
+            webhook_service,
+            email_service,
+            retry_schedule: RetrySchedule::default(),
+            circuit_breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use a different webhook retry schedule
+    pub fn with_retry_schedule(mut self, retry_schedule: RetrySchedule) -> Self {
+        self.retry_schedule = retry_schedule;
+        self
+    }
+
+    /// Circuit breaker of an endpoint, created on first use
+    fn circuit_breaker(&self, endpoint_id: i64) -> Arc<CircuitBreaker> {
+        let mut breakers = self.circuit_breakers.lock().unwrap_or_else(|e| e.into_inner());
+        breakers
+            .entry(endpoint_id)
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(ENDPOINT_FAILURE_THRESHOLD, ENDPOINT_OPEN_SECONDS)))
+            .clone()
+    }
+
+    /// Deliver everything that is due
+    pub async fn dispatch(&self) -> Result<(), ServiceError> {
+        self.dispatch_webhooks().await?;
+        self.dispatch_emails().await?;
+        Ok(())
+    }
+
+    /// Deliver due webhooks, retrying on the [`RetrySchedule`]
+    ///
+    /// Once the schedule is exhausted the delivery is marked failed and moved
+    /// to the dead-letter queue. Deliveries to disabled endpoints wait until
+    /// the endpoint is re-enabled.
+    ///
+    /// # Requirements
+    /// * 4.4: Retry webhook delivery with exponential backoff
+    /// * 4.7: Log all webhook delivery attempts and results
+    pub async fn dispatch_webhooks(&self) -> Result<(), ServiceError> {
+        let mut claimed = claim_webhooks(&self.db_pool, self.retry_schedule.max_attempts).await?;
+
+        if claimed.is_empty() {
+            return Ok(());
+        }
+
+        info!("Dispatching {} webhook deliveries", claimed.len());
+
+        // Keep each merchant's deliveries in event order
+        claimed.sort_by_key(|d| d.id);
+        let mut by_merchant: BTreeMap<i64, Vec<ClaimedDelivery>> = BTreeMap::new();
+        for delivery in claimed {
+            by_merchant.entry(delivery.merchant_id).or_default().push(delivery);
+        }
+
+        let results = join_all(by_merchant.into_values().map(|deliveries| async move {
+            // Endpoints whose earlier delivery is waiting for a retry
+            let mut blocked = HashSet::new();
+            for delivery in deliveries {
+                if blocked.contains(&delivery.endpoint_id) {
+                    self.release_webhook(&delivery).await?;
+                    continue;
+                }
+                let endpoint_id = delivery.endpoint_id;
+                if !self.deliver_webhook(delivery).await? {
+                    blocked.insert(endpoint_id);
+                }
+            }
+            Ok::<(), ServiceError>(())
+        }))
+        .await;
+
+        for result in results {
+            if let Err(e) = result {
+                error!("Error dispatching webhooks: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attempt a delivery; returns whether later deliveries to the endpoint may follow
+    async fn deliver_webhook(&self, delivery: ClaimedDelivery) -> Result<bool, ServiceError> {
+        let attempt_number = delivery.attempts + 1;
+        let breaker = delivery.endpoint_id.map(|id| self.circuit_breaker(id));
+
+        if let Some(breaker) = &breaker {
+            if let Some(remaining) = breaker.open_remaining().await {
+                self.defer_webhook(&delivery, remaining).await?;
+                return Ok(false);
+            }
+        }
+
+        info!(
+            "Delivering webhook {} (attempt {}/{}) for merchant {} - event: {}",
+            delivery.id, attempt_number, self.retry_schedule.max_attempts, delivery.merchant_id, delivery.event_type
+        );
+
+        let body = delivery.payload.to_string();
+        let started = Instant::now();
+        let send = || {
+            self.webhook_service
+                .deliver(delivery.endpoint_id, delivery.event_id.as_deref(), &body)
+        };
+        let result: Result<(u16, String), DeliveryError> = match &breaker {
+            Some(breaker) => breaker.call(send).await,
+            None => send().await,
+        };
+        let latency_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+        match result {
+            Ok((status_code, response_body)) => {
+                sqlx::query!(
+                    r#"
+                    UPDATE webhook_deliveries
+                    SET status = 'delivered',
+                        attempts = $1,
+                        last_attempt_at = $2,
+                        next_retry_at = NULL,
+                        response_status = $3,
+                        response_body = $4,
+                        latency_ms = $5
+                    WHERE id = $6
+                    "#,
+                    attempt_number,
+                    Utc::now(),
+                    status_code as i32,
+                    webhook_delivery_service::truncate_response_body(response_body),
+                    latency_ms,
+                    delivery.id
+                )
+                .execute(&self.db_pool)
+                .await?;
+
+                if let Some(endpoint_id) = delivery.endpoint_id {
+                    self.webhook_delivery_service.record_endpoint_success(endpoint_id).await?;
+                }
+
+                info!("Webhook delivery {} succeeded on attempt {}", delivery.id, attempt_number);
+                Ok(true)
+            }
+            Err(e) => {
+                let now = Utc::now();
+                let exhausted = self.retry_schedule.is_exhausted(attempt_number, delivery.created_at, now);
+                let delay = self
+                    .retry_schedule
+                    .next_delay(attempt_number, e.retry_after, &mut rand::thread_rng());
+                let next_retry = (!exhausted).then(|| {
+                    now + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero())
+                });
+
+                sqlx::query!(
+                    r#"
+                    UPDATE webhook_deliveries
+                    SET status = CASE WHEN $1 THEN 'failed' ELSE 'pending' END,
+                        attempts = $2,
+                        last_attempt_at = $3,
+                        next_retry_at = $4,
+                        response_status = $5,
+                        response_body = $6,
+                        latency_ms = $7,
+                        dead_lettered_at = CASE WHEN $1 THEN NOW() ELSE NULL END
+                    WHERE id = $8
+                    "#,
+                    exhausted,
+                    attempt_number,
+                    now,
+                    next_retry,
+                    e.status.map(i32::from),
+                    webhook_delivery_service::truncate_response_body(e.to_string()),
+                    latency_ms,
+                    delivery.id
+                )
+                .execute(&self.db_pool)
+                .await?;
+
+                if let Some(endpoint_id) = delivery.endpoint_id {
+                    self.webhook_delivery_service.record_endpoint_failure(endpoint_id).await?;
+                }
+
+                if exhausted {
+                    error!(
+                        "Webhook delivery {} failed permanently after {} attempts, moved to dead-letter queue",
+                        delivery.id, attempt_number
+                    );
+                } else {
+                    warn!(
+                        "Webhook delivery {} failed on attempt {}, will retry in {}s",
+                        delivery.id, attempt_number, delay.as_secs()
+                    );
+                }
+                Ok(exhausted)
+            }
+        }
+    }
+
+    /// Give up the lease on a delivery held back behind a retry
+    async fn release_webhook(&self, delivery: &ClaimedDelivery) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET next_retry_at = NOW() WHERE id = $1",
+            delivery.id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Push a delivery back while its endpoint's circuit is open
+    ///
+    /// No request is made and the attempt is not counted.
+    async fn defer_webhook(&self, delivery: &ClaimedDelivery, remaining: Duration) -> Result<(), ServiceError> {
+        let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..=1000));
+        let next_retry = Utc::now()
+            + chrono::Duration::from_std(remaining + jitter).unwrap_or_else(|_| chrono::Duration::zero());
+
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET next_retry_at = $1 WHERE id = $2",
+            next_retry,
+            delivery.id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        info!(
+            "Webhook delivery {} deferred {}s, endpoint circuit is open",
+            delivery.id,
+            remaining.as_secs()
+        );
+
+        Ok(())
+    }
+
+    /// Send due emails with exponential backoff
+    pub async fn dispatch_emails(&self) -> Result<(), ServiceError> {
+        let claimed = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY id ASC
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, body, html_body, template, locale, attempts
+            "#,
+            EMAIL_BATCH_SIZE,
+            CLAIM_LEASE_SECONDS
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        for email in claimed {
+            let attempt_number = email.attempts + 1;
+            let template = EmailTemplate {
+                to: email.recipient,
+                subject: email.subject,
+                body: email.body,
+                html_body: email.html_body,
+                template: email.template,
+                locale: email.locale,
+            };
+
+            match self.email_service.send_template(&template).await {
+                Ok(()) => {
+                    sqlx::query!(
+                        r#"
+                        UPDATE email_outbox
+                        SET status = 'sent', attempts = $1, sent_at = NOW(), next_attempt_at = NULL, last_error = NULL
+                        WHERE id = $2
+                        "#,
+                        attempt_number,
+                        email.id
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+                }
+                Err(e) => {
+                    let exhausted = attempt_number >= MAX_EMAIL_ATTEMPTS;
+                    let next_attempt = (!exhausted)
+                        .then(|| Utc::now() + chrono::Duration::seconds(retry_delay_seconds(attempt_number) * 60));
+
+                    sqlx::query!(
+                        r#"
+                        UPDATE email_outbox
+                        SET status = CASE WHEN $1 THEN 'failed' ELSE 'pending' END,
+                            attempts = $2, next_attempt_at = $3, last_error = $4
+                        WHERE id = $5
+                        "#,
+                        exhausted,
+                        attempt_number,
+                        next_attempt,
+                        e.to_string(),
+                        email.id
+                    )
+                    .execute(&self.db_pool)
+                    .await?;
+
+                    warn!(
+                        "Email {} to {} failed on attempt {}: {}",
+                        email.id, template.to, attempt_number, e
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay_seconds(1), 1);
+        assert_eq!(retry_delay_seconds(2), 2);
+        assert_eq!(retry_delay_seconds(5), 16);
+        assert_eq!(retry_delay_seconds(0), 1);
+    }
+
+    #[test]
+    fn test_retry_schedule_full_jitter_stays_under_ceiling() {
+        let schedule = RetrySchedule::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for attempt in 1..=schedule.max_attempts {
+            let ceiling = schedule.ceiling(attempt);
+            for _ in 0..50 {
+                assert!(schedule.next_delay(attempt, None, &mut rng) <= ceiling);
+            }
+        }
+
+        assert_eq!(schedule.ceiling(1), Duration::from_secs(5));
+        assert_eq!(schedule.ceiling(3), Duration::from_secs(20));
+        assert_eq!(schedule.ceiling(40), schedule.max_delay);
+    }
+
+    #[test]
+    fn test_default_retry_schedule_spans_about_three_days() {
+        let schedule = RetrySchedule::default();
+        let total: Duration = (1..schedule.max_attempts).map(|a| schedule.ceiling(a)).sum();
+
+        assert!(total > Duration::from_secs(2 * 24 * 60 * 60));
+        assert!(total <= schedule.window);
+    }
+
+    #[test]
+    fn test_retry_after_is_honoured_up_to_max_delay() {
+        let schedule = RetrySchedule::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let delay = schedule.next_delay(1, Some(Duration::from_secs(600)), &mut rng);
+        assert_eq!(delay, Duration::from_secs(600));
+
+        let delay = schedule.next_delay(1, Some(Duration::from_secs(7 * 24 * 60 * 60)), &mut rng);
+        assert_eq!(delay, schedule.max_delay);
+    }
+
+    #[test]
+    fn test_retry_schedule_exhausted_by_attempts_or_age() {
+        let schedule = RetrySchedule {
+            max_attempts: 4,
+            ..RetrySchedule::default()
+        };
+        let now = Utc::now();
+
+        assert!(!schedule.is_exhausted(3, now, now));
+        assert!(schedule.is_exhausted(4, now, now));
+        assert!(schedule.is_exhausted(1, now - chrono::Duration::days(3), now));
+    }
+
+    #[test]
+    fn test_event_envelope_carries_event_fields() {
+        let event = Event {
+            event_id: "evt_123".to_string(),
+            event_type: "withdrawal.completed".to_string(),
+            api_version: event_service::API_VERSION.to_string(),
+            object_type: "withdrawal".to_string(),
+            object_id: "wd_1".to_string(),
+            data: json!({ "object": { "status": "COMPLETED" } }),
+            created_at: Utc::now(),
+        };
+
+        let body = event_envelope(&event, 7);
+        assert_eq!(body["event_id"], "evt_123");
+        assert_eq!(body["event_type"], "withdrawal.completed");
+        assert_eq!(body["merchant_id"], 7);
+        assert_eq!(body["data"]["object"]["status"], "COMPLETED");
+    }
+
+    async fn insert_delivery(pool: &PgPool, merchant_id: i64, attempts: i32, next_retry_in_secs: f64) -> i64 {
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO webhook_deliveries (merchant_id, event_type, url, payload, status, attempts, next_retry_at)
+            VALUES ($1, 'payment.confirmed', 'https://example.com/webhook', '{}'::jsonb, 'pending', $2,
+                    NOW() + make_interval(secs => $3))
+            RETURNING id
+            "#,
+            merchant_id,
+            attempts,
+            next_retry_in_secs
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "requires database"]
+    async fn test_later_deliveries_wait_for_a_retry() {
+        let database_url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must point at a test database");
+        let pool = PgPool::connect(&database_url).await.unwrap();
+        let merchant_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO merchants (email, business_name, api_key_hash, fee_percentage, is_active, sandbox_mode)
+            VALUES ($1, 'Test Business', $2, 1.50, true, false)
+            RETURNING id
+            "#,
+            format!("test{}@example.com", uuid::Uuid::new_v4().simple()),
+            format!("test_hash_{}", uuid::Uuid::new_v4().simple())
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        // The first event failed once and waits to be retried; the second is due
+        let retrying = insert_delivery(&pool, merchant_id, 1, 3600.0).await;
+        let later = insert_delivery(&pool, merchant_id, 0, 0.0).await;
+
+        let claimed = claim_webhooks(&pool, 5).await.unwrap();
+        assert!(claimed.iter().all(|d| d.merchant_id != merchant_id));
+
+        // Dead-lettering the retry unblocks the queue
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = 'failed', next_retry_at = NULL, dead_lettered_at = NOW() WHERE id = $1",
+            retrying
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let claimed = claim_webhooks(&pool, 5).await.unwrap();
+        let ours: Vec<i64> = claimed.iter().filter(|d| d.merchant_id == merchant_id).map(|d| d.id).collect();
+        assert_eq!(ours, vec![later]);
+
+        sqlx::query!("DELETE FROM merchants WHERE id = $1", merchant_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+// Payment Policy Service
+// Per-merchant handling of underpaid and overpaid payments
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::error::ServiceError;
+use crate::payment::models::PaymentStatus;
+
+/// What to do when a payment falls short of the requested amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnderpaymentAction {
+    /// Keep the payment open so the customer can send the remaining balance
+    AwaitTopUp,
+    /// Confirm the payment for whatever was received
+    Accept,
+    /// Fail the payment
+    Fail,
+}
+
+impl UnderpaymentAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UnderpaymentAction::AwaitTopUp => "await_top_up",
+            UnderpaymentAction::Accept => "accept",
+            UnderpaymentAction::Fail => "fail",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "accept" => UnderpaymentAction::Accept,
+            "fail" => UnderpaymentAction::Fail,
+            _ => UnderpaymentAction::AwaitTopUp,
+        }
+    }
+}
+
+/// What to do with the surplus of an overpaid payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverpaymentAction {
+    /// Record the surplus as refundable and leave it to the merchant
+    RecordSurplus,
+    /// Create a pending refund for the surplus automatically
+    AutoRefund,
+}
+
+impl OverpaymentAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverpaymentAction::RecordSurplus => "record_surplus",
+            OverpaymentAction::AutoRefund => "auto_refund",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "auto_refund" => OverpaymentAction::AutoRefund,
+            _ => OverpaymentAction::RecordSurplus,
+        }
+    }
+}
+
+/// Merchant policy for amount mismatches
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentPolicy {
+    pub merchant_id: i64,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub underpayment_tolerance_percent: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub overpayment_tolerance_percent: Decimal,
+    pub underpayment_action: UnderpaymentAction,
+    pub overpayment_action: OverpaymentAction,
+    pub top_up_window_minutes: i32,
+}
+
+/// How the amount received compares to the amount requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountOutcome {
+    /// Received amount is within tolerance of the requested amount
+    Exact,
+    /// Received amount is short by `remaining`
+    Underpaid { remaining: Decimal },
+    /// Received amount exceeds the requested amount by `surplus`
+    Overpaid { surplus: Decimal },
+}
+
+impl PaymentPolicy {
+    /// Policy applied to merchants that never configured one
+    pub fn default_for(merchant_id: i64) -> Self {
+        Self {
+            merchant_id,
+            underpayment_tolerance_percent: Decimal::new(10, 2), // 0.10%
+            overpayment_tolerance_percent: Decimal::new(10, 2),  // 0.10%
+            underpayment_action: UnderpaymentAction::AwaitTopUp,
+            overpayment_action: OverpaymentAction::RecordSurplus,
+            top_up_window_minutes: 30,
+        }
+    }
+
+    /// Compare the total received against the requested amount
+    pub fn classify(&self, requested: Decimal, received: Decimal) -> AmountOutcome {
+        let hundred = Decimal::from(100);
+
+        if received < requested {
+            let shortfall = requested - received;
+            if shortfall <= requested * self.underpayment_tolerance_percent / hundred {
+                AmountOutcome::Exact
+            } else {
+                AmountOutcome::Underpaid { remaining: shortfall }
+            }
+        } else {
+            let surplus = received - requested;
+            if surplus <= requested * self.overpayment_tolerance_percent / hundred {
+                AmountOutcome::Exact
+            } else {
+                AmountOutcome::Overpaid { surplus }
+            }
+        }
+    }
+
+    /// Status a payment should move to for the given outcome
+    ///
+    /// `partial_payments_enabled` forces an open top-up window regardless of
+    /// the configured underpayment action.
+    pub fn status_for(&self, outcome: AmountOutcome, partial_payments_enabled: bool) -> PaymentStatus {
+        match outcome {
+            AmountOutcome::Exact => PaymentStatus::Confirmed,
+            AmountOutcome::Overpaid { .. } => PaymentStatus::Overpaid,
+            AmountOutcome::Underpaid { .. } if partial_payments_enabled => PaymentStatus::Underpaid,
+            AmountOutcome::Underpaid { .. } => match self.underpayment_action {
+                UnderpaymentAction::AwaitTopUp => PaymentStatus::Underpaid,
+                UnderpaymentAction::Accept => PaymentStatus::Confirmed,
+                UnderpaymentAction::Fail => PaymentStatus::Failed,
+            },
+        }
+    }
+}
+
+/// Partial update of a merchant payment policy
+#[derive(Debug, Deserialize)]
+pub struct UpdatePaymentPolicyRequest {
+    #[serde(with = "rust_decimal::serde::str_option", default)]
+    pub underpayment_tolerance_percent: Option<Decimal>,
+    #[serde(with = "rust_decimal::serde::str_option", default)]
+    pub overpayment_tolerance_percent: Option<Decimal>,
+    #[serde(default)]
+    pub underpayment_action: Option<UnderpaymentAction>,
+    #[serde(default)]
+    pub overpayment_action: Option<OverpaymentAction>,
+    #[serde(default)]
+    pub top_up_window_minutes: Option<i32>,
+}
+
+pub struct PaymentPolicyService {
+    db_pool: PgPool,
+}
+
+impl PaymentPolicyService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Get the merchant's policy, falling back to the defaults
+    pub async fn get_policy(&self, merchant_id: i64) -> Result<PaymentPolicy, ServiceError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT underpayment_tolerance_percent, overpayment_tolerance_percent,
+                   underpayment_action, overpayment_action, top_up_window_minutes
+            FROM merchant_payment_policies
+            WHERE merchant_id = $1
+            "#,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(match row {
+            Some(row) => PaymentPolicy {
+                merchant_id,
+                underpayment_tolerance_percent: row.underpayment_tolerance_percent,
+                overpayment_tolerance_percent: row.overpayment_tolerance_percent,
+                underpayment_action: UnderpaymentAction::from_string(&row.underpayment_action),
+                overpayment_action: OverpaymentAction::from_string(&row.overpayment_action),
+                top_up_window_minutes: row.top_up_window_minutes,
+            },
+            None => PaymentPolicy::default_for(merchant_id),
+        })
+    }
+
+    /// Update the merchant's policy, creating it on first use
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `request` - Fields to change; omitted fields keep their current value
+    pub async fn update_policy(
+        &self,
+        merchant_id: i64,
+        request: UpdatePaymentPolicyRequest,
+    ) -> Result<PaymentPolicy, ServiceError> {
+        let mut policy = self.get_policy(merchant_id).await?;
+
+        if let Some(value) = request.underpayment_tolerance_percent {
+            policy.underpayment_tolerance_percent = value;
+        }
+        if let Some(value) = request.overpayment_tolerance_percent {
+            policy.overpayment_tolerance_percent = value;
+        }
+        if let Some(value) = request.underpayment_action {
+            policy.underpayment_action = value;
+        }
+        if let Some(value) = request.overpayment_action {
+            policy.overpayment_action = value;
+        }
+        if let Some(value) = request.top_up_window_minutes {
+            policy.top_up_window_minutes = value;
+        }
+
+        Self::validate(&policy)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO merchant_payment_policies (
+                merchant_id, underpayment_tolerance_percent, overpayment_tolerance_percent,
+                underpayment_action, overpayment_action, top_up_window_minutes
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (merchant_id) DO UPDATE SET
+                underpayment_tolerance_percent = EXCLUDED.underpayment_tolerance_percent,
+                overpayment_tolerance_percent = EXCLUDED.overpayment_tolerance_percent,
+                underpayment_action = EXCLUDED.underpayment_action,
+                overpayment_action = EXCLUDED.overpayment_action,
+                top_up_window_minutes = EXCLUDED.top_up_window_minutes,
+                updated_at = NOW()
+            "#,
+            merchant_id,
+            policy.underpayment_tolerance_percent,
+            policy.overpayment_tolerance_percent,
+            policy.underpayment_action.as_str(),
+            policy.overpayment_action.as_str(),
+            policy.top_up_window_minutes
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    fn validate(policy: &PaymentPolicy) -> Result<(), ServiceError> {
+        let max_tolerance = Decimal::from(10);
+        for (name, value) in [
+            ("underpayment_tolerance_percent", policy.underpayment_tolerance_percent),
+            ("overpayment_tolerance_percent", policy.overpayment_tolerance_percent),
+        ] {
+            if value < Decimal::ZERO || value > max_tolerance {
+                return Err(ServiceError::ValidationError(format!(
+                    "{} must be between 0 and 10",
+                    name
+                )));
+            }
+        }
+
+        if policy.top_up_window_minutes <= 0 || policy.top_up_window_minutes > 1440 {
+            return Err(ServiceError::ValidationError(
+                "top_up_window_minutes must be between 1 and 1440".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_within_tolerance() {
+        let policy = PaymentPolicy::default_for(1);
+        assert_eq!(policy.classify(Decimal::from(100), Decimal::from(100)), AmountOutcome::Exact);
+        assert_eq!(policy.classify(Decimal::from(100), Decimal::new(9995, 2)), AmountOutcome::Exact);
+        assert_eq!(policy.classify(Decimal::from(100), Decimal::new(10005, 2)), AmountOutcome::Exact);
+    }
+
+    #[test]
+    fn test_classify_underpaid_and_overpaid() {
+        let policy = PaymentPolicy::default_for(1);
+        assert_eq!(
+            policy.classify(Decimal::from(100), Decimal::from(60)),
+            AmountOutcome::Underpaid { remaining: Decimal::from(40) }
+        );
+        assert_eq!(
+            policy.classify(Decimal::from(100), Decimal::from(125)),
+            AmountOutcome::Overpaid { surplus: Decimal::from(25) }
+        );
+    }
+
+    #[test]
+    fn test_status_for_underpayment_actions() {
+        let mut policy = PaymentPolicy::default_for(1);
+        let short = AmountOutcome::Underpaid { remaining: Decimal::ONE };
+
+        assert_eq!(policy.status_for(short, false), PaymentStatus::Underpaid);
+
+        policy.underpayment_action = UnderpaymentAction::Accept;
+        assert_eq!(policy.status_for(short, false), PaymentStatus::Confirmed);
+
+        policy.underpayment_action = UnderpaymentAction::Fail;
+        assert_eq!(policy.status_for(short, false), PaymentStatus::Failed);
+        assert_eq!(policy.status_for(short, true), PaymentStatus::Underpaid);
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_values() {
+        let mut policy = PaymentPolicy::default_for(1);
+        assert!(PaymentPolicyService::validate(&policy).is_ok());
+
+        policy.overpayment_tolerance_percent = Decimal::from(11);
+        assert!(PaymentPolicyService::validate(&policy).is_err());
+
+        policy.overpayment_tolerance_percent = Decimal::ONE;
+        policy.top_up_window_minutes = 0;
+        assert!(PaymentPolicyService::validate(&policy).is_err());
+    }
+}
@@ -3,13 +3,14 @@
 
 use crate::error::ServiceError;
 use crate::services::price_service::PriceService;
-use crate::services::webhook_service::WebhookService;
 use crate::payment::models::{
     CreatePaymentRequest, PaymentFilters, PaymentList, PaymentResponse, PaymentStatus,
     PaymentTransaction, PartialPaymentInfo, PartialPaymentRecord, CryptoType,
 };
 use crate::payment::processor::PaymentProcessor;
+use crate::payment::state_machine::{self, PaymentStatusHistory, PaymentTransition, TransitionActor};
 use crate::payment::verifier::PaymentVerifier;
+use crate::services::payment_stream_service::PaymentStreamService;
 use std::sync::Arc;
 use chrono::Utc;
 use rust_decimal::Decimal;
@@ -41,17 +42,23 @@ pub struct PaymentService {
 }
 
 impl PaymentService {
-    pub fn new(db_pool: PgPool, payment_page_base_url: &str, price_service: Arc<PriceService>, webhook_signing_key: &str, config: crate::config::Config) -> Self {
-        let webhook_service = WebhookService::new(db_pool.clone(), webhook_signing_key.to_string());
-        
+    pub fn new(db_pool: PgPool, payment_page_base_url: &str, price_service: Arc<PriceService>, config: crate::config::Config) -> Self {
         Self {
-            processor: PaymentProcessor::new(db_pool.clone(), payment_page_base_url.to_string(), price_service, config.clone()),
-            verifier: PaymentVerifier::new(db_pool.clone(), webhook_service, config.clone()),
+            processor: PaymentProcessor::new(db_pool.clone(), payment_page_base_url.to_string(), price_service.clone(), config.clone()),
+            verifier: PaymentVerifier::new(db_pool.clone(), price_service, config.clone()),
             db_pool,
             config,
         }
     }
 
+    /// Publish confirmation progress seen while verifying to live subscribers
+    ///
+    /// Status transitions are streamed by the state machine on commit.
+    pub fn with_payment_stream(mut self, payment_stream: PaymentStreamService) -> Self {
+        self.verifier = self.verifier.with_payment_stream(payment_stream);
+        self
+    }
+
     /// Create a new payment request
     /// 
     /// # Arguments
@@ -100,6 +107,134 @@ impl PaymentService {
             .map_err(|e| PaymentServiceError::VerificationError(e.to_string()))
     }
 
+    /// Move a payment to a new status through the payment state machine
+    /// 
+    /// Used for manual overrides (admin force-confirm / force-fail, merchant
+    /// cancellation). Illegal moves are rejected like any other transition.
+    /// 
+    /// # Arguments
+    /// * `payment_id` - Public payment ID (e.g., "pay_abc123")
+    /// * `to` - Target status
+    /// * `actor` - Who is requesting the change
+    /// * `reason` - Optional free-text reason stored in the status history
+    pub async fn transition_status(
+        &self,
+        payment_id: &str,
+        to: PaymentStatus,
+        actor: TransitionActor,
+        reason: Option<String>,
+    ) -> Result<PaymentStatus, PaymentServiceError> {
+        let payment = sqlx::query!(
+            "SELECT id, merchant_id FROM payment_transactions WHERE payment_id = $1",
+            payment_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(PaymentServiceError::PaymentNotFound)?;
+
+        if let TransitionActor::Merchant(merchant_id) = actor {
+            if payment.merchant_id != merchant_id {
+                return Err(PaymentServiceError::PaymentNotFound);
+            }
+        }
+
+        let mut transition = PaymentTransition::new(to, actor);
+        if let Some(reason) = reason {
+            transition = transition.with_reason(reason);
+        }
+
+        let applied = state_machine::transition_payment(
+            &self.db_pool,
+            payment.id,
+            transition,
+        ).await?;
+
+        Ok(applied.from)
+    }
+
+    /// Accept funds that arrived after the payment expired
+    /// 
+    /// Confirms a `PAID_LATE` payment at the value actually received: any
+    /// partial payments made before expiry plus the late funds repriced at the
+    /// rate when they arrived.
+    /// 
+    /// # Arguments
+    /// * `payment_id` - Public payment ID (e.g., "pay_abc123")
+    /// * `merchant_id` - Merchant ID for ownership verification
+    pub async fn accept_late_payment(
+        &self,
+        payment_id: &str,
+        merchant_id: i64,
+    ) -> Result<(), PaymentServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        let payment = sqlx::query!(
+            "SELECT id, merchant_id, status FROM payment_transactions WHERE payment_id = $1 FOR UPDATE",
+            payment_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(PaymentServiceError::PaymentNotFound)?;
+
+        if payment.merchant_id != merchant_id {
+            return Err(PaymentServiceError::PaymentNotFound);
+        }
+
+        if PaymentStatus::from_string(&payment.status) != PaymentStatus::PaidLate {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Payment {} has no late funds to accept", payment_id
+            )).into());
+        }
+
+        let transition = PaymentTransition::new(PaymentStatus::Confirmed, TransitionActor::Merchant(merchant_id))
+            .with_reason("Late payment accepted by merchant");
+        state_machine::apply_transition(&mut tx, payment.id, &transition).await?;
+
+        // Every receipt is stored in partial_payments, the late ones at their
+        // repriced value, so the sum keeps what was paid before expiry
+        sqlx::query!(
+            r#"
+            UPDATE payment_transactions
+            SET amount_usd = (
+                SELECT COALESCE(SUM(amount_usd), 0) FROM partial_payments WHERE payment_id = $1
+            )
+            WHERE id = $1
+            "#,
+            payment.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Get the status transition history of a payment
+    /// 
+    /// # Arguments
+    /// * `payment_id` - Public payment ID (e.g., "pay_abc123")
+    /// * `merchant_id` - Merchant ID for ownership verification
+    pub async fn get_status_history(
+        &self,
+        payment_id: &str,
+        merchant_id: i64,
+    ) -> Result<Vec<PaymentStatusHistory>, PaymentServiceError> {
+        let payment = sqlx::query!(
+            "SELECT id, merchant_id FROM payment_transactions WHERE payment_id = $1",
+            payment_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(PaymentServiceError::PaymentNotFound)?;
+
+        if payment.merchant_id != merchant_id {
+            return Err(PaymentServiceError::PaymentNotFound);
+        }
+
+        Ok(state_machine::get_status_history(&self.db_pool, payment.id).await?)
+    }
+
     /// Get a single payment by payment ID
     /// 
     /// # Arguments
@@ -310,8 +445,8 @@ impl PaymentService {
         // Parse status from string
         let status = self.parse_status(&payment.status);
 
-        // Get partial payment info if enabled
-        let partial_payments: Option<serde_json::Value> = None;
+        // Get amounts received so far (top-ups, overpayments)
+        let partial_payments = self.get_partial_payments(payment.id).await?;
 
         // Fetch payment link from database
         let payment_link = format!("{}/pay/{}", 
@@ -342,7 +477,7 @@ impl PaymentService {
             created_at: payment.created_at,
             confirmed_at: payment.confirmed_at,
             transaction_hash: None,
-            partial_payments: None,
+            partial_payments,
             to_address: payment.to_address,
             confirmations: payment.confirmations.unwrap_or(0),
             required_confirmations: payment.required_confirmations.unwrap_or(1),
@@ -351,19 +486,51 @@ impl PaymentService {
         })
     }
 
-    /// Get partial payment records for a payment
+    /// Summarize the amounts received for a payment
+    /// 
+    /// Returns `None` until at least one transaction has been recorded.
     async fn get_partial_payments(
         &self,
         payment_id: i64,
-    ) -> Result<Vec<PartialPaymentRecord>, PaymentServiceError> {
-        let records = sqlx::query_as::<_, PartialPaymentRecord>(
-            "SELECT * FROM partial_payments WHERE payment_id = $1 ORDER BY created_at ASC"
+    ) -> Result<Option<serde_json::Value>, PaymentServiceError> {
+        let totals = sqlx::query!(
+            "SELECT total_paid, remaining_balance, overpaid_amount FROM payment_transactions WHERE id = $1",
+            payment_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        if totals.total_paid.is_zero() {
+            return Ok(None);
+        }
+
+        let records = sqlx::query!(
+            r#"
+            SELECT transaction_hash, amount, created_at
+            FROM partial_payments
+            WHERE payment_id = $1
+            ORDER BY created_at ASC
+            "#,
+            payment_id
         )
-        .bind(payment_id)
         .fetch_all(&self.db_pool)
         .await?;
 
-        Ok(records)
+        let transactions: Vec<serde_json::Value> = records
+            .into_iter()
+            .map(|r| serde_json::json!({
+                "transaction_hash": r.transaction_hash,
+                "amount": r.amount.to_string(),
+                "created_at": r.created_at,
+            }))
+            .collect();
+
+        Ok(Some(serde_json::json!({
+            "amount_received": totals.total_paid.to_string(),
+            "remaining_balance": totals.remaining_balance.unwrap_or_default().to_string(),
+            "overpaid_amount": totals.overpaid_amount.to_string(),
+            "transactions": transactions,
+        })))
     }
 
     /// Parse crypto type from string
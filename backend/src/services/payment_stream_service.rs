@@ -0,0 +1,373 @@
+// Payment Stream Service
+// Live payment status updates, fanned out to every replica through Redis pub/sub
+// and Postgres notifications for committed status transitions
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use redis::AsyncCommands;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::payment::models::PaymentStatus;
+
+/// Redis channel payment updates are published on
+pub const PAYMENT_UPDATES_CHANNEL: &str = "fiddupay:payment_updates";
+
+/// Postgres channel the payment state machine notifies, with the payment's internal ID
+pub const PAYMENT_TRANSITIONS_CHANNEL: &str = "payment_transitions";
+
+/// Updates buffered per subscriber before it starts skipping
+const LOCAL_CHANNEL_CAPACITY: usize = 1024;
+
+/// Longest wait between Redis reconnect attempts
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Snapshot of a payment's status, published whenever it may have changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentUpdate {
+    pub payment_id: String,
+    pub merchant_id: i64,
+    pub link_id: Option<String>,
+    pub status: String,
+    pub confirmations: i32,
+    pub required_confirmations: i32,
+    pub transaction_hash: Option<String>,
+    pub amount_received: Decimal,
+    pub remaining_balance: Option<Decimal>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl PaymentUpdate {
+    /// Whether the hosted payment page has nothing further to show
+    ///
+    /// Settled payments can still be refunded, but that is the merchant's
+    /// business; expired payments stay open because late funds may arrive.
+    pub fn is_final(&self) -> bool {
+        let status = PaymentStatus::from_string(&self.status);
+        status.is_terminal() || status.is_settled()
+    }
+
+    /// Fields shown on the hosted payment page, matching `/pay/:link_id/status`
+    pub fn public_view(&self) -> serde_json::Value {
+        json!({
+            "status": self.status,
+            "amount_received": self.amount_received.to_string(),
+            "remaining_balance": self.remaining_balance.map(|b| b.to_string()),
+            "confirmations": self.confirmations,
+            "required_confirmations": self.required_confirmations,
+            "transaction_hash": self.transaction_hash,
+            "timestamp": self.timestamp,
+        })
+    }
+}
+
+/// Publishes payment updates and hands them to local subscribers
+///
+/// With Redis configured, updates are published to [`PAYMENT_UPDATES_CHANNEL`]
+/// and every replica relays what it receives to its own subscribers, so a
+/// browser connected to one instance sees changes made on another. While the
+/// Redis subscription is down, updates made on this instance are delivered
+/// locally instead.
+///
+/// Status transitions need no explicit publish: the state machine notifies
+/// [`PAYMENT_TRANSITIONS_CHANNEL`] when they commit, and every replica
+/// listens there, so changes made by the blockchain monitor or background
+/// tasks reach browsers as they happen.
+#[derive(Clone)]
+pub struct PaymentStreamService {
+    db_pool: PgPool,
+    redis: Option<redis::Client>,
+    local: broadcast::Sender<PaymentUpdate>,
+    relay_connected: Arc<AtomicBool>,
+}
+
+impl PaymentStreamService {
+    /// Stream that only reaches subscribers on this instance
+    pub fn new(db_pool: PgPool) -> Self {
+        let (local, _) = broadcast::channel(LOCAL_CHANNEL_CAPACITY);
+        Self {
+            db_pool,
+            redis: None,
+            local,
+            relay_connected: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn with_redis(mut self, client: redis::Client) -> Self {
+        self.redis = Some(client);
+        self
+    }
+
+    /// Stream using `Config::redis_url` for fan-out, local-only if the URL is invalid
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        let stream = Self::new(db_pool);
+        match redis::Client::open(config.redis_url.as_str()) {
+            Ok(client) => stream.with_redis(client),
+            Err(e) => {
+                warn!("Payment stream running without Redis fan-out: {}", e);
+                stream
+            }
+        }
+    }
+
+    /// Receive updates for every payment handled by this deployment
+    pub fn subscribe(&self) -> broadcast::Receiver<PaymentUpdate> {
+        self.local.subscribe()
+    }
+
+    /// Updates matching `filter`, from the moment this is called
+    ///
+    /// A subscriber that falls more than [`LOCAL_CHANNEL_CAPACITY`] updates
+    /// behind skips the oldest ones.
+    pub fn updates<F>(&self, filter: F) -> impl Stream<Item = PaymentUpdate> + Send + 'static
+    where
+        F: Fn(&PaymentUpdate) -> bool + Send + 'static,
+    {
+        futures::stream::unfold((self.subscribe(), filter), |(mut receiver, filter)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(update) if filter(&update) => return Some((update, (receiver, filter))),
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Payment stream subscriber skipped {} updates", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Current snapshot of a payment
+    pub async fn snapshot(&self, payment_id: i64) -> Result<Option<PaymentUpdate>, sqlx::Error> {
+        let row = sqlx::query!(
+            r#"
+            SELECT pt.payment_id, pt.merchant_id, pl.link_id AS "link_id?", pt.status,
+                   pt.confirmations, pt.required_confirmations, pt.transaction_hash,
+                   pt.total_paid, pt.remaining_balance
+            FROM payment_transactions pt
+            LEFT JOIN payment_links pl ON pl.payment_id = pt.id
+            WHERE pt.id = $1
+            "#,
+            payment_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(row.map(|r| PaymentUpdate {
+            payment_id: r.payment_id,
+            merchant_id: r.merchant_id,
+            link_id: r.link_id,
+            status: r.status,
+            confirmations: r.confirmations,
+            required_confirmations: r.required_confirmations,
+            transaction_hash: r.transaction_hash,
+            amount_received: r.total_paid,
+            remaining_balance: r.remaining_balance,
+            timestamp: Utc::now(),
+        }))
+    }
+
+    /// Publish the current state of a payment
+    ///
+    /// For changes that are not status transitions, such as a new
+    /// confirmation count; call after the change is committed. Failures are
+    /// logged and never returned: the stream is a convenience on top of
+    /// webhooks and polling.
+    ///
+    /// # Arguments
+    /// * `payment_id` - Internal ID of the payment
+    pub async fn publish(&self, payment_id: i64) {
+        if self.redis.is_none() && self.local.receiver_count() == 0 {
+            return;
+        }
+
+        let update = match self.snapshot(payment_id).await {
+            Ok(Some(update)) => update,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to load payment {} for stream: {}", payment_id, e);
+                return;
+            }
+        };
+
+        if let Some(client) = &self.redis {
+            match Self::publish_to_redis(client, &update).await {
+                Ok(()) if self.relay_connected.load(Ordering::Relaxed) => return,
+                Ok(()) => {}
+                Err(e) => warn!("Failed to publish payment update to Redis: {}", e),
+            }
+        }
+
+        // Nobody listening is not an error
+        let _ = self.local.send(update);
+    }
+
+    async fn publish_to_redis(client: &redis::Client, update: &PaymentUpdate) -> redis::RedisResult<()> {
+        let payload = serde_json::to_string(update).map_err(|e| {
+            redis::RedisError::from((redis::ErrorKind::Client, "Invalid payment update", e.to_string()))
+        })?;
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        conn.publish::<_, _, ()>(PAYMENT_UPDATES_CHANNEL, payload).await
+    }
+
+    /// Start relaying updates published by any replica, and committed status
+    /// transitions, to local subscribers
+    pub fn start_relay(&self) {
+        self.start_transition_listener();
+
+        let Some(client) = self.redis.clone() else {
+            return;
+        };
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            let mut delay = Duration::from_secs(1);
+            loop {
+                if let Err(e) = service.relay(&client).await {
+                    warn!("Payment stream relay disconnected: {}", e);
+                }
+                if service.relay_connected.swap(false, Ordering::Relaxed) {
+                    delay = Duration::from_secs(1);
+                }
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+    }
+
+    fn start_transition_listener(&self) {
+        let service = self.clone();
+
+        tokio::spawn(async move {
+            let mut delay = Duration::from_secs(1);
+            loop {
+                // PgListener reconnects by itself; this only runs if that fails
+                if let Err(e) = service.listen_transitions().await {
+                    warn!("Payment transition listener disconnected: {}", e);
+                }
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+            }
+        });
+    }
+
+    /// Deliver a snapshot of every payment whose transition commits
+    async fn listen_transitions(&self) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&self.db_pool).await?;
+        listener.listen(PAYMENT_TRANSITIONS_CHANNEL).await?;
+        info!("Payment stream listening on {}", PAYMENT_TRANSITIONS_CHANNEL);
+
+        loop {
+            let notification = listener.recv().await?;
+            if self.local.receiver_count() == 0 {
+                continue;
+            }
+
+            let Ok(payment_id) = notification.payload().parse::<i64>() else {
+                warn!("Invalid payment transition notification: {}", notification.payload());
+                continue;
+            };
+            match self.snapshot(payment_id).await {
+                Ok(Some(update)) => {
+                    let _ = self.local.send(update);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to load payment {} for stream: {}", payment_id, e),
+            }
+        }
+    }
+
+    async fn relay(&self, client: &redis::Client) -> redis::RedisResult<()> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub.subscribe(PAYMENT_UPDATES_CHANNEL).await?;
+        self.relay_connected.store(true, Ordering::Relaxed);
+        info!("Payment stream relay subscribed to {}", PAYMENT_UPDATES_CHANNEL);
+
+        let mut messages = pubsub.into_on_message();
+        while let Some(message) = messages.next().await {
+            let payload: String = match message.get_payload() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Unreadable payment update: {}", e);
+                    continue;
+                }
+            };
+            match serde_json::from_str::<PaymentUpdate>(&payload) {
+                Ok(update) => {
+                    let _ = self.local.send(update);
+                }
+                Err(e) => warn!("Invalid payment update: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(status: &str) -> PaymentUpdate {
+        PaymentUpdate {
+            payment_id: "pay_test".to_string(),
+            merchant_id: 1,
+            link_id: Some("lnk_test".to_string()),
+            status: status.to_string(),
+            confirmations: 3,
+            required_confirmations: 12,
+            transaction_hash: Some("0xabc".to_string()),
+            amount_received: Decimal::new(5000, 2),
+            remaining_balance: Some(Decimal::new(5000, 2)),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_update_round_trips_through_json() {
+        let original = update("CONFIRMING");
+        let json = serde_json::to_string(&original).unwrap();
+        assert_eq!(serde_json::from_str::<PaymentUpdate>(&json).unwrap(), original);
+    }
+
+    #[test]
+    fn test_public_view_hides_merchant() {
+        let view = update("CONFIRMING").public_view();
+
+        assert_eq!(view["status"], "CONFIRMING");
+        assert_eq!(view["confirmations"], 3);
+        assert_eq!(view["amount_received"], "50.00");
+        assert!(view.get("merchant_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_updates_are_filtered() {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let stream = PaymentStreamService::new(pool);
+        let updates = stream.updates(|u| u.status == "CONFIRMED");
+        futures::pin_mut!(updates);
+
+        stream.local.send(update("CONFIRMING")).unwrap();
+        stream.local.send(update("CONFIRMED")).unwrap();
+
+        assert_eq!(updates.next().await.unwrap().status, "CONFIRMED");
+    }
+
+    #[test]
+    fn test_final_statuses() {
+        assert!(update("CONFIRMED").is_final());
+        assert!(update("CANCELLED").is_final());
+        assert!(!update("EXPIRED").is_final());
+        assert!(!update("CONFIRMING").is_final());
+        assert!(!update("PENDING").is_final());
+    }
+}
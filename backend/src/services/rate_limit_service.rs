@@ -0,0 +1,564 @@
+// Rate Limit Service
+// Sliding-window request quotas shared by every replica through Redis
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::middleware::auth::MerchantContext;
+use axum::http::Method;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{OnceCell, RwLock};
+use tracing::{info, warn};
+
+/// Length of a rate limit window
+pub const WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a merchant's plan is cached before it is read again
+const PLAN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Local windows kept before stale ones are pruned
+const MAX_LOCAL_WINDOWS: usize = 10_000;
+
+/// Checks the sliding window and counts the request if it fits
+///
+/// KEYS: current window, previous window
+/// ARGV: limit, window length (ms), time elapsed in the current window (ms)
+/// Returns {allowed, current count, previous count}
+const SLIDING_WINDOW_SCRIPT: &str = r#"
+local current = tonumber(redis.call('GET', KEYS[1]) or '0')
+local previous = tonumber(redis.call('GET', KEYS[2]) or '0')
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local elapsed = tonumber(ARGV[3])
+if previous * (window - elapsed) / window + current >= limit then
+    return {0, current, previous}
+end
+current = redis.call('INCR', KEYS[1])
+if current == 1 then
+    redis.call('PEXPIRE', KEYS[1], window * 2)
+end
+return {1, current, previous}
+"#;
+
+/// Merchant API plan; quotas are the base rates times the plan multiplier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitPlan {
+    Standard,
+    Business,
+    Enterprise,
+}
+
+impl RateLimitPlan {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitPlan::Standard => "standard",
+            RateLimitPlan::Business => "business",
+            RateLimitPlan::Enterprise => "enterprise",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "business" => RateLimitPlan::Business,
+            "enterprise" => RateLimitPlan::Enterprise,
+            _ => RateLimitPlan::Standard,
+        }
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        match self {
+            RateLimitPlan::Standard => 1,
+            RateLimitPlan::Business => 5,
+            RateLimitPlan::Enterprise => 20,
+        }
+    }
+}
+
+/// Kind of route being called; each has its own quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteClass {
+    /// Authenticated GET/HEAD/OPTIONS
+    Read,
+    /// Authenticated requests that may change state
+    Write,
+    /// Unauthenticated routes, limited per client IP
+    Public,
+}
+
+impl RouteClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteClass::Read => "read",
+            RouteClass::Write => "write",
+            RouteClass::Public => "public",
+        }
+    }
+
+    /// Read or write, for an authenticated request
+    pub fn for_method(method: &Method) -> Self {
+        if method == Method::GET || method == Method::HEAD || method == Method::OPTIONS {
+            RouteClass::Read
+        } else {
+            RouteClass::Write
+        }
+    }
+}
+
+/// Requests allowed per window for each route class on the standard plan
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitQuotas {
+    pub read_per_window: u32,
+    pub write_per_window: u32,
+    pub public_per_window: u32,
+    /// Share of the merchant's quota a single API key may use, in percent
+    pub per_key_percent: u32,
+}
+
+impl RateLimitQuotas {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            read_per_window: config.rate_limit_requests_per_minute,
+            write_per_window: config.rate_limit_write_requests_per_minute,
+            public_per_window: config.rate_limit_public_requests_per_minute,
+            per_key_percent: config.rate_limit_per_api_key_percent,
+        }
+    }
+
+    /// Limit for a route class; public routes are not tied to a plan
+    pub fn limit(&self, class: RouteClass, plan: RateLimitPlan) -> u32 {
+        let base = match class {
+            RouteClass::Read => self.read_per_window,
+            RouteClass::Write => self.write_per_window,
+            RouteClass::Public => return self.public_per_window.max(1),
+        };
+        base.saturating_mul(plan.multiplier()).max(1)
+    }
+
+    /// Limit for one API key, a share of the merchant's limit
+    pub fn key_limit(&self, class: RouteClass, plan: RateLimitPlan) -> u32 {
+        let limit = u64::from(self.limit(class, plan));
+        (limit * u64::from(self.per_key_percent.clamp(1, 100)) / 100).max(1) as u32
+    }
+}
+
+/// Outcome of a rate limit check, with the values for the `RateLimit-*` headers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the current window ends
+    pub reset_secs: u64,
+    /// Seconds until a request would be allowed, when refused
+    pub retry_after_secs: Option<u64>,
+}
+
+impl RateLimitDecision {
+    /// Decision for the counts observed in the window
+    ///
+    /// # Arguments
+    /// * `limit` - Requests allowed per window
+    /// * `elapsed_ms` - Time elapsed in the current window
+    /// * `current` - Requests counted in the current window, including this one if allowed
+    /// * `previous` - Requests counted in the previous window
+    fn from_counts(allowed: bool, limit: u32, elapsed_ms: u64, current: u64, previous: u64) -> Self {
+        let window_ms = WINDOW.as_millis() as u64;
+        let left_ms = window_ms.saturating_sub(elapsed_ms);
+        let used = (previous as f64 * left_ms as f64 / window_ms as f64).floor() as u64 + current;
+
+        Self {
+            allowed,
+            limit,
+            remaining: u64::from(limit).saturating_sub(used) as u32,
+            reset_secs: ceil_secs(left_ms).max(1),
+            retry_after_secs: (!allowed).then(|| retry_after_secs(limit, elapsed_ms, current, previous)),
+        }
+    }
+}
+
+/// Whether a request fits in the sliding window, matching the Redis script
+fn fits(limit: u32, elapsed_ms: u64, current: u64, previous: u64) -> bool {
+    let window_ms = WINDOW.as_millis() as f64;
+    let weight = (window_ms - elapsed_ms as f64) / window_ms;
+    (previous as f64) * weight + (current as f64) < f64::from(limit)
+}
+
+/// Seconds until the weighted count drops below the limit, assuming no further requests
+fn retry_after_secs(limit: u32, elapsed_ms: u64, current: u64, previous: u64) -> u64 {
+    let window_ms = WINDOW.as_millis() as f64;
+    let limit = f64::from(limit);
+    let left_ms = window_ms - elapsed_ms as f64;
+
+    let wait_ms = if (current as f64) < limit && previous > 0 {
+        // Wait for enough of the previous window to slide out
+        left_ms - (limit - current as f64) * window_ms / previous as f64
+    } else {
+        // Wait for the next window, then for this one to slide out of it
+        left_ms + (window_ms - limit * window_ms / current.max(1) as f64).max(0.0)
+    };
+
+    ceil_secs(wait_ms.max(0.0) as u64).max(1)
+}
+
+fn ceil_secs(ms: u64) -> u64 {
+    ms.div_ceil(1000)
+}
+
+/// Window index and time elapsed in it, from the wall clock shared by replicas
+fn window_position() -> (u64, u64) {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let window_ms = WINDOW.as_millis() as u64;
+    (now_ms / window_ms, now_ms % window_ms)
+}
+
+/// In-process window, used while Redis is unreachable
+#[derive(Debug, Default)]
+struct LocalWindow {
+    index: u64,
+    current: u64,
+    previous: u64,
+}
+
+impl LocalWindow {
+    fn advance(&mut self, index: u64) {
+        if self.index == index {
+            return;
+        }
+        self.previous = if self.index + 1 == index { self.current } else { 0 };
+        self.current = 0;
+        self.index = index;
+    }
+}
+
+/// Sliding-window rate limiter keyed by API key or merchant, and by client IP
+///
+/// Counters live in Redis so every replica enforces the same limits. If
+/// Redis cannot be reached, each replica falls back to its own counters
+/// rather than failing requests.
+pub struct RateLimitService {
+    db_pool: PgPool,
+    quotas: RateLimitQuotas,
+    per_api_key: bool,
+    redis: Option<redis::Client>,
+    connection: OnceCell<ConnectionManager>,
+    script: redis::Script,
+    redis_degraded: AtomicBool,
+    local: Mutex<HashMap<String, LocalWindow>>,
+    plans: RwLock<HashMap<i64, (RateLimitPlan, Instant)>>,
+}
+
+impl RateLimitService {
+    /// Limiter counting in-process only
+    pub fn new(db_pool: PgPool, quotas: RateLimitQuotas) -> Self {
+        Self {
+            db_pool,
+            quotas,
+            per_api_key: true,
+            redis: None,
+            connection: OnceCell::new(),
+            script: redis::Script::new(SLIDING_WINDOW_SCRIPT),
+            redis_degraded: AtomicBool::new(false),
+            local: Mutex::new(HashMap::new()),
+            plans: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_redis(mut self, client: redis::Client) -> Self {
+        self.redis = Some(client);
+        self
+    }
+
+    /// Also count authenticated requests per API key, inside the merchant-wide quota
+    pub fn with_per_api_key(mut self, per_api_key: bool) -> Self {
+        self.per_api_key = per_api_key;
+        self
+    }
+
+    /// Limiter using the `RATE_LIMIT_*` settings and `Config::redis_url`
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        let service = Self::new(db_pool, RateLimitQuotas::from_config(config))
+            .with_per_api_key(config.rate_limit_per_api_key);
+        match redis::Client::open(config.redis_url.as_str()) {
+            Ok(client) => service.with_redis(client),
+            Err(e) => {
+                warn!("Rate limiting without Redis, limits are per replica: {}", e);
+                service
+            }
+        }
+    }
+
+    /// Count an authenticated request against its merchant and, if enabled, its API key
+    ///
+    /// The merchant-wide count always applies, so extra keys or team sessions
+    /// never add quota; one key alone is held to `per_key_percent` of it, so a
+    /// runaway integration leaves room for the merchant's other keys. The
+    /// stricter of the two decisions is returned.
+    pub async fn check_merchant(&self, context: &MerchantContext, class: RouteClass) -> RateLimitDecision {
+        let plan = self.plan(context.merchant_id).await;
+        let limit = self.quotas.limit(class, plan);
+
+        let merchant = self.hit(&format!("merchant:{}", context.merchant_id), class, limit).await;
+        if !merchant.allowed || !self.per_api_key {
+            return merchant;
+        }
+
+        let key = self
+            .hit(
+                &format!("key:{}", api_key_fingerprint(&context.api_key)),
+                class,
+                self.quotas.key_limit(class, plan),
+            )
+            .await;
+        stricter(merchant, key)
+    }
+
+    /// Count an unauthenticated request against the client IP
+    pub async fn check_ip(&self, ip: &str) -> RateLimitDecision {
+        let class = RouteClass::Public;
+        self.hit(&format!("ip:{}", ip), class, self.quotas.limit(class, RateLimitPlan::Standard))
+            .await
+    }
+
+    /// Merchant's plan, cached briefly; standard if it cannot be read
+    pub async fn plan(&self, merchant_id: i64) -> RateLimitPlan {
+        if let Some((plan, loaded_at)) = self.plans.read().await.get(&merchant_id) {
+            if loaded_at.elapsed() < PLAN_CACHE_TTL {
+                return *plan;
+            }
+        }
+
+        let plan = match sqlx::query_scalar!(
+            "SELECT rate_limit_plan FROM merchants WHERE id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        {
+            Ok(plan) => plan.map(|p| RateLimitPlan::from_string(&p)).unwrap_or(RateLimitPlan::Standard),
+            Err(e) => {
+                warn!("Failed to load rate limit plan for merchant {}: {}", merchant_id, e);
+                return RateLimitPlan::Standard;
+            }
+        };
+
+        self.plans.write().await.insert(merchant_id, (plan, Instant::now()));
+        plan
+    }
+
+    /// Change a merchant's plan
+    ///
+    /// Takes effect immediately on this replica and within a minute on the others.
+    pub async fn set_plan(&self, merchant_id: i64, plan: RateLimitPlan) -> Result<(), ServiceError> {
+        let result = sqlx::query!(
+            "UPDATE merchants SET rate_limit_plan = $1, updated_at = NOW() WHERE id = $2",
+            plan.as_str(),
+            merchant_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::MerchantNotFound);
+        }
+
+        self.plans.write().await.insert(merchant_id, (plan, Instant::now()));
+        Ok(())
+    }
+
+    async fn hit(&self, subject: &str, class: RouteClass, limit: u32) -> RateLimitDecision {
+        let (index, elapsed_ms) = window_position();
+        let key = format!("fiddupay:ratelimit:{{{}:{}}}", class.as_str(), subject);
+
+        if self.redis.is_some() {
+            match self.hit_redis(&key, index, elapsed_ms, limit).await {
+                Ok((allowed, current, previous)) => {
+                    if self.redis_degraded.swap(false, Ordering::Relaxed) {
+                        info!("Rate limiting is using Redis again");
+                    }
+                    return RateLimitDecision::from_counts(allowed, limit, elapsed_ms, current, previous);
+                }
+                Err(e) => {
+                    if !self.redis_degraded.swap(true, Ordering::Relaxed) {
+                        warn!("Rate limiting fell back to per-replica counters: {}", e);
+                    }
+                }
+            }
+        }
+
+        self.hit_local(&key, index, elapsed_ms, limit)
+    }
+
+    async fn hit_redis(
+        &self,
+        key: &str,
+        index: u64,
+        elapsed_ms: u64,
+        limit: u32,
+    ) -> redis::RedisResult<(bool, u64, u64)> {
+        let Some(client) = &self.redis else {
+            return Err(redis::RedisError::from((redis::ErrorKind::Client, "Redis not configured")));
+        };
+        let mut connection = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(client.clone()))
+            .await?
+            .clone();
+
+        let (allowed, current, previous): (u8, u64, u64) = self
+            .script
+            .key(format!("{}:{}", key, index))
+            .key(format!("{}:{}", key, index.wrapping_sub(1)))
+            .arg(limit)
+            .arg(WINDOW.as_millis() as u64)
+            .arg(elapsed_ms)
+            .invoke_async(&mut connection)
+            .await?;
+
+        Ok((allowed == 1, current, previous))
+    }
+
+    fn hit_local(&self, key: &str, index: u64, elapsed_ms: u64, limit: u32) -> RateLimitDecision {
+        let mut windows = self.local.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= MAX_LOCAL_WINDOWS {
+            windows.retain(|_, w| w.index + 1 >= index);
+        }
+
+        let window = windows.entry(key.to_string()).or_default();
+        window.advance(index);
+
+        let allowed = fits(limit, elapsed_ms, window.current, window.previous);
+        if allowed {
+            window.current += 1;
+        }
+        RateLimitDecision::from_counts(allowed, limit, elapsed_ms, window.current, window.previous)
+    }
+}
+
+/// Decision leaving the fewest requests, preferring a refusal
+fn stricter(a: RateLimitDecision, b: RateLimitDecision) -> RateLimitDecision {
+    match (a.allowed, b.allowed) {
+        (true, false) => b,
+        (false, true) => a,
+        _ if b.remaining < a.remaining => b,
+        _ => a,
+    }
+}
+
+/// Redis keys name the API key by a truncated hash, never the key itself
+fn api_key_fingerprint(api_key: &str) -> String {
+    hex::encode(&Sha256::digest(api_key.as_bytes())[..8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotas() -> RateLimitQuotas {
+        RateLimitQuotas {
+            read_per_window: 100,
+            write_per_window: 50,
+            public_per_window: 3,
+            per_key_percent: 50,
+        }
+    }
+
+    fn service() -> RateLimitService {
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        RateLimitService::new(pool, quotas())
+    }
+
+    #[test]
+    fn test_limits_scale_with_plan_except_public() {
+        let quotas = quotas();
+        assert_eq!(quotas.limit(RouteClass::Read, RateLimitPlan::Standard), 100);
+        assert_eq!(quotas.limit(RouteClass::Write, RateLimitPlan::Business), 250);
+        assert_eq!(quotas.limit(RouteClass::Read, RateLimitPlan::Enterprise), 2000);
+        assert_eq!(quotas.limit(RouteClass::Public, RateLimitPlan::Enterprise), 3);
+        assert_eq!(RouteClass::for_method(&Method::GET), RouteClass::Read);
+        assert_eq!(RouteClass::for_method(&Method::DELETE), RouteClass::Write);
+    }
+
+    #[test]
+    fn test_api_key_gets_a_share_of_the_merchant_quota() {
+        let mut quotas = quotas();
+        assert_eq!(quotas.key_limit(RouteClass::Read, RateLimitPlan::Standard), 50);
+        assert_eq!(quotas.key_limit(RouteClass::Write, RateLimitPlan::Business), 125);
+
+        quotas.per_key_percent = 0;
+        assert_eq!(quotas.key_limit(RouteClass::Read, RateLimitPlan::Standard), 1);
+        quotas.per_key_percent = 250;
+        assert_eq!(quotas.key_limit(RouteClass::Read, RateLimitPlan::Standard), 100);
+    }
+
+    #[test]
+    fn test_previous_window_is_weighted_by_overlap() {
+        // Halfway through the window, half of the previous 100 still count
+        assert!(fits(100, 30_000, 49, 100));
+        assert!(!fits(100, 30_000, 50, 100));
+        assert!(fits(100, 59_999, 99, 100));
+
+        let decision = RateLimitDecision::from_counts(true, 100, 30_000, 20, 100);
+        assert_eq!(decision.remaining, 30);
+        assert_eq!(decision.reset_secs, 30);
+        assert_eq!(decision.retry_after_secs, None);
+    }
+
+    #[test]
+    fn test_retry_after_waits_for_room_in_the_window() {
+        // 50 + 100 * (30s left / 60s) = 100: room opens as more of the previous window slides out
+        assert_eq!(retry_after_secs(100, 30_000, 50, 100), 1);
+        // 20 + 100 * (30s left / 60s) = 70 with a limit of 60: wait 6s for 10 more to slide out
+        assert_eq!(retry_after_secs(60, 30_000, 20, 100), 6);
+        // Current window is full: wait for it to end
+        assert_eq!(retry_after_secs(100, 45_000, 100, 0), 15);
+        // Over the limit: also wait for part of it to slide out of the next window
+        assert_eq!(retry_after_secs(100, 50_000, 200, 0), 40);
+    }
+
+    #[test]
+    fn test_local_counters_refuse_over_limit() {
+        let service = service();
+        let (index, _) = window_position();
+
+        for expected_remaining in [2, 1, 0] {
+            let decision = service.hit_local("ip:10.0.0.1", index, 0, 3);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, expected_remaining);
+        }
+        let refused = service.hit_local("ip:10.0.0.1", index, 0, 3);
+        assert!(!refused.allowed);
+        assert!(refused.retry_after_secs.is_some());
+
+        // Other keys and later windows have their own counts
+        assert!(service.hit_local("ip:10.0.0.2", index, 0, 3).allowed);
+        assert!(service.hit_local("ip:10.0.0.1", index + 2, 0, 3).allowed);
+    }
+
+    #[test]
+    fn test_stricter_decision_wins() {
+        let allowed = RateLimitDecision::from_counts(true, 100, 0, 10, 0);
+        let busier = RateLimitDecision::from_counts(true, 100, 0, 60, 0);
+        let refused = RateLimitDecision::from_counts(false, 100, 0, 100, 0);
+
+        assert_eq!(stricter(allowed.clone(), busier.clone()), busier);
+        assert_eq!(stricter(busier.clone(), allowed.clone()), busier);
+        assert_eq!(stricter(refused.clone(), allowed), refused);
+    }
+
+    #[test]
+    fn test_api_key_fingerprint_hides_key() {
+        let fingerprint = api_key_fingerprint("sk_live_secret");
+        assert_eq!(fingerprint.len(), 16);
+        assert!(!fingerprint.contains("secret"));
+    }
+}
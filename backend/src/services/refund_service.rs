@@ -5,32 +5,40 @@ use chrono::Utc;
 use nanoid::nanoid;
 use rust_decimal::Decimal;
 use sqlx::PgPool;
-use std::sync::Arc;
 use tracing::{error, info};
 
 use crate::error::ServiceError;
 use crate::models::refund::RefundResponse;
 use crate::models::webhook::WebhookPayload;
 use crate::payment::models::PaymentStatus;
-use crate::services::webhook_service::WebhookService;
+use crate::payment::state_machine::{self, PaymentTransition, TransitionActor};
+use crate::services::outbox;
+use crate::services::screening_service::{ScreeningService, SubjectType};
 
 pub struct RefundService {
     db_pool: PgPool,
-    webhook_service: Arc<WebhookService>,
+    screening: ScreeningService,
 }
 
 impl RefundService {
-    pub fn new(db_pool: PgPool, webhook_service: Arc<WebhookService>) -> Self {
+    pub fn new(db_pool: PgPool) -> Self {
         Self {
+            screening: ScreeningService::new(db_pool.clone()),
             db_pool,
-            webhook_service,
         }
     }
 
+    pub fn with_screening(mut self, screening: ScreeningService) -> Self {
+        self.screening = screening;
+        self
+    }
+
     /// Create a refund for a payment
     /// 
     /// Creates a refund record for a completed payment. Supports both full and partial refunds.
     /// Validates that the refund amount does not exceed the original payment amount.
+    /// Refunds to a payer address on a sanctions or deny list are created
+    /// `held` until compliance review releases them.
     /// 
     /// # Arguments
     /// * `merchant_id` - ID of the merchant requesting the refund
@@ -55,7 +63,8 @@ impl RefundService {
         // Fetch the payment to validate it exists and belongs to the merchant
         let payment = sqlx::query!(
             r#"
-            SELECT id, merchant_id, amount, amount_usd, crypto_type, status
+            SELECT id, merchant_id, amount, amount_usd, crypto_type, status, total_paid, from_address,
+                   late_received_at
             FROM payment_transactions
             WHERE payment_id = $1
             "#,
@@ -70,8 +79,10 @@ impl RefundService {
             return Err(ServiceError::PaymentNotFound);
         }
 
-        // Verify the payment is confirmed (can only refund confirmed payments)
-        if payment.status != "CONFIRMED" {
+        // Verify the payment is settled (can only refund confirmed payments)
+        // or holds late funds awaiting the merchant's decision
+        let status = PaymentStatus::from_string(&payment.status);
+        if !status.is_settled() && status != PaymentStatus::PaidLate {
             return Err(ServiceError::Internal(
                 "Can only refund confirmed payments".to_string()
             ));
@@ -82,7 +93,7 @@ impl RefundService {
             r#"
             SELECT COALESCE(SUM(amount), 0) as total_refunded
             FROM refunds
-            WHERE payment_id = $1 AND status IN ('pending', 'completed')
+            WHERE payment_id = $1 AND status IN ('pending', 'held', 'completed')
             "#,
             payment.id
         )
@@ -91,11 +102,24 @@ impl RefundService {
         .total_refunded
         .unwrap_or(Decimal::ZERO);
 
+        // Late funds are refunded as received, including after a first partial
+        // refund; overpaid payments can refund everything that was received
+        let received_late = payment.late_received_at.is_some();
+        let refundable_total = if received_late {
+            payment.total_paid
+        } else {
+            payment.amount.max(payment.total_paid)
+        };
+
         // Determine refund amount (full or partial)
-        let refund_amount = amount.unwrap_or(payment.amount);
+        let refund_amount = amount.unwrap_or(if received_late {
+            payment.total_paid
+        } else {
+            payment.amount
+        });
 
         // Validate refund amount doesn't exceed remaining payment amount
-        let remaining_amount = payment.amount - total_refunded;
+        let remaining_amount = refundable_total - total_refunded;
         if refund_amount > remaining_amount {
             return Err(ServiceError::Internal(format!(
                 "Refund amount {} exceeds remaining payment amount {}",
@@ -122,6 +146,15 @@ impl RefundService {
         // Generate unique refund ID
         let refund_id = format!("ref_{}", nanoid!(16));
 
+        // Refunds go back to the payer, who is screened like any destination
+        let list_match = match payment.from_address.as_deref() {
+            Some(address) => self.screening.find_match(address).await?,
+            None => None,
+        };
+        let status = if list_match.is_some() { "held" } else { "pending" };
+
+        let mut tx = self.db_pool.begin().await?;
+
         // Insert refund record
         let refund = sqlx::query!(
             r#"
@@ -139,12 +172,26 @@ impl RefundService {
             refund_amount,
             refund_amount_usd,
             &reason,
-            "pending",
+            status,
             Utc::now()
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        if let (Some(list_match), Some(address)) = (&list_match, payment.from_address.as_deref()) {
+            ScreeningService::create_hold(
+                &mut tx,
+                merchant_id,
+                SubjectType::Refund,
+                &refund.refund_id,
+                address,
+                None,
+                list_match,
+            ).await?;
+        }
+
+        tx.commit().await?;
+
         info!(
             "Created refund {} for payment {} - amount: {} (${:.2})",
             refund_id, payment_id, refund_amount, refund_amount_usd
@@ -163,6 +210,40 @@ impl RefundService {
         })
     }
 
+    /// Refund funds that arrived after the payment expired
+    /// 
+    /// Creates a full refund of everything received for a `PAID_LATE` payment.
+    /// The payment moves to `REFUNDED` once the refund is completed.
+    /// 
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant requesting the refund
+    /// * `payment_id` - Public payment ID (e.g., "pay_abc123")
+    /// * `reason` - Reason for the refund
+    pub async fn refund_late_payment(
+        &self,
+        merchant_id: i64,
+        payment_id: String,
+        reason: String,
+    ) -> Result<RefundResponse, ServiceError> {
+        let status = sqlx::query_scalar!(
+            "SELECT status FROM payment_transactions WHERE payment_id = $1 AND merchant_id = $2",
+            &payment_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::PaymentNotFound)?;
+
+        if PaymentStatus::from_string(&status) != PaymentStatus::PaidLate {
+            return Err(ServiceError::InvalidStateTransition(format!(
+                "Payment {} has no late funds to refund",
+                payment_id
+            )));
+        }
+
+        self.create_refund(merchant_id, payment_id, None, reason).await
+    }
+
     /// Complete a refund with transaction hash
     /// 
     /// Updates a refund record with the blockchain transaction hash and marks it as completed.
@@ -186,7 +267,7 @@ impl RefundService {
         // Fetch the refund to validate it exists
         let refund = sqlx::query!(
             r#"
-            SELECT id, merchant_id, payment_id, status
+            SELECT id, merchant_id, payment_id, status, amount
             FROM refunds
             WHERE refund_id = $1
             "#,
@@ -204,6 +285,8 @@ impl RefundService {
             )));
         }
 
+        let mut tx = self.db_pool.begin().await?;
+
         // Update refund with transaction hash and mark as completed
         sqlx::query!(
             r#"
@@ -216,7 +299,7 @@ impl RefundService {
             Utc::now(),
             &refund_id
         )
-        .execute(&self.db_pool)
+        .execute(&mut *tx)
         .await?;
 
         info!(
@@ -227,35 +310,68 @@ impl RefundService {
         // Fetch payment details for webhook
         let payment = sqlx::query!(
             r#"
-            SELECT payment_id, amount, crypto_type
+            SELECT payment_id, amount, crypto_type, status, total_paid, late_received_at
             FROM payment_transactions
             WHERE id = $1
             "#,
             refund.payment_id
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        // Move the payment to refunded or partially refunded
+        let total_refunded = sqlx::query!(
+            r#"
+            SELECT COALESCE(SUM(amount), 0) as total_refunded
+            FROM refunds
+            WHERE payment_id = $1 AND status = 'completed'
+            "#,
+            refund.payment_id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .total_refunded
+        .unwrap_or(Decimal::ZERO);
+
+        let full_amount = if payment.late_received_at.is_some() {
+            payment.total_paid
+        } else {
+            payment.amount
+        };
+
+        let payment_status = if total_refunded >= full_amount {
+            PaymentStatus::Refunded
+        } else {
+            PaymentStatus::PartiallyRefunded
+        };
+
+        let transition = PaymentTransition::new(payment_status, TransitionActor::Merchant(refund.merchant_id))
+            .with_reason(format!("Refund {} completed", refund_id));
+        if let Err(e) = state_machine::apply_transition(&mut tx, refund.payment_id, &transition).await {
+            error!("Failed to update payment status for refund {}: {}", refund_id, e);
+        }
+
         // Trigger webhook notification
         let webhook_payload = WebhookPayload {
+            event_id: None,
             event_type: "refund.completed".to_string(),
             payment_id: payment.payment_id,
             merchant_id: refund.merchant_id,
-            status: PaymentStatus::Refunded,
+            status: payment_status,
             amount: payment.amount,
             crypto_type: payment.crypto_type,
             transaction_hash: Some(transaction_hash),
             timestamp: Utc::now().timestamp(),
+            data: Some(serde_json::json!({
+                "refund_id": &refund_id,
+                "refund_amount": refund.amount.to_string(),
+            })),
         };
 
-        // Queue webhook for delivery (don't fail if webhook fails)
-        if let Err(e) = self
-            .webhook_service
-            .queue_webhook(refund.merchant_id, refund.payment_id, webhook_payload)
-            .await
-        {
-            error!("Failed to queue webhook for refund {}: {}", refund_id, e);
-        }
+        // Queue webhook in the same transaction as the refund
+        outbox::enqueue_payment_event(&mut tx, refund.payment_id, webhook_payload).await?;
+
+        tx.commit().await?;
 
         Ok(())
     }
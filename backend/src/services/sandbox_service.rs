@@ -2,6 +2,8 @@
 // Business logic for sandbox testing environment
 
 use crate::error::ServiceError;
+use crate::payment::models::PaymentStatus;
+use crate::payment::state_machine::{self, PaymentTransition, TransitionActor};
 use crate::services::merchant_service::MerchantService;
 use crate::utils::api_keys::ApiKeyGenerator;
 use chrono::Utc;
@@ -113,17 +115,14 @@ impl SandboxService {
             return Err(ServiceError::Forbidden("Access denied".to_string()));
         }
 
-        let new_status = if success { "CONFIRMED" } else { "FAILED" };
-        let confirmed_at = if success { Some(Utc::now()) } else { None };
+        let new_status = if success { PaymentStatus::Confirmed } else { PaymentStatus::Failed };
 
-        sqlx::query!(
-            "UPDATE payment_transactions SET status = $1, confirmed_at = $2 WHERE id = $3",
-            new_status,
-            confirmed_at,
-            payment.id
-        )
-        .execute(&self.db_pool)
-        .await?;
+        state_machine::transition_payment(
+            &self.db_pool,
+            payment.id,
+            PaymentTransition::new(new_status, TransitionActor::Merchant(merchant_id))
+                .with_reason("Sandbox simulation"),
+        ).await?;
 
         Ok(())
     }
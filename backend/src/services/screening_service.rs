@@ -0,0 +1,853 @@
+// Screening Service
+// Sanctions and deny-list screening of counterparty addresses
+
+use chrono::{DateTime, Utc};
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::services::audit_service::{AuditEntry, AuditService};
+use crate::services::outbox;
+
+pub const SOURCE_OFAC_SDN: &str = "ofac_sdn";
+pub const SOURCE_FILE: &str = "file";
+pub const SOURCE_CUSTOM: &str = "custom";
+
+/// List name of the OFAC Specially Designated Nationals list
+pub const OFAC_SDN_LIST: &str = "SDN";
+/// List name used for custom deny-list entries
+pub const CUSTOM_LIST: &str = "custom";
+
+/// Prefix of crypto address identifiers in the SDN list, followed by the currency code
+const OFAC_ADDRESS_MARKER: &str = "Digital Currency Address - ";
+
+/// A deny-listed address parsed from a list source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    pub address: String,
+    pub currency: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// A source of deny-listed addresses that can be imported
+///
+/// Importing a source replaces every entry previously imported under the
+/// same source and list name.
+pub trait ListSource: Send + Sync {
+    /// Source recorded on imported entries
+    fn source(&self) -> &'static str;
+
+    fn list_name(&self) -> &str;
+
+    /// Parse list content into normalized entries
+    fn parse(&self, content: &str) -> Result<Vec<ListEntry>, ServiceError>;
+}
+
+/// OFAC SDN list, as published in SDN.XML or SDN.CSV
+///
+/// Only the "Digital Currency Address" identifiers are imported.
+pub struct OfacSdnSource;
+
+impl ListSource for OfacSdnSource {
+    fn source(&self) -> &'static str {
+        SOURCE_OFAC_SDN
+    }
+
+    fn list_name(&self) -> &str {
+        OFAC_SDN_LIST
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<ListEntry>, ServiceError> {
+        Ok(parse_ofac_sdn(content))
+    }
+}
+
+/// Local deny-list file
+///
+/// One `address[,currency[,reason]]` per line; blank lines and lines
+/// starting with `#` are ignored.
+pub struct LocalFileSource {
+    name: String,
+}
+
+impl LocalFileSource {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+}
+
+impl ListSource for LocalFileSource {
+    fn source(&self) -> &'static str {
+        SOURCE_FILE
+    }
+
+    fn list_name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<ListEntry>, ServiceError> {
+        parse_local_list(content)
+    }
+}
+
+/// Kind of transaction a hold stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectType {
+    /// Incoming payment; the payer address is screened at verification
+    Payment,
+    /// Withdrawal; the destination is screened at creation
+    Withdrawal,
+    /// Refund; the payer address receiving it is screened at creation
+    Refund,
+}
+
+impl SubjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubjectType::Payment => "payment",
+            SubjectType::Withdrawal => "withdrawal",
+            SubjectType::Refund => "refund",
+        }
+    }
+}
+
+/// List entry an address matched
+#[derive(Debug, Clone, Serialize)]
+pub struct ListMatch {
+    pub source: String,
+    pub list_name: String,
+    pub address: String,
+    pub currency: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreeningHold {
+    pub id: i64,
+    pub hold_id: String,
+    pub merchant_id: i64,
+    pub subject_type: String,
+    pub subject_id: String,
+    pub address: String,
+    pub transaction_hash: Option<String>,
+    pub matched_source: String,
+    pub matched_list: String,
+    pub matched_reason: Option<String>,
+    pub status: String,
+    pub reviewed_by: Option<i64>,
+    pub review_notes: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of screening an address for a transaction
+#[derive(Debug)]
+pub enum ScreeningOutcome {
+    /// No match, or a previous hold was released
+    Clear,
+    /// Waiting for compliance review
+    Held(ScreeningHold),
+    /// Compliance rejected the transaction
+    Rejected(ScreeningHold),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SanctionsListEntry {
+    pub id: i64,
+    pub source: String,
+    pub list_name: String,
+    pub address: String,
+    pub currency: Option<String>,
+    pub reason: Option<String>,
+    pub created_by: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListSummary {
+    pub source: String,
+    pub list_name: String,
+    pub entries: i64,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub list_name: String,
+    pub imported: u64,
+    pub removed: u64,
+}
+
+#[derive(Clone)]
+pub struct ScreeningService {
+    db_pool: PgPool,
+    enabled: bool,
+    ofac_sdn_file: Option<String>,
+    sanctions_list_file: Option<String>,
+}
+
+impl ScreeningService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+            enabled: true,
+            ofac_sdn_file: None,
+            sanctions_list_file: None,
+        }
+    }
+
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self::new(db_pool)
+            .with_enabled(config.sanctions_screening_enabled)
+            .with_list_files(config.ofac_sdn_file.clone(), config.sanctions_list_file.clone())
+    }
+
+    /// Turn screening of new transactions on or off; existing holds still apply
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Files imported by [`ScreeningService::import_configured`]
+    pub fn with_list_files(mut self, ofac_sdn_file: Option<String>, sanctions_list_file: Option<String>) -> Self {
+        self.ofac_sdn_file = ofac_sdn_file;
+        self.sanctions_list_file = sanctions_list_file;
+        self
+    }
+
+    /// Look up an address in every imported and custom list
+    ///
+    /// Returns `None` when screening is disabled.
+    pub async fn find_match(&self, address: &str) -> Result<Option<ListMatch>, ServiceError> {
+        let address = normalize_address(address);
+        if !self.enabled || address.is_empty() {
+            return Ok(None);
+        }
+
+        let list_match = sqlx::query_as!(
+            ListMatch,
+            r#"
+            SELECT source, list_name, address, currency, reason
+            FROM sanctions_list_entries
+            WHERE address = $1
+            ORDER BY CASE source WHEN 'ofac_sdn' THEN 0 WHEN 'file' THEN 1 ELSE 2 END, id
+            LIMIT 1
+            "#,
+            address
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(list_match)
+    }
+
+    /// Screen the counterparty address of a transaction
+    ///
+    /// A previous review decision for the same transaction and address
+    /// stands; otherwise a list match puts the transaction on hold.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - Merchant the transaction belongs to
+    /// * `subject_type` - Kind of transaction
+    /// * `subject_id` - Public id of the transaction (e.g. "pay_abc123")
+    /// * `address` - Counterparty address
+    /// * `transaction_hash` - On-chain transaction, if there is one
+    pub async fn screen(
+        &self,
+        merchant_id: i64,
+        subject_type: SubjectType,
+        subject_id: &str,
+        address: &str,
+        transaction_hash: Option<&str>,
+    ) -> Result<ScreeningOutcome, ServiceError> {
+        let existing = sqlx::query_as!(
+            ScreeningHold,
+            r#"
+            SELECT * FROM screening_holds
+            WHERE subject_type = $1 AND subject_id = $2 AND address = $3
+            "#,
+            subject_type.as_str(),
+            subject_id,
+            normalize_address(address)
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        if let Some(hold) = existing {
+            return Ok(match hold.status.as_str() {
+                "released" => ScreeningOutcome::Clear,
+                "rejected" => ScreeningOutcome::Rejected(hold),
+                _ => ScreeningOutcome::Held(hold),
+            });
+        }
+
+        let Some(list_match) = self.find_match(address).await? else {
+            return Ok(ScreeningOutcome::Clear);
+        };
+
+        let mut tx = self.db_pool.begin().await?;
+        let hold = Self::create_hold(
+            &mut tx,
+            merchant_id,
+            subject_type,
+            subject_id,
+            address,
+            transaction_hash,
+            &list_match,
+        ).await?;
+        tx.commit().await?;
+
+        Ok(ScreeningOutcome::Held(hold))
+    }
+
+    /// Record a compliance hold inside the caller's transaction
+    ///
+    /// Used where the held transaction is created in the same database
+    /// transaction, so a hold never exists without its subject.
+    pub async fn create_hold(
+        tx: &mut Transaction<'_, Postgres>,
+        merchant_id: i64,
+        subject_type: SubjectType,
+        subject_id: &str,
+        address: &str,
+        transaction_hash: Option<&str>,
+        list_match: &ListMatch,
+    ) -> Result<ScreeningHold, ServiceError> {
+        let hold_id = format!("hold_{}", nanoid!(16));
+
+        let hold = sqlx::query_as!(
+            ScreeningHold,
+            r#"
+            INSERT INTO screening_holds (
+                hold_id, merchant_id, subject_type, subject_id, address, transaction_hash,
+                matched_source, matched_list, matched_reason
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#,
+            hold_id,
+            merchant_id,
+            subject_type.as_str(),
+            subject_id,
+            normalize_address(address),
+            transaction_hash,
+            list_match.source,
+            list_match.list_name,
+            list_match.reason
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        AuditService::record_in_tx(
+            tx,
+            &AuditEntry::new("SCREENING_HOLD")
+                .with_merchant(merchant_id)
+                .with_entity(subject_type.as_str(), subject_id)
+                .with_details(json!({
+                    "hold_id": hold.hold_id,
+                    "address": hold.address,
+                    "source": hold.matched_source,
+                    "list": hold.matched_list,
+                    "reason": hold.matched_reason,
+                })),
+        ).await?;
+
+        warn!(
+            "Sanctions list match: {} {} of merchant {} held ({}, {} list {})",
+            subject_type.as_str(), subject_id, merchant_id, hold.hold_id, hold.matched_source, hold.matched_list
+        );
+
+        Ok(hold)
+    }
+
+    pub async fn get_hold(&self, hold_id: &str) -> Result<ScreeningHold, ServiceError> {
+        sqlx::query_as!(
+            ScreeningHold,
+            "SELECT * FROM screening_holds WHERE hold_id = $1",
+            hold_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Screening hold {} not found", hold_id)))
+    }
+
+    /// List holds, newest first, optionally filtered by status
+    pub async fn list_holds(
+        &self,
+        status: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ScreeningHold>, ServiceError> {
+        let holds = sqlx::query_as!(
+            ScreeningHold,
+            r#"
+            SELECT * FROM screening_holds
+            WHERE ($1::text IS NULL OR status = $1)
+            ORDER BY created_at DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            status,
+            limit,
+            offset
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(holds)
+    }
+
+    /// Release a hold after review
+    ///
+    /// Held withdrawals and refunds return to pending. A held payment
+    /// settles on its next verification.
+    ///
+    /// # Arguments
+    /// * `hold_id` - Hold to release
+    /// * `admin_id` - Reviewing admin
+    /// * `notes` - Review notes
+    pub async fn release_hold(
+        &self,
+        hold_id: &str,
+        admin_id: i64,
+        notes: Option<&str>,
+    ) -> Result<ScreeningHold, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+        let hold = Self::review_hold(&mut tx, hold_id, "released", admin_id, notes).await?;
+
+        match hold.subject_type.as_str() {
+            "withdrawal" => {
+                sqlx::query!(
+                    r#"
+                    UPDATE withdrawals SET status = 'PENDING', updated_at = NOW()
+                    WHERE withdrawal_id = $1 AND status = 'HELD'
+                    "#,
+                    hold.subject_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            "refund" => {
+                sqlx::query!(
+                    "UPDATE refunds SET status = 'pending' WHERE refund_id = $1 AND status = 'held'",
+                    hold.subject_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            _ => {}
+        }
+
+        tx.commit().await?;
+        info!("Screening hold {} released by admin {}", hold_id, admin_id);
+
+        Ok(hold)
+    }
+
+    /// Reject a held transaction after review
+    ///
+    /// Held withdrawals are rejected and held refunds fail. Held payments
+    /// can no longer be verified; the caller moves them to failed.
+    ///
+    /// # Arguments
+    /// * `hold_id` - Hold to reject
+    /// * `admin_id` - Reviewing admin
+    /// * `notes` - Review notes
+    pub async fn reject_hold(
+        &self,
+        hold_id: &str,
+        admin_id: i64,
+        notes: Option<&str>,
+    ) -> Result<ScreeningHold, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+        let hold = Self::review_hold(&mut tx, hold_id, "rejected", admin_id, notes).await?;
+
+        match hold.subject_type.as_str() {
+            "withdrawal" => {
+                let updated = sqlx::query!(
+                    r#"
+                    UPDATE withdrawals w
+                    SET status = 'REJECTED', rejection_reason = 'Rejected by compliance review', updated_at = NOW()
+                    WHERE withdrawal_id = $1 AND status = 'HELD'
+                    RETURNING merchant_id, to_jsonb(w) AS "snapshot!"
+                    "#,
+                    hold.subject_id
+                )
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                if let Some(updated) = updated {
+                    outbox::enqueue_event(
+                        &mut tx,
+                        updated.merchant_id,
+                        "withdrawal.rejected",
+                        &hold.subject_id,
+                        json!({ "object": updated.snapshot }),
+                    ).await?;
+                }
+            }
+            "refund" => {
+                sqlx::query!(
+                    "UPDATE refunds SET status = 'failed' WHERE refund_id = $1 AND status = 'held'",
+                    hold.subject_id
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            _ => {}
+        }
+
+        tx.commit().await?;
+        info!("Screening hold {} rejected by admin {}", hold_id, admin_id);
+
+        Ok(hold)
+    }
+
+    async fn review_hold(
+        tx: &mut Transaction<'_, Postgres>,
+        hold_id: &str,
+        decision: &str,
+        admin_id: i64,
+        notes: Option<&str>,
+    ) -> Result<ScreeningHold, ServiceError> {
+        let reviewed = sqlx::query_as!(
+            ScreeningHold,
+            r#"
+            UPDATE screening_holds
+            SET status = $2, reviewed_by = $3, review_notes = $4, reviewed_at = NOW()
+            WHERE hold_id = $1 AND status = 'held'
+            RETURNING *
+            "#,
+            hold_id,
+            decision,
+            admin_id,
+            notes
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        if let Some(hold) = reviewed {
+            return Ok(hold);
+        }
+
+        let status = sqlx::query_scalar!("SELECT status FROM screening_holds WHERE hold_id = $1", hold_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("Screening hold {} not found", hold_id)))?;
+
+        Err(ServiceError::InvalidStateTransition(format!(
+            "Screening hold {} is already {}",
+            hold_id, status
+        )))
+    }
+
+    /// Replace the entries of a list with freshly parsed content
+    ///
+    /// Content without any address is refused, so a truncated or wrong
+    /// file cannot silently empty a list.
+    pub async fn import_list(&self, source: &dyn ListSource, content: &str) -> Result<ImportReport, ServiceError> {
+        let entries = source.parse(content)?;
+        if entries.is_empty() {
+            return Err(ServiceError::ValidationError(format!(
+                "No addresses found for {} list {}",
+                source.source(),
+                source.list_name()
+            )));
+        }
+
+        let addresses: Vec<String> = entries.iter().map(|e| e.address.clone()).collect();
+        let currencies: Vec<Option<String>> = entries.iter().map(|e| e.currency.clone()).collect();
+        let reasons: Vec<Option<String>> = entries.iter().map(|e| e.reason.clone()).collect();
+
+        let mut tx = self.db_pool.begin().await?;
+
+        let removed = sqlx::query!(
+            "DELETE FROM sanctions_list_entries WHERE source = $1 AND list_name = $2",
+            source.source(),
+            source.list_name()
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let imported = sqlx::query!(
+            r#"
+            INSERT INTO sanctions_list_entries (source, list_name, address, currency, reason)
+            SELECT $1, $2, e.address, e.currency, e.reason
+            FROM UNNEST($3::text[], $4::text[], $5::text[]) AS e(address, currency, reason)
+            ON CONFLICT (source, list_name, address) DO NOTHING
+            "#,
+            source.source(),
+            source.list_name(),
+            &addresses,
+            &currencies as &[Option<String>],
+            &reasons as &[Option<String>]
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        info!(
+            "Imported {} addresses into {} list {} ({} replaced)",
+            imported, source.source(), source.list_name(), removed
+        );
+
+        Ok(ImportReport {
+            source: source.source().to_string(),
+            list_name: source.list_name().to_string(),
+            imported,
+            removed,
+        })
+    }
+
+    /// Read a list file from disk and import it
+    pub async fn import_file(&self, source: &dyn ListSource, path: &str) -> Result<ImportReport, ServiceError> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| ServiceError::InternalError(format!("Failed to read list file {}: {}", path, e)))?;
+        self.import_list(source, &content).await
+    }
+
+    /// Re-import the OFAC SDN and local list files from configuration
+    pub async fn import_configured(&self) -> Result<Vec<ImportReport>, ServiceError> {
+        let mut reports = Vec::new();
+        if let Some(path) = &self.ofac_sdn_file {
+            reports.push(self.import_file(&OfacSdnSource, path).await?);
+        }
+        if let Some(path) = &self.sanctions_list_file {
+            reports.push(self.import_file(&LocalFileSource::new("local"), path).await?);
+        }
+        Ok(reports)
+    }
+
+    /// Entry counts of every imported and custom list
+    pub async fn list_summaries(&self) -> Result<Vec<ListSummary>, ServiceError> {
+        let summaries = sqlx::query_as!(
+            ListSummary,
+            r#"
+            SELECT source, list_name, COUNT(*) AS "entries!", MAX(created_at) AS updated_at
+            FROM sanctions_list_entries
+            GROUP BY source, list_name
+            ORDER BY source, list_name
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(summaries)
+    }
+
+    pub async fn list_custom_entries(&self) -> Result<Vec<SanctionsListEntry>, ServiceError> {
+        let entries = sqlx::query_as!(
+            SanctionsListEntry,
+            "SELECT * FROM sanctions_list_entries WHERE source = 'custom' ORDER BY created_at DESC"
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Add an address to the custom deny list
+    pub async fn add_custom_entry(
+        &self,
+        address: &str,
+        currency: Option<&str>,
+        reason: &str,
+        admin_id: i64,
+    ) -> Result<SanctionsListEntry, ServiceError> {
+        let address = normalize_address(address);
+        if !is_plausible_address(&address) {
+            return Err(ServiceError::InvalidWalletAddress(address));
+        }
+
+        let entry = sqlx::query_as!(
+            SanctionsListEntry,
+            r#"
+            INSERT INTO sanctions_list_entries (source, list_name, address, currency, reason, created_by)
+            VALUES ('custom', $1, $2, $3, $4, $5)
+            ON CONFLICT (source, list_name, address)
+            DO UPDATE SET currency = EXCLUDED.currency, reason = EXCLUDED.reason, created_by = EXCLUDED.created_by
+            RETURNING *
+            "#,
+            CUSTOM_LIST,
+            address,
+            currency,
+            reason,
+            admin_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    pub async fn remove_custom_entry(&self, id: i64) -> Result<SanctionsListEntry, ServiceError> {
+        sqlx::query_as!(
+            SanctionsListEntry,
+            "DELETE FROM sanctions_list_entries WHERE id = $1 AND source = 'custom' RETURNING *",
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound(format!("Deny-list entry {} not found", id)))
+    }
+}
+
+/// Canonical form used for storage and lookup
+///
+/// Hex (EVM) and bech32 addresses are case-insensitive and are lowercased;
+/// base58 addresses (Bitcoin legacy, Solana, Tron) are case-sensitive and
+/// kept as they are.
+pub fn normalize_address(address: &str) -> String {
+    let address = address.trim();
+    let lower = address.to_ascii_lowercase();
+    if lower.starts_with("0x") || lower.starts_with("bc1") || lower.starts_with("tb1") || lower.starts_with("ltc1") {
+        lower
+    } else {
+        address.to_string()
+    }
+}
+
+fn is_plausible_address(address: &str) -> bool {
+    (20..=255).contains(&address.len()) && address.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Extract crypto addresses from SDN.XML or SDN.CSV content
+///
+/// XML lists them as `<idType>Digital Currency Address - XBT</idType>`
+/// followed by `<idNumber>address</idNumber>`; the CSV remarks column as
+/// `Digital Currency Address - XBT address;`.
+fn parse_ofac_sdn(content: &str) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    let mut rest = content;
+
+    while let Some(position) = rest.find(OFAC_ADDRESS_MARKER) {
+        rest = &rest[position + OFAC_ADDRESS_MARKER.len()..];
+
+        let currency_len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+        let currency = &rest[..currency_len];
+        let after = rest[currency_len..].trim_start();
+
+        let address = match after.strip_prefix("</idType>") {
+            Some(xml) => xml
+                .trim_start()
+                .strip_prefix("<idNumber>")
+                .map(|value| value.split('<').next().unwrap_or_default()),
+            None => after.split(|c: char| c == ';' || c == ',' || c == '"' || c.is_whitespace()).next(),
+        };
+
+        let Some(address) = address.map(normalize_address) else { continue };
+        if currency.is_empty() || !is_plausible_address(&address) || !seen.insert(address.clone()) {
+            continue;
+        }
+        entries.push(ListEntry {
+            address,
+            currency: Some(currency.to_string()),
+            reason: Some("OFAC SDN".to_string()),
+        });
+    }
+
+    entries
+}
+
+/// Parse a local deny-list file
+fn parse_local_list(content: &str) -> Result<Vec<ListEntry>, ServiceError> {
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, ',').map(str::trim);
+        let address = normalize_address(fields.next().unwrap_or_default());
+        if !is_plausible_address(&address) {
+            return Err(ServiceError::ValidationError(format!(
+                "Line {}: invalid address '{}'",
+                number + 1,
+                address
+            )));
+        }
+        let currency = fields.next().filter(|c| !c.is_empty()).map(|c| c.to_uppercase());
+        let reason = fields.next().filter(|r| !r.is_empty()).map(str::to_string);
+
+        if seen.insert(address.clone()) {
+            entries.push(ListEntry { address, currency, reason });
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(
+            normalize_address(" 0x8589427373D6D84E98730D7795D8F6F8731FDA16 "),
+            "0x8589427373d6d84e98730d7795d8f6f8731fda16"
+        );
+        assert_eq!(
+            normalize_address("BC1QSA3TGMV0C8KDLVJFMPAHVRLPSU0QWP0MKQEFS9"),
+            "bc1qsa3tgmv0c8kdlvjfmpahvrlpsu0qwp0mkqefs9"
+        );
+        assert_eq!(
+            normalize_address("12QtD5BFwRsdNsAZY76UVE1xyCGNTojH9h"),
+            "12QtD5BFwRsdNsAZY76UVE1xyCGNTojH9h"
+        );
+    }
+
+    #[test]
+    fn test_parse_ofac_sdn_xml_and_csv() {
+        let xml = r#"
+            <id><uid>1</uid><idType>Digital Currency Address - XBT</idType>
+                <idNumber>12QtD5BFwRsdNsAZY76UVE1xyCGNTojH9h</idNumber></id>
+            <id><uid>2</uid><idType>Digital Currency Address - ETH</idType><idNumber>0x8589427373D6D84E98730D7795D8F6F8731FDA16</idNumber></id>
+            <id><uid>3</uid><idType>Passport</idType><idNumber>A1234567</idNumber></id>
+        "#;
+        let entries = OfacSdnSource.parse(xml).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address, "12QtD5BFwRsdNsAZY76UVE1xyCGNTojH9h");
+        assert_eq!(entries[0].currency.as_deref(), Some("XBT"));
+        assert_eq!(entries[1].address, "0x8589427373d6d84e98730d7795d8f6f8731fda16");
+
+        let csv = r#"36216,"EXAMPLE, Name","individual","CYBER2",-0- ,"Digital Currency Address - XBT 12QtD5BFwRsdNsAZY76UVE1xyCGNTojH9h; alt. Digital Currency Address - USDT TJ7hhYhVhaxNx6BPyq7yFpqZrQULL3JSdb; Gender Male.""#;
+        let entries = OfacSdnSource.parse(csv).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].address, "TJ7hhYhVhaxNx6BPyq7yFpqZrQULL3JSdb");
+        assert_eq!(entries[1].currency.as_deref(), Some("USDT"));
+    }
+
+    #[test]
+    fn test_parse_local_list() {
+        let content = "# internal deny list\n\
+            0xAbC0000000000000000000000000000000000001, eth, fraud ring, case 42\n\
+            \n\
+            TJ7hhYhVhaxNx6BPyq7yFpqZrQULL3JSdb\n\
+            0xabc0000000000000000000000000000000000001\n";
+        let entries = LocalFileSource::new("local").parse(content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].address, "0xabc0000000000000000000000000000000000001");
+        assert_eq!(entries[0].currency.as_deref(), Some("ETH"));
+        assert_eq!(entries[0].reason.as_deref(), Some("fraud ring, case 42"));
+        assert_eq!(entries[1].currency, None);
+
+        assert!(matches!(
+            LocalFileSource::new("local").parse("not an address\n"),
+            Err(ServiceError::ValidationError(_))
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::ServiceError;
+use crate::services::audit_service::{AuditEntry, AuditService};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
@@ -30,17 +31,14 @@ impl SecurityMonitoringService {
     }
 
     pub async fn log_security_event(&self, event: SecurityEvent) -> Result<(), ServiceError> {
-        sqlx::query!(
-            r#"INSERT INTO audit_logs 
-               (action_type, ip_address, details, created_at)
-               VALUES ($1, $2, $3, $4)"#,
-            event.event_type,
-            event.source_ip,
-            event.details,
-            event.timestamp
-        )
-        .execute(&self.db_pool)
-        .await?;
+        let entry = AuditEntry::new(&event.event_type)
+            .with_ip(Some(&event.source_ip))
+            .with_details(serde_json::json!({
+                "severity": event.severity,
+                "occurred_at": event.timestamp,
+                "details": event.details,
+            }));
+        AuditService::new(self.db_pool.clone()).record(&entry).await?;
 
         Ok(())
     }
@@ -0,0 +1,729 @@
+// Transaction Signer
+// Signs forwarding transactions in process or through a separate signer daemon
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::payment::models::CryptoType;
+use crate::services::wallet_config_service::keygen_network;
+use crate::utils::encryption::{decrypt_data, encrypt_data};
+use crate::utils::keygen::{GeneratedWallet, KeyGenerator};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use web3::{
+    api::{Accounts, Namespace},
+    signing::{Key, SecretKey},
+    types::{Address, Bytes, TransactionParameters, H256, U256},
+};
+
+/// Unsigned native-currency transfer on an EVM chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvmTransaction {
+    pub chain_id: u64,
+    pub nonce: U256,
+    pub to: Address,
+    /// Amount in wei
+    pub value: U256,
+    pub gas_price: U256,
+    pub gas: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignedEvmTransaction {
+    /// Ready for `eth_sendRawTransaction`
+    pub raw_transaction: Bytes,
+    pub transaction_hash: H256,
+}
+
+/// Unsigned SOL transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolanaTransfer {
+    /// Base58 destination account
+    pub to: String,
+    pub lamports: u64,
+    pub recent_blockhash: String,
+}
+
+/// Signs transactions with the key of a deposit address
+///
+/// Callers only name the address; the key stays with the implementation,
+/// which may live in another process. Keys are also generated and encrypted
+/// for storage here, so they are only ever readable under the signer's KEK.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Generate a key for `crypto_type`, returning its address and the encrypted key to store
+    async fn generate_key(&self, crypto_type: CryptoType) -> Result<GeneratedWallet, ServiceError>;
+
+    /// Encrypt an imported private key for storage
+    async fn encrypt_key(&self, private_key: &str) -> Result<String, ServiceError>;
+
+    /// Sign an EVM transaction sent from `from`
+    async fn sign_evm_transaction(
+        &self,
+        from: &str,
+        transaction: &EvmTransaction,
+    ) -> Result<SignedEvmTransaction, ServiceError>;
+
+    /// Sign a Solana transfer message sent from `from`, returning the base58 signature
+    ///
+    /// The signed message is [`solana_transfer_message`] for the same inputs.
+    async fn sign_solana_message(
+        &self,
+        from: &str,
+        transfer: &SolanaTransfer,
+    ) -> Result<String, ServiceError>;
+}
+
+/// Signer selected by `SIGNER_MODE`: `local` (default) or `remote`
+pub fn signer_from_config(db_pool: PgPool, config: &Config) -> Arc<dyn Signer> {
+    match config.signer_mode.as_str() {
+        "remote" => Arc::new(RemoteSigner::new(config.signer_socket_path.clone())),
+        _ => Arc::new(LocalSigner::new(db_pool)),
+    }
+}
+
+/// Signs in the current process with keys decrypted from the database
+pub struct LocalSigner {
+    db_pool: PgPool,
+}
+
+impl LocalSigner {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Decrypted private key of a deposit address
+    async fn private_key(&self, address: &str) -> Result<String, ServiceError> {
+        let keypair = sqlx::query_scalar!(
+            "SELECT encrypted_private_key FROM deposit_keypairs WHERE address = $1",
+            address
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        let encrypted = match keypair {
+            Some(encrypted) => encrypted,
+            None => sqlx::query_scalar!(
+                "SELECT private_key_encrypted FROM deposit_addresses WHERE deposit_address = $1",
+                address
+            )
+            .fetch_optional(&self.db_pool)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound(format!("No signing key for {}", address)))?,
+        };
+
+        decrypt_data(&encrypted)
+            .map_err(|e| ServiceError::Internal(format!("Failed to decrypt signing key: {}", e)))
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    async fn generate_key(&self, crypto_type: CryptoType) -> Result<GeneratedWallet, ServiceError> {
+        let network = keygen_network(crypto_type);
+        let wallet = match network {
+            "solana" => KeyGenerator::generate_solana_wallet()?,
+            _ => KeyGenerator::generate_evm_wallet()?,
+        };
+
+        Ok(GeneratedWallet {
+            address: wallet.address,
+            encrypted_private_key: self.encrypt_key(&wallet.private_key).await?,
+            network: network.to_string(),
+        })
+    }
+
+    async fn encrypt_key(&self, private_key: &str) -> Result<String, ServiceError> {
+        encrypt_data(private_key)
+            .map_err(|e| ServiceError::Internal(format!("Failed to encrypt key: {}", e)))
+    }
+
+    async fn sign_evm_transaction(
+        &self,
+        from: &str,
+        transaction: &EvmTransaction,
+    ) -> Result<SignedEvmTransaction, ServiceError> {
+        let private_key = self.private_key(from).await?;
+        sign_evm_with_key(&private_key, from, transaction).await
+    }
+
+    async fn sign_solana_message(
+        &self,
+        from: &str,
+        transfer: &SolanaTransfer,
+    ) -> Result<String, ServiceError> {
+        let keypair = self.private_key(from).await?;
+        sign_solana_with_key(&keypair, from, transfer)
+    }
+}
+
+/// Solana's System Program, which owns SOL transfers
+const SYSTEM_PROGRAM_ID: [u8; 32] = [0; 32];
+
+/// System Program instruction index for `Transfer`
+const SYSTEM_TRANSFER: u32 = 2;
+
+/// Legacy transaction message moving `transfer.lamports` from `from`
+///
+/// Accounts are the sender (signer, writable), the destination (writable)
+/// and the System Program (read-only). A transaction is this message
+/// preceded by the signature count and signatures.
+pub fn solana_transfer_message(from: &str, transfer: &SolanaTransfer) -> Result<Vec<u8>, ServiceError> {
+    let from = decode_solana_key(from, "sender address")?;
+    let to = decode_solana_key(&transfer.to, "destination address")?;
+    let blockhash = decode_solana_key(&transfer.recent_blockhash, "recent blockhash")?;
+    if from == to {
+        return Err(ServiceError::ValidationError("Sender and destination are the same".to_string()));
+    }
+
+    let mut message = vec![
+        1, // required signatures
+        0, // read-only signed accounts
+        1, // read-only unsigned accounts
+        3, // account keys
+    ];
+    message.extend_from_slice(&from);
+    message.extend_from_slice(&to);
+    message.extend_from_slice(&SYSTEM_PROGRAM_ID);
+    message.extend_from_slice(&blockhash);
+
+    // One instruction: program index, account indexes, data
+    message.extend_from_slice(&[1, 2, 2, 0, 1, 12]);
+    message.extend_from_slice(&SYSTEM_TRANSFER.to_le_bytes());
+    message.extend_from_slice(&transfer.lamports.to_le_bytes());
+
+    Ok(message)
+}
+
+fn decode_solana_key(value: &str, name: &str) -> Result<[u8; 32], ServiceError> {
+    bs58::decode(value)
+        .into_vec()
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| ServiceError::ValidationError(format!("Invalid Solana {}", name)))
+}
+
+/// Sign with a base58 64-byte keypair, checking that it controls `from`
+fn sign_solana_with_key(keypair: &str, from: &str, transfer: &SolanaTransfer) -> Result<String, ServiceError> {
+    use ed25519_dalek::Signer as _;
+
+    let keypair_bytes: [u8; 64] = bs58::decode(keypair.trim())
+        .into_vec()
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ServiceError::Internal("Stored Solana key is not a 64-byte keypair".to_string()))?;
+    let signing_key = ed25519_dalek::SigningKey::from_keypair_bytes(&keypair_bytes)
+        .map_err(|_| ServiceError::Internal("Invalid Solana keypair".to_string()))?;
+
+    if bs58::encode(signing_key.verifying_key().as_bytes()).into_string() != from {
+        return Err(ServiceError::Internal("Signing key does not match sender address".to_string()));
+    }
+
+    let message = solana_transfer_message(from, transfer)?;
+    Ok(bs58::encode(signing_key.sign(&message).to_bytes()).into_string())
+}
+
+/// Sign with a hex private key, checking that it controls `from`
+async fn sign_evm_with_key(
+    private_key: &str,
+    from: &str,
+    transaction: &EvmTransaction,
+) -> Result<SignedEvmTransaction, ServiceError> {
+    let key_bytes = hex::decode(private_key.strip_prefix("0x").unwrap_or(private_key))
+        .map_err(|_| ServiceError::Internal("Invalid private key hex".to_string()))?;
+    let secret_key = SecretKey::from_slice(&key_bytes)
+        .map_err(|_| ServiceError::Internal("Invalid private key".to_string()))?;
+
+    let from: Address = from.parse()
+        .map_err(|_| ServiceError::ValidationError("Invalid sender address".to_string()))?;
+    if (&secret_key).address() != from {
+        return Err(ServiceError::Internal("Signing key does not match sender address".to_string()));
+    }
+
+    let params = TransactionParameters {
+        nonce: Some(transaction.nonce),
+        to: Some(transaction.to),
+        value: transaction.value,
+        gas_price: Some(transaction.gas_price),
+        gas: transaction.gas,
+        chain_id: Some(transaction.chain_id),
+        data: Bytes::default(),
+        ..Default::default()
+    };
+
+    // With nonce, gas price and chain id all set, signing makes no RPC calls
+    let signed = Accounts::new(OfflineTransport)
+        .sign_transaction(params, &secret_key)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Failed to sign transaction: {}", e)))?;
+
+    Ok(SignedEvmTransaction {
+        raw_transaction: signed.raw_transaction,
+        transaction_hash: signed.transaction_hash,
+    })
+}
+
+/// Transport that refuses every call, for offline signing
+#[derive(Debug, Clone)]
+struct OfflineTransport;
+
+impl web3::Transport for OfflineTransport {
+    type Out = futures::future::Ready<web3::error::Result<serde_json::Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<serde_json::Value>) -> (web3::RequestId, jsonrpc_core::Call) {
+        (0, web3::helpers::build_request(0, method, params))
+    }
+
+    fn send(&self, _id: web3::RequestId, _request: jsonrpc_core::Call) -> Self::Out {
+        futures::future::ready(Err(web3::Error::Unreachable))
+    }
+}
+
+/// Request sent to the signer daemon, one JSON line each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SignerRequest {
+    SignEvmTransaction { from: String, transaction: EvmTransaction },
+    SignSolanaMessage { from: String, transfer: SolanaTransfer },
+    GenerateKey { crypto_type: CryptoType },
+    EncryptKey { private_key: String },
+}
+
+/// Reply from the signer daemon, one JSON line each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignerResponse {
+    EvmTransaction(SignedEvmTransaction),
+    SolanaSignature { signature: String },
+    GeneratedKey(GeneratedWallet),
+    EncryptedKey { encrypted_private_key: String },
+    Error { code: String, message: String },
+}
+
+impl SignerResponse {
+    fn from_error(error: ServiceError) -> Self {
+        let code = match &error {
+            ServiceError::Forbidden(_) => "POLICY_DENIED",
+            ServiceError::NotFound(_) => "KEY_NOT_FOUND",
+            ServiceError::ValidationError(_) => "INVALID_REQUEST",
+            _ => "SIGNING_FAILED",
+        };
+        SignerResponse::Error { code: code.to_string(), message: error.to_string() }
+    }
+
+    fn into_error(code: &str, message: String) -> ServiceError {
+        match code {
+            "POLICY_DENIED" => ServiceError::Forbidden(message),
+            "KEY_NOT_FOUND" => ServiceError::NotFound(message),
+            "INVALID_REQUEST" => ServiceError::ValidationError(message),
+            _ => ServiceError::Internal(message),
+        }
+    }
+}
+
+/// Signs through the signer daemon over a Unix socket; keys never enter this process
+pub struct RemoteSigner {
+    socket_path: String,
+    timeout: Duration,
+}
+
+impl RemoteSigner {
+    pub fn new(socket_path: String) -> Self {
+        Self { socket_path, timeout: Duration::from_secs(10) }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn call(&self, request: &SignerRequest) -> Result<SignerResponse, ServiceError> {
+        tokio::time::timeout(self.timeout, self.exchange(request))
+            .await
+            .map_err(|_| ServiceError::Internal("Signer daemon timed out".to_string()))?
+    }
+
+    async fn exchange(&self, request: &SignerRequest) -> Result<SignerResponse, ServiceError> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let stream = tokio::net::UnixStream::connect(&self.socket_path)
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Signer daemon unreachable at {}: {}", self.socket_path, e)))?;
+        let (reader, mut writer) = stream.into_split();
+
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes())
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Signer request failed: {}", e)))?;
+
+        let mut response = String::new();
+        BufReader::new(reader)
+            .read_line(&mut response)
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Signer response failed: {}", e)))?;
+
+        match serde_json::from_str(&response)? {
+            SignerResponse::Error { code, message } => Err(SignerResponse::into_error(&code, message)),
+            response => Ok(response),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn generate_key(&self, crypto_type: CryptoType) -> Result<GeneratedWallet, ServiceError> {
+        match self.call(&SignerRequest::GenerateKey { crypto_type }).await? {
+            SignerResponse::GeneratedKey(wallet) => Ok(wallet),
+            _ => Err(ServiceError::Internal("Unexpected signer response".to_string())),
+        }
+    }
+
+    async fn encrypt_key(&self, private_key: &str) -> Result<String, ServiceError> {
+        let request = SignerRequest::EncryptKey { private_key: private_key.to_string() };
+        match self.call(&request).await? {
+            SignerResponse::EncryptedKey { encrypted_private_key } => Ok(encrypted_private_key),
+            _ => Err(ServiceError::Internal("Unexpected signer response".to_string())),
+        }
+    }
+
+    async fn sign_evm_transaction(
+        &self,
+        from: &str,
+        transaction: &EvmTransaction,
+    ) -> Result<SignedEvmTransaction, ServiceError> {
+        let request = SignerRequest::SignEvmTransaction { from: from.to_string(), transaction: transaction.clone() };
+        match self.call(&request).await? {
+            SignerResponse::EvmTransaction(signed) => Ok(signed),
+            _ => Err(ServiceError::Internal("Unexpected signer response".to_string())),
+        }
+    }
+
+    async fn sign_solana_message(
+        &self,
+        from: &str,
+        transfer: &SolanaTransfer,
+    ) -> Result<String, ServiceError> {
+        let request = SignerRequest::SignSolanaMessage { from: from.to_string(), transfer: transfer.clone() };
+        match self.call(&request).await? {
+            SignerResponse::SolanaSignature { signature } => Ok(signature),
+            _ => Err(ServiceError::Internal("Unexpected signer response".to_string())),
+        }
+    }
+}
+
+/// What the signer daemon agrees to sign
+///
+/// An empty destination list allows any destination. Chains without a
+/// maximum amount are unlimited, but transactions for chain ids the policy
+/// does not know are refused once any maximum is set.
+#[derive(Debug, Clone, Default)]
+pub struct SignerPolicy {
+    allowed_destinations: Vec<String>,
+    max_amounts: Vec<(CryptoType, Decimal)>,
+    evm_chains: Vec<(u64, CryptoType)>,
+}
+
+impl SignerPolicy {
+    /// Policy for the chain ids in `config`, with no restrictions yet
+    pub fn new(config: &Config) -> Self {
+        Self {
+            evm_chains: vec![
+                (config.ethereum_chain_id, CryptoType::Eth),
+                (config.ethereum_sepolia_chain_id, CryptoType::Eth),
+                (config.bsc_chain_id, CryptoType::Bnb),
+                (config.bsc_testnet_chain_id, CryptoType::Bnb),
+                (config.polygon_chain_id, CryptoType::Matic),
+                (config.polygon_mumbai_chain_id, CryptoType::Matic),
+                (config.arbitrum_chain_id, CryptoType::Arb),
+                (config.arbitrum_sepolia_chain_id, CryptoType::Arb),
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// Policy from `SIGNER_ALLOWED_DESTINATIONS` (comma-separated addresses)
+    /// and `SIGNER_MAX_AMOUNTS` (e.g. `ETH:1.5,SOL:100`, in whole coins)
+    pub fn from_env(config: &Config) -> Result<Self, String> {
+        let mut policy = Self::new(config).with_allowed_destinations(
+            std::env::var("SIGNER_ALLOWED_DESTINATIONS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        );
+
+        for entry in std::env::var("SIGNER_MAX_AMOUNTS").unwrap_or_default().split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (crypto_type, amount) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("SIGNER_MAX_AMOUNTS entry {} must be CURRENCY:amount", entry))?;
+            let crypto_type: CryptoType = crypto_type.trim().parse()
+                .map_err(|_| format!("Unknown currency in SIGNER_MAX_AMOUNTS: {}", crypto_type))?;
+            let amount: Decimal = amount.trim().parse()
+                .map_err(|_| format!("Invalid amount in SIGNER_MAX_AMOUNTS: {}", amount))?;
+            policy = policy.with_max_amount(crypto_type, amount);
+        }
+
+        Ok(policy)
+    }
+
+    pub fn with_allowed_destinations(mut self, destinations: Vec<String>) -> Self {
+        self.allowed_destinations = destinations.into_iter().map(|d| d.to_lowercase()).collect();
+        self
+    }
+
+    pub fn with_max_amount(mut self, crypto_type: CryptoType, amount: Decimal) -> Self {
+        self.max_amounts.retain(|(existing, _)| *existing != crypto_type);
+        self.max_amounts.push((crypto_type, amount));
+        self
+    }
+
+    pub fn check_evm(&self, transaction: &EvmTransaction) -> Result<(), ServiceError> {
+        self.check_destination(&format!("{:?}", transaction.to))?;
+
+        let crypto_type = self.evm_chains.iter()
+            .find(|(chain_id, _)| *chain_id == transaction.chain_id)
+            .map(|(_, crypto_type)| *crypto_type);
+        match crypto_type {
+            Some(crypto_type) => self.check_amount(crypto_type, &transaction.value.to_string(), 18),
+            None if self.max_amounts.is_empty() => Ok(()),
+            None => Err(ServiceError::Forbidden(format!("Chain {} is not allowed", transaction.chain_id))),
+        }
+    }
+
+    pub fn check_solana(&self, transfer: &SolanaTransfer) -> Result<(), ServiceError> {
+        self.check_destination(&transfer.to)?;
+        self.check_amount(CryptoType::Sol, &transfer.lamports.to_string(), 9)
+    }
+
+    fn check_destination(&self, destination: &str) -> Result<(), ServiceError> {
+        if self.allowed_destinations.is_empty() || self.allowed_destinations.contains(&destination.to_lowercase()) {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden(format!("Destination {} is not allowed", destination)))
+        }
+    }
+
+    /// Compare an amount in base units (wei, lamports) to the whole-coin maximum
+    fn check_amount(&self, crypto_type: CryptoType, base_units: &str, decimals: u32) -> Result<(), ServiceError> {
+        let Some((_, max)) = self.max_amounts.iter().find(|(t, _)| *t == crypto_type) else {
+            return Ok(());
+        };
+
+        let amount = base_units.parse::<Decimal>().ok()
+            .and_then(|units| units.checked_div(Decimal::from(10u64.pow(decimals))));
+        match amount {
+            Some(amount) if amount <= *max => Ok(()),
+            _ => Err(ServiceError::Forbidden(format!(
+                "Amount exceeds the {} {} signing limit",
+                max, crypto_type
+            ))),
+        }
+    }
+}
+
+/// Serve signing requests on `listener`, applying `policy` to each one
+///
+/// Run by the signer daemon, which holds the keys and its own encryption
+/// settings; the API server only needs the socket path.
+pub async fn serve(
+    listener: tokio::net::UnixListener,
+    signer: Arc<dyn Signer>,
+    policy: Arc<SignerPolicy>,
+) -> std::io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let signer = signer.clone();
+        let policy = policy.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, signer.as_ref(), &policy).await {
+                tracing::warn!("Signer connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: tokio::net::UnixStream,
+    signer: &dyn Signer,
+    policy: &SignerPolicy,
+) -> std::io::Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<SignerRequest>(&line) {
+            Ok(request) => handle_request(signer, policy, request).await,
+            Err(e) => SignerResponse::from_error(ServiceError::ValidationError(format!("Invalid request: {}", e))),
+        };
+
+        let mut reply = serde_json::to_string(&response).map_err(std::io::Error::other)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+async fn handle_request(signer: &dyn Signer, policy: &SignerPolicy, request: SignerRequest) -> SignerResponse {
+    let result = match request {
+        SignerRequest::SignEvmTransaction { from, transaction } => {
+            match policy.check_evm(&transaction) {
+                Ok(()) => signer.sign_evm_transaction(&from, &transaction).await.map(SignerResponse::EvmTransaction),
+                Err(e) => Err(e),
+            }
+        }
+        SignerRequest::SignSolanaMessage { from, transfer } => {
+            match policy.check_solana(&transfer) {
+                Ok(()) => signer
+                    .sign_solana_message(&from, &transfer)
+                    .await
+                    .map(|signature| SignerResponse::SolanaSignature { signature }),
+                Err(e) => Err(e),
+            }
+        }
+        // Keys only go in encrypted, so generating and storing them needs no policy
+        SignerRequest::GenerateKey { crypto_type } => {
+            signer.generate_key(crypto_type).await.map(SignerResponse::GeneratedKey)
+        }
+        SignerRequest::EncryptKey { private_key } => signer
+            .encrypt_key(&private_key)
+            .await
+            .map(|encrypted_private_key| SignerResponse::EncryptedKey { encrypted_private_key }),
+    };
+
+    result.unwrap_or_else(|e| {
+        tracing::warn!("Refused signer request: {}", e);
+        SignerResponse::from_error(e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_KEY: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const ADDRESS: &str = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+    fn transfer(chain_id: u64, wei: u64) -> EvmTransaction {
+        EvmTransaction {
+            chain_id,
+            nonce: U256::zero(),
+            to: "0xf0109fc8df283027b6285cc889f5aa624eac1f55".parse().unwrap(),
+            value: U256::from(wei),
+            gas_price: U256::from(20_000_000_000u64),
+            gas: U256::from(21000),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evm_signing_needs_no_rpc() {
+        let signed = sign_evm_with_key(PRIVATE_KEY, ADDRESS, &transfer(1, 1_000_000_000)).await.unwrap();
+        assert!(!signed.raw_transaction.0.is_empty());
+        assert_ne!(signed.transaction_hash, H256::zero());
+
+        // Same input, same signature
+        let again = sign_evm_with_key(PRIVATE_KEY, ADDRESS, &transfer(1, 1_000_000_000)).await.unwrap();
+        assert_eq!(signed, again);
+    }
+
+    #[tokio::test]
+    async fn test_evm_signing_rejects_key_for_other_address() {
+        let other = "0xf0109fc8df283027b6285cc889f5aa624eac1f55";
+        assert!(sign_evm_with_key(PRIVATE_KEY, other, &transfer(1, 1)).await.is_err());
+    }
+
+    #[test]
+    fn test_solana_signing_verifies_against_sender() {
+        use ed25519_dalek::{Signature, SigningKey, Verifier};
+
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let keypair = bs58::encode(signing_key.to_keypair_bytes()).into_string();
+        let from = bs58::encode(signing_key.verifying_key().as_bytes()).into_string();
+        let transfer = SolanaTransfer {
+            to: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+            lamports: 5_000,
+            recent_blockhash: "11111111111111111111111111111111".to_string(),
+        };
+
+        let message = solana_transfer_message(&from, &transfer).unwrap();
+        assert_eq!(message.len(), 4 + 4 * 32 + 6 + 12);
+        assert_eq!(&message[message.len() - 8..], &5_000u64.to_le_bytes());
+
+        let signature = sign_solana_with_key(&keypair, &from, &transfer).unwrap();
+        let signature = Signature::from_slice(&bs58::decode(signature).into_vec().unwrap()).unwrap();
+        assert!(signing_key.verifying_key().verify(&message, &signature).is_ok());
+
+        // A key for another address is refused
+        assert!(sign_solana_with_key(&keypair, &transfer.to, &transfer).is_err());
+    }
+
+    #[test]
+    fn test_policy_limits_destinations_and_amounts() {
+        let config = Config::default();
+        let policy = SignerPolicy::new(&config)
+            .with_allowed_destinations(vec!["0xF0109FC8DF283027B6285CC889F5AA624EAC1F55".to_string()])
+            .with_max_amount(CryptoType::Eth, Decimal::new(15, 1));
+
+        let one_eth = 1_000_000_000_000_000_000u64;
+        assert!(policy.check_evm(&transfer(config.ethereum_chain_id, one_eth)).is_ok());
+        assert!(matches!(
+            policy.check_evm(&transfer(config.ethereum_chain_id, 2 * one_eth)),
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(policy.check_evm(&transfer(999_999, 1)).is_err());
+
+        let mut elsewhere = transfer(config.ethereum_chain_id, 1);
+        elsewhere.to = ADDRESS.parse().unwrap();
+        assert!(policy.check_evm(&elsewhere).is_err());
+
+        // Unlimited currency, unrestricted destinations
+        let open = SignerPolicy::new(&config);
+        assert!(open.check_solana(&SolanaTransfer {
+            to: "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin".to_string(),
+            lamports: u64::MAX,
+            recent_blockhash: "11111111111111111111111111111111".to_string(),
+        }).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generated_keys_are_returned_encrypted() {
+        std::env::set_var("ENCRYPTION_KEY", "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef");
+        let signer = LocalSigner::new(PgPool::connect_lazy("postgres://localhost/unused").unwrap());
+        let policy = SignerPolicy::new(&Config::default());
+
+        let SignerResponse::GeneratedKey(wallet) =
+            handle_request(&signer, &policy, SignerRequest::GenerateKey { crypto_type: CryptoType::Eth }).await
+        else {
+            panic!("expected a generated key");
+        };
+        assert_eq!(wallet.network, "ethereum");
+
+        let private_key = decrypt_data(&wallet.encrypted_private_key).unwrap();
+        assert_eq!(KeyGenerator::validate_private_key(&private_key, "ethereum").unwrap(), wallet.address);
+    }
+
+    #[test]
+    fn test_protocol_round_trips_errors() {
+        let request = SignerRequest::SignEvmTransaction { from: ADDRESS.to_string(), transaction: transfer(1, 5) };
+        let line = serde_json::to_string(&request).unwrap();
+        assert!(line.contains("\"op\":\"sign_evm_transaction\""));
+
+        let denied = SignerResponse::from_error(ServiceError::Forbidden("Destination not allowed".to_string()));
+        let SignerResponse::Error { code, message } = serde_json::from_str(&serde_json::to_string(&denied).unwrap()).unwrap() else {
+            panic!("expected an error response");
+        };
+        assert!(matches!(SignerResponse::into_error(&code, message), ServiceError::Forbidden(_)));
+    }
+}
@@ -1,10 +1,26 @@
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use chrono::{DateTime, Utc};
 use crate::error::ServiceError;
+use crate::services::email_templates::TwoFactorEnabled;
+use crate::services::notification_service;
 use crate::utils::encryption::Encryption;
 use totp_lite::{totp_custom, Sha1};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Header carrying the 2FA code for sensitive operations
+pub const STEP_UP_HEADER: &str = "X-2FA-Code";
+
+/// Seconds per TOTP time step
+const TIME_STEP: u64 = 30;
+
+/// Digits in a TOTP code
+const DIGITS: u32 = 6;
+
+/// Recovery codes issued per setup or regeneration
+const RECOVERY_CODE_COUNT: usize = 10;
+
 #[derive(Debug, Serialize)]
 pub struct TwoFactorSetup {
     pub secret: String,
@@ -12,18 +28,36 @@ pub struct TwoFactorSetup {
     pub recovery_codes: Vec<String>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub recovery_codes_remaining: i64,
+    /// Whether sensitive operations are refused until 2FA is enabled
+    pub required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    /// TOTP code or recovery code
+    pub code: String,
+}
+
 pub struct TwoFactorService {
     pool: PgPool,
     enabled: bool,
-    encryption: Encryption,
+    required: bool,
 }
 
 impl TwoFactorService {
-    pub fn new(pool: PgPool, enabled: bool) -> Result<Self, ServiceError> {
-        let encryption = Encryption::new()
-            .map_err(|e| ServiceError::InternalError(format!("Encryption init failed: {}", e)))?;
-        
-        Ok(Self { pool, enabled, encryption })
+    pub fn new(pool: PgPool, enabled: bool) -> Self {
+        Self { pool, enabled, required: false }
+    }
+
+    /// Refuse sensitive operations for merchants who have not enabled 2FA
+    pub fn with_required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
     }
 
     pub async fn setup_2fa(&self, merchant_id: i64, merchant_email: &str) -> Result<TwoFactorSetup, ServiceError> {
@@ -31,30 +65,40 @@ impl TwoFactorService {
             return Err(ServiceError::ValidationError("2FA is disabled".to_string()));
         }
 
+        if self.is_enabled(merchant_id).await? {
+            return Err(ServiceError::ValidationError(
+                "2FA is already enabled; disable it before setting up a new authenticator".to_string(),
+            ));
+        }
+
         // Generate secret (base32 encoded, 20 bytes = 32 chars)
         let secret = self.generate_secret();
-        
+
         // Generate recovery codes
-        let recovery_codes = self.generate_recovery_codes(10);
+        let recovery_codes = self.generate_recovery_codes(RECOVERY_CODE_COUNT);
 
-        // Encrypt secret and codes
-        let secret_encrypted = self.encryption.encrypt(&secret)
-            .map_err(|e| ServiceError::InternalError(format!("Encryption failed: {}", e)))?;
-        let codes_json = serde_json::to_string(&recovery_codes)?;
-        let codes_encrypted = self.encryption.encrypt(&codes_json)
+        // Encrypt secret
+        let secret_encrypted = encryption()?.encrypt(&secret)
             .map_err(|e| ServiceError::InternalError(format!("Encryption failed: {}", e)))?;
 
-        // Store in database
+        // Store in database; recovery codes are kept as hashes only
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query!(
             r#"INSERT INTO two_factor_auth (merchant_id, secret_encrypted, recovery_codes_encrypted)
-               VALUES ($1, $2, $3)
-               ON CONFLICT (merchant_id) DO UPDATE 
-               SET secret_encrypted = $2, recovery_codes_encrypted = $3, is_enabled = false"#,
-            merchant_id, secret_encrypted, codes_encrypted
+               VALUES ($1, $2, NULL)
+               ON CONFLICT (merchant_id) DO UPDATE
+               SET secret_encrypted = $2, recovery_codes_encrypted = NULL,
+                   is_enabled = false, last_used_step = NULL"#,
+            merchant_id, secret_encrypted
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        replace_recovery_codes(&mut tx, merchant_id, &recovery_codes).await?;
+
+        tx.commit().await?;
+
         // Generate QR code URL for authenticator apps
         let qr_code_url = format!(
             "otpauth://totp/CryptoGateway:{}?secret={}&issuer=CryptoGateway",
@@ -73,18 +117,39 @@ impl TwoFactorService {
             return Err(ServiceError::ValidationError("2FA is disabled".to_string()));
         }
 
-        // Verify code before enabling
-        if !self.verify_code(merchant_id, code).await? {
+        let record = sqlx::query!(
+            "SELECT secret_encrypted, is_enabled FROM two_factor_auth WHERE merchant_id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| ServiceError::ValidationError("2FA has not been set up".to_string()))?;
+
+        if record.is_enabled {
+            return Err(ServiceError::ValidationError("2FA is already enabled".to_string()));
+        }
+
+        // Verify a code from the new authenticator before enabling
+        if !self.verify_totp(merchant_id, &record.secret_encrypted, code).await? {
             return Err(ServiceError::ValidationError("Invalid 2FA code".to_string()));
         }
 
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query!(
             "UPDATE two_factor_auth SET is_enabled = true, enabled_at = NOW() WHERE merchant_id = $1",
             merchant_id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        let notice = TwoFactorEnabled {
+            enabled_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        };
+        notification_service::enqueue_notification(&mut tx, merchant_id, &notice).await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -93,16 +158,57 @@ impl TwoFactorService {
             return Err(ServiceError::ValidationError("Invalid 2FA code".to_string()));
         }
 
+        let mut tx = self.pool.begin().await?;
+
         sqlx::query!(
             "UPDATE two_factor_auth SET is_enabled = false WHERE merchant_id = $1",
             merchant_id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        sqlx::query!(
+            "DELETE FROM two_factor_recovery_codes WHERE merchant_id = $1",
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
         Ok(())
     }
 
+    /// Replace the merchant's recovery codes, invalidating the old ones
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `code` - Current TOTP code or an unused recovery code
+    pub async fn regenerate_recovery_codes(&self, merchant_id: i64, code: &str) -> Result<Vec<String>, ServiceError> {
+        if !self.is_enabled(merchant_id).await? {
+            return Err(ServiceError::ValidationError("2FA is not enabled".to_string()));
+        }
+
+        if !self.verify_code(merchant_id, code).await? {
+            return Err(ServiceError::ValidationError("Invalid 2FA code".to_string()));
+        }
+
+        let recovery_codes = self.generate_recovery_codes(RECOVERY_CODE_COUNT);
+
+        let mut tx = self.pool.begin().await?;
+        replace_recovery_codes(&mut tx, merchant_id, &recovery_codes).await?;
+        tx.commit().await?;
+
+        Ok(recovery_codes)
+    }
+
+    /// Check a TOTP or recovery code, consuming it
+    ///
+    /// A TOTP code is accepted once: its time step is stored and codes for
+    /// that step or an earlier one are rejected. A recovery code is marked
+    /// used. Returns `true` without checking when 2FA is off for the platform;
+    /// a merchant without 2FA enabled has no code to check and is refused with
+    /// `TwoFactorRequired`.
     pub async fn verify_code(&self, merchant_id: i64, code: &str) -> Result<bool, ServiceError> {
         if !self.enabled {
             return Ok(true); // Skip verification if disabled
@@ -115,19 +221,52 @@ impl TwoFactorService {
         .fetch_optional(&self.pool)
         .await?;
 
-        let Some(record) = record else {
-            return Ok(false);
-        };
+        let record = record.filter(|r| r.is_enabled).ok_or_else(|| {
+            ServiceError::TwoFactorRequired("Two-factor authentication is not enabled".to_string())
+        })?;
 
-        if !record.is_enabled {
-            return Ok(true); // Not enabled yet, allow
+        let code = normalize_code(code);
+        if is_totp_code(&code) {
+            self.verify_totp(merchant_id, &record.secret_encrypted, &code).await
+        } else {
+            self.consume_recovery_code(merchant_id, &code).await
         }
+    }
 
-        let secret = self.encryption.decrypt(&record.secret_encrypted)
-            .map_err(|e| ServiceError::InternalError(format!("Decryption failed: {}", e)))?;
-        
-        // Verify TOTP code
-        self.verify_totp(&secret, code)
+    /// Step-up check for sensitive operations
+    ///
+    /// Merchants with 2FA enabled must supply a valid code. Merchants without
+    /// it pass, unless 2FA is required, in which case they are refused.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `code` - Code from the [`STEP_UP_HEADER`] header, if any
+    pub async fn verify_step_up(&self, merchant_id: i64, code: Option<&str>) -> Result<(), ServiceError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if !self.is_enabled(merchant_id).await? {
+            if self.required {
+                return Err(ServiceError::TwoFactorRequired(
+                    "Two-factor authentication must be enabled for this operation".to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
+        let code = code.filter(|c| !c.trim().is_empty()).ok_or_else(|| {
+            ServiceError::TwoFactorRequired(format!(
+                "Two-factor code required in the {} header",
+                STEP_UP_HEADER
+            ))
+        })?;
+
+        if !self.verify_code(merchant_id, code).await? {
+            return Err(ServiceError::TwoFactorRequired("Invalid or already used two-factor code".to_string()));
+        }
+
+        Ok(())
     }
 
     pub async fn is_enabled(&self, merchant_id: i64) -> Result<bool, ServiceError> {
@@ -145,47 +284,237 @@ impl TwoFactorService {
         Ok(result.unwrap_or(false))
     }
 
+    pub async fn get_status(&self, merchant_id: i64) -> Result<TwoFactorStatus, ServiceError> {
+        let record = sqlx::query!(
+            r#"
+            SELECT t.is_enabled, t.enabled_at,
+                   (SELECT COUNT(*) FROM two_factor_recovery_codes r
+                    WHERE r.merchant_id = t.merchant_id AND r.used_at IS NULL) AS "remaining!"
+            FROM two_factor_auth t
+            WHERE t.merchant_id = $1
+            "#,
+            merchant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let (enabled, enabled_at, recovery_codes_remaining) = match record {
+            Some(r) if self.enabled && r.is_enabled => (true, r.enabled_at, r.remaining),
+            _ => (false, None, 0),
+        };
+
+        Ok(TwoFactorStatus {
+            enabled,
+            enabled_at,
+            recovery_codes_remaining,
+            required: self.enabled && self.required,
+        })
+    }
+
     fn generate_secret(&self) -> String {
         // Generate 20 random bytes and encode as base32
         use rand::Rng;
         let mut rng = rand::thread_rng();
         let bytes: Vec<u8> = (0..20).map(|_| rng.gen()).collect();
-        
-        // Base32 encode (simplified - use proper base32 in production)
+
         base32::encode(base32::Alphabet::RFC4648 { padding: false }, &bytes)
     }
 
     fn generate_recovery_codes(&self, count: usize) -> Vec<String> {
+        // 40 random bits per code, shown as XXXX-XXXX
         (0..count)
-            .map(|_| format!("{:04}-{:04}", rand::random::<u16>() % 10000, rand::random::<u16>() % 10000))
+            .map(|_| {
+                let bytes: [u8; 5] = rand::random();
+                let code = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &bytes);
+                format!("{}-{}", &code[..4], &code[4..])
+            })
             .collect()
     }
 
-    fn verify_totp(&self, secret: &str, code: &str) -> Result<bool, ServiceError> {
-        // Decode base32 secret
-        let secret_bytes = base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)
-            .ok_or_else(|| ServiceError::InternalError("Invalid secret".to_string()))?;
+    /// Verify a TOTP code and record its time step so it cannot be replayed
+    async fn verify_totp(&self, merchant_id: i64, secret_encrypted: &str, code: &str) -> Result<bool, ServiceError> {
+        let secret = encryption()?.decrypt(secret_encrypted)
+            .map_err(|e| ServiceError::InternalError(format!("Decryption failed: {}", e)))?;
 
-        // Get current timestamp
-        let timestamp = SystemTime::now()
+        let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|e| ServiceError::InternalError(format!("Time error: {}", e)))?
             .as_secs();
 
-        // TOTP parameters: 30 second window, 6 digits
-        let time_step: u64 = 30;
-        let digits = 6;
-
-        // Check current time window and ±1 window (90 seconds total)
-        for offset in [-1, 0, 1] {
-            let time = (timestamp as i64 + (offset * time_step as i64)) as u64 / time_step;
-            let expected = totp_custom::<Sha1>(time_step, digits, &secret_bytes, time);
-            
-            if code == expected {
-                return Ok(true);
-            }
+        let Some(step) = matching_step(&secret, &normalize_code(code), now)? else {
+            return Ok(false);
+        };
+
+        // Only a step later than the last accepted one may be used
+        let accepted = sqlx::query!(
+            r#"
+            UPDATE two_factor_auth SET last_used_step = $2
+            WHERE merchant_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            merchant_id,
+            step as i64
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(accepted == 1)
+    }
+
+    /// Mark an unused recovery code as used
+    async fn consume_recovery_code(&self, merchant_id: i64, code: &str) -> Result<bool, ServiceError> {
+        self.import_legacy_recovery_codes(merchant_id).await?;
+
+        let used = sqlx::query!(
+            r#"
+            UPDATE two_factor_recovery_codes SET used_at = NOW()
+            WHERE merchant_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            merchant_id,
+            hash_recovery_code(code)
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(used == 1)
+    }
+
+    /// Move codes from the old encrypted column into the hashed table
+    async fn import_legacy_recovery_codes(&self, merchant_id: i64) -> Result<(), ServiceError> {
+        let legacy = sqlx::query_scalar!(
+            "SELECT recovery_codes_encrypted FROM two_factor_auth WHERE merchant_id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        let Some(encrypted) = legacy else {
+            return Ok(());
+        };
+
+        let codes_json = encryption()?.decrypt(&encrypted)
+            .map_err(|e| ServiceError::InternalError(format!("Decryption failed: {}", e)))?;
+        let codes: Vec<String> = serde_json::from_str(&codes_json)?;
+
+        let mut tx = self.pool.begin().await?;
+        replace_recovery_codes(&mut tx, merchant_id, &codes).await?;
+        sqlx::query!(
+            "UPDATE two_factor_auth SET recovery_codes_encrypted = NULL WHERE merchant_id = $1",
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+fn encryption() -> Result<Encryption, ServiceError> {
+    Encryption::new().map_err(|e| ServiceError::InternalError(format!("Encryption init failed: {}", e)))
+}
+
+async fn replace_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    codes: &[String],
+) -> Result<(), ServiceError> {
+    sqlx::query!(
+        "DELETE FROM two_factor_recovery_codes WHERE merchant_id = $1",
+        merchant_id
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    let hashes: Vec<String> = codes.iter().map(|c| hash_recovery_code(&normalize_code(c))).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO two_factor_recovery_codes (merchant_id, code_hash)
+        SELECT $1, UNNEST($2::varchar[])
+        ON CONFLICT DO NOTHING
+        "#,
+        merchant_id,
+        &hashes
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Uppercase with spaces and dashes removed, so `abcd-efgh` matches `ABCDEFGH`
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == DIGITS as usize && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+fn hash_recovery_code(normalized: &str) -> String {
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+/// Time step (within one step of `now`) whose code matches
+fn matching_step(secret: &str, code: &str, now: u64) -> Result<Option<u64>, ServiceError> {
+    // Decode base32 secret
+    let secret_bytes = base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)
+        .ok_or_else(|| ServiceError::InternalError("Invalid secret".to_string()))?;
+
+    let current = now / TIME_STEP;
+
+    // Check current time window and ±1 window (90 seconds total)
+    for step in [current.saturating_sub(1), current, current + 1] {
+        let expected = totp_custom::<Sha1>(TIME_STEP, DIGITS, &secret_bytes, step * TIME_STEP);
+        if constant_time_eq(expected.as_bytes(), code.as_bytes()) {
+            return Ok(Some(step));
         }
+    }
+
+    Ok(None)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 6238 test secret "12345678901234567890"
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn test_matching_step_uses_rfc_6238_counter() {
+        // RFC 6238: T = 59s -> 94287082 (8 digits), 287082 with 6 digits
+        assert_eq!(matching_step(RFC_SECRET, "287082", 59).unwrap(), Some(1));
+        // One step of drift either way is accepted
+        assert_eq!(matching_step(RFC_SECRET, "287082", 89).unwrap(), Some(1));
+        assert_eq!(matching_step(RFC_SECRET, "287082", 150).unwrap(), None);
+        assert_eq!(matching_step(RFC_SECRET, "000000", 59).unwrap(), None);
+    }
+
+    #[test]
+    fn test_recovery_codes_are_normalized_before_hashing() {
+        assert_eq!(normalize_code(" abcd-efgh "), "ABCDEFGH");
+        assert_eq!(
+            hash_recovery_code(&normalize_code("abcd-efgh")),
+            hash_recovery_code(&normalize_code("ABCDEFGH"))
+        );
+    }
 
-        Ok(false)
+    #[test]
+    fn test_totp_and_recovery_codes_are_told_apart() {
+        assert!(is_totp_code("123456"));
+        assert!(!is_totp_code("1234567"));
+        assert!(!is_totp_code(&normalize_code("ABCD-EFGH")));
+        // Legacy numeric recovery codes are 8 digits
+        assert!(!is_totp_code(&normalize_code("1234-5678")));
     }
 }
@@ -1,81 +1,472 @@
+// Volume Tracking Service
+// Daily volume limits per KYC tier, computed from received and open payments and withdrawals
+
 use rust_decimal::Decimal;
-use sqlx::PgPool;
-use chrono::{DateTime, Utc, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use crate::config::Config;
 use crate::error::ServiceError;
 
+/// Payment statuses whose funds were received and count towards volume
+const RECEIVED_PAYMENT_STATUSES: &[&str] = &["CONFIRMED", "OVERPAID", "PARTIALLY_REFUNDED", "REFUNDED", "PAID_LATE"];
+
+/// Payment statuses that may still receive funds; their full amount is
+/// reserved from the moment they are created
+const OPEN_PAYMENT_STATUSES: &[&str] = &["PENDING", "CONFIRMING", "UNDERPAID"];
+
+/// Merchant verification tier; each has its own daily volume limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KycTier {
+    Unverified,
+    Basic,
+    Full,
+}
+
+impl KycTier {
+    pub const ALL: [KycTier; 3] = [KycTier::Unverified, KycTier::Basic, KycTier::Full];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KycTier::Unverified => "unverified",
+            KycTier::Basic => "basic",
+            KycTier::Full => "full",
+        }
+    }
+
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "basic" => KycTier::Basic,
+            "full" => KycTier::Full,
+            _ => KycTier::Unverified,
+        }
+    }
+}
+
+/// Period the daily limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum VolumeWindow {
+    /// The 24 hours before now
+    #[serde(rename = "rolling")]
+    Rolling24h,
+    /// Since midnight UTC
+    #[serde(rename = "calendar")]
+    CalendarDay,
+}
+
+impl VolumeWindow {
+    pub fn from_string(s: &str) -> Self {
+        match s {
+            "calendar" => VolumeWindow::CalendarDay,
+            _ => VolumeWindow::Rolling24h,
+        }
+    }
+
+    pub fn start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            VolumeWindow::Rolling24h => now - Duration::hours(24),
+            VolumeWindow::CalendarDay => start_of_day(now.date_naive()),
+        }
+    }
+
+    /// When the whole window's volume is released; a rolling window frees volume gradually
+    pub fn resets_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            VolumeWindow::Rolling24h => None,
+            VolumeWindow::CalendarDay => Some(start_of_day(now.date_naive()) + Duration::days(1)),
+        }
+    }
+}
+
+/// Daily limit in USD per tier; `None` is unlimited
+#[derive(Debug, Clone, Copy)]
+pub struct TierLimits {
+    pub unverified: Decimal,
+    pub basic: Decimal,
+    pub full: Option<Decimal>,
+}
+
+impl TierLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            unverified: config.daily_volume_limit_non_kyc_usd,
+            basic: config.daily_volume_limit_basic_kyc_usd,
+            full: config.daily_volume_limit_full_kyc_usd,
+        }
+    }
+
+    pub fn daily_limit(&self, tier: KycTier) -> Option<Decimal> {
+        match tier {
+            KycTier::Unverified => Some(self.unverified),
+            KycTier::Basic => Some(self.basic),
+            KycTier::Full => self.full,
+        }
+    }
+}
+
+impl Default for TierLimits {
+    fn default() -> Self {
+        Self {
+            unverified: Decimal::new(100000, 2), // 1000.00
+            basic: Decimal::new(2500000, 2),     // 25000.00
+            full: None,
+        }
+    }
+}
+
+/// Volume in USD over a period
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeUsage {
+    pub since: DateTime<Utc>,
+    pub inbound_usd: Decimal,
+    pub outbound_usd: Decimal,
+    pub total_usd: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TierLimit {
+    pub tier: KycTier,
+    pub daily_limit_usd: Option<Decimal>,
+}
+
+/// Limits, usage and headroom of a merchant
+#[derive(Debug, Clone, Serialize)]
+pub struct MerchantLimits {
+    pub kyc_tier: KycTier,
+    pub window: VolumeWindow,
+    pub daily_limit_usd: Option<Decimal>,
+    pub used_usd: Decimal,
+    pub remaining_usd: Option<Decimal>,
+    pub resets_at: Option<DateTime<Utc>>,
+    pub rolling_24h: VolumeUsage,
+    pub calendar_day: VolumeUsage,
+    pub tiers: Vec<TierLimit>,
+}
+
 /// Service for tracking and validating daily transaction volumes
 pub struct VolumeTrackingService {
     db_pool: PgPool,
+    limits: TierLimits,
+    window: VolumeWindow,
 }
 
 impl VolumeTrackingService {
     pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
+        Self {
+            db_pool,
+            limits: TierLimits::default(),
+            window: VolumeWindow::Rolling24h,
+        }
     }
 
-    /// Get total daily volume (deposits + withdrawals) for a merchant
-    pub async fn get_daily_volume(&self, merchant_id: i64, date: NaiveDate) -> Result<Decimal, ServiceError> {
-        let start_of_day = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
-        let end_of_day = date.and_hms_opt(23, 59, 59).unwrap().and_utc();
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self {
+            db_pool,
+            limits: TierLimits::from_config(config),
+            window: VolumeWindow::from_string(&config.daily_volume_limit_window),
+        }
+    }
 
-        // For now, return zero as a placeholder until we have actual payment/withdrawal tables
-        // In a real implementation, this would query the actual tables
-        Ok(Decimal::ZERO)
+    pub async fn kyc_tier(&self, merchant_id: i64) -> Result<KycTier, ServiceError> {
+        let tier = sqlx::query_scalar!(
+            "SELECT kyc_tier FROM merchants WHERE id = $1",
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+
+        Ok(KycTier::from_string(&tier))
     }
 
-    /// Check if a merchant can process a transaction without exceeding daily volume limit
-    pub async fn can_process_transaction(
+    /// Set the verification tier; any tier above unverified counts as KYC verified
+    pub async fn set_kyc_tier(&self, merchant_id: i64, tier: KycTier) -> Result<(), ServiceError> {
+        let result = sqlx::query!(
+            "UPDATE merchants SET kyc_tier = $1, kyc_verified = $2, updated_at = NOW() WHERE id = $3",
+            tier.as_str(),
+            tier != KycTier::Unverified,
+            merchant_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ServiceError::MerchantNotFound);
+        }
+        Ok(())
+    }
+
+    /// Received and still-open payments and non-cancelled withdrawals since `since`
+    ///
+    /// Open payments count at their full amount from creation, so a merchant
+    /// cannot get around the limit by creating many invoices before any is paid.
+    ///
+    /// Withdrawals have no USD amount and are valued like new withdrawals
+    /// (see `withdrawal_value_usd`); those without a known rate are not counted.
+    pub async fn volume_since(&self, merchant_id: i64, since: DateTime<Utc>) -> Result<VolumeUsage, ServiceError> {
+        self.volume_between(merchant_id, since, None).await
+    }
+
+    async fn volume_between(
         &self,
         merchant_id: i64,
-        transaction_amount_usd: Decimal,
-        daily_limit_usd: Decimal,
-        is_kyc_verified: bool,
-    ) -> Result<bool, ServiceError> {
-        // KYC verified merchants have no daily volume limit
-        if is_kyc_verified {
-            return Ok(true);
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<VolumeUsage, ServiceError> {
+        let statuses: Vec<String> = RECEIVED_PAYMENT_STATUSES.iter().map(|s| s.to_string()).collect();
+        let open_statuses: Vec<String> = OPEN_PAYMENT_STATUSES.iter().map(|s| s.to_string()).collect();
+
+        let inbound = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(
+                CASE WHEN status = ANY($5) THEN amount_usd
+                     WHEN total_paid > 0 THEN amount_usd * total_paid / amount
+                     ELSE amount_usd END
+            ), 0) AS "total!"
+            FROM payment_transactions
+            WHERE merchant_id = $1 AND (status = ANY($2) OR status = ANY($5))
+              AND COALESCE(confirmed_at, created_at) >= $3
+              AND ($4::timestamptz IS NULL OR COALESCE(confirmed_at, created_at) < $4)
+            "#,
+            merchant_id,
+            &statuses,
+            since,
+            until,
+            &open_statuses
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        let outbound = sqlx::query_scalar!(
+            r#"
+            SELECT COALESCE(SUM(
+                COALESCE(
+                    CASE WHEN w.crypto_type LIKE 'USDT%' THEN w.amount END,
+                    w.amount * (
+                        SELECT p.amount_usd / p.amount FROM payment_transactions p
+                        WHERE p.crypto_type = w.crypto_type
+                        ORDER BY p.created_at DESC LIMIT 1
+                    )
+                )
+            ), 0) AS "total!"
+            FROM withdrawals w
+            WHERE w.merchant_id = $1 AND w.status NOT IN ('CANCELLED', 'REJECTED')
+              AND w.created_at >= $2
+              AND ($3::timestamptz IS NULL OR w.created_at < $3)
+            "#,
+            merchant_id,
+            since,
+            until
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(VolumeUsage {
+            since,
+            inbound_usd: inbound.round_dp(2),
+            outbound_usd: outbound.round_dp(2),
+            total_usd: (inbound + outbound).round_dp(2),
+        })
+    }
+
+    /// Get total daily volume (deposits + withdrawals) for a merchant on a UTC calendar day
+    pub async fn get_daily_volume(&self, merchant_id: i64, date: NaiveDate) -> Result<Decimal, ServiceError> {
+        let start = start_of_day(date);
+        let usage = self
+            .volume_between(merchant_id, start, Some(start + Duration::days(1)))
+            .await?;
+        Ok(usage.total_usd)
+    }
+
+    /// USD value of a withdrawal: stablecoins at par, other currencies at
+    /// the rate of the latest payment in that currency
+    pub async fn withdrawal_value_usd(&self, crypto_type: &str, amount: Decimal) -> Result<Option<Decimal>, ServiceError> {
+        if crypto_type.starts_with("USDT") {
+            return Ok(Some(amount));
         }
 
-        let today = Utc::now().date_naive();
-        let current_volume = self.get_daily_volume(merchant_id, today).await?;
-        let new_total = current_volume + transaction_amount_usd;
+        let rate = sqlx::query_scalar!(
+            r#"
+            SELECT amount_usd / amount AS "rate!"
+            FROM payment_transactions
+            WHERE crypto_type = $1
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+            crypto_type
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        Ok(rate.map(|rate| amount * rate))
+    }
+
+    /// Lock the merchant row until `tx` ends
+    ///
+    /// Take it in the transaction that inserts the payment or withdrawal,
+    /// before checking the limit, so concurrent requests are checked one at
+    /// a time against committed usage.
+    pub async fn lock_merchant(tx: &mut Transaction<'_, Postgres>, merchant_id: i64) -> Result<(), ServiceError> {
+        sqlx::query_scalar!("SELECT id FROM merchants WHERE id = $1 FOR UPDATE", merchant_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or(ServiceError::MerchantNotFound)?;
+        Ok(())
+    }
+
+    /// Reject a transaction that would take the merchant over its daily limit
+    ///
+    /// Callers that go on to record the transaction should hold
+    /// [`Self::lock_merchant`] while checking and inserting.
+    pub async fn check_transaction(&self, merchant_id: i64, amount_usd: Decimal) -> Result<(), ServiceError> {
+        let tier = self.kyc_tier(merchant_id).await?;
+        let Some(limit) = self.limits.daily_limit(tier) else {
+            return Ok(());
+        };
+
+        let used = self
+            .volume_since(merchant_id, self.window.start(Utc::now()))
+            .await?
+            .total_usd;
+        check_headroom(tier, limit, used, amount_usd)
+    }
+
+    /// Reject a withdrawal that would take the merchant over its daily limit
+    pub async fn check_withdrawal(&self, merchant_id: i64, crypto_type: &str, amount: Decimal) -> Result<(), ServiceError> {
+        let tier = self.kyc_tier(merchant_id).await?;
+        if self.limits.daily_limit(tier).is_none() {
+            return Ok(());
+        }
 
-        Ok(new_total <= daily_limit_usd)
+        let amount_usd = self
+            .withdrawal_value_usd(crypto_type, amount)
+            .await?
+            .ok_or_else(|| ServiceError::ValidationError(format!(
+                "No USD rate available for {} to check the daily volume limit",
+                crypto_type
+            )))?;
+        self.check_transaction(merchant_id, amount_usd).await
     }
 
-    /// Get remaining daily volume for a merchant
-    pub async fn get_remaining_daily_volume(
+    /// Check if a merchant can process a transaction without exceeding its daily volume limit
+    pub async fn can_process_transaction(
         &self,
         merchant_id: i64,
-        daily_limit_usd: Decimal,
-        is_kyc_verified: bool,
-    ) -> Result<Option<Decimal>, ServiceError> {
-        // KYC verified merchants have no limit
-        if is_kyc_verified {
-            return Ok(None);
+        transaction_amount_usd: Decimal,
+    ) -> Result<bool, ServiceError> {
+        match self.check_transaction(merchant_id, transaction_amount_usd).await {
+            Ok(()) => Ok(true),
+            Err(ServiceError::DailyVolumeLimitExceeded(_)) => Ok(false),
+            Err(e) => Err(e),
         }
+    }
+
+    /// Get remaining daily volume for a merchant; `None` when it has no limit
+    pub async fn get_remaining_daily_volume(&self, merchant_id: i64) -> Result<Option<Decimal>, ServiceError> {
+        Ok(self.get_limits(merchant_id).await?.remaining_usd)
+    }
+
+    /// Tier, limit, usage over both windows and remaining headroom
+    pub async fn get_limits(&self, merchant_id: i64) -> Result<MerchantLimits, ServiceError> {
+        let now = Utc::now();
+        let tier = self.kyc_tier(merchant_id).await?;
+        let daily_limit_usd = self.limits.daily_limit(tier);
+
+        let rolling_24h = self
+            .volume_since(merchant_id, VolumeWindow::Rolling24h.start(now))
+            .await?;
+        let calendar_day = self
+            .volume_since(merchant_id, VolumeWindow::CalendarDay.start(now))
+            .await?;
+        let used_usd = match self.window {
+            VolumeWindow::Rolling24h => rolling_24h.total_usd,
+            VolumeWindow::CalendarDay => calendar_day.total_usd,
+        };
+
+        Ok(MerchantLimits {
+            kyc_tier: tier,
+            window: self.window,
+            daily_limit_usd,
+            used_usd,
+            remaining_usd: daily_limit_usd.map(|limit| (limit - used_usd).max(Decimal::ZERO)),
+            resets_at: self.window.resets_at(now),
+            rolling_24h,
+            calendar_day,
+            tiers: KycTier::ALL
+                .iter()
+                .map(|&tier| TierLimit { tier, daily_limit_usd: self.limits.daily_limit(tier) })
+                .collect(),
+        })
+    }
+}
 
-        let today = Utc::now().date_naive();
-        let current_volume = self.get_daily_volume(merchant_id, today).await?;
-        let remaining = daily_limit_usd - current_volume;
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
 
-        Ok(Some(remaining.max(Decimal::ZERO)))
+fn check_headroom(tier: KycTier, limit: Decimal, used: Decimal, amount_usd: Decimal) -> Result<(), ServiceError> {
+    if used + amount_usd <= limit {
+        return Ok(());
     }
+
+    let remaining = (limit - used).max(Decimal::ZERO);
+    Err(ServiceError::DailyVolumeLimitExceeded(format!(
+        "Transaction of ${:.2} exceeds the daily volume limit of ${:.2} for the {} KYC tier (${:.2} remaining)",
+        amount_usd,
+        limit,
+        tier.as_str(),
+        remaining
+    )))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use rust_decimal_macros::dec;
 
-    #[tokio::test]
-    async fn test_kyc_verified_no_limit() {
-        // This would require a test database setup
-        // For now, just test the logic
-        let large_amount = dec!(10000.00);
-        let small_limit = dec!(100.00);
-        
-        // KYC verified should always return true regardless of amount
-        // This test would need actual database setup to run
+    #[test]
+    fn test_tier_limits() {
+        let limits = TierLimits::default();
+        assert_eq!(limits.daily_limit(KycTier::Unverified), Some(dec!(1000.00)));
+        assert_eq!(limits.daily_limit(KycTier::Basic), Some(dec!(25000.00)));
+        assert_eq!(limits.daily_limit(KycTier::Full), None);
+        assert_eq!(KycTier::from_string("basic"), KycTier::Basic);
+        assert_eq!(KycTier::from_string("bogus"), KycTier::Unverified);
+    }
+
+    #[test]
+    fn test_window_start() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 15, 30, 0).unwrap();
+        assert_eq!(
+            VolumeWindow::Rolling24h.start(now),
+            Utc.with_ymd_and_hms(2024, 3, 9, 15, 30, 0).unwrap()
+        );
+        assert_eq!(
+            VolumeWindow::CalendarDay.start(now),
+            Utc.with_ymd_and_hms(2024, 3, 10, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            VolumeWindow::CalendarDay.resets_at(now),
+            Some(Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap())
+        );
+        assert_eq!(VolumeWindow::Rolling24h.resets_at(now), None);
+    }
+
+    #[test]
+    fn test_check_headroom() {
+        assert!(check_headroom(KycTier::Unverified, dec!(1000), dec!(600), dec!(400)).is_ok());
+
+        match check_headroom(KycTier::Unverified, dec!(1000), dec!(600), dec!(400.01)) {
+            Err(ServiceError::DailyVolumeLimitExceeded(msg)) => assert!(msg.contains("$400.00 remaining")),
+            other => panic!("expected limit error, got {:?}", other),
+        }
+
+        // Usage already over the limit reports no headroom
+        match check_headroom(KycTier::Basic, dec!(1000), dec!(1200), dec!(1)) {
+            Err(ServiceError::DailyVolumeLimitExceeded(msg)) => assert!(msg.contains("$0.00 remaining")),
+            other => panic!("expected limit error, got {:?}", other),
+        }
     }
 }
@@ -1,9 +1,13 @@
+use crate::config::Config;
 use crate::error::ServiceError;
 use crate::payment::models::CryptoType;
+use crate::services::signer::{signer_from_config, Signer};
+use crate::utils::keygen::KeyGenerator;
 use chrono::{DateTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
+use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WalletConfig {
@@ -17,13 +21,23 @@ pub struct WalletConfig {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Merchant wallet addresses and keys
+///
+/// Generated and imported keys are encrypted by the [`Signer`], so with
+/// `SIGNER_MODE=remote` only the signer daemon can read them back.
 pub struct WalletConfigService {
     db_pool: PgPool,
+    signer: Arc<dyn Signer>,
 }
 
 impl WalletConfigService {
-    pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
+    pub fn new(db_pool: PgPool, signer: Arc<dyn Signer>) -> Self {
+        Self { db_pool, signer }
+    }
+
+    /// Service using the signer selected by `SIGNER_MODE`
+    pub fn from_config(db_pool: PgPool, config: &Config) -> Self {
+        Self::new(db_pool.clone(), signer_from_config(db_pool, config))
     }
 
     pub async fn set_wallet_address(
@@ -102,22 +116,47 @@ impl WalletConfigService {
     }
 
     pub async fn generate_wallet(&self, merchant_id: i64, request: GenerateWalletRequest) -> Result<WalletConfig, ServiceError> {
-        // For now, return a placeholder - this would integrate with actual wallet generation
         let crypto_type = CryptoType::from_string(&request.crypto_type);
-        let placeholder_address = format!("generated_address_for_{}", request.crypto_type);
-        self.set_wallet_address(merchant_id, crypto_type, placeholder_address).await
+        let wallet = self.signer.generate_key(crypto_type).await?;
+        self.store_wallet_key(merchant_id, crypto_type, "generated", wallet.address, wallet.encrypted_private_key).await
     }
 
     pub async fn import_wallet(&self, merchant_id: i64, request: ImportWalletRequest) -> Result<WalletConfig, ServiceError> {
-        // For now, return a placeholder - this would integrate with actual wallet import
         let crypto_type = CryptoType::from_string(&request.crypto_type);
-        let placeholder_address = format!("imported_address_for_{}", request.crypto_type);
-        self.set_wallet_address(merchant_id, crypto_type, placeholder_address).await
+        let private_key = request.private_key.trim();
+        let address = KeyGenerator::validate_private_key(private_key, keygen_network(crypto_type))?;
+        let encrypted_private_key = self.signer.encrypt_key(private_key).await?;
+        self.store_wallet_key(merchant_id, crypto_type, "imported", address, encrypted_private_key).await
     }
 
-    pub async fn export_private_key(&self, merchant_id: i64, request: ExportKeyRequest) -> Result<String, ServiceError> {
-        // For now, return a placeholder - this would integrate with actual key export
-        Ok(format!("private_key_for_{}_{}", merchant_id, request.crypto_type))
+    async fn store_wallet_key(
+        &self,
+        merchant_id: i64,
+        crypto_type: CryptoType,
+        wallet_mode: &str,
+        address: String,
+        encrypted_private_key: String,
+    ) -> Result<WalletConfig, ServiceError> {
+        let config = sqlx::query_as!(
+            WalletConfig,
+            r#"
+            INSERT INTO merchant_wallets (merchant_id, crypto_type, network, address, encrypted_private_key, wallet_mode)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (merchant_id, crypto_type)
+            DO UPDATE SET address = $4, encrypted_private_key = $5, wallet_mode = $6, updated_at = NOW()
+            RETURNING id, merchant_id, crypto_type, network, address, is_active, created_at, updated_at
+            "#,
+            merchant_id,
+            crypto_type.to_string(),
+            crypto_type.network(),
+            address,
+            encrypted_private_key,
+            wallet_mode
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        Ok(config)
     }
 
     pub async fn validate_gas_for_withdrawal(&self, merchant_id: i64, crypto_type: CryptoType, amount: Decimal) -> Result<GasValidationResult, ServiceError> {
@@ -141,6 +180,17 @@ impl WalletConfigService {
         Ok(balance >= amount)
     }
 }
+/// Network name `KeyGenerator` uses for a crypto type
+pub fn keygen_network(crypto_type: CryptoType) -> &'static str {
+    match crypto_type {
+        CryptoType::Sol | CryptoType::UsdtSpl => "solana",
+        CryptoType::Eth | CryptoType::UsdtEth => "ethereum",
+        CryptoType::Bnb | CryptoType::UsdtBep20 => "bsc",
+        CryptoType::Matic | CryptoType::UsdtPolygon => "polygon",
+        CryptoType::Arb | CryptoType::UsdtArbitrum => "arbitrum",
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ConfigureWalletRequest {
     pub crypto_type: String,
@@ -158,11 +208,6 @@ pub struct ImportWalletRequest {
     pub private_key: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ExportKeyRequest {
-    pub crypto_type: String,
-}
-
 #[derive(Debug, Serialize)]
 pub struct GasValidationResult {
     pub valid: bool,
@@ -2,6 +2,9 @@ use crate::error::ServiceError;
 use serde_json::json;
 use sqlx::PgPool;
 
+use crate::services::audit_service::{AuditEntry, AuditService};
+use crate::services::outbox;
+
 pub struct WalletSecurityService {
     db_pool: PgPool,
 }
@@ -17,15 +20,12 @@ impl WalletSecurityService {
             "event_type": "wallet_access"
         });
 
-        sqlx::query!(
-            "INSERT INTO audit_logs (merchant_id, action_type, ip_address, details) VALUES ($1, $2, $3, $4)",
-            merchant_id,
-            "WALLET_ACCESS",
-            ip_address,
-            details_json
-        )
-        .execute(&self.db_pool)
-        .await?;
+        let entry = AuditEntry::new("WALLET_ACCESS")
+            .with_merchant(merchant_id)
+            .with_actor("merchant", Some(merchant_id.to_string()), Some("merchant"))
+            .with_ip(Some(ip_address))
+            .with_details(details_json);
+        AuditService::new(self.db_pool.clone()).record(&entry).await?;
 
         Ok(())
     }
@@ -36,14 +36,22 @@ impl WalletSecurityService {
             "message": message
         });
 
-        sqlx::query!(
-            "INSERT INTO audit_logs (merchant_id, action_type, details) VALUES ($1, $2, $3)",
+        let mut tx = self.db_pool.begin().await?;
+
+        let entry = AuditEntry::new("SECURITY_ALERT")
+            .with_merchant(merchant_id)
+            .with_details(details_json.clone());
+        AuditService::record_in_tx(&mut tx, &entry).await?;
+
+        outbox::enqueue_event(
+            &mut tx,
             merchant_id,
-            "SECURITY_ALERT",
-            details_json
-        )
-        .execute(&self.db_pool)
-        .await?;
+            "security.alert",
+            alert_type,
+            json!({ "object": details_json }),
+        ).await?;
+
+        tx.commit().await?;
 
         Ok(())
     }
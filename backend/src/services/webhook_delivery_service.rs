@@ -0,0 +1,501 @@
+// Webhook Delivery Service
+// Delivery dashboard, manual redelivery, dead-letter queue and endpoint health
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::error::ServiceError;
+use crate::services::email_templates::WebhookEndpointDisabled;
+use crate::services::notification_service;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Maximum number of deliveries a single bulk redelivery may create
+pub const MAX_BULK_REDELIVERIES: i64 = 500;
+
+/// An endpoint with no successful delivery for this long is disabled
+pub const AUTO_DISABLE_AFTER_HOURS: i64 = 72;
+
+/// Response bodies are stored up to this many bytes
+pub const MAX_STORED_RESPONSE_BYTES: usize = 4096;
+
+const DELIVERY_STATUSES: [&str; 3] = ["pending", "delivered", "failed"];
+
+/// A webhook delivery as shown on the merchant dashboard
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    #[serde(rename = "id")]
+    pub delivery_id: String,
+    pub event_id: Option<String>,
+    pub event_type: String,
+    pub endpoint_id: Option<String>,
+    pub url: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub latency_ms: Option<i32>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub next_retry_at: Option<DateTime<Utc>>,
+    pub dead_lettered_at: Option<DateTime<Utc>>,
+    /// Delivery this one was redelivered from
+    pub redelivery_of: Option<String>,
+    /// Request body; only included when fetching a single delivery
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Query parameters for listing deliveries
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliveryQuery {
+    pub endpoint_id: Option<String>,
+    /// `pending`, `delivered` or `failed`
+    pub status: Option<String>,
+    pub event_type: Option<String>,
+    pub event_id: Option<String>,
+    /// Return deliveries older than this delivery id
+    pub starting_after: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// One page of deliveries, newest first
+#[derive(Debug, Serialize)]
+pub struct DeliveryList {
+    pub data: Vec<WebhookDelivery>,
+    pub has_more: bool,
+    pub next_cursor: Option<String>,
+}
+
+/// Selection of deliveries to redeliver in bulk
+///
+/// Either explicit `delivery_ids` or a `created_after` range is required.
+#[derive(Debug, Default, Deserialize)]
+pub struct BulkRedeliverRequest {
+    #[serde(default)]
+    pub delivery_ids: Option<Vec<String>>,
+    #[serde(default)]
+    pub endpoint_id: Option<String>,
+    /// Defaults to `failed`
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkRedeliverResponse {
+    pub redelivered: usize,
+    pub delivery_ids: Vec<String>,
+}
+
+/// Truncate a response body to what is kept for the dashboard
+pub fn truncate_response_body(body: String) -> String {
+    if body.len() <= MAX_STORED_RESPONSE_BYTES {
+        return body;
+    }
+    let mut end = MAX_STORED_RESPONSE_BYTES;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...[truncated]", &body[..end])
+}
+
+fn validate_status(status: &str) -> Result<(), ServiceError> {
+    if DELIVERY_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(ServiceError::ValidationError(format!(
+            "status must be one of: {}",
+            DELIVERY_STATUSES.join(", ")
+        )))
+    }
+}
+
+pub struct WebhookDeliveryService {
+    db_pool: PgPool,
+}
+
+impl WebhookDeliveryService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// List a merchant's webhook deliveries, newest first, with cursor pagination
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `query` - Filters, cursor and page size
+    /// * `dead_letter_only` - Only return deliveries that exhausted their retries
+    pub async fn list_deliveries(
+        &self,
+        merchant_id: i64,
+        query: DeliveryQuery,
+        dead_letter_only: bool,
+    ) -> Result<DeliveryList, ServiceError> {
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        if let Some(status) = &query.status {
+            validate_status(status)?;
+        }
+
+        let cursor = match &query.starting_after {
+            Some(delivery_id) => Some(self.internal_id(merchant_id, delivery_id).await.map_err(|_| {
+                ServiceError::ValidationError("Invalid starting_after cursor".to_string())
+            })?),
+            None => None,
+        };
+
+        let mut deliveries = sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT d.delivery_id, d.event_id, d.event_type, e.endpoint_id AS "endpoint_id?", d.url,
+                   d.status, d.attempts, d.response_status, d.response_body, d.latency_ms,
+                   d.last_attempt_at, d.next_retry_at, d.dead_lettered_at,
+                   o.delivery_id AS "redelivery_of?", NULL::jsonb AS payload, d.created_at
+            FROM webhook_deliveries d
+            LEFT JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            LEFT JOIN webhook_deliveries o ON o.id = d.redelivery_of
+            WHERE d.merchant_id = $1
+              AND ($2::bigint IS NULL OR d.id < $2)
+              AND ($3::text IS NULL OR e.endpoint_id = $3)
+              AND ($4::text IS NULL OR d.status = $4)
+              AND ($5::text IS NULL OR d.event_type = $5)
+              AND ($6::text IS NULL OR d.event_id = $6)
+              AND (NOT $7 OR d.dead_lettered_at IS NOT NULL)
+            ORDER BY d.id DESC
+            LIMIT $8
+            "#,
+            merchant_id,
+            cursor,
+            query.endpoint_id,
+            query.status,
+            query.event_type,
+            query.event_id,
+            dead_letter_only,
+            limit + 1
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let has_more = deliveries.len() as i64 > limit;
+        deliveries.truncate(limit as usize);
+        let next_cursor = if has_more {
+            deliveries.last().map(|d| d.delivery_id.clone())
+        } else {
+            None
+        };
+
+        Ok(DeliveryList {
+            data: deliveries,
+            has_more,
+            next_cursor,
+        })
+    }
+
+    /// Get a single delivery, including its payload
+    pub async fn get_delivery(&self, merchant_id: i64, delivery_id: &str) -> Result<WebhookDelivery, ServiceError> {
+        sqlx::query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT d.delivery_id, d.event_id, d.event_type, e.endpoint_id AS "endpoint_id?", d.url,
+                   d.status, d.attempts, d.response_status, d.response_body, d.latency_ms,
+                   d.last_attempt_at, d.next_retry_at, d.dead_lettered_at,
+                   o.delivery_id AS "redelivery_of?", d.payload AS "payload?", d.created_at
+            FROM webhook_deliveries d
+            LEFT JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            LEFT JOIN webhook_deliveries o ON o.id = d.redelivery_of
+            WHERE d.delivery_id = $1 AND d.merchant_id = $2
+            "#,
+            delivery_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook delivery not found".to_string()))
+    }
+
+    /// Queue a fresh delivery of the same payload to the same endpoint
+    ///
+    /// The original delivery is left untouched; the new one starts with a
+    /// full retry budget and records which delivery it came from.
+    pub async fn redeliver(&self, merchant_id: i64, delivery_id: &str) -> Result<WebhookDelivery, ServiceError> {
+        let original = sqlx::query!(
+            r#"
+            SELECT d.id, d.status, e.is_active AS "endpoint_active?",
+                   EXISTS (
+                       SELECT 1 FROM webhook_deliveries r
+                       WHERE r.redelivery_of = d.id AND r.status = 'pending'
+                   ) AS "redelivery_pending!"
+            FROM webhook_deliveries d
+            LEFT JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            WHERE d.delivery_id = $1 AND d.merchant_id = $2
+            "#,
+            delivery_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook delivery not found".to_string()))?;
+
+        match original.endpoint_active {
+            None => {
+                return Err(ServiceError::ValidationError(
+                    "The endpoint for this delivery has been deleted".to_string(),
+                ))
+            }
+            Some(false) => {
+                return Err(ServiceError::ValidationError(
+                    "The endpoint for this delivery is disabled; re-enable it before redelivering".to_string(),
+                ))
+            }
+            Some(true) => {}
+        }
+        if original.status == "pending" || original.redelivery_pending {
+            return Err(ServiceError::ValidationError(
+                "This delivery is still pending".to_string(),
+            ));
+        }
+
+        let created = self.insert_redeliveries(merchant_id, &[original.id]).await?;
+        let new_id = created
+            .into_iter()
+            .next()
+            .ok_or_else(|| ServiceError::Internal("Redelivery was not created".to_string()))?;
+
+        info!("Redelivering webhook {} as {} for merchant {}", delivery_id, new_id, merchant_id);
+
+        self.get_delivery(merchant_id, &new_id).await
+    }
+
+    /// Redeliver a set or range of deliveries
+    ///
+    /// Deliveries that are still pending, already have a pending redelivery,
+    /// or whose endpoint is deleted or disabled are skipped.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `request` - Explicit delivery IDs, or a created-at range with optional filters
+    pub async fn bulk_redeliver(
+        &self,
+        merchant_id: i64,
+        request: BulkRedeliverRequest,
+    ) -> Result<BulkRedeliverResponse, ServiceError> {
+        if request.delivery_ids.is_none() && request.created_after.is_none() {
+            return Err(ServiceError::ValidationError(
+                "Provide delivery_ids or a created_after range".to_string(),
+            ));
+        }
+        let status = request.status.unwrap_or_else(|| "failed".to_string());
+        validate_status(&status)?;
+
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT d.id
+            FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON e.id = d.endpoint_id AND e.is_active = true
+            WHERE d.merchant_id = $1
+              AND d.status <> 'pending'
+              AND d.status = $2
+              AND ($3::text[] IS NULL OR d.delivery_id = ANY($3))
+              AND ($4::text IS NULL OR e.endpoint_id = $4)
+              AND ($5::text IS NULL OR d.event_type = $5)
+              AND ($6::timestamptz IS NULL OR d.created_at >= $6)
+              AND ($7::timestamptz IS NULL OR d.created_at < $7)
+              AND NOT EXISTS (
+                  SELECT 1 FROM webhook_deliveries r
+                  WHERE r.redelivery_of = d.id AND r.status = 'pending'
+              )
+            ORDER BY d.id ASC
+            LIMIT $8
+            "#,
+            merchant_id,
+            status,
+            request.delivery_ids.as_deref(),
+            request.endpoint_id,
+            request.event_type,
+            request.created_after,
+            request.created_before,
+            MAX_BULK_REDELIVERIES + 1
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        if ids.len() as i64 > MAX_BULK_REDELIVERIES {
+            return Err(ServiceError::ValidationError(format!(
+                "More than {} deliveries match; narrow the range",
+                MAX_BULK_REDELIVERIES
+            )));
+        }
+
+        let delivery_ids = self.insert_redeliveries(merchant_id, &ids).await?;
+
+        info!("Bulk redelivering {} webhooks for merchant {}", delivery_ids.len(), merchant_id);
+
+        Ok(BulkRedeliverResponse {
+            redelivered: delivery_ids.len(),
+            delivery_ids,
+        })
+    }
+
+    async fn insert_redeliveries(&self, merchant_id: i64, ids: &[i64]) -> Result<Vec<String>, ServiceError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let created = sqlx::query_scalar!(
+            r#"
+            INSERT INTO webhook_deliveries (
+                merchant_id, payment_id, event_type, url, payload,
+                status, attempts, next_retry_at, created_at, event_id, endpoint_id, redelivery_of
+            )
+            SELECT d.merchant_id, d.payment_id, d.event_type, e.url, d.payload,
+                   'pending', 0, NOW(), NOW(), d.event_id, d.endpoint_id, d.id
+            FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            WHERE d.merchant_id = $1 AND d.id = ANY($2)
+            ORDER BY d.id ASC
+            RETURNING delivery_id
+            "#,
+            merchant_id,
+            ids
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(created)
+    }
+
+    async fn internal_id(&self, merchant_id: i64, delivery_id: &str) -> Result<i64, ServiceError> {
+        sqlx::query_scalar!(
+            "SELECT id FROM webhook_deliveries WHERE delivery_id = $1 AND merchant_id = $2",
+            delivery_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook delivery not found".to_string()))
+    }
+
+    /// Clear an endpoint's failure streak after a successful delivery
+    pub async fn record_endpoint_success(&self, endpoint_id: i64) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE webhook_endpoints SET failing_since = NULL WHERE id = $1 AND failing_since IS NOT NULL",
+            endpoint_id
+        )
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt and disable the endpoint once it has been
+    /// failing for [`AUTO_DISABLE_AFTER_HOURS`]
+    ///
+    /// The merchant's notice email is queued in the same transaction that
+    /// disables the endpoint. Its pending deliveries are paused until the
+    /// endpoint is re-enabled.
+    ///
+    /// # Returns
+    /// `true` if this failure disabled the endpoint
+    pub async fn record_endpoint_failure(&self, endpoint_id: i64) -> Result<bool, ServiceError> {
+        let failing_since = sqlx::query_scalar!(
+            r#"
+            UPDATE webhook_endpoints
+            SET failing_since = COALESCE(failing_since, NOW())
+            WHERE id = $1 AND is_active = true
+            RETURNING failing_since AS "failing_since!"
+            "#,
+            endpoint_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+
+        let Some(failing_since) = failing_since else {
+            return Ok(false);
+        };
+        if Utc::now() - failing_since < Duration::hours(AUTO_DISABLE_AFTER_HOURS) {
+            return Ok(false);
+        }
+
+        let reason = format!(
+            "No successful delivery for {} hours (failing since {})",
+            AUTO_DISABLE_AFTER_HOURS,
+            failing_since.to_rfc3339()
+        );
+
+        let mut tx = self.db_pool.begin().await?;
+
+        let disabled = sqlx::query!(
+            r#"
+            UPDATE webhook_endpoints e
+            SET is_active = false, disabled_at = NOW(), disabled_reason = $2
+            FROM merchants m
+            WHERE e.id = $1 AND e.is_active = true AND m.id = e.merchant_id
+            RETURNING e.endpoint_id, e.url, e.is_primary, e.merchant_id
+            "#,
+            endpoint_id,
+            reason
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(disabled) = disabled else {
+            return Ok(false);
+        };
+
+        if disabled.is_primary {
+            sqlx::query!(
+                "UPDATE webhook_configs SET is_active = false, updated_at = NOW() WHERE merchant_id = $1",
+                disabled.merchant_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let notice = WebhookEndpointDisabled {
+            endpoint_id: disabled.endpoint_id.clone(),
+            url: disabled.url.clone(),
+            failing_since: failing_since.to_rfc3339(),
+        };
+        notification_service::enqueue_notification(&mut tx, disabled.merchant_id, &notice).await?;
+
+        tx.commit().await?;
+
+        warn!(
+            "Disabled webhook endpoint {} for merchant {}: {}",
+            disabled.endpoint_id, disabled.merchant_id, reason
+        );
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_response_body() {
+        let short = "ok".to_string();
+        assert_eq!(truncate_response_body(short.clone()), short);
+
+        let long = "é".repeat(MAX_STORED_RESPONSE_BYTES);
+        let truncated = truncate_response_body(long);
+        assert!(truncated.ends_with("...[truncated]"));
+        assert!(truncated.len() <= MAX_STORED_RESPONSE_BYTES + "...[truncated]".len());
+    }
+
+    #[test]
+    fn test_validate_status() {
+        assert!(validate_status("failed").is_ok());
+        assert!(validate_status("delivered").is_ok());
+        assert!(validate_status("dead").is_err());
+    }
+}
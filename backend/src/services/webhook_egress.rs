@@ -0,0 +1,255 @@
+// Webhook Egress
+// SSRF-safe DNS resolution, connection pinning and response limits for webhook delivery
+
+use ipnetwork::IpNetwork;
+use reqwest::{redirect, Client, Response};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use url::{Host, Url};
+
+use crate::config::Config;
+use crate::error::ServiceError;
+use crate::middleware::validation::{is_blocked_ip, is_private_or_localhost, validate_webhook_url};
+
+/// Most bytes read from a receiver's response body
+pub const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Redirect hops followed for a single delivery
+pub const MAX_REDIRECTS: usize = 3;
+
+/// An entry of the test-mode allowlist
+#[derive(Debug, Clone, PartialEq)]
+enum AllowEntry {
+    Host(String),
+    Network(IpNetwork),
+}
+
+/// Where webhook requests may go
+///
+/// By default only public HTTPS hosts are reachable: every address a host
+/// resolves to must be public, and the request is pinned to the vetted
+/// address so a second DNS answer cannot redirect it. Outside production an
+/// allowlist (`WEBHOOK_TEST_ALLOWLIST`) lets sandbox merchants use local
+/// receivers such as `localhost` or `10.0.0.0/8`, over HTTP or HTTPS.
+#[derive(Debug, Clone, Default)]
+pub struct EgressPolicy {
+    allowlist: Vec<AllowEntry>,
+}
+
+impl EgressPolicy {
+    /// Policy with the given allowlist entries (hostnames, IPs or CIDRs)
+    pub fn with_allowlist<S: AsRef<str>>(entries: &[S]) -> Self {
+        let allowlist = entries
+            .iter()
+            .map(|e| e.as_ref().trim().trim_end_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .map(|e| match e.trim_start_matches('[').trim_end_matches(']').parse::<IpNetwork>() {
+                Ok(network) => AllowEntry::Network(network),
+                Err(_) => AllowEntry::Host(e),
+            })
+            .collect();
+
+        Self { allowlist }
+    }
+
+    /// Policy from `Config::webhook_test_allowlist`, ignored in production
+    pub fn from_config(config: &Config) -> Self {
+        if config.environment == "production" {
+            return Self::default();
+        }
+        Self::with_allowlist(&config.webhook_test_allowlist)
+    }
+
+    fn allows_host(&self, host: &Host<&str>) -> bool {
+        match host {
+            Host::Domain(domain) => {
+                let domain = domain.trim_end_matches('.').to_lowercase();
+                self.allowlist.iter().any(|e| matches!(e, AllowEntry::Host(h) if *h == domain))
+            }
+            Host::Ipv4(ip) => self.allows_ip(IpAddr::V4(*ip)),
+            Host::Ipv6(ip) => self.allows_ip(IpAddr::V6(*ip)),
+        }
+    }
+
+    fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.allowlist.iter().any(|e| matches!(e, AllowEntry::Network(n) if n.contains(ip)))
+    }
+
+    /// Validate a webhook URL before it is stored
+    ///
+    /// Allowlisted hosts may use HTTP; everything else must pass
+    /// [`validate_webhook_url`]. Hostnames are resolved at delivery time.
+    pub fn validate_url(&self, url: &str) -> Result<Url, ServiceError> {
+        let parsed = Url::parse(url)
+            .map_err(|_| ServiceError::InvalidWebhookUrl("Invalid URL format".to_string()))?;
+
+        let host = parsed.host().ok_or_else(|| {
+            ServiceError::InvalidWebhookUrl("Webhook URL must have a valid host".to_string())
+        })?;
+
+        if self.allows_host(&host) {
+            if !matches!(parsed.scheme(), "https" | "http") {
+                return Err(ServiceError::InvalidWebhookUrl("Webhook URL must use HTTPS".to_string()));
+            }
+            return Ok(parsed);
+        }
+
+        validate_webhook_url(url).map_err(|e| ServiceError::InvalidWebhookUrl(e.code.to_string()))?;
+
+        Ok(parsed)
+    }
+
+    /// Resolve a webhook URL and vet every address it points to
+    ///
+    /// Returns the address the request must be pinned to. A host is rejected
+    /// if any of its addresses is private, loopback, link-local, metadata or
+    /// otherwise reserved, unless the allowlist covers it.
+    pub async fn resolve(&self, url: &Url) -> Result<SocketAddr, String> {
+        let host = url.host().ok_or_else(|| "Webhook URL has no host".to_string())?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| "Webhook URL has no port".to_string())?;
+        let host_allowed = self.allows_host(&host);
+
+        if !host_allowed {
+            if url.scheme() != "https" {
+                return Err("Webhook URL must use HTTPS".to_string());
+            }
+            if let Host::Domain(domain) = &host {
+                if is_private_or_localhost(domain) {
+                    return Err(format!("Webhook host {} is not allowed", domain));
+                }
+            }
+        }
+
+        let addrs: Vec<SocketAddr> = match host {
+            Host::Ipv4(ip) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+            Host::Ipv6(ip) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+            Host::Domain(domain) => tokio::net::lookup_host((domain, port))
+                .await
+                .map_err(|e| format!("DNS resolution failed for {}: {}", domain, e))?
+                .collect(),
+        };
+
+        if !host_allowed {
+            if let Some(blocked) = addrs.iter().find(|a| is_blocked_ip(a.ip()) && !self.allows_ip(a.ip())) {
+                return Err(format!("Webhook host resolves to a blocked address ({})", blocked.ip()));
+            }
+        }
+
+        addrs
+            .into_iter()
+            .next()
+            .ok_or_else(|| "DNS resolution returned no addresses".to_string())
+    }
+
+    /// HTTP client that connects only to `addr` for the URL's host
+    ///
+    /// Redirects and proxies are disabled; callers follow redirects
+    /// themselves so every hop is vetted.
+    pub fn pinned_client(&self, url: &Url, addr: SocketAddr, timeout: Duration) -> Result<Client, String> {
+        let mut builder = Client::builder()
+            .timeout(timeout)
+            .redirect(redirect::Policy::none())
+            .no_proxy();
+
+        if let Some(Host::Domain(domain)) = url.host() {
+            builder = builder.resolve(domain, addr);
+        }
+
+        builder
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))
+    }
+}
+
+/// Target of a redirect response, resolved against the request URL
+pub fn redirect_target(current: &Url, response: &Response) -> Result<Url, String> {
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| format!("HTTP {} redirect without Location", response.status().as_u16()))?;
+
+    current
+        .join(location)
+        .map_err(|_| format!("Invalid redirect Location: {}", location))
+}
+
+/// Read a response body, keeping at most [`MAX_RESPONSE_BYTES`]
+///
+/// Stops reading once the cap is reached so a receiver cannot stream an
+/// unbounded body into the dispatcher.
+pub async fn read_capped(mut response: Response) -> String {
+    let mut body = Vec::new();
+
+    while let Ok(Some(chunk)) = response.chunk().await {
+        let room = MAX_RESPONSE_BYTES - body.len();
+        body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        if body.len() >= MAX_RESPONSE_BYTES {
+            break;
+        }
+    }
+
+    String::from_utf8_lossy(&body).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url_requires_public_https_by_default() {
+        let policy = EgressPolicy::default();
+
+        assert!(policy.validate_url("https://example.com/webhook").is_ok());
+        assert!(policy.validate_url("http://example.com/webhook").is_err());
+        assert!(policy.validate_url("https://localhost:3000/webhook").is_err());
+        assert!(policy.validate_url("https://[fd00::1]/webhook").is_err());
+        assert!(policy.validate_url("https://169.254.169.254/latest").is_err());
+    }
+
+    #[test]
+    fn test_allowlist_admits_local_receivers() {
+        let policy = EgressPolicy::with_allowlist(&["localhost", "10.0.0.0/8", "[::1]"]);
+
+        assert!(policy.validate_url("http://localhost:3000/webhook").is_ok());
+        assert!(policy.validate_url("http://10.1.2.3/webhook").is_ok());
+        assert!(policy.validate_url("http://[::1]:8080/webhook").is_ok());
+        assert!(policy.validate_url("http://192.168.1.10/webhook").is_err());
+        assert!(policy.validate_url("ftp://localhost/webhook").is_err());
+    }
+
+    #[test]
+    fn test_allowlist_ignored_in_production() {
+        let config = Config {
+            environment: "production".to_string(),
+            webhook_test_allowlist: vec!["localhost".to_string()],
+            ..Config::default()
+        };
+
+        let policy = EgressPolicy::from_config(&config);
+        assert!(policy.validate_url("http://localhost:3000/webhook").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_rejects_blocked_literals() {
+        let policy = EgressPolicy::default();
+
+        for url in ["https://127.0.0.1/", "https://[::ffff:10.0.0.1]/", "https://169.254.169.254/"] {
+            let url = Url::parse(url).unwrap();
+            assert!(policy.resolve(&url).await.is_err(), "{} should be rejected", url);
+        }
+
+        let url = Url::parse("https://8.8.8.8/hook").unwrap();
+        assert_eq!(policy.resolve(&url).await.unwrap(), "8.8.8.8:443".parse().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_pins_allowlisted_localhost() {
+        let policy = EgressPolicy::with_allowlist(&["127.0.0.1"]);
+        let url = Url::parse("http://127.0.0.1:9000/hook").unwrap();
+
+        assert_eq!(policy.resolve(&url).await.unwrap(), "127.0.0.1:9000".parse().unwrap());
+    }
+}
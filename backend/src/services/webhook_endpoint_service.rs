@@ -0,0 +1,658 @@
+// Webhook Endpoint Service
+// Multiple webhook endpoints per merchant with per-endpoint secrets and subscriptions
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::ServiceError;
+use crate::services::webhook_egress::EgressPolicy;
+use crate::utils::encryption::Encryption;
+
+/// Maximum number of endpoints a merchant can register
+pub const MAX_ENDPOINTS_PER_MERCHANT: i64 = 16;
+
+/// How long the previous secret keeps signing deliveries after a rotation
+pub const DEFAULT_ROLLOVER_HOURS: i64 = 24;
+const MAX_ROLLOVER_HOURS: i64 = 7 * 24;
+
+/// Event families an endpoint can subscribe to
+const EVENT_FAMILIES: [&str; 6] = ["payment", "refund", "withdrawal", "invoice", "balance", "security"];
+
+/// A webhook endpoint as shown to the merchant (never includes the secret)
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEndpoint {
+    #[serde(rename = "id")]
+    pub endpoint_id: String,
+    pub url: String,
+    pub description: Option<String>,
+    pub enabled_events: Vec<String>,
+    pub is_primary: bool,
+    pub is_active: bool,
+    /// True while deliveries are also signed with the rotated-out secret
+    pub rollover_active: bool,
+    pub previous_secret_expires_at: Option<DateTime<Utc>>,
+    /// First failed attempt since the last successful delivery
+    pub failing_since: Option<DateTime<Utc>>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub disabled_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Endpoint returned from create and rotate, carrying the plaintext secret once
+#[derive(Debug, Serialize)]
+pub struct WebhookEndpointWithSecret {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookEndpointRequest {
+    pub url: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Defaults to every event (`["*"]`)
+    #[serde(default)]
+    pub enabled_events: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhookEndpointRequest {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub enabled_events: Option<Vec<String>>,
+    #[serde(default)]
+    pub is_active: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RotateSecretRequest {
+    /// Hours the old secret keeps signing deliveries; 0 revokes it immediately
+    #[serde(default)]
+    pub rollover_hours: Option<i64>,
+}
+
+/// Endpoint a delivery should be queued for
+#[derive(Debug, Clone)]
+pub struct DeliveryTarget {
+    pub id: i64,
+    pub url: String,
+}
+
+/// Secrets used to sign a delivery to one endpoint
+#[derive(Debug, Clone)]
+pub struct SigningSecrets {
+    pub current: String,
+    /// Rotated-out secret that is still inside its rollover window
+    pub previous: Option<String>,
+}
+
+struct EndpointRow {
+    endpoint_id: String,
+    url: String,
+    description: Option<String>,
+    enabled_events: Vec<String>,
+    is_primary: bool,
+    is_active: bool,
+    previous_secret_expires_at: Option<DateTime<Utc>>,
+    failing_since: Option<DateTime<Utc>>,
+    disabled_at: Option<DateTime<Utc>>,
+    disabled_reason: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<EndpointRow> for WebhookEndpoint {
+    fn from(row: EndpointRow) -> Self {
+        let rollover_active = row
+            .previous_secret_expires_at
+            .map(|expires| expires > Utc::now())
+            .unwrap_or(false);
+
+        Self {
+            endpoint_id: row.endpoint_id,
+            url: row.url,
+            description: row.description,
+            enabled_events: row.enabled_events,
+            is_primary: row.is_primary,
+            is_active: row.is_active,
+            rollover_active,
+            previous_secret_expires_at: row.previous_secret_expires_at.filter(|_| rollover_active),
+            failing_since: row.failing_since,
+            disabled_at: row.disabled_at,
+            disabled_reason: row.disabled_reason,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Whether an endpoint's subscriptions include an event type
+///
+/// `"*"` matches everything and `"payment.*"` matches every payment event.
+pub fn endpoint_subscribes(enabled_events: &[String], event_type: &str) -> bool {
+    enabled_events.iter().any(|pattern| {
+        pattern == "*"
+            || pattern == event_type
+            || pattern
+                .strip_suffix(".*")
+                .map(|family| event_type.strip_prefix(family).is_some_and(|rest| rest.starts_with('.')))
+                .unwrap_or(false)
+    })
+}
+
+/// Generate a new endpoint signing secret
+pub fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::random();
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+fn new_endpoint_id() -> String {
+    format!("we_{}", Uuid::new_v4().simple())
+}
+
+fn encrypt_secret(secret: &str) -> Result<String, ServiceError> {
+    Encryption::new()
+        .and_then(|enc| enc.encrypt(secret))
+        .map_err(|e| ServiceError::Internal(format!("Webhook secret encryption failed: {}", e)))
+}
+
+fn decrypt_secret(encrypted: &str) -> Result<String, ServiceError> {
+    Encryption::new()
+        .and_then(|enc| enc.decrypt(encrypted))
+        .map_err(|e| ServiceError::Internal(format!("Webhook secret decryption failed: {}", e)))
+}
+
+fn validate_events(events: &[String]) -> Result<(), ServiceError> {
+    if events.is_empty() {
+        return Err(ServiceError::ValidationError(
+            "enabled_events must contain at least one event type".to_string(),
+        ));
+    }
+
+    for event in events {
+        if event == "*" {
+            continue;
+        }
+        let family = event.split('.').next().unwrap_or_default();
+        let rest = &event[family.len()..];
+        if !EVENT_FAMILIES.contains(&family) || rest.len() < 2 || !rest.starts_with('.') {
+            return Err(ServiceError::ValidationError(format!(
+                "Unknown event type '{}'",
+                event
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Active endpoints of a merchant subscribed to an event type
+pub async fn delivery_targets<'e, E: PgExecutor<'e>>(
+    executor: E,
+    merchant_id: i64,
+    event_type: &str,
+) -> Result<Vec<DeliveryTarget>, ServiceError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, url, enabled_events
+        FROM webhook_endpoints
+        WHERE merchant_id = $1 AND is_active = true
+        ORDER BY id
+        "#,
+        merchant_id
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|row| endpoint_subscribes(&row.enabled_events, event_type))
+        .map(|row| DeliveryTarget { id: row.id, url: row.url })
+        .collect())
+}
+
+/// Current URL and signing secrets of an endpoint, by database ID
+///
+/// Endpoints migrated without a secret get one on their first delivery; the
+/// merchant rotates it to learn the value. Returns `None` when the endpoint
+/// no longer exists.
+pub async fn signing_target(
+    db_pool: &PgPool,
+    endpoint_id: i64,
+) -> Result<Option<(String, SigningSecrets)>, ServiceError> {
+    let row = sqlx::query!(
+        r#"
+        SELECT url, secret_encrypted, previous_secret_encrypted, previous_secret_expires_at
+        FROM webhook_endpoints
+        WHERE id = $1
+        "#,
+        endpoint_id
+    )
+    .fetch_optional(db_pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let current = match row.secret_encrypted {
+        Some(encrypted) => decrypt_secret(&encrypted)?,
+        None => provision_secret(db_pool, endpoint_id).await?,
+    };
+    let previous = match (row.previous_secret_encrypted, row.previous_secret_expires_at) {
+        (Some(encrypted), Some(expires)) if expires > Utc::now() => Some(decrypt_secret(&encrypted)?),
+        _ => None,
+    };
+
+    Ok(Some((row.url, SigningSecrets { current, previous })))
+}
+
+/// Give an endpoint that has no signing secret its own
+///
+/// Concurrent callers agree on a single secret: whichever is stored first wins.
+async fn provision_secret(db_pool: &PgPool, endpoint_id: i64) -> Result<String, ServiceError> {
+    let stored = sqlx::query_scalar!(
+        r#"
+        UPDATE webhook_endpoints
+        SET secret_encrypted = COALESCE(secret_encrypted, $2)
+        WHERE id = $1
+        RETURNING secret_encrypted AS "secret_encrypted!"
+        "#,
+        endpoint_id,
+        encrypt_secret(&generate_secret())?
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    info!("Provisioned signing secret for webhook endpoint {}", endpoint_id);
+
+    decrypt_secret(&stored)
+}
+
+/// Give every endpoint that still lacks a signing secret its own
+///
+/// Covers endpoints folded in by migrations (from `webhook_configs` and
+/// `merchants.webhook_url`) before anything is delivered to them.
+///
+/// # Returns
+/// Number of endpoints that got a secret
+pub async fn provision_missing_secrets(db_pool: &PgPool) -> Result<u64, ServiceError> {
+    let endpoint_ids = sqlx::query_scalar!("SELECT id FROM webhook_endpoints WHERE secret_encrypted IS NULL")
+        .fetch_all(db_pool)
+        .await?;
+
+    for endpoint_id in &endpoint_ids {
+        provision_secret(db_pool, *endpoint_id).await?;
+    }
+
+    Ok(endpoint_ids.len() as u64)
+}
+
+/// Point the merchant's primary endpoint at a URL, creating it if needed
+///
+/// Backs the legacy `PUT /api/v1/merchant/webhook` API.
+///
+/// # Returns
+/// The signing secret when a new primary endpoint was created
+pub async fn upsert_primary_endpoint(
+    tx: &mut Transaction<'_, Postgres>,
+    merchant_id: i64,
+    url: &str,
+) -> Result<Option<String>, ServiceError> {
+    let updated = sqlx::query!(
+        "UPDATE webhook_endpoints SET url = $2, is_active = true WHERE merchant_id = $1 AND is_primary",
+        merchant_id,
+        url
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    if updated.rows_affected() > 0 {
+        return Ok(None);
+    }
+
+    let secret = generate_secret();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_endpoints (endpoint_id, merchant_id, url, is_primary, secret_encrypted)
+        VALUES ($1, $2, $3, true, $4)
+        "#,
+        new_endpoint_id(),
+        merchant_id,
+        url,
+        encrypt_secret(&secret)?
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(Some(secret))
+}
+
+pub struct WebhookEndpointService {
+    db_pool: PgPool,
+    egress: EgressPolicy,
+}
+
+impl WebhookEndpointService {
+    pub fn new(db_pool: PgPool) -> Self {
+        Self {
+            db_pool,
+            egress: EgressPolicy::default(),
+        }
+    }
+
+    /// Validate endpoint URLs against a different egress policy
+    pub fn with_egress_policy(mut self, egress: EgressPolicy) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Register a new webhook endpoint
+    ///
+    /// The signing secret is generated here and returned only in this response.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `request` - URL, description and event subscriptions
+    pub async fn create_endpoint(
+        &self,
+        merchant_id: i64,
+        request: CreateWebhookEndpointRequest,
+    ) -> Result<WebhookEndpointWithSecret, ServiceError> {
+        self.egress.validate_url(&request.url)?;
+        let enabled_events = request.enabled_events.unwrap_or_else(|| vec!["*".to_string()]);
+        validate_events(&enabled_events)?;
+
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM webhook_endpoints WHERE merchant_id = $1"#,
+            merchant_id
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        if count >= MAX_ENDPOINTS_PER_MERCHANT {
+            return Err(ServiceError::ValidationError(format!(
+                "A merchant can register at most {} webhook endpoints",
+                MAX_ENDPOINTS_PER_MERCHANT
+            )));
+        }
+
+        let secret = generate_secret();
+        let row = sqlx::query_as!(
+            EndpointRow,
+            r#"
+            INSERT INTO webhook_endpoints (endpoint_id, merchant_id, url, description, enabled_events, secret_encrypted)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING endpoint_id, url, description, enabled_events, is_primary, is_active,
+                      previous_secret_expires_at, failing_since, disabled_at, disabled_reason,
+                      created_at, updated_at
+            "#,
+            new_endpoint_id(),
+            merchant_id,
+            request.url,
+            request.description,
+            &enabled_events,
+            encrypt_secret(&secret)?
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+
+        info!("Created webhook endpoint {} for merchant {}", row.endpoint_id, merchant_id);
+
+        Ok(WebhookEndpointWithSecret {
+            endpoint: row.into(),
+            secret,
+        })
+    }
+
+    /// List a merchant's webhook endpoints, oldest first
+    pub async fn list_endpoints(&self, merchant_id: i64) -> Result<Vec<WebhookEndpoint>, ServiceError> {
+        let rows = sqlx::query_as!(
+            EndpointRow,
+            r#"
+            SELECT endpoint_id, url, description, enabled_events, is_primary, is_active,
+                   previous_secret_expires_at, failing_since, disabled_at, disabled_reason,
+                   created_at, updated_at
+            FROM webhook_endpoints
+            WHERE merchant_id = $1
+            ORDER BY id ASC
+            "#,
+            merchant_id
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    /// Get a single endpoint by its public ID
+    pub async fn get_endpoint(&self, merchant_id: i64, endpoint_id: &str) -> Result<WebhookEndpoint, ServiceError> {
+        sqlx::query_as!(
+            EndpointRow,
+            r#"
+            SELECT endpoint_id, url, description, enabled_events, is_primary, is_active,
+                   previous_secret_expires_at, failing_since, disabled_at, disabled_reason,
+                   created_at, updated_at
+            FROM webhook_endpoints
+            WHERE endpoint_id = $1 AND merchant_id = $2
+            "#,
+            endpoint_id,
+            merchant_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .map(Into::into)
+        .ok_or_else(|| ServiceError::NotFound("Webhook endpoint not found".to_string()))
+    }
+
+    /// Update an endpoint's URL, description, subscriptions or enabled state
+    ///
+    /// Re-enabling an endpoint clears its failure streak and resumes its
+    /// paused deliveries.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `endpoint_id` - Public ID of the endpoint
+    /// * `request` - Fields to change; omitted fields keep their current value
+    pub async fn update_endpoint(
+        &self,
+        merchant_id: i64,
+        endpoint_id: &str,
+        request: UpdateWebhookEndpointRequest,
+    ) -> Result<WebhookEndpoint, ServiceError> {
+        if let Some(url) = &request.url {
+            self.egress.validate_url(url)?;
+        }
+        if let Some(events) = &request.enabled_events {
+            validate_events(events)?;
+        }
+
+        let row = sqlx::query_as!(
+            EndpointRow,
+            r#"
+            UPDATE webhook_endpoints
+            SET url = COALESCE($3, url),
+                description = COALESCE($4, description),
+                enabled_events = COALESCE($5, enabled_events),
+                is_active = COALESCE($6, is_active),
+                failing_since = CASE WHEN $6 IS NOT NULL THEN NULL ELSE failing_since END,
+                disabled_at = CASE WHEN $6 IS TRUE THEN NULL ELSE disabled_at END,
+                disabled_reason = CASE WHEN $6 IS TRUE THEN NULL ELSE disabled_reason END
+            WHERE endpoint_id = $1 AND merchant_id = $2
+            RETURNING endpoint_id, url, description, enabled_events, is_primary, is_active,
+                      previous_secret_expires_at, failing_since, disabled_at, disabled_reason,
+                      created_at, updated_at
+            "#,
+            endpoint_id,
+            merchant_id,
+            request.url,
+            request.description,
+            request.enabled_events.as_deref(),
+            request.is_active
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook endpoint not found".to_string()))?;
+
+        // Keep the legacy single-URL configuration in step with the primary endpoint
+        if row.is_primary {
+            sqlx::query!(
+                "UPDATE webhook_configs SET url = $2, is_active = $3, updated_at = NOW() WHERE merchant_id = $1",
+                merchant_id,
+                row.url,
+                row.is_active
+            )
+            .execute(&self.db_pool)
+            .await?;
+        }
+
+        info!("Updated webhook endpoint {} for merchant {}", endpoint_id, merchant_id);
+
+        Ok(row.into())
+    }
+
+    /// Delete an endpoint and abandon its undelivered webhooks
+    pub async fn delete_endpoint(&self, merchant_id: i64, endpoint_id: &str) -> Result<(), ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
+        // Fail the endpoint's queued deliveries before ON DELETE SET NULL detaches them
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'failed', next_retry_at = NULL, response_body = 'Webhook endpoint deleted'
+            WHERE status = 'pending'
+              AND endpoint_id = (SELECT id FROM webhook_endpoints WHERE endpoint_id = $1 AND merchant_id = $2)
+            "#,
+            endpoint_id,
+            merchant_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let deleted = sqlx::query!(
+            "DELETE FROM webhook_endpoints WHERE endpoint_id = $1 AND merchant_id = $2 RETURNING id, is_primary",
+            endpoint_id,
+            merchant_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook endpoint not found".to_string()))?;
+
+        if deleted.is_primary {
+            sqlx::query!("DELETE FROM webhook_configs WHERE merchant_id = $1", merchant_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        info!("Deleted webhook endpoint {} (id {}) for merchant {}", endpoint_id, deleted.id, merchant_id);
+
+        Ok(())
+    }
+
+    /// Replace an endpoint's signing secret
+    ///
+    /// The old secret keeps signing deliveries alongside the new one for the
+    /// rollover window so the merchant can deploy the new secret without
+    /// rejecting webhooks in between.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `endpoint_id` - Public ID of the endpoint
+    /// * `rollover_hours` - Hours to keep the old secret; defaults to 24, 0 revokes it immediately
+    pub async fn rotate_secret(
+        &self,
+        merchant_id: i64,
+        endpoint_id: &str,
+        rollover_hours: Option<i64>,
+    ) -> Result<WebhookEndpointWithSecret, ServiceError> {
+        let rollover_hours = rollover_hours.unwrap_or(DEFAULT_ROLLOVER_HOURS);
+        if !(0..=MAX_ROLLOVER_HOURS).contains(&rollover_hours) {
+            return Err(ServiceError::ValidationError(format!(
+                "rollover_hours must be between 0 and {}",
+                MAX_ROLLOVER_HOURS
+            )));
+        }
+
+        let secret = generate_secret();
+        let previous_expires_at = (rollover_hours > 0).then(|| Utc::now() + Duration::hours(rollover_hours));
+
+        // Endpoints that have not been sent anything yet may have no secret to roll over
+        let row = sqlx::query_as!(
+            EndpointRow,
+            r#"
+            UPDATE webhook_endpoints
+            SET previous_secret_encrypted = CASE WHEN $4::timestamptz IS NULL THEN NULL ELSE secret_encrypted END,
+                previous_secret_expires_at = CASE WHEN secret_encrypted IS NULL THEN NULL ELSE $4 END,
+                secret_encrypted = $3
+            WHERE endpoint_id = $1 AND merchant_id = $2
+            RETURNING endpoint_id, url, description, enabled_events, is_primary, is_active,
+                      previous_secret_expires_at, failing_since, disabled_at, disabled_reason,
+                      created_at, updated_at
+            "#,
+            endpoint_id,
+            merchant_id,
+            encrypt_secret(&secret)?,
+            previous_expires_at
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Webhook endpoint not found".to_string()))?;
+
+        info!(
+            "Rotated signing secret for webhook endpoint {} (rollover {}h)",
+            endpoint_id, rollover_hours
+        );
+
+        Ok(WebhookEndpointWithSecret {
+            endpoint: row.into(),
+            secret,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_endpoint_subscribes_patterns() {
+        assert!(endpoint_subscribes(&events(&["*"]), "refund.completed"));
+        assert!(endpoint_subscribes(&events(&["payment.*"]), "payment.confirmed"));
+        assert!(endpoint_subscribes(&events(&["refund.completed"]), "refund.completed"));
+        assert!(!endpoint_subscribes(&events(&["payment.*"]), "refund.completed"));
+        assert!(!endpoint_subscribes(&events(&["payment.*"]), "payments.confirmed"));
+        assert!(!endpoint_subscribes(&events(&[]), "payment.confirmed"));
+    }
+
+    #[test]
+    fn test_validate_events() {
+        assert!(validate_events(&events(&["*"])).is_ok());
+        assert!(validate_events(&events(&["payment.*", "withdrawal.completed"])).is_ok());
+        assert!(validate_events(&events(&[])).is_err());
+        assert!(validate_events(&events(&["orders.created"])).is_err());
+        assert!(validate_events(&events(&["payment"])).is_err());
+        assert!(validate_events(&events(&["payment."])).is_err());
+    }
+
+    #[test]
+    fn test_generate_secret_format() {
+        let secret = generate_secret();
+        assert!(secret.starts_with("whsec_"));
+        assert_eq!(secret.len(), "whsec_".len() + 64);
+        assert_ne!(secret, generate_secret());
+    }
+}
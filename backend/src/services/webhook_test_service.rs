@@ -0,0 +1,235 @@
+// Webhook Test Service
+// Signed sample events sent to a merchant's webhook endpoint on demand
+
+use chrono::Utc;
+use nanoid::nanoid;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::error::ServiceError;
+use crate::models::webhook::WebhookPayload;
+use crate::payment::models::PaymentStatus;
+use crate::services::event_service::{self, Event};
+use crate::services::outbox;
+use crate::services::webhook_service::{InspectedDelivery, WebhookService};
+
+/// Event types that can be sent as test events
+pub const TEST_EVENT_TYPES: [&str; 21] = [
+    "payment.created",
+    "payment.confirming",
+    "payment.confirmed",
+    "payment.failed",
+    "payment.expired",
+    "payment.cancelled",
+    "payment.underpaid",
+    "payment.overpaid",
+    "payment.paid_late",
+    "payment.refunded",
+    "payment.partially_refunded",
+    "payment.forwarded",
+    "refund.completed",
+    "withdrawal.created",
+    "withdrawal.completed",
+    "withdrawal.rejected",
+    "withdrawal.cancelled",
+    "invoice.created",
+    "invoice.paid",
+    "balance.updated",
+    "security.alert",
+];
+
+const PAYMENT_STATUSES: [PaymentStatus; 11] = [
+    PaymentStatus::Pending,
+    PaymentStatus::Confirming,
+    PaymentStatus::Confirmed,
+    PaymentStatus::Failed,
+    PaymentStatus::Expired,
+    PaymentStatus::Refunded,
+    PaymentStatus::Cancelled,
+    PaymentStatus::Underpaid,
+    PaymentStatus::Overpaid,
+    PaymentStatus::PartiallyRefunded,
+    PaymentStatus::PaidLate,
+];
+
+#[derive(Debug, Deserialize)]
+pub struct SendTestWebhookRequest {
+    pub event_type: String,
+    /// Target endpoint (`we_...`); defaults to the primary endpoint
+    #[serde(default)]
+    pub endpoint_id: Option<String>,
+}
+
+/// Result of a test event, with the full request and response
+#[derive(Debug, Serialize)]
+pub struct TestWebhookResult {
+    pub endpoint_id: String,
+    pub event_id: String,
+    pub event_type: String,
+    #[serde(flatten)]
+    pub delivery: InspectedDelivery,
+}
+
+/// Body of a sample event, shaped exactly like the live event
+///
+/// Payment events (and `refund.completed`) use the payment payload; other
+/// events use the generic event envelope. Every sample carries `"test": true`.
+fn sample_body(event_type: &str, merchant_id: i64, event_id: &str) -> Result<serde_json::Value, ServiceError> {
+    let payment_status = match event_type {
+        "refund.completed" => Some(PaymentStatus::Refunded),
+        _ => PAYMENT_STATUSES.iter().copied().find(|s| s.event_type() == event_type),
+    };
+
+    if let Some(status) = payment_status {
+        let data = if event_type == "refund.completed" {
+            json!({ "refund_id": "ref_test_0000", "refund_amount": "100.00", "test": true })
+        } else {
+            json!({ "test": true })
+        };
+        let payload = WebhookPayload {
+            event_id: Some(event_id.to_string()),
+            event_type: event_type.to_string(),
+            payment_id: "pay_test_0000".to_string(),
+            merchant_id,
+            status,
+            amount: Decimal::new(10000, 2),
+            crypto_type: "USDT_BEP20".to_string(),
+            transaction_hash: (!matches!(status, PaymentStatus::Pending | PaymentStatus::Expired | PaymentStatus::Cancelled))
+                .then(|| format!("0x{}", "0".repeat(64))),
+            timestamp: Utc::now().timestamp(),
+            data: Some(data),
+        };
+        return Ok(serde_json::to_value(payload)?);
+    }
+
+    let (object_id, object) = match event_service::object_type_of(event_type) {
+        "payment" => ("pay_test_0000", json!({ "payment_id": "pay_test_0000", "status": "COMPLETED", "forwarding_tx_hash": format!("0x{}", "0".repeat(64)) })),
+        "withdrawal" => ("wd_test_0000", json!({ "withdrawal_id": "wd_test_0000", "crypto_type": "USDT_BEP20", "amount": "100.00", "status": event_type.rsplit('.').next().unwrap_or_default().to_uppercase() })),
+        "invoice" => ("inv_test_0000", json!({ "invoice_id": "inv_test_0000", "total": "100.00", "status": if event_type == "invoice.paid" { "PAID" } else { "PENDING" } })),
+        "balance" => ("USDT_BEP20", json!({ "crypto_type": "USDT_BEP20", "available_balance": "100.00", "reserved_balance": "0" })),
+        "security" => ("test_alert", json!({ "alert_type": "test_alert", "message": "This is a test security alert" })),
+        _ => {
+            return Err(ServiceError::ValidationError(format!(
+                "Unknown event type '{}'",
+                event_type
+            )))
+        }
+    };
+
+    let event = Event {
+        event_id: event_id.to_string(),
+        event_type: event_type.to_string(),
+        api_version: event_service::API_VERSION.to_string(),
+        object_type: event_service::object_type_of(event_type).to_string(),
+        object_id: object_id.to_string(),
+        data: json!({ "object": object, "test": true }),
+        created_at: Utc::now(),
+    };
+
+    Ok(outbox::event_envelope(&event, merchant_id))
+}
+
+pub struct WebhookTestService {
+    db_pool: PgPool,
+    webhook_service: Arc<WebhookService>,
+}
+
+impl WebhookTestService {
+    pub fn new(db_pool: PgPool, webhook_service: Arc<WebhookService>) -> Self {
+        Self {
+            db_pool,
+            webhook_service,
+        }
+    }
+
+    /// Send a signed sample event to one of the merchant's endpoints
+    ///
+    /// The event is not recorded and no delivery is queued; the request is
+    /// sent immediately and the full exchange is returned.
+    ///
+    /// # Arguments
+    /// * `merchant_id` - ID of the merchant
+    /// * `request` - Event type and optional target endpoint
+    pub async fn send_test_event(
+        &self,
+        merchant_id: i64,
+        request: SendTestWebhookRequest,
+    ) -> Result<TestWebhookResult, ServiceError> {
+        if !TEST_EVENT_TYPES.contains(&request.event_type.as_str()) {
+            return Err(ServiceError::ValidationError(format!(
+                "Unknown event type '{}'. Supported: {}",
+                request.event_type,
+                TEST_EVENT_TYPES.join(", ")
+            )));
+        }
+
+        let endpoint = sqlx::query!(
+            r#"
+            SELECT id, endpoint_id
+            FROM webhook_endpoints
+            WHERE merchant_id = $1
+              AND (($2::varchar IS NULL AND is_primary) OR endpoint_id = $2)
+            "#,
+            merchant_id,
+            request.endpoint_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await?
+        .ok_or_else(|| match &request.endpoint_id {
+            Some(_) => ServiceError::NotFound("Webhook endpoint not found".to_string()),
+            None => ServiceError::NotFound("No primary webhook endpoint configured".to_string()),
+        })?;
+
+        let event_id = format!("evt_test_{}", nanoid!(20));
+        let body = sample_body(&request.event_type, merchant_id, &event_id)?;
+
+        let delivery = self
+            .webhook_service
+            .send_inspected(endpoint.id, &event_id, body.to_string())
+            .await?;
+
+        Ok(TestWebhookResult {
+            endpoint_id: endpoint.endpoint_id,
+            event_id,
+            event_type: request.event_type,
+            delivery,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_test_event_type_has_a_sample() {
+        for event_type in TEST_EVENT_TYPES {
+            let body = sample_body(event_type, 1, "evt_test_1").unwrap();
+            assert_eq!(body["event_type"], event_type);
+            assert_eq!(body["event_id"], "evt_test_1");
+        }
+    }
+
+    #[test]
+    fn test_payment_sample_matches_live_payload() {
+        let body = sample_body("payment.confirmed", 7, "evt_test_1").unwrap();
+        let payload: WebhookPayload = serde_json::from_value(body).unwrap();
+
+        assert_eq!(payload.status, PaymentStatus::Confirmed);
+        assert_eq!(payload.merchant_id, 7);
+        assert!(payload.transaction_hash.is_some());
+        assert_eq!(payload.data.unwrap()["test"], true);
+    }
+
+    #[test]
+    fn test_envelope_sample_for_other_events() {
+        let body = sample_body("withdrawal.completed", 7, "evt_test_1").unwrap();
+
+        assert_eq!(body["object_type"], "withdrawal");
+        assert_eq!(body["data"]["object"]["status"], "COMPLETED");
+        assert_eq!(body["data"]["test"], true);
+    }
+}
@@ -2,6 +2,9 @@ use crate::error::ServiceError;
 use rust_decimal::Decimal;
 use sqlx::PgPool;
 
+use crate::services::email_templates::WithdrawalCompleted;
+use crate::services::{notification_service, outbox};
+
 pub struct WithdrawalProcessor {
     db_pool: PgPool,
 }
@@ -12,26 +15,70 @@ impl WithdrawalProcessor {
     }
 
     pub async fn process_withdrawal(&self, withdrawal_id: &str) -> Result<(), ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
         // Simplified processing - just mark as completed
-        sqlx::query!(
-            "UPDATE withdrawals SET status = 'COMPLETED', completed_at = NOW() WHERE withdrawal_id = $1",
+        let updated = sqlx::query!(
+            r#"
+            UPDATE withdrawals w SET status = 'COMPLETED', completed_at = NOW()
+            WHERE withdrawal_id = $1 AND status <> 'HELD'
+              AND NOT EXISTS (SELECT 1 FROM merchants m WHERE m.id = w.merchant_id AND m.withdrawals_frozen)
+            RETURNING merchant_id, amount, crypto_type, to_jsonb(w) AS "snapshot!"
+            "#,
             withdrawal_id
         )
-        .execute(&self.db_pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        if let Some(updated) = updated {
+            outbox::enqueue_event(
+                &mut tx,
+                updated.merchant_id,
+                "withdrawal.completed",
+                withdrawal_id,
+                serde_json::json!({ "object": updated.snapshot }),
+            ).await?;
+
+            let notice = WithdrawalCompleted {
+                withdrawal_id: withdrawal_id.to_string(),
+                amount: updated.amount.normalize().to_string(),
+                crypto_type: updated.crypto_type,
+            };
+            notification_service::enqueue_notification(&mut tx, updated.merchant_id, &notice).await?;
+        }
+
+        tx.commit().await?;
+
         Ok(())
     }
 
     pub async fn reject_withdrawal(&self, withdrawal_id: &str, reason: &str) -> Result<(), ServiceError> {
-        sqlx::query!(
-            "UPDATE withdrawals SET status = 'REJECTED', rejection_reason = $1, updated_at = NOW() WHERE withdrawal_id = $2",
+        let mut tx = self.db_pool.begin().await?;
+
+        let updated = sqlx::query!(
+            r#"
+            UPDATE withdrawals w SET status = 'REJECTED', rejection_reason = $1, updated_at = NOW()
+            WHERE withdrawal_id = $2
+            RETURNING merchant_id, to_jsonb(w) AS "snapshot!"
+            "#,
             reason,
             withdrawal_id
         )
-        .execute(&self.db_pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+        if let Some(updated) = updated {
+            outbox::enqueue_event(
+                &mut tx,
+                updated.merchant_id,
+                "withdrawal.rejected",
+                withdrawal_id,
+                serde_json::json!({ "object": updated.snapshot }),
+            ).await?;
+        }
+
+        tx.commit().await?;
+
         Ok(())
     }
 }
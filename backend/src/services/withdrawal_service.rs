@@ -4,6 +4,10 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
+use crate::services::outbox;
+use crate::services::screening_service::{ScreeningService, SubjectType};
+use crate::services::volume_tracking_service::VolumeTrackingService;
+
 #[derive(Debug, Deserialize)]
 pub struct WithdrawalRequest {
     pub crypto_type: String,
@@ -35,11 +39,27 @@ pub struct Withdrawal {
 
 pub struct WithdrawalService {
     db_pool: PgPool,
+    screening: ScreeningService,
+    volume_tracking: VolumeTrackingService,
 }
 
 impl WithdrawalService {
     pub fn new(db_pool: PgPool) -> Self {
-        Self { db_pool }
+        Self {
+            screening: ScreeningService::new(db_pool.clone()),
+            volume_tracking: VolumeTrackingService::new(db_pool.clone()),
+            db_pool,
+        }
+    }
+
+    pub fn with_screening(mut self, screening: ScreeningService) -> Self {
+        self.screening = screening;
+        self
+    }
+
+    pub fn with_volume_tracking(mut self, volume_tracking: VolumeTrackingService) -> Self {
+        self.volume_tracking = volume_tracking;
+        self
     }
 
     pub async fn create_withdrawal(
@@ -48,7 +68,32 @@ impl WithdrawalService {
         request: WithdrawalRequest,
     ) -> Result<Withdrawal, ServiceError> {
         let withdrawal_id = format!("wd_{}", uuid::Uuid::new_v4().to_string().replace("-", ""));
+
+        // Destinations on a sanctions or deny list wait for compliance review
+        let list_match = self.screening.find_match(&request.destination_address).await?;
+        let status = if list_match.is_some() { "HELD" } else { "PENDING" };
         
+        let mut tx = self.db_pool.begin().await?;
+
+        // The merchant row stays locked until commit, so concurrent withdrawals
+        // cannot both pass the daily limit check
+        let frozen = sqlx::query_scalar!(
+            "SELECT withdrawals_frozen FROM merchants WHERE id = $1 FOR UPDATE",
+            merchant_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ServiceError::MerchantNotFound)?;
+        if frozen {
+            return Err(ServiceError::Forbidden(
+                "Withdrawals are frozen pending compliance review".to_string(),
+            ));
+        }
+
+        self.volume_tracking
+            .check_withdrawal(merchant_id, &request.crypto_type, request.amount)
+            .await?;
+
         let withdrawal = sqlx::query_as!(
             Withdrawal,
             r#"
@@ -56,7 +101,7 @@ impl WithdrawalService {
                 withdrawal_id, merchant_id, crypto_type, amount, destination_address,
                 status, fee, net_amount, created_at, updated_at
             )
-            VALUES ($1, $2, $3, $4, $5, 'PENDING', $6, $7, NOW(), NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, NOW(), NOW())
             RETURNING id, withdrawal_id, merchant_id, crypto_type, 
                      amount, destination_address, status, fee, net_amount, transaction_hash,
                      rejection_reason, requires_approval, approved_by, approved_at, 
@@ -67,12 +112,35 @@ impl WithdrawalService {
             request.crypto_type,
             request.amount,
             request.destination_address,
+            status,
             Decimal::ZERO, // fee
             request.amount, // net_amount
         )
-        .fetch_one(&self.db_pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        if let Some(list_match) = &list_match {
+            ScreeningService::create_hold(
+                &mut tx,
+                merchant_id,
+                SubjectType::Withdrawal,
+                &withdrawal.withdrawal_id,
+                &withdrawal.destination_address,
+                None,
+                list_match,
+            ).await?;
+        }
+
+        outbox::enqueue_event(
+            &mut tx,
+            merchant_id,
+            "withdrawal.created",
+            &withdrawal.withdrawal_id,
+            serde_json::json!({ "object": &withdrawal }),
+        ).await?;
+
+        tx.commit().await?;
+
         Ok(withdrawal)
     }
 
@@ -128,6 +196,8 @@ impl WithdrawalService {
         merchant_id: i64,
         withdrawal_id: &str,
     ) -> Result<Withdrawal, ServiceError> {
+        let mut tx = self.db_pool.begin().await?;
+
         let withdrawal = sqlx::query_as!(
             Withdrawal,
             r#"
@@ -142,9 +212,20 @@ impl WithdrawalService {
             withdrawal_id,
             merchant_id
         )
-        .fetch_optional(&self.db_pool)
-        .await?;
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ServiceError::PaymentNotFound)?;
 
-        withdrawal.ok_or(ServiceError::PaymentNotFound)
+        outbox::enqueue_event(
+            &mut tx,
+            merchant_id,
+            "withdrawal.cancelled",
+            &withdrawal.withdrawal_id,
+            serde_json::json!({ "object": &withdrawal }),
+        ).await?;
+
+        tx.commit().await?;
+
+        Ok(withdrawal)
     }
 }
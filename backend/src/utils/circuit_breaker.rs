@@ -87,4 +87,17 @@ impl CircuitBreaker {
     pub async fn is_open(&self) -> bool {
         *self.state.read().await == CircuitState::Open
     }
+
+    /// Time left before an open circuit lets a trial call through
+    ///
+    /// Returns `None` when the circuit is closed, half-open or its timeout has
+    /// already elapsed.
+    pub async fn open_remaining(&self) -> Option<Duration> {
+        if !self.is_open().await {
+            return None;
+        }
+
+        let last_failure = (*self.last_failure_time.read().await)?;
+        self.timeout.checked_sub(last_failure.elapsed()).filter(|d| !d.is_zero())
+    }
 }
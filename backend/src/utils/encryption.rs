@@ -1,91 +1,268 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
+    aead::{Aead, KeyInit},
     Aes256Gcm, Key, Nonce,
 };
 use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
 use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use crate::utils::key_provider::{kek_file_modified, provider_from_env, KeyProvider};
+
+/// Prefix of envelope-encrypted values: `v2:<kek id>:<wrapped data key>:<nonce + ciphertext>`
+const ENVELOPE_PREFIX: &str = "v2";
+
+/// Provider shared by every `Encryption::new`, built on first successful use
+///
+/// With the file provider it is rebuilt when `ENCRYPTION_KEK_FILE` changes,
+/// so a rotated key file is picked up without a restart. Environment KEKs
+/// are read once; changing them needs a restart.
+static SHARED_PROVIDER: Mutex<Option<SharedProvider>> = Mutex::new(None);
+
+struct SharedProvider {
+    provider: Arc<dyn KeyProvider>,
+    kek_file_modified: Option<SystemTime>,
+}
 
 pub fn encrypt_data(data: &str) -> Result<String, String> {
     let encryption = Encryption::new()?;
     encryption.encrypt(data)
 }
 
+pub fn decrypt_data(encrypted: &str) -> Result<String, String> {
+    let encryption = Encryption::new()?;
+    encryption.decrypt(encrypted)
+}
+
+/// Envelope encryption for stored secrets
+///
+/// Every value gets its own random data key, which is wrapped by the
+/// provider's current key-encryption key (KEK). The KEK id is stored in the
+/// value, so KEKs can be rotated: old values still decrypt, and
+/// [`Encryption::rewrap`] moves them to the current KEK without touching the
+/// encrypted data. Values written before envelope encryption (AES-GCM
+/// directly under `ENCRYPTION_KEY`) are still read.
 pub struct Encryption {
-    cipher: Aes256Gcm,
+    provider: Arc<dyn KeyProvider>,
+    legacy: Option<Aes256Gcm>,
 }
 
 impl Encryption {
+    /// Encryption using the KEK provider configured in the environment
     pub fn new() -> Result<Self, String> {
-        let key_hex = env::var("ENCRYPTION_KEY")
-            .map_err(|_| "ENCRYPTION_KEY not set in environment".to_string())?;
-        
-        let key_bytes = hex::decode(&key_hex)
-            .map_err(|e| format!("Invalid ENCRYPTION_KEY hex: {}", e))?;
-        
-        if key_bytes.len() != 32 {
-            return Err("ENCRYPTION_KEY must be 32 bytes (64 hex chars)".to_string());
-        }
+        let provider = {
+            let mut shared = SHARED_PROVIDER.lock().unwrap_or_else(|e| e.into_inner());
+            let modified = kek_file_modified();
+            match shared.as_ref() {
+                Some(cached) if cached.kek_file_modified == modified => cached.provider.clone(),
+                cached => match provider_from_env() {
+                    Ok(provider) => {
+                        let provider: Arc<dyn KeyProvider> = Arc::from(provider);
+                        *shared = Some(SharedProvider { provider: provider.clone(), kek_file_modified: modified });
+                        provider
+                    }
+                    // Keep the loaded keys if the new file cannot be read, e.g. while it is being written
+                    Err(e) => match cached {
+                        Some(cached) => {
+                            tracing::warn!("Keeping previous KEKs, reloading the key file failed: {}", e);
+                            cached.provider.clone()
+                        }
+                        None => return Err(e),
+                    },
+                },
+            }
+        };
+
+        let legacy_key = env::var("ENCRYPTION_KEY").ok().and_then(|key| hex::decode(key).ok());
+        Ok(Self::with_provider(provider, legacy_key.as_deref()))
+    }
 
-        let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
+    /// Encryption with an explicit provider
+    ///
+    /// # Arguments
+    /// * `provider` - Source of KEKs
+    /// * `legacy_key` - 32-byte key that encrypted values before envelope encryption
+    pub fn with_provider(provider: Arc<dyn KeyProvider>, legacy_key: Option<&[u8]>) -> Self {
+        let legacy = legacy_key
+            .filter(|key| key.len() == 32)
+            .map(|key| Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)));
+        Self { provider, legacy }
+    }
+
+    /// KEK id stored in an encrypted value; `None` for values from before envelope encryption
+    pub fn key_id(encrypted: &str) -> Option<&str> {
+        let mut parts = encrypted.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(ENVELOPE_PREFIX), Some(key_id), Some(_)) => Some(key_id),
+            _ => None,
+        }
+    }
 
-        Ok(Self { cipher })
+    /// Id of the KEK new values are encrypted under
+    pub fn current_key_id(&self) -> Result<String, String> {
+        self.provider.current_key_id()
     }
 
     pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
-        // Generate random nonce
-        let nonce_bytes: [u8; 12] = rand::random();
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        // Encrypt
-        let ciphertext = self.cipher
-            .encrypt(nonce, plaintext.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
-
-        // Combine nonce + ciphertext and encode
-        let mut result = nonce_bytes.to_vec();
-        result.extend_from_slice(&ciphertext);
-        
-        Ok(BASE64.encode(&result))
+        let data_key: [u8; 32] = rand::random();
+        let body = seal(&cipher(&data_key)?, plaintext.as_bytes())?;
+        let (key_id, wrapped) = self.provider.wrap(&data_key)?;
+
+        Ok(format!("{}:{}:{}:{}", ENVELOPE_PREFIX, key_id, BASE64.encode(wrapped), BASE64.encode(body)))
     }
 
     pub fn decrypt(&self, encrypted: &str) -> Result<String, String> {
-        // Decode
-        let data = BASE64.decode(encrypted)
-            .map_err(|e| format!("Base64 decode failed: {}", e))?;
+        let plaintext = match parse_envelope(encrypted)? {
+            Some(Envelope { key_id, wrapped, body }) => {
+                let data_key = self.provider.unwrap(key_id, &wrapped)?;
+                open(&cipher(&data_key)?, &body)?
+            }
+            None => {
+                let legacy = self.legacy.as_ref()
+                    .ok_or_else(|| "ENCRYPTION_KEY is required to decrypt legacy values".to_string())?;
+                let data = BASE64.decode(encrypted)
+                    .map_err(|e| format!("Base64 decode failed: {}", e))?;
+                open(legacy, &data)?
+            }
+        };
+
+        String::from_utf8(plaintext)
+            .map_err(|e| format!("Invalid UTF-8: {}", e))
+    }
+
+    /// Move a value to the current KEK
+    ///
+    /// Envelope values only have their data key re-wrapped; legacy values
+    /// are re-encrypted.
+    ///
+    /// # Returns
+    /// The new value, or `None` if it already uses the current KEK
+    pub fn rewrap(&self, encrypted: &str) -> Result<Option<String>, String> {
+        let current = self.provider.current_key_id()?;
 
-        if data.len() < 12 {
-            return Err("Invalid encrypted data".to_string());
+        match parse_envelope(encrypted)? {
+            Some(envelope) if envelope.key_id == current => Ok(None),
+            Some(Envelope { key_id, wrapped, body }) => {
+                let data_key = self.provider.unwrap(key_id, &wrapped)?;
+                let (new_key_id, rewrapped) = self.provider.wrap(&data_key)?;
+                Ok(Some(format!(
+                    "{}:{}:{}:{}",
+                    ENVELOPE_PREFIX, new_key_id, BASE64.encode(rewrapped), BASE64.encode(body)
+                )))
+            }
+            None => self.decrypt(encrypted).and_then(|plaintext| self.encrypt(&plaintext)).map(Some),
         }
+    }
+}
 
-        // Split nonce and ciphertext
-        let (nonce_bytes, ciphertext) = data.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
+/// Parts of an envelope value
+struct Envelope<'a> {
+    key_id: &'a str,
+    wrapped: Vec<u8>,
+    body: Vec<u8>,
+}
 
-        // Decrypt
-        let plaintext = self.cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
+/// Split an envelope value into its parts; `None` if legacy
+fn parse_envelope(encrypted: &str) -> Result<Option<Envelope<'_>>, String> {
+    let parts: Vec<&str> = encrypted.split(':').collect();
+    match parts.as_slice() {
+        [ENVELOPE_PREFIX, key_id, wrapped, body] => {
+            let wrapped = BASE64.decode(wrapped).map_err(|e| format!("Base64 decode failed: {}", e))?;
+            let body = BASE64.decode(body).map_err(|e| format!("Base64 decode failed: {}", e))?;
+            Ok(Some(Envelope { key_id, wrapped, body }))
+        }
+        [_] => Ok(None),
+        _ => Err("Invalid encrypted data".to_string()),
+    }
+}
 
-        String::from_utf8(plaintext)
-            .map_err(|e| format!("Invalid UTF-8: {}", e))
+fn cipher(key: &[u8]) -> Result<Aes256Gcm, String> {
+    if key.len() != 32 {
+        return Err("Data key must be 32 bytes".to_string());
     }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)))
+}
+
+/// Encrypt under a random nonce, returning nonce + ciphertext
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce_bytes: [u8; 12] = rand::random();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut result = nonce_bytes.to_vec();
+    result.extend_from_slice(&ciphertext);
+    Ok(result)
+}
+
+fn open(cipher: &Aes256Gcm, data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 12 {
+        return Err("Invalid encrypted data".to_string());
+    }
+    let (nonce_bytes, ciphertext) = data.split_at(12);
+    cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::key_provider::Keyring;
+
+    const KEY_1: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const KEY_2: &str = "fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210";
+
+    fn keyring(current: &str) -> Arc<dyn KeyProvider> {
+        Arc::new(Keyring::new(current, &[
+            ("k1".to_string(), KEY_1.to_string()),
+            ("k2".to_string(), KEY_2.to_string()),
+        ]).unwrap())
+    }
 
     #[test]
     fn test_encryption_roundtrip() {
         std::env::set_var("ENCRYPTION_KEY", "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef");
-        
+
         let enc = Encryption::new().unwrap();
         let plaintext = "secret data";
-        
+
         let encrypted = enc.encrypt(plaintext).unwrap();
         let decrypted = enc.decrypt(&encrypted).unwrap();
-        
+
         assert_eq!(plaintext, decrypted);
     }
+
+    #[test]
+    fn test_values_record_their_kek_and_survive_rotation() {
+        let before = Encryption::with_provider(keyring("k1"), None);
+        let encrypted = before.encrypt("secret data").unwrap();
+        assert_eq!(Encryption::key_id(&encrypted), Some("k1"));
+
+        let after = Encryption::with_provider(keyring("k2"), None);
+        assert_eq!(after.decrypt(&encrypted).unwrap(), "secret data");
+
+        let rewrapped = after.rewrap(&encrypted).unwrap().unwrap();
+        assert_eq!(Encryption::key_id(&rewrapped), Some("k2"));
+        assert_eq!(after.decrypt(&rewrapped).unwrap(), "secret data");
+        // Only the data key changed
+        assert_eq!(rewrapped.rsplit(':').next(), encrypted.rsplit(':').next());
+        assert_eq!(after.rewrap(&rewrapped).unwrap(), None);
+    }
+
+    #[test]
+    fn test_legacy_values_are_read_and_upgraded() {
+        let legacy_key = hex::decode(KEY_1).unwrap();
+        let legacy = BASE64.encode(seal(&cipher(&legacy_key).unwrap(), b"old secret").unwrap());
+        assert_eq!(Encryption::key_id(&legacy), None);
+
+        let enc = Encryption::with_provider(keyring("k2"), Some(&legacy_key));
+        assert_eq!(enc.decrypt(&legacy).unwrap(), "old secret");
+
+        let upgraded = enc.rewrap(&legacy).unwrap().unwrap();
+        assert_eq!(Encryption::key_id(&upgraded), Some("k2"));
+        assert_eq!(enc.decrypt(&upgraded).unwrap(), "old secret");
+
+        let without_legacy = Encryption::with_provider(keyring("k2"), None);
+        assert!(without_legacy.decrypt(&legacy).is_err());
+    }
 }
@@ -0,0 +1,313 @@
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, SystemTime};
+
+/// Source of key-encryption keys (KEKs) that wrap per-record data keys
+///
+/// Implementations decide where KEKs live: in process memory ([`Keyring`],
+/// loaded from the environment or a file) or in a separate process that
+/// never hands them out ([`KmsSocketProvider`]).
+pub trait KeyProvider: Send + Sync {
+    /// Id of the KEK new data keys are wrapped with
+    fn current_key_id(&self) -> Result<String, String>;
+
+    /// Wrap a data key with the current KEK
+    ///
+    /// # Returns
+    /// Id of the KEK used and the wrapped key
+    fn wrap(&self, data_key: &[u8]) -> Result<(String, Vec<u8>), String>;
+
+    /// Unwrap a data key wrapped by the KEK `key_id`
+    fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, String>;
+}
+
+/// Provider selected by `ENCRYPTION_KEK_PROVIDER`: `env` (default), `file` or `kms`
+pub fn provider_from_env() -> Result<Box<dyn KeyProvider>, String> {
+    match non_empty_var("ENCRYPTION_KEK_PROVIDER").unwrap_or_else(|| "env".to_string()).as_str() {
+        "env" => Ok(Box::new(Keyring::from_env()?)),
+        "file" => {
+            let path = env::var("ENCRYPTION_KEK_FILE")
+                .map_err(|_| "ENCRYPTION_KEK_FILE not set in environment".to_string())?;
+            Ok(Box::new(Keyring::from_file(&path)?))
+        }
+        #[cfg(unix)]
+        "kms" => {
+            let path = env::var("ENCRYPTION_KMS_SOCKET")
+                .map_err(|_| "ENCRYPTION_KMS_SOCKET not set in environment".to_string())?;
+            Ok(Box::new(KmsSocketProvider::new(path)))
+        }
+        other => Err(format!("Unknown ENCRYPTION_KEK_PROVIDER: {}", other)),
+    }
+}
+
+/// KEKs held in process memory
+pub struct Keyring {
+    current: String,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+impl Keyring {
+    /// Keyring from hex-encoded 32-byte keys
+    ///
+    /// # Arguments
+    /// * `current` - Id of the key new data keys are wrapped with
+    /// * `keys` - Key id and hex key pairs, including retired keys still needed to unwrap
+    pub fn new(current: &str, keys: &[(String, String)]) -> Result<Self, String> {
+        let mut ring = HashMap::new();
+        for (id, key_hex) in keys {
+            validate_key_id(id)?;
+            let key_bytes = hex::decode(key_hex.trim())
+                .map_err(|e| format!("Invalid hex for KEK {}: {}", id, e))?;
+            if key_bytes.len() != 32 {
+                return Err(format!("KEK {} must be 32 bytes (64 hex chars)", id));
+            }
+            ring.insert(id.clone(), Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key_bytes)));
+        }
+
+        if !ring.contains_key(current) {
+            return Err(format!("Current KEK {} is not in the keyring", current));
+        }
+
+        Ok(Self { current: current.to_string(), keys: ring })
+    }
+
+    /// Keyring from `ENCRYPTION_KEKS` (`id:hex,id:hex`) and `ENCRYPTION_CURRENT_KEK`
+    ///
+    /// Without `ENCRYPTION_KEKS`, `ENCRYPTION_KEY` is the only KEK, with id `k1`.
+    /// The current KEK defaults to the first one listed.
+    pub fn from_env() -> Result<Self, String> {
+        let keys: Vec<(String, String)> = match non_empty_var("ENCRYPTION_KEKS") {
+            Some(list) => list
+                .split(',')
+                .filter(|entry| !entry.trim().is_empty())
+                .map(|entry| {
+                    entry
+                        .trim()
+                        .split_once(':')
+                        .map(|(id, key)| (id.to_string(), key.to_string()))
+                        .ok_or_else(|| "ENCRYPTION_KEKS entries must be id:hex".to_string())
+                })
+                .collect::<Result<_, _>>()?,
+            None => {
+                let key = env::var("ENCRYPTION_KEY")
+                    .map_err(|_| "ENCRYPTION_KEY not set in environment".to_string())?;
+                vec![("k1".to_string(), key)]
+            }
+        };
+
+        let current = match non_empty_var("ENCRYPTION_CURRENT_KEK") {
+            Some(current) => current,
+            None => keys.first().map(|(id, _)| id.clone()).ok_or("ENCRYPTION_KEKS is empty")?,
+        };
+
+        Self::new(&current, &keys)
+    }
+
+    /// Keyring from a JSON file: `{"current": "k2", "keys": {"k1": "<hex>", "k2": "<hex>"}}`
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct KeyFile {
+            current: String,
+            keys: HashMap<String, String>,
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read KEK file {}: {}", path, e))?;
+        let file: KeyFile = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid KEK file {}: {}", path, e))?;
+
+        Self::new(&file.current, &file.keys.into_iter().collect::<Vec<_>>())
+    }
+
+    fn key(&self, key_id: &str) -> Result<&Aes256Gcm, String> {
+        self.keys.get(key_id).ok_or_else(|| format!("Unknown KEK: {}", key_id))
+    }
+}
+
+impl KeyProvider for Keyring {
+    fn current_key_id(&self) -> Result<String, String> {
+        Ok(self.current.clone())
+    }
+
+    fn wrap(&self, data_key: &[u8]) -> Result<(String, Vec<u8>), String> {
+        let nonce_bytes: [u8; 12] = rand::random();
+        // The KEK id is authenticated, so a wrapped key cannot be relabelled
+        let wrapped = self.key(&self.current)?
+            .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: data_key, aad: self.current.as_bytes() })
+            .map_err(|e| format!("Key wrap failed: {}", e))?;
+
+        let mut result = nonce_bytes.to_vec();
+        result.extend_from_slice(&wrapped);
+        Ok((self.current.clone(), result))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, String> {
+        if wrapped.len() < 12 {
+            return Err("Invalid wrapped key".to_string());
+        }
+        let (nonce_bytes, ciphertext) = wrapped.split_at(12);
+        self.key(key_id)?
+            .decrypt(Nonce::from_slice(nonce_bytes), Payload { msg: ciphertext, aad: key_id.as_bytes() })
+            .map_err(|e| format!("Key unwrap failed: {}", e))
+    }
+}
+
+/// KEKs kept by a local KMS-style process, reached over a Unix socket
+///
+/// Each request is one JSON line and gets one JSON line back:
+/// * `{"op": "current"}` -> `{"key_id": "k2"}`
+/// * `{"op": "wrap", "data": "<base64>"}` -> `{"key_id": "k2", "data": "<base64>"}`
+/// * `{"op": "unwrap", "key_id": "k1", "data": "<base64>"}` -> `{"data": "<base64>"}`
+///
+/// Failures are reported as `{"error": "..."}`.
+#[cfg(unix)]
+pub struct KmsSocketProvider {
+    socket_path: String,
+    timeout: Duration,
+}
+
+#[cfg(unix)]
+impl KmsSocketProvider {
+    pub fn new(socket_path: String) -> Self {
+        Self { socket_path, timeout: Duration::from_secs(5) }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send one request to the key service
+    ///
+    /// Callers are synchronous, so on a multi-threaded runtime the round trip
+    /// runs under `block_in_place`: the worker's other tasks move to another
+    /// thread instead of stalling for up to the timeout.
+    fn call(&self, request: serde_json::Value) -> Result<serde_json::Value, String> {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        match Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.call_blocking(request))
+            }
+            _ => self.call_blocking(request),
+        }
+    }
+
+    fn call_blocking(&self, request: serde_json::Value) -> Result<serde_json::Value, String> {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixStream;
+
+        let mut stream = UnixStream::connect(&self.socket_path)
+            .map_err(|e| format!("KMS connection to {} failed: {}", self.socket_path, e))?;
+        stream.set_read_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+        stream.set_write_timeout(Some(self.timeout)).map_err(|e| e.to_string())?;
+
+        let mut line = request.to_string();
+        line.push('\n');
+        stream.write_all(line.as_bytes()).map_err(|e| format!("KMS request failed: {}", e))?;
+
+        let mut response = String::new();
+        BufReader::new(stream)
+            .read_line(&mut response)
+            .map_err(|e| format!("KMS response failed: {}", e))?;
+        let response: serde_json::Value = serde_json::from_str(&response)
+            .map_err(|e| format!("Invalid KMS response: {}", e))?;
+
+        match response.get("error").and_then(|e| e.as_str()) {
+            Some(error) => Err(format!("KMS error: {}", error)),
+            None => Ok(response),
+        }
+    }
+
+    fn field<'a>(response: &'a serde_json::Value, name: &str) -> Result<&'a str, String> {
+        response
+            .get(name)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("KMS response missing {}", name))
+    }
+}
+
+#[cfg(unix)]
+impl KeyProvider for KmsSocketProvider {
+    fn current_key_id(&self) -> Result<String, String> {
+        let response = self.call(json!({"op": "current"}))?;
+        Ok(Self::field(&response, "key_id")?.to_string())
+    }
+
+    fn wrap(&self, data_key: &[u8]) -> Result<(String, Vec<u8>), String> {
+        let response = self.call(json!({"op": "wrap", "data": BASE64.encode(data_key)}))?;
+        let key_id = Self::field(&response, "key_id")?.to_string();
+        validate_key_id(&key_id)?;
+        let wrapped = BASE64.decode(Self::field(&response, "data")?)
+            .map_err(|e| format!("Invalid KMS data: {}", e))?;
+        Ok((key_id, wrapped))
+    }
+
+    fn unwrap(&self, key_id: &str, wrapped: &[u8]) -> Result<Vec<u8>, String> {
+        let response = self.call(json!({"op": "unwrap", "key_id": key_id, "data": BASE64.encode(wrapped)}))?;
+        BASE64.decode(Self::field(&response, "data")?)
+            .map_err(|e| format!("Invalid KMS data: {}", e))
+    }
+}
+
+/// Modification time of `ENCRYPTION_KEK_FILE` when the file provider is selected
+///
+/// Lets callers that cache a provider notice a rotated key file.
+pub fn kek_file_modified() -> Option<SystemTime> {
+    if non_empty_var("ENCRYPTION_KEK_PROVIDER").as_deref() != Some("file") {
+        return None;
+    }
+    let path = env::var("ENCRYPTION_KEK_FILE").ok()?;
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Key ids are embedded in ciphertexts between colons
+fn validate_key_id(id: &str) -> Result<(), String> {
+    if id.is_empty() || id.len() > 32 || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid KEK id {:?}: use up to 32 letters, digits, '-' or '_'", id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_1: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const KEY_2: &str = "fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210";
+
+    #[test]
+    fn test_keyring_wraps_with_current_and_unwraps_with_any() {
+        let old = Keyring::new("k1", &[("k1".to_string(), KEY_1.to_string())]).unwrap();
+        let (old_id, old_wrapped) = old.wrap(b"data key").unwrap();
+        assert_eq!(old_id, "k1");
+
+        let rotated = Keyring::new("k2", &[
+            ("k1".to_string(), KEY_1.to_string()),
+            ("k2".to_string(), KEY_2.to_string()),
+        ]).unwrap();
+        assert_eq!(rotated.wrap(b"data key").unwrap().0, "k2");
+        assert_eq!(rotated.unwrap("k1", &old_wrapped).unwrap(), b"data key");
+
+        // A wrapped key relabelled with another KEK id does not unwrap
+        assert!(rotated.unwrap("k2", &old_wrapped).is_err());
+    }
+
+    #[test]
+    fn test_keyring_rejects_bad_configuration() {
+        assert!(Keyring::new("k2", &[("k1".to_string(), KEY_1.to_string())]).is_err());
+        assert!(Keyring::new("k1", &[("k1".to_string(), "abcd".to_string())]).is_err());
+        assert!(Keyring::new("k:1", &[("k:1".to_string(), KEY_1.to_string())]).is_err());
+    }
+}
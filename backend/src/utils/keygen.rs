@@ -44,12 +44,15 @@ impl KeyGenerator {
         })
     }
 
-    /// Generate Solana wallet (placeholder)
+    /// Generate Solana wallet
+    ///
+    /// The private key is the base58 64-byte keypair (secret key followed by
+    /// public key), as written by solana-keygen.
     pub fn generate_solana_wallet() -> Result<WalletKeyPair, ServiceError> {
-        // Placeholder implementation without ed25519-dalek
-        let private_key = format!("solana_private_key_{}", uuid::Uuid::new_v4());
-        let public_key = format!("solana_public_key_{}", uuid::Uuid::new_v4());
-        
+        let signing_key = ed25519_dalek::SigningKey::generate(&mut OsRng);
+        let private_key = bs58::encode(signing_key.to_keypair_bytes()).into_string();
+        let public_key = bs58::encode(signing_key.verifying_key().as_bytes()).into_string();
+
         Ok(WalletKeyPair {
             private_key,
             public_key: public_key.clone(),
@@ -123,15 +126,19 @@ impl KeyGenerator {
     }
 
     fn validate_solana_private_key(private_key: &str) -> Result<String, ServiceError> {
-        // Placeholder validation for Solana keys
-        if private_key.len() < 32 {
+        // Base58 keypair as exported by Phantom/solana-keygen: secret key followed by public key
+        let keypair = bs58::decode(private_key.trim())
+            .into_vec()
+            .map_err(|_| ServiceError::ValidationError("Invalid base58 format".to_string()))?;
+
+        if keypair.len() != 64 {
             return Err(ServiceError::ValidationError(
-                "Solana private key too short".to_string()
+                "Solana private key must be a 64-byte base58 keypair".to_string()
             ));
         }
-        
-        // Return a placeholder address
-        Ok(format!("solana_address_for_{}", &private_key[..8]))
+
+        // In Solana, address = public key
+        Ok(bs58::encode(&keypair[32..]).into_string())
     }
 
     fn public_key_to_eth_address(public_key_hex: &str) -> Result<String, ServiceError> {
@@ -179,6 +186,7 @@ mod tests {
         assert!(!wallet.private_key.is_empty());
         assert!(!wallet.address.is_empty());
         assert_eq!(wallet.address, wallet.public_key); // In Solana, address = public key
+        assert_eq!(KeyGenerator::validate_private_key(&wallet.private_key, "solana").unwrap(), wallet.address);
     }
 
     #[test]
@@ -193,4 +201,17 @@ mod tests {
         let result = KeyGenerator::validate_evm_private_key(invalid_key);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_validate_solana_keypair() {
+        let mut keypair = [1u8; 64];
+        keypair[32..].copy_from_slice(&[2u8; 32]);
+        let key = bs58::encode(keypair).into_string();
+
+        let address = KeyGenerator::validate_private_key(&key, "solana").unwrap();
+        assert_eq!(address, bs58::encode([2u8; 32]).into_string());
+
+        let short = bs58::encode([1u8; 32]).into_string();
+        assert!(KeyGenerator::validate_private_key(&short, "solana").is_err());
+    }
 }
@@ -0,0 +1,198 @@
+// Encrypted Keystores
+// Ethereum Web3 Secret Storage (V3) and a Solana keypair equivalent
+
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tiny_keccak::{Hasher, Keccak};
+
+/// PBKDF2 rounds used by geth for pbkdf2 keystores
+pub const DEFAULT_ITERATIONS: u32 = 262_144;
+
+/// Shortest passphrase accepted for a new keystore
+pub const MIN_PASSPHRASE_LENGTH: usize = 12;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// Ethereum V3 keystore for a 32-byte secp256k1 private key
+///
+/// Uses PBKDF2-HMAC-SHA256 and AES-128-CTR; MetaMask, geth and ethers all import it.
+///
+/// # Arguments
+/// * `private_key` - Raw private key
+/// * `address` - `0x` address of the key
+/// * `passphrase` - Passphrase chosen by the merchant
+/// * `iterations` - PBKDF2 rounds, normally [`DEFAULT_ITERATIONS`]
+pub fn evm_keystore(private_key: &[u8], address: &str, passphrase: &str, iterations: u32) -> Result<Value, String> {
+    if private_key.len() != 32 {
+        return Err("EVM private key must be 32 bytes".to_string());
+    }
+
+    Ok(json!({
+        "version": 3,
+        "id": uuid::Uuid::new_v4().to_string(),
+        "address": address.trim_start_matches("0x").to_lowercase(),
+        "crypto": encrypt(private_key, passphrase, iterations)?,
+    }))
+}
+
+/// Solana keypair protected the same way as a V3 keystore
+///
+/// The plaintext is the 64-byte keypair (secret key followed by public
+/// key) that `solana-keygen` writes as a JSON array.
+pub fn solana_keystore(keypair: &[u8], address: &str, passphrase: &str, iterations: u32) -> Result<Value, String> {
+    if keypair.len() != 64 {
+        return Err("Solana keypair must be 64 bytes".to_string());
+    }
+    let public_key = bs58::decode(address).into_vec().map_err(|_| "Invalid Solana address".to_string())?;
+    if public_key != keypair[32..] {
+        return Err("Solana keypair does not match its address".to_string());
+    }
+
+    Ok(json!({
+        "version": 1,
+        "type": "solana-keypair",
+        "id": uuid::Uuid::new_v4().to_string(),
+        "address": address,
+        "crypto": encrypt(keypair, passphrase, iterations)?,
+    }))
+}
+
+/// Recover the key from a keystore written by [`evm_keystore`] or [`solana_keystore`]
+pub fn decrypt_keystore(keystore: &Value, passphrase: &str) -> Result<Vec<u8>, String> {
+    let crypto = &keystore["crypto"];
+    if crypto["cipher"] != "aes-128-ctr" || crypto["kdf"] != "pbkdf2" || crypto["kdfparams"]["prf"] != "hmac-sha256" {
+        return Err("Unsupported keystore parameters".to_string());
+    }
+
+    let field = |value: &Value| -> Result<Vec<u8>, String> {
+        value.as_str().and_then(|s| hex::decode(s).ok()).ok_or_else(|| "Malformed keystore".to_string())
+    };
+    let salt = field(&crypto["kdfparams"]["salt"])?;
+    let iv = field(&crypto["cipherparams"]["iv"])?;
+    let ciphertext = field(&crypto["ciphertext"])?;
+    let mac = field(&crypto["mac"])?;
+    let iterations = crypto["kdfparams"]["c"].as_u64()
+        .and_then(|c| u32::try_from(c).ok())
+        .ok_or_else(|| "Malformed keystore".to_string())?;
+    if iv.len() != 16 {
+        return Err("Malformed keystore".to_string());
+    }
+
+    let derived = pbkdf2_sha256(passphrase.as_bytes(), &salt, iterations);
+    if keystore_mac(&derived, &ciphertext) != mac.as_slice() {
+        return Err("Wrong passphrase".to_string());
+    }
+
+    let mut plaintext = ciphertext;
+    Aes128Ctr::new(derived[..16].into(), iv.as_slice().into()).apply_keystream(&mut plaintext);
+    Ok(plaintext)
+}
+
+fn encrypt(plaintext: &[u8], passphrase: &str, iterations: u32) -> Result<Value, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_LENGTH));
+    }
+
+    let salt: [u8; 32] = rand::random();
+    let iv: [u8; 16] = rand::random();
+    let derived = pbkdf2_sha256(passphrase.as_bytes(), &salt, iterations);
+
+    let mut ciphertext = plaintext.to_vec();
+    Aes128Ctr::new(derived[..16].into(), (&iv).into()).apply_keystream(&mut ciphertext);
+
+    Ok(json!({
+        "cipher": "aes-128-ctr",
+        "cipherparams": { "iv": hex::encode(iv) },
+        "ciphertext": hex::encode(&ciphertext),
+        "kdf": "pbkdf2",
+        "kdfparams": { "c": iterations, "dklen": 32, "prf": "hmac-sha256", "salt": hex::encode(salt) },
+        "mac": hex::encode(keystore_mac(&derived, &ciphertext)),
+    }))
+}
+
+/// PBKDF2-HMAC-SHA256 with a 32-byte output (a single block)
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let prf = Hmac::<Sha256>::new_from_slice(password).expect("HMAC accepts any key length");
+
+    let mut mac = prf.clone();
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+    let mut block: [u8; 32] = mac.finalize().into_bytes().into();
+    let mut output = block;
+
+    for _ in 1..iterations {
+        let mut mac = prf.clone();
+        mac.update(&block);
+        block = mac.finalize().into_bytes().into();
+        output.iter_mut().zip(block.iter()).for_each(|(o, b)| *o ^= b);
+    }
+
+    output
+}
+
+/// keccak256 of the second half of the derived key and the ciphertext
+fn keystore_mac(derived: &[u8; 32], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(&derived[16..]);
+    hasher.update(ciphertext);
+    let mut mac = [0u8; 32];
+    hasher.finalize(&mut mac);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypts_web3_secret_storage_test_vector() {
+        // PBKDF2 test vector from the Web3 Secret Storage definition
+        let keystore = json!({
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+                "ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 262144,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+                },
+                "mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+            },
+            "version": 3
+        });
+
+        let key = decrypt_keystore(&keystore, "testpassword").unwrap();
+        assert_eq!(hex::encode(key), "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d");
+        assert!(decrypt_keystore(&keystore, "wrongpassword").is_err());
+    }
+
+    #[test]
+    fn test_evm_keystore_round_trip() {
+        let key = [7u8; 32];
+        let keystore = evm_keystore(&key, "0xABCDEF0123456789abcdef0123456789ABCDEF01", "correct horse battery", 1024).unwrap();
+
+        assert_eq!(keystore["version"], 3);
+        assert_eq!(keystore["address"], "abcdef0123456789abcdef0123456789abcdef01");
+        assert_eq!(decrypt_keystore(&keystore, "correct horse battery").unwrap(), key);
+        assert!(evm_keystore(&key, "0x00", "short", 1024).is_err());
+    }
+
+    #[test]
+    fn test_solana_keystore_checks_keypair_matches_address() {
+        let mut keypair = [1u8; 64];
+        keypair[32..].copy_from_slice(&[2u8; 32]);
+        let address = bs58::encode([2u8; 32]).into_string();
+
+        let keystore = solana_keystore(&keypair, &address, "correct horse battery", 1024).unwrap();
+        assert_eq!(keystore["type"], "solana-keypair");
+        assert_eq!(decrypt_keystore(&keystore, "correct horse battery").unwrap(), keypair);
+
+        let other = bs58::encode([3u8; 32]).into_string();
+        assert!(solana_keystore(&keypair, &other, "correct horse battery", 1024).is_err());
+    }
+}
@@ -1,7 +1,9 @@
 pub mod retry;
 pub mod circuit_breaker;
 pub mod encryption;
+pub mod key_provider;
 pub mod qr;
 pub mod keygen;
+pub mod keystore;
 pub mod api_keys;
 pub mod network_config;
@@ -0,0 +1,342 @@
+// Webhook Verifier
+// Signature verification for FidduPay webhooks, with an axum extractor for receivers
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRef, FromRequest, Request},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying `t=<timestamp>,v1=<signature>[,v1=<signature>]`
+pub const SIGNATURE_HEADER: &str = "FidduPay-Signature";
+
+/// Legacy signature header (hex HMAC of `<timestamp>.<body>`)
+pub const LEGACY_SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Legacy timestamp header paired with [`LEGACY_SIGNATURE_HEADER`]
+pub const LEGACY_TIMESTAMP_HEADER: &str = "X-Timestamp";
+
+/// Header carrying the event id, for deduplication
+pub const EVENT_ID_HEADER: &str = "X-Event-Id";
+
+/// Default accepted clock difference between sender and receiver
+pub const DEFAULT_TOLERANCE_SECONDS: i64 = 300;
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum VerifyError {
+    #[error("Missing webhook signature headers")]
+    MissingSignature,
+    #[error("Malformed webhook signature header")]
+    MalformedHeader,
+    #[error("Webhook timestamp is outside the tolerance window")]
+    TimestampOutOfTolerance,
+    #[error("Webhook signature does not match")]
+    SignatureMismatch,
+    #[error("Invalid webhook body: {0}")]
+    InvalidBody(String),
+}
+
+impl IntoResponse for VerifyError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            VerifyError::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::UNAUTHORIZED,
+        };
+
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Hex HMAC-SHA256 of `<timestamp>.<payload>`
+///
+/// This is the signature FidduPay sends in both `X-Signature` and the `v1`
+/// entries of `FidduPay-Signature`.
+pub fn compute_signature(secret: &str, timestamp: i64, payload: &[u8]) -> String {
+    hex::encode(signed_mac(secret, timestamp, payload).finalize().into_bytes())
+}
+
+fn signed_mac(secret: &str, timestamp: i64, payload: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload);
+    mac
+}
+
+/// Constant-time comparison of a hex signature
+fn signature_matches(secret: &str, timestamp: i64, payload: &[u8], signature: &str) -> bool {
+    match hex::decode(signature.trim()) {
+        Ok(expected) => signed_mac(secret, timestamp, payload).verify_slice(&expected).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Parse a `FidduPay-Signature` header into its timestamp and signatures
+pub fn parse_signature_header(header: &str) -> Result<(i64, Vec<&str>), VerifyError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => {
+                timestamp = Some(value.parse::<i64>().map_err(|_| VerifyError::MalformedHeader)?);
+            }
+            Some(("v1", value)) => signatures.push(value),
+            Some(_) => {} // Unknown schemes are ignored for forward compatibility
+            None => return Err(VerifyError::MalformedHeader),
+        }
+    }
+
+    match timestamp {
+        Some(timestamp) if !signatures.is_empty() => Ok((timestamp, signatures)),
+        _ => Err(VerifyError::MalformedHeader),
+    }
+}
+
+/// Verifies incoming FidduPay webhooks
+///
+/// Checks the timestamp first, so replayed requests are rejected once they
+/// fall outside the tolerance, then the signature over the raw body.
+#[derive(Debug, Clone)]
+pub struct WebhookVerifier {
+    secret: String,
+    tolerance_seconds: i64,
+}
+
+impl WebhookVerifier {
+    /// Verifier for an endpoint secret (`whsec_...`) or the platform signing key
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self {
+            secret: secret.into(),
+            tolerance_seconds: DEFAULT_TOLERANCE_SECONDS,
+        }
+    }
+
+    /// Accept timestamps up to `seconds` away from the local clock
+    pub fn with_tolerance(mut self, seconds: i64) -> Self {
+        self.tolerance_seconds = seconds;
+        self
+    }
+
+    /// Verify a signature and timestamp against the raw body at time `now`
+    ///
+    /// # Arguments
+    /// * `payload` - Raw request body, exactly as received
+    /// * `timestamp` - Unix timestamp the sender signed
+    /// * `signatures` - Candidate hex signatures; one must match
+    /// * `now` - Current Unix timestamp
+    pub fn verify_at(
+        &self,
+        payload: &[u8],
+        timestamp: i64,
+        signatures: &[&str],
+        now: i64,
+    ) -> Result<(), VerifyError> {
+        if (now - timestamp).abs() > self.tolerance_seconds {
+            return Err(VerifyError::TimestampOutOfTolerance);
+        }
+
+        if signatures
+            .iter()
+            .any(|sig| signature_matches(&self.secret, timestamp, payload, sig))
+        {
+            Ok(())
+        } else {
+            Err(VerifyError::SignatureMismatch)
+        }
+    }
+
+    /// Verify a `FidduPay-Signature` header value against the raw body
+    pub fn verify_header(&self, payload: &[u8], header: &str) -> Result<i64, VerifyError> {
+        let (timestamp, signatures) = parse_signature_header(header)?;
+        self.verify_at(payload, timestamp, &signatures, Utc::now().timestamp())?;
+        Ok(timestamp)
+    }
+
+    /// Verify a request's headers against its raw body
+    ///
+    /// Uses `FidduPay-Signature` when present (it also carries the previous
+    /// secret's signature during a rotation), otherwise `X-Signature` with
+    /// `X-Timestamp`. Returns the signed timestamp.
+    pub fn verify_headers(&self, headers: &HeaderMap, payload: &[u8]) -> Result<i64, VerifyError> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        if let Some(value) = header(SIGNATURE_HEADER) {
+            return self.verify_header(payload, value);
+        }
+
+        let (Some(signature), Some(timestamp)) = (header(LEGACY_SIGNATURE_HEADER), header(LEGACY_TIMESTAMP_HEADER)) else {
+            return Err(VerifyError::MissingSignature);
+        };
+        let timestamp = timestamp.trim().parse::<i64>().map_err(|_| VerifyError::MalformedHeader)?;
+
+        self.verify_at(payload, timestamp, &[signature], Utc::now().timestamp())?;
+        Ok(timestamp)
+    }
+}
+
+/// A verified webhook request, for FidduPay receivers built on axum
+///
+/// The handler's state must provide a [`WebhookVerifier`] through `FromRef`.
+/// Requests with a missing, stale or wrong signature are rejected with 401
+/// before the body is parsed.
+///
+/// ```ignore
+/// async fn receive(VerifiedWebhook { payload, event_id, .. }: VerifiedWebhook) -> StatusCode {
+///     // payload is the parsed JSON body
+///     StatusCode::OK
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VerifiedWebhook<T = serde_json::Value> {
+    pub payload: T,
+    pub event_id: Option<String>,
+    pub timestamp: i64,
+}
+
+#[async_trait]
+impl<S, T> FromRequest<S> for VerifiedWebhook<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+    WebhookVerifier: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let verifier = WebhookVerifier::from_ref(state);
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let timestamp = verifier
+            .verify_headers(&headers, &body)
+            .map_err(IntoResponse::into_response)?;
+        let payload = serde_json::from_slice(&body)
+            .map_err(|e| VerifyError::InvalidBody(e.to_string()).into_response())?;
+        let event_id = headers
+            .get(EVENT_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        Ok(Self {
+            payload,
+            event_id,
+            timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request as HttpRequest};
+
+    const SECRET: &str = "whsec_test";
+    const BODY: &[u8] = br#"{"event_type":"payment.confirmed"}"#;
+
+    #[test]
+    fn test_verify_accepts_valid_signature_within_tolerance() {
+        let verifier = WebhookVerifier::new(SECRET);
+        let signature = compute_signature(SECRET, 1_700_000_000, BODY);
+
+        assert!(verifier.verify_at(BODY, 1_700_000_000, &[&signature], 1_700_000_100).is_ok());
+        assert_eq!(
+            verifier.verify_at(BODY, 1_700_000_000, &[&signature], 1_700_000_301),
+            Err(VerifyError::TimestampOutOfTolerance)
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_body_and_wrong_secret() {
+        let verifier = WebhookVerifier::new(SECRET);
+        let signature = compute_signature("whsec_other", 1_700_000_000, BODY);
+
+        assert_eq!(
+            verifier.verify_at(BODY, 1_700_000_000, &[&signature], 1_700_000_000),
+            Err(VerifyError::SignatureMismatch)
+        );
+
+        let signature = compute_signature(SECRET, 1_700_000_000, BODY);
+        assert_eq!(
+            verifier.verify_at(b"{}", 1_700_000_000, &[&signature], 1_700_000_000),
+            Err(VerifyError::SignatureMismatch)
+        );
+    }
+
+    #[test]
+    fn test_parse_signature_header_with_rollover() {
+        let (timestamp, signatures) = parse_signature_header("t=123,v1=aa,v1=bb,v0=ignored").unwrap();
+        assert_eq!(timestamp, 123);
+        assert_eq!(signatures, vec!["aa", "bb"]);
+
+        assert_eq!(parse_signature_header("v1=aa"), Err(VerifyError::MalformedHeader));
+        assert_eq!(parse_signature_header("t=abc,v1=aa"), Err(VerifyError::MalformedHeader));
+    }
+
+    #[test]
+    fn test_verify_headers_falls_back_to_legacy_headers() {
+        let verifier = WebhookVerifier::new(SECRET);
+        let now = Utc::now().timestamp();
+        let mut headers = HeaderMap::new();
+        headers.insert(LEGACY_SIGNATURE_HEADER, compute_signature(SECRET, now, BODY).parse().unwrap());
+        headers.insert(LEGACY_TIMESTAMP_HEADER, now.to_string().parse().unwrap());
+
+        assert_eq!(verifier.verify_headers(&headers, BODY), Ok(now));
+        assert_eq!(
+            verifier.verify_headers(&HeaderMap::new(), BODY),
+            Err(VerifyError::MissingSignature)
+        );
+    }
+
+    #[derive(Clone)]
+    struct ReceiverState {
+        verifier: WebhookVerifier,
+    }
+
+    impl FromRef<ReceiverState> for WebhookVerifier {
+        fn from_ref(state: &ReceiverState) -> Self {
+            state.verifier.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extractor_verifies_and_parses() {
+        let state = ReceiverState {
+            verifier: WebhookVerifier::new(SECRET),
+        };
+        let now = Utc::now().timestamp();
+        let header = format!("t={},v1={}", now, compute_signature(SECRET, now, BODY));
+
+        let request = HttpRequest::builder()
+            .header(SIGNATURE_HEADER, header)
+            .header(EVENT_ID_HEADER, "evt_123")
+            .body(Body::from(BODY))
+            .unwrap();
+        let webhook = VerifiedWebhook::<serde_json::Value>::from_request(request, &state)
+            .await
+            .unwrap();
+        assert_eq!(webhook.payload["event_type"], "payment.confirmed");
+        assert_eq!(webhook.event_id.as_deref(), Some("evt_123"));
+
+        let request = HttpRequest::builder()
+            .header(SIGNATURE_HEADER, format!("t={},v1=00", now))
+            .body(Body::from(BODY))
+            .unwrap();
+        let rejection = VerifiedWebhook::<serde_json::Value>::from_request(request, &state)
+            .await
+            .unwrap_err();
+        assert_eq!(rejection.status(), StatusCode::UNAUTHORIZED);
+    }
+}